@@ -1,7 +1,22 @@
+#[path = "build/attributes.rs"]
+mod attributes;
+#[path = "build/connect.rs"]
+mod connect;
+#[path = "build/descriptor.rs"]
+mod descriptor;
+#[path = "build/json.rs"]
+mod json;
+#[path = "build/path.rs"]
+mod path;
+#[path = "build/rest.rs"]
+mod rest;
+
+use std::{fs, path::Path, process::Command};
+
+use attributes::{Attribute, Config};
 use glob::glob;
-use regex::Regex;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+
+const DESCRIPTOR_SET: &str = "src/infrastructure/proto/reflection_descriptor.bin";
 
 fn main() {
     let proto_files: Vec<_> = glob("proto/*.proto")
@@ -10,53 +25,67 @@ fn main() {
         .map(|path| path.to_string_lossy().into_owned())
         .collect();
 
-    println!("cargo:warning={:?}", proto_files);
-
-    let mut attributes = Vec::new();
-
-    for proto_file in &proto_files {
-        let messages = extract_messages(proto_file);
-        for message in messages {
-            if message.contains("Request") {
-                println!("cargo:warning={:?}", message);
-                attributes.push((message, "#[derive(serde::Deserialize)]".to_string()));
-            } else if message.contains("Response") {
-                println!("cargo:warning={:?}", message);
-                attributes.push((message, "#[derive(serde::Serialize)]".to_string()));
-            }
-        }
+    for path in ["proto", "build"] {
+        println!("cargo:rerun-if-changed={path}");
     }
 
-    let mut config = tonic_build::configure()
-        .out_dir("src/infrastructure/proto")
-        .build_server(true)
-        .build_client(true)
-        .file_descriptor_set_path("src/infrastructure/proto/reflection_descriptor.bin");
-
-    for (message, attribute) in attributes {
-        config = config.type_attribute(&message, &attribute);
-    }
+    // The attributes come from the compiled descriptors, so protoc runs
+    // before code generation.
+    compile_descriptor_set(&proto_files);
+    let descriptor_set = fs::read(DESCRIPTOR_SET).expect("Failed to read descriptor set");
+    let config = Config::load(Path::new("proto/codegen.toml"));
 
-    config
+    let builder = attributes::plan(&descriptor_set, &config).into_iter().fold(
+        tonic_build::configure()
+            .out_dir("src/infrastructure/proto")
+            .build_server(true)
+            .build_client(true)
+            .file_descriptor_set_path(DESCRIPTOR_SET)
+            .skip_protoc_run()
+            .emit_rerun_if_changed(false),
+        |builder, attribute| match attribute {
+            Attribute::Message(path, attribute) => builder.message_attribute(path, attribute),
+            Attribute::Field(path, attribute) => builder.field_attribute(path, attribute),
+        },
+    );
+    builder
         .compile(&proto_files, &["proto"])
         .unwrap_or_else(|e| panic!("Failed to compile protobuf {:?}", e));
-}
-
-fn extract_messages(proto_file: &str) -> Vec<String> {
-    let file = File::open(proto_file).expect("Failed to open proto file");
-    let reader = BufReader::new(file);
 
-    let message_regex = Regex::new(r"^\s*message\s+(\w+)").expect("Failed to create regex");
-    let mut messages = Vec::new();
+    json::generate(
+        &descriptor_set,
+        &config,
+        Path::new("src/infrastructure/proto/json.rs"),
+    );
+    rest::generate(
+        Path::new(DESCRIPTOR_SET),
+        &config,
+        Path::new("src/infrastructure/proto/rest.rs"),
+    );
+    connect::generate(
+        Path::new(DESCRIPTOR_SET),
+        Path::new("src/infrastructure/proto/connect.rs"),
+    );
+}
 
-    for line in reader.lines() {
-        let line = line.expect("Failed to read line");
-        if let Some(captures) = message_regex.captures(&line) {
-            if let Some(message) = captures.get(1) {
-                messages.push(message.as_str().to_string());
-            }
-        }
+/// Runs protoc the way prost-build does, with imports and source info.
+fn compile_descriptor_set(proto_files: &[String]) {
+    let mut protoc = Command::new(prost_build::protoc_from_env());
+    protoc
+        .arg("--include_imports")
+        .arg("--include_source_info")
+        .arg("-o")
+        .arg(DESCRIPTOR_SET)
+        .arg("-I")
+        .arg("proto");
+    if let Some(include) = prost_build::protoc_include_from_env() {
+        protoc.arg("-I").arg(include);
+    }
+    let output = protoc
+        .args(proto_files)
+        .output()
+        .unwrap_or_else(|e| panic!("Failed to run protoc: {e}"));
+    if !output.status.success() {
+        panic!("protoc failed: {}", String::from_utf8_lossy(&output.stderr));
     }
-
-    messages
 }
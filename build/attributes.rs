@@ -0,0 +1,146 @@
+//! The attributes `build.rs` puts on the generated types, the overrides in
+//! `proto/codegen.toml`.
+//!
+//! Paths are given without their leading `.`, prost also applies a path to
+//! everything nested under it but only matches the dot-less form as a
+//! suffix, so attributes stay on the type or field they were meant for.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+};
+
+use prost::Message;
+use prost_types::{DescriptorProto, FileDescriptorSet};
+use serde::Deserialize;
+
+pub enum Attribute {
+    Message(String, String),
+    Field(String, String),
+}
+
+/// `proto/codegen.toml`: attribute overrides keyed by fully qualified
+/// message name, and the command of every RPC keyed by
+/// `{package}.{Service}/{Method}`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    messages: BTreeMap<String, Override>,
+    pub commands: BTreeMap<String, CommandMapping>,
+}
+
+/// The command an RPC's request converts to, or `false` when it is not
+/// one.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum CommandMapping {
+    Command(String),
+    None(bool),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Override {
+    /// `false` leaves the message without `Serialize` and `Deserialize`.
+    serde: bool,
+    attributes: Vec<String>,
+    fields: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for Override {
+    fn default() -> Self {
+        Self {
+            serde: true,
+            attributes: Vec::new(),
+            fields: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text)
+                .unwrap_or_else(|e| panic!("Invalid {}: {}", path.display(), e)),
+            Err(_) => Config::default(),
+        }
+    }
+
+    /// Whether the message `name`, e.g. `.users.GetUserRequest`, gets
+    /// `Serialize` and `Deserialize`.
+    pub fn serde(&self, name: &str) -> bool {
+        self.overrides(name.trim_start_matches('.'))
+            .is_none_or(|overrides| overrides.serde)
+    }
+
+    fn overrides(&self, name: &str) -> Option<&Override> {
+        self.messages
+            .iter()
+            .find(|(key, _)| key.trim_start_matches('.') == name)
+            .map(|(_, overrides)| overrides)
+    }
+}
+
+pub fn plan(descriptor_set: &[u8], config: &Config) -> Vec<Attribute> {
+    let set = FileDescriptorSet::decode(descriptor_set).expect("Invalid descriptor set");
+    let mut planner = Planner {
+        config,
+        used: BTreeSet::new(),
+        attributes: Vec::new(),
+    };
+    for file in &set.file {
+        // Generated by prost-types rather than this crate.
+        if file.package() == "google.protobuf" {
+            continue;
+        }
+        for message in &file.message_type {
+            planner.message(file.package(), message);
+        }
+    }
+    for name in config.messages.keys() {
+        if !planner.used.contains(name.trim_start_matches('.')) {
+            println!("cargo:warning=codegen.toml: no message {name}");
+        }
+    }
+    planner.attributes
+}
+
+struct Planner<'a> {
+    config: &'a Config,
+    used: BTreeSet<String>,
+    attributes: Vec<Attribute>,
+}
+
+impl Planner<'_> {
+    fn message(&mut self, scope: &str, message: &DescriptorProto) {
+        let name = qualified(scope, message.name());
+        for nested in &message.nested_type {
+            self.message(&name, nested);
+        }
+
+        let Some(overrides) = self.config.overrides(&name) else {
+            return;
+        };
+        self.used.insert(name.clone());
+        for attribute in &overrides.attributes {
+            self.attributes
+                .push(Attribute::Message(name.clone(), attribute.clone()));
+        }
+        for (field, attributes) in &overrides.fields {
+            for attribute in attributes {
+                self.attributes.push(Attribute::Field(
+                    format!("{name}.{field}"),
+                    attribute.clone(),
+                ));
+            }
+        }
+    }
+}
+
+fn qualified(scope: &str, name: &str) -> String {
+    match scope {
+        "" => name.to_string(),
+        scope => format!("{scope}.{name}"),
+    }
+}
@@ -0,0 +1,135 @@
+//! Generates `src/infrastructure/proto/connect.rs`: axum routes serving
+//! every RPC as a Connect procedure at `POST /{package}.{Service}/{Method}`.
+
+use std::{fmt::Write, fs, path::Path};
+
+use prost::Message;
+
+use crate::{
+    descriptor::{FileDescriptorSet, ServiceDescriptorProto},
+    rest::{module, snake_case},
+};
+
+const HEADER: &str = "\
+// @generated by build.rs from the services in proto/, do not edit.
+
+use std::sync::Arc;
+
+use axum::{extract::Request, routing::post, Router};
+
+use crate::infrastructure::grpc::connect::{self, Procedure};
+";
+
+pub fn generate(descriptor_set: &Path, out: &Path) {
+    let bytes = fs::read(descriptor_set).expect("Failed to read descriptor set");
+    let set = FileDescriptorSet::decode(bytes.as_slice()).expect("Invalid descriptor set");
+
+    let mut code = String::from(HEADER);
+    let mut paths = Vec::new();
+    for file in &set.file {
+        for service in &file.service {
+            service_routes(&mut code, &mut paths, &file.package, service);
+        }
+    }
+    writeln!(
+        code,
+        "
+/// Paths of every procedure.
+pub static PATHS: &[&str] = &[{}];",
+        paths
+            .iter()
+            .map(|path| format!("\n    {path},"))
+            .collect::<String>()
+            + "\n"
+    )
+    .unwrap();
+
+    // Rewriting an unchanged file would make cargo rebuild every time.
+    if fs::read_to_string(out).ok().as_deref() != Some(code.as_str()) {
+        fs::write(out, code).expect("Failed to write Connect routes");
+    }
+}
+
+fn service_routes(
+    code: &mut String,
+    paths: &mut Vec<String>,
+    package: &str,
+    service: &ServiceDescriptorProto,
+) {
+    let qualified = match package {
+        "" => service.name.clone(),
+        package => format!("{package}.{}", service.name),
+    };
+    let prefix = snake_case(&service.name).to_uppercase();
+    let mut routes = String::new();
+
+    for method in &service.method {
+        let name = format!("{prefix}_{}", snake_case(&method.name).to_uppercase());
+        let path = format!("/{qualified}/{}", method.name);
+        let (kind, handler) = match (method.client_streaming, method.server_streaming) {
+            (false, false) => ("unary", "unary"),
+            (false, true) => ("server streaming", "server_streaming"),
+            (true, false) => ("client streaming", "client_streaming"),
+            (true, true) => ("bidirectional streaming", "bidi_streaming"),
+        };
+        paths.push(format!("{path:?}"));
+
+        writeln!(
+            code,
+            "
+/// `{qualified}.{method}`, {kind}.
+pub static {name}: Procedure = Procedure {{
+    path: {path:?},
+    input: {input:?},
+    output: {output:?},
+}};",
+            method = method.name,
+            input = method.input_type,
+            output = method.output_type,
+        )
+        .unwrap();
+
+        write!(
+            routes,
+            "
+        .route(
+            {name}.path,
+            post({{
+                let service = service.clone();
+                move |request: Request| async move {{
+                    connect::{handler}(&{name}, request, |request| async move {{
+                        service.{method}(request).await
+                    }})
+                    .await
+                }}
+            }}),
+        )",
+            method = snake_case(&method.name),
+        )
+        .unwrap();
+    }
+
+    if routes.is_empty() {
+        return;
+    }
+    let server = format!(
+        "{}::{}_server::{}",
+        module(package),
+        snake_case(&service.name),
+        service.name
+    );
+    writeln!(
+        code,
+        "
+/// Connect routes of `{qualified}`.
+pub fn {snake}_routes<S>(service: S) -> Router
+where
+    S: {server},
+{{
+    let service = Arc::new(service);
+    Router::new(){routes}
+}}",
+        snake = snake_case(&service.name),
+    )
+    .unwrap();
+}
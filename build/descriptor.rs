@@ -0,0 +1,100 @@
+//! The subset of `descriptor.proto` and `google/api/http.proto` needed to
+//! read method options, the `prost_types` versions drop the
+//! `google.api.http` extension.
+
+use prost::{Message, Oneof};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FileDescriptorSet {
+    #[prost(message, repeated, tag = "1")]
+    pub file: Vec<FileDescriptorProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FileDescriptorProto {
+    #[prost(string, tag = "2")]
+    pub package: String,
+    #[prost(message, repeated, tag = "6")]
+    pub service: Vec<ServiceDescriptorProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ServiceDescriptorProto {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(message, repeated, tag = "2")]
+    pub method: Vec<MethodDescriptorProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct MethodDescriptorProto {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub input_type: String,
+    #[prost(string, tag = "3")]
+    pub output_type: String,
+    #[prost(message, optional, tag = "4")]
+    pub options: Option<MethodOptions>,
+    #[prost(bool, tag = "5")]
+    pub client_streaming: bool,
+    #[prost(bool, tag = "6")]
+    pub server_streaming: bool,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct MethodOptions {
+    #[prost(message, optional, tag = "72295728")]
+    pub http: Option<HttpRule>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct HttpRule {
+    #[prost(oneof = "Pattern", tags = "2, 3, 4, 5, 6, 8")]
+    pub pattern: Option<Pattern>,
+    #[prost(string, tag = "7")]
+    pub body: String,
+    #[prost(message, repeated, tag = "11")]
+    pub additional_bindings: Vec<HttpRule>,
+    #[prost(string, tag = "12")]
+    pub response_body: String,
+}
+
+#[derive(Clone, PartialEq, Oneof)]
+pub enum Pattern {
+    #[prost(string, tag = "2")]
+    Get(String),
+    #[prost(string, tag = "3")]
+    Put(String),
+    #[prost(string, tag = "4")]
+    Post(String),
+    #[prost(string, tag = "5")]
+    Delete(String),
+    #[prost(string, tag = "6")]
+    Patch(String),
+    #[prost(message, tag = "8")]
+    Custom(CustomHttpPattern),
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct CustomHttpPattern {
+    #[prost(string, tag = "1")]
+    pub kind: String,
+    #[prost(string, tag = "2")]
+    pub path: String,
+}
+
+impl HttpRule {
+    /// HTTP method and path template, `None` when the rule has neither.
+    pub fn pattern(&self) -> Option<(String, &str)> {
+        let (verb, path) = match self.pattern.as_ref()? {
+            Pattern::Get(path) => ("GET", path),
+            Pattern::Put(path) => ("PUT", path),
+            Pattern::Post(path) => ("POST", path),
+            Pattern::Delete(path) => ("DELETE", path),
+            Pattern::Patch(path) => ("PATCH", path),
+            Pattern::Custom(custom) => (custom.kind.as_str(), &custom.path),
+        };
+        Some((verb.to_uppercase(), path))
+    }
+}
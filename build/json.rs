@@ -0,0 +1,77 @@
+//! Generates `src/infrastructure/proto/json.rs`: `Serialize` and
+//! `Deserialize` for every message, through the proto3 JSON mapping the
+//! REST and Connect routes use, unless `proto/codegen.toml` turns `serde`
+//! off for it.
+
+use std::{fmt::Write, fs, path::Path};
+
+use prost::Message;
+use prost_types::{DescriptorProto, FileDescriptorSet};
+
+use crate::{attributes::Config, rest::rust_type};
+
+const HEADER: &str = "\
+// @generated by build.rs from the messages in proto/, do not edit.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::infrastructure::grpc::transcoding;
+";
+
+pub fn generate(descriptor_set: &[u8], config: &Config, out: &Path) {
+    let set = FileDescriptorSet::decode(descriptor_set).expect("Invalid descriptor set");
+    let packages: Vec<_> = set.file.iter().map(|file| file.package()).collect();
+
+    let mut names = Vec::new();
+    for file in &set.file {
+        // Generated by prost-types rather than this crate.
+        if file.package() == "google.protobuf" {
+            continue;
+        }
+        for message in &file.message_type {
+            messages(&mut names, &format!(".{}", file.package()), message);
+        }
+    }
+
+    let mut code = String::from(HEADER);
+    for name in names.iter().filter(|name| config.serde(name)) {
+        writeln!(
+            code,
+            "
+impl Serialize for {message} {{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {{
+        transcoding::serialize(self, {name:?}, serializer)
+    }}
+}}
+
+impl<'de> Deserialize<'de> for {message} {{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {{
+        transcoding::deserialize({name:?}, deserializer)
+    }}
+}}",
+            message = rust_type(&packages, name),
+        )
+        .unwrap();
+    }
+
+    // Rewriting an unchanged file would make cargo rebuild every time.
+    if fs::read_to_string(out).ok().as_deref() != Some(code.as_str()) {
+        fs::write(out, code).expect("Failed to write serde impls");
+    }
+}
+
+/// Fully qualified names of `message` and those nested in it, map entries
+/// aside.
+fn messages(names: &mut Vec<String>, scope: &str, message: &DescriptorProto) {
+    let name = format!("{scope}.{}", message.name());
+    for nested in &message.nested_type {
+        if !nested
+            .options
+            .as_ref()
+            .is_some_and(|options| options.map_entry())
+        {
+            messages(names, &name, nested);
+        }
+    }
+    names.push(name);
+}
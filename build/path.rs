@@ -0,0 +1,38 @@
+/// A `google.api.http` path template turned into an axum route, only
+/// single segment variables (`{id}` or `{id=*}`) are supported.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathTemplate {
+    pub route: String,
+    /// Axum parameter name and the request field it is copied to.
+    pub variables: Vec<(String, String)>,
+}
+
+impl PathTemplate {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let Some(path) = template.strip_prefix('/') else {
+            return Err(format!("{template} does not start with /"));
+        };
+        let mut route = String::new();
+        let mut variables = Vec::new();
+
+        for segment in path.split('/') {
+            route.push('/');
+            if let Some(variable) = segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                let field = match variable.split_once('=') {
+                    None | Some((_, "*")) => variable.split('=').next().unwrap_or_default(),
+                    Some(_) => return Err(format!("{template}: multi segment variables")),
+                };
+                let param = field.replace('.', "_");
+                route.push(':');
+                route.push_str(&param);
+                variables.push((param, field.to_string()));
+            } else if segment.is_empty() || segment.contains(['{', '}', '*', ':']) {
+                return Err(format!("{template}: unsupported segment {segment}"));
+            } else {
+                route.push_str(segment);
+            }
+        }
+
+        Ok(Self { route, variables })
+    }
+}
@@ -0,0 +1,290 @@
+//! Generates `src/infrastructure/proto/rest.rs`: axum routes for every unary
+//! RPC of the services with `google.api.http` annotations, at its bindings or
+//! `POST /{package}.{Service}/{Method}` when it has none, and `TryFrom`
+//! conversions from request messages to the commands `proto/codegen.toml`
+//! maps their RPC to. Services without annotations are not transcoded, and
+//! an RPC missing from the mapping fails the build.
+
+use std::{collections::BTreeSet, fmt::Write, fs, path::Path};
+
+use prost::Message;
+
+use crate::{
+    attributes::{CommandMapping, Config},
+    descriptor::{FileDescriptorSet, HttpRule, MethodDescriptorProto, ServiceDescriptorProto},
+    path::PathTemplate,
+};
+
+const HEADER: &str = "\
+// @generated by build.rs from the services in proto/, do not edit.
+
+use std::sync::Arc;
+
+use axum::{
+    routing::{on, MethodFilter},
+    Router,
+};
+
+use crate::infrastructure::grpc::transcoding::{self, Binding, RestRequest};
+";
+
+/// Methods with an axum `MethodFilter`.
+const METHODS: [&str; 8] = [
+    "GET", "HEAD", "POST", "PUT", "DELETE", "OPTIONS", "TRACE", "PATCH",
+];
+
+pub fn generate(descriptor_set: &Path, config: &Config, out: &Path) {
+    let bytes = fs::read(descriptor_set).expect("Failed to read descriptor set");
+    let set = FileDescriptorSet::decode(bytes.as_slice()).expect("Invalid descriptor set");
+    let packages: Vec<_> = set.file.iter().map(|file| file.package.as_str()).collect();
+
+    let mut code = String::from(HEADER);
+    for file in &set.file {
+        for service in &file.service {
+            service_routes(&mut code, &file.package, service);
+        }
+    }
+
+    let mut converted = BTreeSet::new();
+    let mut mapped = BTreeSet::new();
+    let mut unmapped = Vec::new();
+    for file in &set.file {
+        for service in &file.service {
+            for method in &service.method {
+                let rpc = format!("{}/{}", qualified(&file.package, service), method.name);
+                match config.commands.get(&rpc) {
+                    Some(CommandMapping::Command(command)) => {
+                        if converted.insert((&method.input_type, command)) {
+                            conversion(&mut code, &packages, method, command);
+                        }
+                    }
+                    Some(CommandMapping::None(false)) => {}
+                    Some(CommandMapping::None(true)) => {
+                        panic!("codegen.toml: {rpc} maps to true, name its command or use false")
+                    }
+                    None => unmapped.push(rpc.clone()),
+                }
+                mapped.insert(rpc);
+            }
+        }
+    }
+    if !unmapped.is_empty() {
+        panic!(
+            "codegen.toml: no [commands] entry for {}, name their command or use false",
+            unmapped.join(", ")
+        );
+    }
+    for rpc in config.commands.keys() {
+        if !mapped.contains(rpc) {
+            panic!("codegen.toml: no RPC {rpc}");
+        }
+    }
+
+    // Rewriting an unchanged file would make cargo rebuild every time.
+    if fs::read_to_string(out).ok().as_deref() != Some(code.as_str()) {
+        fs::write(out, code).expect("Failed to write REST routes");
+    }
+}
+
+/// `{package}.{Service}`, or the service name alone without a package.
+fn qualified(package: &str, service: &ServiceDescriptorProto) -> String {
+    match package {
+        "" => service.name.clone(),
+        package => format!("{package}.{}", service.name),
+    }
+}
+
+fn service_routes(code: &mut String, package: &str, service: &ServiceDescriptorProto) {
+    let qualified = qualified(package, service);
+    if !service
+        .method
+        .iter()
+        .any(|method| http_rule(method).is_some())
+    {
+        return;
+    }
+    let prefix = snake_case(&service.name).to_uppercase();
+    let mut routes = String::new();
+
+    for method in &service.method {
+        if method.client_streaming || method.server_streaming {
+            continue;
+        }
+        let default = (
+            "POST".to_string(),
+            format!("/{qualified}/{}", method.name),
+            "*",
+            "",
+        );
+        let bindings: Vec<_> = match http_rule(method) {
+            Some(rule) => std::iter::once(rule)
+                .chain(&rule.additional_bindings)
+                .filter_map(|rule| {
+                    let (verb, template) = rule.pattern()?;
+                    Some((
+                        verb,
+                        template.to_string(),
+                        rule.body.as_str(),
+                        rule.response_body.as_str(),
+                    ))
+                })
+                .collect(),
+            None => vec![default],
+        };
+
+        for (i, (verb, template, body, response_body)) in bindings.into_iter().enumerate() {
+            let rpc = format!("{qualified}/{}", method.name);
+            if !METHODS.contains(&verb.as_str()) {
+                println!("cargo:warning=No REST route for {rpc}: method {verb}");
+                continue;
+            }
+            let path = match PathTemplate::parse(&template) {
+                Ok(path) => path,
+                Err(e) => {
+                    println!("cargo:warning=No REST route for {rpc}: {e}");
+                    continue;
+                }
+            };
+            let mut name = format!("{prefix}_{}", snake_case(&method.name).to_uppercase());
+            if i > 0 {
+                write!(name, "_{i}").unwrap();
+            }
+            let variables: Vec<_> = path
+                .variables
+                .iter()
+                .map(|(param, field)| format!("({param:?}, {field:?})"))
+                .collect();
+
+            writeln!(
+                code,
+                "
+/// `{verb} {template}`
+pub static {name}: Binding = Binding {{
+    rpc: \"/{rpc}\",
+    route: {route:?},
+    variables: &[{variables}],
+    body: {body:?},
+    response_body: {response_body:?},
+    input: {input:?},
+    output: {output:?},
+}};",
+                route = path.route,
+                variables = variables.join(", "),
+                input = method.input_type,
+                output = method.output_type,
+            )
+            .unwrap();
+
+            write!(
+                routes,
+                "
+        .route(
+            {name}.route,
+            on(MethodFilter::{verb}, {{
+                let service = service.clone();
+                move |request: RestRequest| async move {{
+                    transcoding::unary(&{name}, request, |request| async move {{
+                        service.{method}(request).await
+                    }})
+                    .await
+                }}
+            }}),
+        )",
+                method = snake_case(&method.name),
+            )
+            .unwrap();
+        }
+    }
+
+    if routes.is_empty() {
+        return;
+    }
+    let server = format!(
+        "{}::{}_server::{}",
+        module(package),
+        snake_case(&service.name),
+        service.name
+    );
+    writeln!(
+        code,
+        "
+/// REST routes of `{qualified}`.
+pub fn {snake}_routes<S>(service: S) -> Router
+where
+    S: {server},
+{{
+    let service = Arc::new(service);
+    Router::new(){routes}
+}}",
+        snake = snake_case(&service.name),
+    )
+    .unwrap();
+}
+
+fn http_rule(method: &MethodDescriptorProto) -> Option<&HttpRule> {
+    method.options.as_ref()?.http.as_ref()
+}
+
+fn conversion(code: &mut String, packages: &[&str], method: &MethodDescriptorProto, command: &str) {
+    writeln!(
+        code,
+        "
+impl TryFrom<{message}> for crate::commands::{command} {{
+    type Error = transcoding::InvalidMessage;
+
+    fn try_from(message: {message}) -> Result<Self, Self::Error> {{
+        transcoding::to_command(&message, {input:?})
+    }}
+}}",
+        message = rust_type(packages, &method.input_type),
+        input = method.input_type,
+    )
+    .unwrap();
+}
+
+/// Rust module holding the types of `package`, `crate::proto` re-exports
+/// the packages of this crate's own protos.
+pub fn module(package: &str) -> String {
+    match package {
+        "google.protobuf" => "::prost_types".to_string(),
+        package if package.starts_with("google.") => {
+            format!("crate::proto::{}", package.replace('.', "::"))
+        }
+        _ => "crate::proto".to_string(),
+    }
+}
+
+/// Rust path of the message `name`, e.g. `.users.GetUserRequest`.
+pub fn rust_type(packages: &[&str], name: &str) -> String {
+    let name = name.trim_start_matches('.');
+    let package = packages
+        .iter()
+        .filter(|package| {
+            name.strip_prefix(**package)
+                .is_some_and(|rest| rest.starts_with('.'))
+        })
+        .max_by_key(|package| package.len())
+        .copied()
+        .unwrap_or_default();
+    let mut path = module(package);
+    let mut segments: Vec<_> = name[package.len()..]
+        .trim_start_matches('.')
+        .split('.')
+        .collect();
+    let message = segments.pop().unwrap_or_default();
+    for nested in segments {
+        write!(path, "::{}", snake_case(nested)).unwrap();
+    }
+    format!("{path}::{message}")
+}
+
+pub fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS command_dead_letters;
+DROP TABLE IF EXISTS command_queue;
@@ -0,0 +1,25 @@
+CREATE TABLE command_queue (
+    id UUID PRIMARY KEY,
+    command_type VARCHAR(255) NOT NULL,
+    payload JSONB NOT NULL,
+    aggregate_id UUID,
+    attempts INT NOT NULL DEFAULT 0,
+    max_attempts INT NOT NULL,
+    visible_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX command_queue_visible_at_idx ON command_queue (visible_at);
+CREATE INDEX command_queue_aggregate_id_idx ON command_queue (aggregate_id, id);
+
+CREATE TABLE command_dead_letters (
+    id UUID PRIMARY KEY,
+    command_type VARCHAR(255) NOT NULL,
+    payload JSONB NOT NULL,
+    aggregate_id UUID,
+    attempts INT NOT NULL,
+    last_error TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    failed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,2 @@
+ALTER TABLE command_dead_letters DROP COLUMN IF EXISTS errors;
+ALTER TABLE command_queue DROP COLUMN IF EXISTS errors;
@@ -0,0 +1,2 @@
+ALTER TABLE command_queue ADD COLUMN errors JSONB NOT NULL DEFAULT '[]';
+ALTER TABLE command_dead_letters ADD COLUMN errors JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,12 @@
+CREATE TABLE scheduled_commands (
+    id UUID PRIMARY KEY,
+    command_type TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    aggregate_id UUID,
+    run_at TIMESTAMPTZ NOT NULL,
+    cron TEXT,
+    last_run_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX scheduled_commands_run_at_idx ON scheduled_commands (run_at);
@@ -0,0 +1,13 @@
+CREATE TABLE workflows (
+    id UUID PRIMARY KEY,
+    name TEXT NOT NULL,
+    correlation_id UUID NOT NULL,
+    status TEXT NOT NULL,
+    step INT NOT NULL DEFAULT 0,
+    context JSONB NOT NULL,
+    history JSONB NOT NULL DEFAULT '[]',
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (name, correlation_id)
+);
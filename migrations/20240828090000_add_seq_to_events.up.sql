@@ -0,0 +1,3 @@
+ALTER TABLE events ADD COLUMN seq BIGSERIAL;
+
+CREATE UNIQUE INDEX events_seq_idx ON events (seq);
@@ -0,0 +1,3 @@
+ALTER TABLE events ADD COLUMN tx xid8 NOT NULL DEFAULT pg_current_xact_id();
+
+CREATE INDEX events_tx_seq_idx ON events (tx, seq);
@@ -0,0 +1 @@
+ALTER TABLE workflows DROP COLUMN leased_until;
@@ -0,0 +1 @@
+ALTER TABLE scheduled_commands ADD COLUMN leased_until TIMESTAMPTZ;
@@ -0,0 +1,5 @@
+CREATE TABLE workflow_checkpoints (
+    name TEXT PRIMARY KEY,
+    tx BIGINT NOT NULL,
+    seq BIGINT NOT NULL
+);
@@ -0,0 +1,4 @@
+ALTER TABLE idempotency_keys
+    DROP COLUMN leased_until,
+    ADD COLUMN status_code INT,
+    ADD COLUMN content_type VARCHAR(255);
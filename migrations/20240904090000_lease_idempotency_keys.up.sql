@@ -0,0 +1,4 @@
+ALTER TABLE idempotency_keys
+    DROP COLUMN status_code,
+    DROP COLUMN content_type,
+    ADD COLUMN leased_until TIMESTAMPTZ;
@@ -0,0 +1,16 @@
+CREATE TABLE users (
+    id BLOB PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    email TEXT NOT NULL UNIQUE,
+    version INTEGER NOT NULL DEFAULT 1
+);
+
+CREATE TABLE events (
+    id BLOB PRIMARY KEY,
+    aggregate_id BLOB NOT NULL,
+    event_type TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    payload TEXT NOT NULL
+);
+
+CREATE INDEX events_aggregate_id_idx ON events (aggregate_id);
@@ -0,0 +1,435 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt::Debug,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use axum::async_trait;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::{
+    mpsc::{
+        self,
+        error::{SendTimeoutError, TrySendError},
+    },
+    oneshot,
+};
+use tower::{util::BoxCloneService, Layer, Service};
+use uuid::Uuid;
+
+use crate::{application::consistency, domain::Command, models::AttemptError};
+
+use super::{
+    CommandBusConfig, CommandError, OverflowPolicy, QueueMode, ShardMetrics, ShardSnapshot,
+};
+
+pub type BoxedOutput = Box<dyn Any + Send>;
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+/// The type-erased dispatch pipeline that layers wrap.
+pub type CommandService = BoxCloneService<CommandRequest, BoxedOutput, CommandError>;
+pub(super) type Reply = oneshot::Sender<Result<BoxedOutput, CommandError>>;
+type BoxedLayer = Box<dyn Fn(CommandService) -> CommandService + Send + Sync>;
+
+#[async_trait]
+pub trait CommandHandler<C: Command>: Send + Sync + 'static {
+    async fn handle(&self, command: C) -> Result<C::Output, CommandError>;
+}
+
+/// Persistent storage for fire-and-forget commands, used in
+/// [`QueueMode::Durable`] and for [`OverflowPolicy::Spill`].
+#[async_trait]
+pub trait DurableQueue: Debug + Send + Sync + 'static {
+    async fn push(&self, request: CommandRequest) -> Result<(), CommandError>;
+}
+
+/// A type-erased command as seen by layers.
+pub struct CommandRequest {
+    type_id: TypeId,
+    name: &'static str,
+    aggregate_id: Option<Uuid>,
+    command: Box<dyn Any + Send>,
+    clone: fn(&(dyn Any + Send)) -> Box<dyn Any + Send>,
+    serialize: fn(&(dyn Any + Send)) -> Value,
+    serialize_output: fn(&(dyn Any + Send)) -> Value,
+    /// Shared by every clone, so retries of one command add to one log.
+    failures: Arc<Mutex<Vec<AttemptError>>>,
+}
+
+impl CommandRequest {
+    pub fn new<C: Command>(command: C) -> Self {
+        Self {
+            type_id: TypeId::of::<C>(),
+            name: C::NAME,
+            aggregate_id: command.aggregate_id(),
+            command: Box::new(command),
+            clone: |command| Box::new(command.downcast_ref::<C>().unwrap().clone()),
+            serialize: |command| {
+                serde_json::to_value(command.downcast_ref::<C>().unwrap()).unwrap()
+            },
+            serialize_output: |output| {
+                serde_json::to_value(output.downcast_ref::<C::Output>().unwrap()).unwrap()
+            },
+            failures: Arc::default(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn aggregate_id(&self) -> Option<Uuid> {
+        self.aggregate_id
+    }
+
+    pub fn downcast_ref<C: Command>(&self) -> Option<&C> {
+        self.command.downcast_ref()
+    }
+
+    /// The command serialized to JSON.
+    pub fn payload(&self) -> Value {
+        (self.serialize)(self.command.as_ref())
+    }
+
+    /// Logs a failed attempt at running this command.
+    pub fn record_failure(&self, error: &CommandError) {
+        let mut failures = self.failures.lock().unwrap();
+        let attempt = failures.len() as i32 + 1;
+        failures.push(AttemptError::new(attempt, error.to_string()));
+    }
+
+    /// Every failed attempt logged so far, oldest first.
+    pub fn failures(&self) -> Vec<AttemptError> {
+        self.failures.lock().unwrap().clone()
+    }
+}
+
+impl Clone for CommandRequest {
+    fn clone(&self) -> Self {
+        Self {
+            type_id: self.type_id,
+            name: self.name,
+            aggregate_id: self.aggregate_id,
+            command: (self.clone)(self.command.as_ref()),
+            clone: self.clone,
+            serialize: self.serialize,
+            serialize_output: self.serialize_output,
+            failures: self.failures.clone(),
+        }
+    }
+}
+
+/// A command on its way from a [`CommandBus`] to a [`CommandWorker`](super::CommandWorker).
+pub struct Envelope {
+    pub(super) request: CommandRequest,
+    pub(super) reply: Option<Reply>,
+}
+
+/// The receiving ends of every shard, consumed by a [`CommandWorker`](super::CommandWorker).
+pub struct CommandReceiver {
+    pub(super) shards: Vec<mpsc::Receiver<Envelope>>,
+    pub(super) metrics: Arc<[ShardMetrics]>,
+}
+
+/// Creates a bus with one channel per worker shard.
+pub fn command_bus(config: &CommandBusConfig) -> (CommandBus, CommandReceiver) {
+    let (senders, receivers) = (0..config.workers)
+        .map(|_| mpsc::channel(config.capacity))
+        .unzip();
+    let metrics: Arc<[ShardMetrics]> = (0..config.workers)
+        .map(|_| ShardMetrics::default())
+        .collect();
+
+    let bus = CommandBus {
+        shards: Arc::new(senders),
+        metrics: metrics.clone(),
+        next: Arc::new(AtomicUsize::new(0)),
+        overflow: config.overflow,
+        retry_after: config.retry_after,
+        mode: config.mode,
+        durable: None,
+    };
+    let receiver = CommandReceiver {
+        shards: receivers,
+        metrics,
+    };
+    (bus, receiver)
+}
+
+#[derive(Clone, Debug)]
+pub struct CommandBus {
+    shards: Arc<Vec<mpsc::Sender<Envelope>>>,
+    metrics: Arc<[ShardMetrics]>,
+    next: Arc<AtomicUsize>,
+    overflow: OverflowPolicy,
+    retry_after: Duration,
+    mode: QueueMode,
+    durable: Option<Arc<dyn DurableQueue>>,
+}
+
+/// How full the bus' channels are.
+#[derive(Serialize, Debug, Clone)]
+pub struct QueueDepth {
+    pub queued: usize,
+    pub capacity: usize,
+    /// Shards with no room left, their commands are being shed.
+    pub full_shards: usize,
+}
+
+impl CommandBus {
+    pub fn with_durable_queue(mut self, queue: impl DurableQueue) -> Self {
+        self.durable = Some(Arc::new(queue));
+        self
+    }
+
+    /// Queues `command` and waits for its handler's output.
+    pub async fn dispatch<C: Command>(&self, command: C) -> Result<C::Output, CommandError> {
+        consistency::record_write();
+        let (reply, output) = oneshot::channel();
+        self.enqueue(CommandRequest::new(command), Some(reply))
+            .await?;
+
+        let output = output.await.map_err(|_| CommandError::Closed)??;
+        Ok(*output
+            .downcast::<C::Output>()
+            .expect("handler output matches the command's output type"))
+    }
+
+    /// Queues an already type-erased request, such as one decoded from a
+    /// dead letter, and waits for its output.
+    pub async fn dispatch_request(
+        &self,
+        request: CommandRequest,
+    ) -> Result<BoxedOutput, CommandError> {
+        consistency::record_write();
+        let (reply, output) = oneshot::channel();
+        self.enqueue(request, Some(reply)).await?;
+        output.await.map_err(|_| CommandError::Closed)?
+    }
+
+    /// Like [`Self::dispatch_request`], with the output serialized to JSON.
+    pub async fn dispatch_json(&self, request: CommandRequest) -> Result<Value, CommandError> {
+        let serialize_output = request.serialize_output;
+        let output = self.dispatch_request(request).await?;
+        Ok(serialize_output(output.as_ref()))
+    }
+
+    /// Queues `command` without waiting for it to be handled, persisting
+    /// it first in [`QueueMode::Durable`].
+    pub async fn send<C: Command>(&self, command: C) -> Result<(), CommandError> {
+        self.send_request(CommandRequest::new(command)).await
+    }
+
+    /// Queues an already type-erased request without waiting for it.
+    pub async fn send_request(&self, request: CommandRequest) -> Result<(), CommandError> {
+        consistency::record_write();
+        match (&self.durable, self.mode) {
+            (Some(durable), QueueMode::Durable) => durable.push(request).await,
+            _ => self.enqueue(request, None).await,
+        }
+    }
+
+    pub fn queue_depth(&self) -> QueueDepth {
+        self.shards.iter().fold(
+            QueueDepth {
+                queued: 0,
+                capacity: 0,
+                full_shards: 0,
+            },
+            |mut depth, shard| {
+                depth.queued += shard.max_capacity() - shard.capacity();
+                depth.capacity += shard.max_capacity();
+                depth.full_shards += usize::from(shard.capacity() == 0);
+                depth
+            },
+        )
+    }
+
+    pub fn shard_metrics(&self) -> Vec<ShardSnapshot> {
+        self.metrics
+            .iter()
+            .enumerate()
+            .map(|(shard, metrics)| metrics.snapshot(shard))
+            .collect()
+    }
+
+    /// Commands for one aggregate always land on the same shard, the rest
+    /// are spread round-robin.
+    fn shard(&self, request: &CommandRequest) -> usize {
+        let shards = self.shards.len();
+        match request.aggregate_id {
+            Some(id) => (id.as_u128() % shards as u128) as usize,
+            None => self.next.fetch_add(1, Ordering::Relaxed) % shards,
+        }
+    }
+
+    async fn enqueue(
+        &self,
+        request: CommandRequest,
+        reply: Option<Reply>,
+    ) -> Result<(), CommandError> {
+        let shard = self.shard(&request);
+        let sender = &self.shards[shard];
+        let envelope = Envelope { request, reply };
+        self.metrics[shard].enqueued();
+
+        let full = match self.overflow {
+            OverflowPolicy::Wait(timeout) => match sender.send_timeout(envelope, timeout).await {
+                Ok(()) => return Ok(()),
+                Err(SendTimeoutError::Timeout(envelope)) => envelope,
+                Err(SendTimeoutError::Closed(_)) => {
+                    self.metrics[shard].rejected();
+                    return Err(CommandError::Closed);
+                }
+            },
+            OverflowPolicy::Reject | OverflowPolicy::Spill => match sender.try_send(envelope) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Full(envelope)) => envelope,
+                Err(TrySendError::Closed(_)) => {
+                    self.metrics[shard].rejected();
+                    return Err(CommandError::Closed);
+                }
+            },
+        };
+
+        self.metrics[shard].shed();
+        match (&self.durable, self.overflow, full.reply) {
+            (Some(durable), OverflowPolicy::Spill, None) => durable.push(full.request).await,
+            _ => Err(CommandError::Overloaded {
+                retry_after: self.retry_after,
+            }),
+        }
+    }
+}
+
+trait ErasedHandler: Send + Sync {
+    fn handle(&self, command: Box<dyn Any + Send>) -> BoxFuture<Result<BoxedOutput, CommandError>>;
+}
+
+struct Typed<C, H> {
+    handler: Arc<H>,
+    command: PhantomData<fn(C)>,
+}
+
+impl<C: Command, H: CommandHandler<C>> ErasedHandler for Typed<C, H> {
+    fn handle(&self, command: Box<dyn Any + Send>) -> BoxFuture<Result<BoxedOutput, CommandError>> {
+        let handler = self.handler.clone();
+        Box::pin(async move {
+            let command = *command
+                .downcast::<C>()
+                .expect("request is routed by the command's TypeId");
+            let output = handler.handle(command).await?;
+            Ok(Box::new(output) as BoxedOutput)
+        })
+    }
+}
+
+/// The innermost service, routes a request to its registered handler.
+#[derive(Clone)]
+struct Handlers(Arc<HashMap<TypeId, Arc<dyn ErasedHandler>>>);
+
+impl Service<CommandRequest> for Handlers {
+    type Response = BoxedOutput;
+    type Error = CommandError;
+    type Future = BoxFuture<Result<BoxedOutput, CommandError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: CommandRequest) -> Self::Future {
+        match self.0.get(&request.type_id) {
+            Some(handler) => handler.handle(request.command),
+            None => Box::pin(async move { Err(CommandError::Unregistered(request.name)) }),
+        }
+    }
+}
+
+type Decoder = fn(Value) -> Result<CommandRequest, serde_json::Error>;
+
+/// Turns persisted `(name, payload)` pairs back into requests.
+#[derive(Clone, Default)]
+pub struct CommandDecoders(Arc<HashMap<&'static str, Decoder>>);
+
+impl CommandDecoders {
+    pub fn decode(&self, name: &str, payload: Value) -> Result<CommandRequest, CommandError> {
+        let decode = self
+            .0
+            .get(name)
+            .ok_or_else(|| CommandError::Unknown(name.to_string()))?;
+        Ok(decode(payload)?)
+    }
+}
+
+/// The layered dispatch service together with the decoders of every
+/// registered command.
+#[derive(Clone)]
+pub struct CommandPipeline {
+    pub service: CommandService,
+    pub decoders: CommandDecoders,
+}
+
+/// Handlers keyed by the command type they accept, each bounded context
+/// registers its own.
+#[derive(Default)]
+pub struct CommandHandlers {
+    handlers: HashMap<TypeId, Arc<dyn ErasedHandler>>,
+    decoders: HashMap<&'static str, Decoder>,
+    layers: Vec<BoxedLayer>,
+}
+
+impl CommandHandlers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<C: Command, H: CommandHandler<C>>(mut self, handler: H) -> Self {
+        let handler = Typed {
+            handler: Arc::new(handler),
+            command: PhantomData::<fn(C)>,
+        };
+        self.handlers.insert(TypeId::of::<C>(), Arc::new(handler));
+        self.decoders.insert(C::NAME, |payload| {
+            serde_json::from_value::<C>(payload).map(CommandRequest::new)
+        });
+        self
+    }
+
+    /// Wraps dispatch in `layer`, like `tower::ServiceBuilder` the first
+    /// layer added is the outermost.
+    pub fn layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<CommandService> + Send + Sync + 'static,
+        L::Service: Service<CommandRequest, Response = BoxedOutput, Error = CommandError>
+            + Clone
+            + Send
+            + 'static,
+        <L::Service as Service<CommandRequest>>::Future: Send + 'static,
+    {
+        self.layers.push(Box::new(move |inner| {
+            BoxCloneService::new(layer.layer(inner))
+        }));
+        self
+    }
+
+    pub fn build(self) -> CommandPipeline {
+        let handlers = BoxCloneService::new(Handlers(Arc::new(self.handlers)));
+        let service = self
+            .layers
+            .iter()
+            .rev()
+            .fold(handlers, |service, layer| layer(service));
+        CommandPipeline {
+            service,
+            decoders: CommandDecoders(Arc::new(self.decoders)),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::{env, time::Duration};
+
+/// What the bus does when a shard's channel is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait up to the given duration for room, then reject.
+    Wait(Duration),
+    /// Reject right away.
+    Reject,
+    /// Hand fire-and-forget commands to the bus' durable queue,
+    /// rejecting when none is configured.
+    Spill,
+}
+
+/// Where fire-and-forget commands are queued.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueueMode {
+    /// In-memory channels, fast but lost on restart.
+    Memory,
+    /// The durable queue, consumed cooperatively by every replica.
+    Durable,
+}
+
+/// Sizing of the command bus, read from `COMMAND_WORKERS`,
+/// `COMMAND_QUEUE_CAPACITY`, `COMMAND_OVERFLOW` (`wait`, `reject` or `spill`),
+/// `COMMAND_OVERFLOW_TIMEOUT_MS`, `COMMAND_RETRY_AFTER_SECS` and
+/// `COMMAND_QUEUE` (`memory` or `durable`).
+#[derive(Clone, Debug)]
+pub struct CommandBusConfig {
+    /// Number of shards, each handled by its own worker task.
+    pub workers: usize,
+    /// Buffer size of every shard's channel.
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+    /// Hint returned to rejected clients.
+    pub retry_after: Duration,
+    pub mode: QueueMode,
+}
+
+impl Default for CommandBusConfig {
+    fn default() -> Self {
+        Self {
+            workers: 4,
+            capacity: 32,
+            overflow: OverflowPolicy::Wait(Duration::from_secs(5)),
+            retry_after: Duration::from_secs(1),
+            mode: QueueMode::Memory,
+        }
+    }
+}
+
+impl CommandBusConfig {
+    pub fn uses_durable_queue(&self) -> bool {
+        self.mode == QueueMode::Durable || self.overflow == OverflowPolicy::Spill
+    }
+
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            workers: env_var("COMMAND_WORKERS").unwrap_or(default.workers).max(1),
+            capacity: env_var("COMMAND_QUEUE_CAPACITY")
+                .unwrap_or(default.capacity)
+                .max(1),
+            overflow: match env::var("COMMAND_OVERFLOW").as_deref() {
+                Ok("reject") => OverflowPolicy::Reject,
+                Ok("spill") => OverflowPolicy::Spill,
+                _ => env_var("COMMAND_OVERFLOW_TIMEOUT_MS")
+                    .map(|ms| OverflowPolicy::Wait(Duration::from_millis(ms as u64)))
+                    .unwrap_or(default.overflow),
+            },
+            retry_after: env_var("COMMAND_RETRY_AFTER_SECS")
+                .map(|secs| Duration::from_secs(secs as u64))
+                .unwrap_or(default.retry_after),
+            mode: match env::var("COMMAND_QUEUE").as_deref() {
+                Ok("durable") => QueueMode::Durable,
+                _ => default.mode,
+            },
+        }
+    }
+}
+
+pub(crate) fn env_var(name: &str) -> Option<usize> {
+    env::var(name).ok().and_then(|value| value.parse().ok())
+}
@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use axum::async_trait;
+use tokio::task::JoinSet;
+use tower::ServiceExt;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::{
+    models::QueuedCommand,
+    repositories::{CommandQueueRepository, RepositoryError},
+    PostgreSQL,
+};
+
+use super::{config::env_var, CommandError, CommandPipeline, CommandRequest, DurableQueue};
+
+/// Tuning of the durable queue, read from `COMMAND_QUEUE_BATCH`,
+/// `COMMAND_VISIBILITY_TIMEOUT_SECS`, `COMMAND_MAX_ATTEMPTS` and
+/// `COMMAND_POLL_INTERVAL_MS`.
+#[derive(Clone, Debug)]
+pub struct DurableQueueConfig {
+    /// Commands leased per poll.
+    pub batch_size: i64,
+    /// How long a leased command stays hidden before another replica may
+    /// pick it up again.
+    pub visibility_timeout: Duration,
+    pub max_attempts: i32,
+    pub poll_interval: Duration,
+}
+
+impl Default for DurableQueueConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 16,
+            visibility_timeout: Duration::from_secs(30),
+            max_attempts: 5,
+            poll_interval: Duration::from_millis(500),
+        }
+    }
+}
+
+impl DurableQueueConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            batch_size: env_var("COMMAND_QUEUE_BATCH")
+                .map(|batch| batch.max(1) as i64)
+                .unwrap_or(default.batch_size),
+            visibility_timeout: env_var("COMMAND_VISIBILITY_TIMEOUT_SECS")
+                .map(|secs| Duration::from_secs(secs as u64))
+                .unwrap_or(default.visibility_timeout),
+            max_attempts: env_var("COMMAND_MAX_ATTEMPTS")
+                .map(|attempts| attempts.max(1) as i32)
+                .unwrap_or(default.max_attempts),
+            poll_interval: env_var("COMMAND_POLL_INTERVAL_MS")
+                .map(|ms| Duration::from_millis(ms as u64))
+                .unwrap_or(default.poll_interval),
+        }
+    }
+}
+
+/// Postgres-backed [`DurableQueue`].
+#[derive(Clone, Debug)]
+pub struct DurableCommandQueue {
+    repo: PostgreSQL,
+    config: DurableQueueConfig,
+}
+
+impl DurableCommandQueue {
+    pub fn new(repo: PostgreSQL, config: DurableQueueConfig) -> Self {
+        Self { repo, config }
+    }
+}
+
+#[async_trait]
+impl DurableQueue for DurableCommandQueue {
+    async fn push(&self, request: CommandRequest) -> Result<(), CommandError> {
+        let command = QueuedCommand {
+            id: Uuid::now_v7(),
+            command_type: request.name().to_string(),
+            payload: request.payload(),
+            aggregate_id: request.aggregate_id(),
+            attempts: 0,
+            max_attempts: self.config.max_attempts,
+        };
+        Ok(self.repo.enqueue_command(command).await?)
+    }
+}
+
+/// Polls the durable queue and runs leased commands through the pipeline,
+/// any number of replicas can run one side by side.
+pub struct DurableWorker {
+    repo: PostgreSQL,
+    pipeline: CommandPipeline,
+    config: DurableQueueConfig,
+}
+
+impl DurableWorker {
+    pub fn new(repo: PostgreSQL, pipeline: CommandPipeline, config: DurableQueueConfig) -> Self {
+        Self {
+            repo,
+            pipeline,
+            config,
+        }
+    }
+
+    pub async fn run(self) {
+        loop {
+            let commands = match self
+                .repo
+                .claim_commands(self.config.batch_size, self.config.visibility_timeout)
+                .await
+            {
+                Ok(commands) => commands,
+                Err(e) => {
+                    error!("Failed to claim queued commands: {}", e);
+                    Vec::new()
+                }
+            };
+
+            if commands.is_empty() {
+                tokio::time::sleep(self.config.poll_interval).await;
+                continue;
+            }
+
+            let mut batch = JoinSet::new();
+            for command in commands {
+                batch.spawn(handle(self.repo.clone(), self.pipeline.clone(), command));
+            }
+            while batch.join_next().await.is_some() {}
+        }
+    }
+}
+
+async fn handle(repo: PostgreSQL, pipeline: CommandPipeline, command: QueuedCommand) {
+    let request = match pipeline
+        .decoders
+        .decode(&command.command_type, command.payload.clone())
+    {
+        Ok(request) => request,
+        Err(e) => {
+            // Retrying will not make an unknown or malformed payload decodable.
+            report(repo.dead_letter_command(command.id, &e.to_string()).await);
+            return;
+        }
+    };
+
+    let outcome = match pipeline.service.oneshot(request).await {
+        Ok(_) => repo.complete_command(command.id).await,
+        Err(e) if command.attempts >= command.max_attempts => {
+            warn!(
+                "{} {} failed after {} attempts: {}",
+                command.command_type, command.id, command.attempts, e
+            );
+            repo.dead_letter_command(command.id, &e.to_string()).await
+        }
+        Err(e) => {
+            repo.retry_command(command.id, &e.to_string(), backoff(command.attempts))
+                .await
+        }
+    };
+    report(outcome);
+}
+
+fn backoff(attempts: i32) -> Duration {
+    Duration::from_secs(1 << attempts.clamp(0, 8))
+}
+
+fn report(outcome: Result<(), RepositoryError>) {
+    if let Err(e) = outcome {
+        error!("Failed to update queued command: {}", e);
+    }
+}
@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use derive_more::{Display, Error, From};
+
+use crate::{models::InvalidUser, repositories::RepositoryError};
+
+#[derive(Debug, Display, Error, From)]
+pub enum CommandError {
+    #[display("no handler registered for {_0}")]
+    #[from(ignore)]
+    Unregistered(#[error(not(source))] &'static str),
+    #[display("unknown command {_0}")]
+    #[from(ignore)]
+    Unknown(#[error(not(source))] String),
+    #[display("invalid schedule: {_0}")]
+    #[from(ignore)]
+    InvalidSchedule(#[error(not(source))] String),
+    #[display("{_0}")]
+    InvalidUser(InvalidUser),
+    #[display("invalid command payload: {_0}")]
+    Payload(serde_json::Error),
+    #[display("command bus is closed")]
+    Closed,
+    #[display("command queue is full, retry after {retry_after:?}")]
+    #[from(ignore)]
+    Overloaded { retry_after: Duration },
+    #[display("command timed out")]
+    Timeout,
+    #[display("{_0}")]
+    Repository(RepositoryError),
+}
+
+impl CommandError {
+    pub fn is_transient(&self) -> bool {
+        matches!(self, CommandError::Repository(e) if e.is_transient())
+    }
+}
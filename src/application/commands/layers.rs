@@ -0,0 +1,198 @@
+use std::{
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use tower::{Layer, Service, ServiceExt};
+use tracing::{info_span, warn, Instrument};
+
+use super::{BoxFuture, BoxedOutput, CommandError, CommandRequest};
+
+type CommandFuture = BoxFuture<Result<BoxedOutput, CommandError>>;
+
+/// Fails a command with [`CommandError::Timeout`] once `timeout` elapses.
+#[derive(Clone, Debug)]
+pub struct TimeoutLayer {
+    timeout: Duration,
+}
+
+impl TimeoutLayer {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+impl<S> Layer<S> for TimeoutLayer {
+    type Service = Timeout<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Timeout {
+            inner,
+            timeout: self.timeout,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Timeout<S> {
+    inner: S,
+    timeout: Duration,
+}
+
+impl<S> Service<CommandRequest> for Timeout<S>
+where
+    S: Service<CommandRequest, Response = BoxedOutput, Error = CommandError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+{
+    type Response = BoxedOutput;
+    type Error = CommandError;
+    type Future = CommandFuture;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: CommandRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        let timeout = self.timeout;
+        Box::pin(async move {
+            tokio::time::timeout(timeout, inner.oneshot(request))
+                .await
+                .map_err(|_| CommandError::Timeout)?
+        })
+    }
+}
+
+/// Retries commands that failed with a transient database error,
+/// doubling `backoff` after every attempt.
+#[derive(Clone, Debug)]
+pub struct RetryLayer {
+    max_retries: usize,
+    backoff: Duration,
+}
+
+impl RetryLayer {
+    pub fn new(max_retries: usize, backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            backoff,
+        }
+    }
+}
+
+impl<S> Layer<S> for RetryLayer {
+    type Service = Retry<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Retry {
+            inner,
+            max_retries: self.max_retries,
+            backoff: self.backoff,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Retry<S> {
+    inner: S,
+    max_retries: usize,
+    backoff: Duration,
+}
+
+impl<S> Service<CommandRequest> for Retry<S>
+where
+    S: Service<CommandRequest, Response = BoxedOutput, Error = CommandError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+{
+    type Response = BoxedOutput;
+    type Error = CommandError;
+    type Future = CommandFuture;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: CommandRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        let max_retries = self.max_retries;
+        let mut backoff = self.backoff;
+        Box::pin(async move {
+            let mut attempt = 0;
+            loop {
+                match inner.clone().oneshot(request.clone()).await {
+                    Err(e) if e.is_transient() && attempt < max_retries => {
+                        request.record_failure(&e);
+                        attempt += 1;
+                        warn!(
+                            "Retrying {} after transient error ({}/{}): {}",
+                            request.name(),
+                            attempt,
+                            max_retries,
+                            e
+                        );
+                        tokio::time::sleep(backoff).await;
+                        backoff *= 2;
+                    }
+                    result => return result,
+                }
+            }
+        })
+    }
+}
+
+/// Runs every command inside a `command` span and logs its outcome.
+#[derive(Clone, Debug, Default)]
+pub struct TracingLayer;
+
+impl<S> Layer<S> for TracingLayer {
+    type Service = Tracing<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Tracing { inner }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Tracing<S> {
+    inner: S,
+}
+
+impl<S> Service<CommandRequest> for Tracing<S>
+where
+    S: Service<CommandRequest, Response = BoxedOutput, Error = CommandError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+{
+    type Response = BoxedOutput;
+    type Error = CommandError;
+    type Future = CommandFuture;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: CommandRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        let span = info_span!("command", name = request.name());
+        Box::pin(
+            async move {
+                let started = Instant::now();
+                let result = inner.oneshot(request).await;
+                match &result {
+                    Ok(_) => tracing::debug!("handled in {:?}", started.elapsed()),
+                    Err(e) => warn!("failed after {:?}: {}", started.elapsed(), e),
+                }
+                result
+            }
+            .instrument(span),
+        )
+    }
+}
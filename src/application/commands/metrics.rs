@@ -0,0 +1,71 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+/// Counters for a single worker shard.
+#[derive(Debug, Default)]
+pub struct ShardMetrics {
+    queued: AtomicU64,
+    in_flight: AtomicU64,
+    handled: AtomicU64,
+    failed: AtomicU64,
+    shed: AtomicU64,
+    busy_micros: AtomicU64,
+}
+
+impl ShardMetrics {
+    pub(super) fn enqueued(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn rejected(&self) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn shed(&self) {
+        self.rejected();
+        self.shed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn started(&self) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn finished(&self, elapsed: Duration, failed: bool) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        self.handled.fetch_add(1, Ordering::Relaxed);
+        if failed {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+        }
+        self.busy_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, shard: usize) -> ShardSnapshot {
+        ShardSnapshot {
+            shard,
+            queued: self.queued.load(Ordering::Relaxed),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            handled: self.handled.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            shed: self.shed.load(Ordering::Relaxed),
+            busy_micros: self.busy_micros.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ShardSnapshot {
+    pub shard: usize,
+    pub queued: u64,
+    pub in_flight: u64,
+    pub handled: u64,
+    pub failed: u64,
+    /// Commands rejected or spilled because the shard was full.
+    pub shed: u64,
+    pub busy_micros: u64,
+}
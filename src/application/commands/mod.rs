@@ -1,2 +1,24 @@
+mod bus;
+mod config;
+mod durable;
+mod error;
+mod layers;
+mod metrics;
+mod scheduler;
 mod user_commands;
+mod worker;
+
+pub use bus::{
+    command_bus, BoxFuture, BoxedOutput, CommandBus, CommandDecoders, CommandHandler,
+    CommandHandlers, CommandPipeline, CommandReceiver, CommandRequest, CommandService,
+    DurableQueue, Envelope, QueueDepth,
+};
+pub(crate) use config::env_var;
+pub use config::{CommandBusConfig, OverflowPolicy, QueueMode};
+pub use durable::{DurableCommandQueue, DurableQueueConfig, DurableWorker};
+pub use error::CommandError;
+pub use layers::{Retry, RetryLayer, Timeout, TimeoutLayer, Tracing, TracingLayer};
+pub use metrics::{ShardMetrics, ShardSnapshot};
+pub use scheduler::{CommandScheduler, SchedulerConfig};
 pub use user_commands::*;
+pub use worker::CommandWorker;
@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures_util::future::join_all;
+use tracing::{error, info, warn};
+
+use crate::{
+    models::{AttemptError, DeadLetter, ScheduledCommand},
+    repositories::{DeadLetterRepository, ScheduleClaim, ScheduleRepository},
+    PostgreSQL,
+};
+
+use super::{config::env_var, CommandBus, CommandDecoders, CommandError};
+
+/// Tuning of the scheduler, read from `SCHEDULER_BATCH`,
+/// `SCHEDULER_POLL_INTERVAL_MS` and `SCHEDULER_LEASE_SECS`.
+#[derive(Clone, Debug)]
+pub struct SchedulerConfig {
+    /// Due commands claimed per poll.
+    pub batch_size: i64,
+    pub poll_interval: Duration,
+    /// How long claimed occurrences are hidden from other replicas, it
+    /// should outlast the commands. One that could not run, as the bus was
+    /// overloaded, waits this long before it is claimed again.
+    pub lease: Duration,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 32,
+            poll_interval: Duration::from_secs(1),
+            lease: Duration::from_secs(60),
+        }
+    }
+}
+
+impl SchedulerConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            batch_size: env_var("SCHEDULER_BATCH")
+                .map(|batch| batch.max(1) as i64)
+                .unwrap_or(default.batch_size),
+            poll_interval: env_var("SCHEDULER_POLL_INTERVAL_MS")
+                .map(|ms| Duration::from_millis(ms as u64))
+                .unwrap_or(default.poll_interval),
+            lease: env_var("SCHEDULER_LEASE_SECS")
+                .map(|secs| Duration::from_secs(secs as u64))
+                .unwrap_or(default.lease),
+        }
+    }
+}
+
+/// Dispatches due scheduled commands through the bus. Every replica may
+/// run one, a batch is leased as it is claimed so no other replica runs it
+/// until the lease ends, and each occurrence moves on once it was handled
+/// or dead-lettered. One left over by a crash, or still running when its
+/// lease ends, runs again, so commands are run at least once.
+pub struct CommandScheduler {
+    repo: PostgreSQL,
+    bus: CommandBus,
+    decoders: CommandDecoders,
+    config: SchedulerConfig,
+}
+
+impl CommandScheduler {
+    pub fn new(
+        repo: PostgreSQL,
+        bus: CommandBus,
+        decoders: CommandDecoders,
+        config: SchedulerConfig,
+    ) -> Self {
+        Self {
+            repo,
+            bus,
+            decoders,
+            config,
+        }
+    }
+
+    pub async fn run(self) {
+        loop {
+            let claim = match self
+                .repo
+                .claim_due_schedules(self.config.batch_size, self.config.lease)
+                .await
+            {
+                Ok(claim) if !claim.schedules().is_empty() => claim,
+                Ok(_) => {
+                    tokio::time::sleep(self.config.poll_interval).await;
+                    continue;
+                }
+                Err(e) => {
+                    error!("Failed to claim scheduled commands: {}", e);
+                    tokio::time::sleep(self.config.poll_interval).await;
+                    continue;
+                }
+            };
+
+            join_all(claim.schedules().iter().map(|schedule| async {
+                let Some(fired_at) = self.fire(schedule).await else {
+                    return;
+                };
+                if let Err(e) = claim.complete(schedule, fired_at).await {
+                    error!("Failed to complete schedule {}: {}", schedule.id, e);
+                }
+            }))
+            .await;
+        }
+    }
+
+    /// Waits for the command, one that fails is dead-lettered with its
+    /// attempts. Returns when it fired if either worked, an overloaded bus
+    /// leaves the occurrence to its lease.
+    async fn fire(&self, schedule: &ScheduledCommand) -> Option<DateTime<Utc>> {
+        let fired_at = Utc::now();
+        let outcome = match self
+            .decoders
+            .decode(&schedule.command_type, schedule.payload.clone())
+        {
+            Ok(request) => match self.bus.dispatch_request(request.clone()).await {
+                Ok(_) => Ok(()),
+                Err(CommandError::Overloaded { .. }) => {
+                    warn!(
+                        "Bus overloaded, scheduled {} {} runs when its lease ends",
+                        schedule.command_type, schedule.id
+                    );
+                    return None;
+                }
+                Err(e) => {
+                    request.record_failure(&e);
+                    Err((e, request.failures()))
+                }
+            },
+            Err(e) => {
+                let failures = vec![AttemptError::new(1, e.to_string())];
+                Err((e, failures))
+            }
+        };
+
+        match outcome {
+            Ok(()) => info!(
+                "Ran scheduled {} {} due at {}",
+                schedule.command_type, schedule.id, schedule.run_at
+            ),
+            Err((e, failures)) => {
+                error!(
+                    "Failed to run scheduled {} {}: {}",
+                    schedule.command_type, schedule.id, e
+                );
+                let dead_letter = DeadLetter::new(
+                    schedule.command_type.clone(),
+                    schedule.payload.clone(),
+                    schedule.aggregate_id,
+                    failures,
+                );
+                if let Err(e) = self.repo.save_dead_letter(dead_letter).await {
+                    error!("Failed to dead-letter scheduled command: {}", e);
+                    return None;
+                }
+            }
+        }
+        Some(fired_at)
+    }
+}
@@ -1,55 +1,93 @@
-use serde::Deserialize;
-use tokio::sync::mpsc;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-use crate::{domain::Command, proto::CreateUserRequest, services::UserService};
+use crate::{
+    domain::Command,
+    models::{InvalidUser, User},
+};
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CreateUser {
     pub username: String,
     pub email: String,
 }
 
-impl Command for CreateUser {}
+impl Command for CreateUser {
+    const NAME: &'static str = "CreateUser";
+    type Output = ();
+}
 
-impl From<CreateUserRequest> for CreateUser {
-    fn from(value: CreateUserRequest) -> Self {
-        CreateUser {
-            email: value.email,
-            username: value.username,
-        }
+impl CreateUser {
+    /// The user it creates, with the username and email normalized.
+    pub fn user(&self) -> Result<User, InvalidUser> {
+        let (username, email) = User::validate(&self.username, &self.email)?;
+        Ok(User::new(username, email))
     }
 }
 
-pub enum CommandMessage {
-    CreateUser(CreateUser),
+/// `id` and `expected_version` are taken from the path and `If-Match` header on REST.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UpdateUser {
+    #[serde(default)]
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+    #[serde(default)]
+    pub expected_version: Option<i64>,
 }
 
-pub struct CommandHandler {
-    receiver: mpsc::Receiver<CommandMessage>,
+impl Command for UpdateUser {
+    const NAME: &'static str = "UpdateUser";
+    type Output = User;
+
+    fn aggregate_id(&self) -> Option<Uuid> {
+        Some(self.id)
+    }
 }
 
-impl CommandHandler {
-    pub fn new(receiver: mpsc::Receiver<CommandMessage>) -> Self {
-        CommandHandler { receiver }
+impl UpdateUser {
+    /// The user it saves, with the username and email normalized.
+    pub fn user(&self) -> Result<User, InvalidUser> {
+        let (username, email) = User::validate(&self.username, &self.email)?;
+        Ok(User {
+            id: self.id,
+            username: username.into_inner(),
+            email: email.into_inner(),
+            version: self.expected_version.unwrap_or_default(),
+        })
     }
+}
+
+/// Soft deletes a user, `expected_version` comes from `If-Match` on REST.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeleteUser {
+    pub id: Uuid,
+    #[serde(default)]
+    pub expected_version: Option<i64>,
+}
+
+impl Command for DeleteUser {
+    const NAME: &'static str = "DeleteUser";
+    type Output = ();
 
-    pub async fn run(self, user_service: UserService) {
-        let mut receiver = self.receiver;
-        while let Some(command) = receiver.recv().await {
-            match command {
-                CommandMessage::CreateUser(cmd) => {
-                    if let Err(e) = user_service.handle_create_user(cmd).await {
-                        //TODO: Add error Hander and bubble up this error to send 400
-                        tracing::error!("Failed to handle CreateUser command: {}", e);
-                    }
-                }
-            }
-        }
+    fn aggregate_id(&self) -> Option<Uuid> {
+        Some(self.id)
     }
 }
 
-pub async fn send_command(sender: mpsc::Sender<CommandMessage>, command: CommandMessage) {
-    if sender.send(command).await.is_err() {
-        eprintln!("Failed to send command");
+/// Welcomes a newly created user, sent by the user provisioning workflow.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SendWelcomeEmail {
+    pub user_id: Uuid,
+    pub username: String,
+    pub email: String,
+}
+
+impl Command for SendWelcomeEmail {
+    const NAME: &'static str = "SendWelcomeEmail";
+    type Output = ();
+
+    fn aggregate_id(&self) -> Option<Uuid> {
+        Some(self.user_id)
     }
 }
@@ -0,0 +1,103 @@
+use std::{sync::Arc, time::Instant};
+
+use tokio::{sync::mpsc, task::JoinSet};
+use tower::ServiceExt;
+use tracing::error;
+
+use crate::{models::DeadLetter, repositories::DeadLetterRepository, PostgreSQL};
+
+use super::{
+    CommandError, CommandPipeline, CommandReceiver, CommandRequest, CommandService, Envelope,
+    ShardMetrics,
+};
+
+/// Runs one task per shard: a shard handles its commands in order while
+/// shards run in parallel.
+pub struct CommandWorker {
+    receiver: CommandReceiver,
+    service: CommandService,
+    dead_letters: Option<PostgreSQL>,
+}
+
+impl CommandWorker {
+    pub fn new(receiver: CommandReceiver, pipeline: CommandPipeline) -> Self {
+        CommandWorker {
+            receiver,
+            service: pipeline.service,
+            dead_letters: None,
+        }
+    }
+
+    /// Keeps fire-and-forget commands that still fail after the pipeline's
+    /// retries instead of only logging them.
+    pub fn with_dead_letters(mut self, repo: PostgreSQL) -> Self {
+        self.dead_letters = Some(repo);
+        self
+    }
+
+    pub async fn run(self) {
+        let mut shards = JoinSet::new();
+        for (shard, receiver) in self.receiver.shards.into_iter().enumerate() {
+            shards.spawn(run_shard(
+                receiver,
+                self.service.clone(),
+                self.dead_letters.clone(),
+                self.receiver.metrics.clone(),
+                shard,
+            ));
+        }
+        while shards.join_next().await.is_some() {}
+    }
+}
+
+async fn run_shard(
+    mut receiver: mpsc::Receiver<Envelope>,
+    service: CommandService,
+    dead_letters: Option<PostgreSQL>,
+    metrics: Arc<[ShardMetrics]>,
+    shard: usize,
+) {
+    let metrics = &metrics[shard];
+    while let Some(Envelope { request, reply }) = receiver.recv().await {
+        let name = request.name();
+        let original = match (&reply, &dead_letters) {
+            (None, Some(_)) => Some(request.clone()),
+            _ => None,
+        };
+        metrics.started();
+        let started = Instant::now();
+        let result = service.clone().oneshot(request).await;
+        metrics.finished(started.elapsed(), result.is_err());
+
+        match reply {
+            Some(reply) => {
+                let _ = reply.send(result);
+            }
+            None => {
+                if let Err(e) = result {
+                    error!(
+                        "Failed to handle {} command on shard {}: {}",
+                        name, shard, e
+                    );
+                    if let (Some(repo), Some(request)) = (&dead_letters, original) {
+                        dead_letter(repo, request, e).await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Keeps the attempts the retry layer logged along with the final error.
+async fn dead_letter(repo: &PostgreSQL, request: CommandRequest, e: CommandError) {
+    request.record_failure(&e);
+    let dead_letter = DeadLetter::new(
+        request.name().to_string(),
+        request.payload(),
+        request.aggregate_id(),
+        request.failures(),
+    );
+    if let Err(e) = repo.save_dead_letter(dead_letter).await {
+        error!("Failed to dead-letter {} command: {}", request.name(), e);
+    }
+}
@@ -0,0 +1,41 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+tokio::task_local! {
+    static SESSION: Arc<Session>;
+}
+
+/// Read consistency of the request being served, see [`scope`].
+#[derive(Debug, Default)]
+pub struct Session {
+    read_primary: bool,
+    wrote: AtomicBool,
+}
+
+/// Runs `future` as one client request, returning whether it sent any
+/// command. With `read_primary` its queries skip the read replica.
+pub async fn scope<F: Future>(read_primary: bool, future: F) -> (F::Output, bool) {
+    let session = Arc::new(Session {
+        read_primary,
+        wrote: AtomicBool::new(false),
+    });
+    let output = SESSION.scope(session.clone(), future).await;
+    (output, session.wrote.load(Ordering::Relaxed))
+}
+
+/// Called by the command bus for every command it is given.
+pub fn record_write() {
+    let _ = SESSION.try_with(|session| session.wrote.store(true, Ordering::Relaxed));
+}
+
+/// Whether queries of the current request must read from the primary.
+pub fn reads_primary() -> bool {
+    SESSION
+        .try_with(|session| session.read_primary)
+        .unwrap_or(false)
+}
@@ -0,0 +1,33 @@
+use tokio::sync::broadcast;
+
+use crate::events::StoredEvent;
+
+/// Fans committed domain events out to in-process subscribers such as
+/// process managers. Subscribers that fall more than `capacity` events
+/// behind skip the oldest ones.
+#[derive(Clone, Debug)]
+pub struct EventBus {
+    sender: broadcast::Sender<StoredEvent>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: StoredEvent) {
+        // No subscribers is fine, the event is already stored.
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<StoredEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(1024)
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::VecDeque;
+
+use derive_more::{Display, Error, From};
+use futures_util::{stream, Stream};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use uuid::Uuid;
+
+use crate::{
+    events::StoredEvent,
+    repositories::{RepositoryError, UserRepository},
+    EventBus,
+};
+
+/// Stored events read per query while replaying.
+const PAGE: i64 = 100;
+
+/// Which events a feed delivers, an empty list matches everything.
+#[derive(Clone, Debug, Default)]
+pub struct EventFilter {
+    pub event_types: Vec<String>,
+    pub aggregate_ids: Vec<Uuid>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &StoredEvent) -> bool {
+        (self.event_types.is_empty() || self.event_types.contains(&event.event_type))
+            && (self.aggregate_ids.is_empty() || self.aggregate_ids.contains(&event.aggregate_id))
+    }
+}
+
+#[derive(Debug, Display, Error, From)]
+pub enum FeedError {
+    /// The subscriber was too slow and events were dropped, it has to
+    /// resume from the last event it saw.
+    #[display("fell {_0} events behind")]
+    #[from(ignore)]
+    Lagged(#[error(not(source))] u64),
+    #[display("{_0}")]
+    Repository(RepositoryError),
+}
+
+/// Committed events matching `filter`, in the order of their
+/// [`StoredEvent::position`]: those stored after
+/// the `after` event first, every stored one when `after` is unknown, then
+/// the ones published on `events` from now on. The feed ends after an
+/// error.
+pub fn event_feed<R>(
+    repo: R,
+    events: &EventBus,
+    filter: EventFilter,
+    after: Option<Uuid>,
+) -> impl Stream<Item = Result<StoredEvent, FeedError>> + Send + 'static
+where
+    R: UserRepository + Send + Sync + 'static,
+{
+    // Subscribed before replaying, so nothing committed meanwhile is missed.
+    let feed = Feed {
+        repo,
+        receiver: events.subscribe(),
+        filter,
+        replay_after: after,
+        replayed_up_to: None,
+        pending: VecDeque::new(),
+        done: false,
+    };
+    stream::unfold(feed, |mut feed| async move {
+        let item = feed.next().await?;
+        Some((item, feed))
+    })
+}
+
+struct Feed<R> {
+    repo: R,
+    receiver: Receiver<StoredEvent>,
+    filter: EventFilter,
+    /// Where the next replayed page starts, `None` once caught up.
+    replay_after: Option<Uuid>,
+    /// The position of the last event replayed. Published events up to it
+    /// were replayed already, those published while replaying included.
+    replayed_up_to: Option<(i64, i64)>,
+    pending: VecDeque<StoredEvent>,
+    done: bool,
+}
+
+impl<R: UserRepository> Feed<R> {
+    async fn next(&mut self) -> Option<Result<StoredEvent, FeedError>> {
+        while !self.done {
+            if let Some(event) = self.pending.pop_front() {
+                if self.filter.matches(&event) {
+                    return Some(Ok(event));
+                }
+                continue;
+            }
+
+            if let Some(after) = self.replay_after {
+                let page = match self.repo.find_events_after(after, PAGE).await {
+                    Ok(page) => page,
+                    Err(e) => return self.fail(e.into()),
+                };
+                self.replay_after = page
+                    .last()
+                    .filter(|_| page.len() as i64 == PAGE)
+                    .map(|event| event.id);
+                if let Some(last) = page.last() {
+                    self.replayed_up_to = Some(last.position());
+                }
+                self.pending.extend(page);
+                continue;
+            }
+
+            match self.receiver.recv().await {
+                Ok(event)
+                    if self
+                        .replayed_up_to
+                        .is_some_and(|last| event.position() <= last) => {}
+                Ok(event) => self.pending.push_back(event),
+                Err(RecvError::Lagged(skipped)) => return self.fail(FeedError::Lagged(skipped)),
+                Err(RecvError::Closed) => self.done = true,
+            }
+        }
+        None
+    }
+
+    fn fail(&mut self, e: FeedError) -> Option<Result<StoredEvent, FeedError>> {
+        self.done = true;
+        Some(Err(e))
+    }
+}
@@ -1,2 +1,6 @@
 pub mod commands;
+pub mod consistency;
+pub mod event_bus;
+pub mod event_feed;
 pub mod services;
+pub mod workflows;
@@ -0,0 +1,75 @@
+use uuid::Uuid;
+
+use crate::{
+    commands::{CommandBus, CommandDecoders, CommandError},
+    models::DeadLetter,
+    repositories::{page_size, DeadLetterClaim, DeadLetterRepository, RepositoryError},
+    PostgreSQL,
+};
+
+/// Inspects failed commands and sends them through the bus again.
+#[derive(Clone)]
+pub struct DeadLetterService {
+    pub repo: PostgreSQL,
+    pub bus: CommandBus,
+    pub decoders: CommandDecoders,
+}
+
+impl DeadLetterService {
+    pub fn new(repo: PostgreSQL, bus: CommandBus, decoders: CommandDecoders) -> Self {
+        Self {
+            repo,
+            bus,
+            decoders,
+        }
+    }
+
+    pub async fn list(&self, limit: i64, offset: i64) -> Result<Vec<DeadLetter>, RepositoryError> {
+        self.repo
+            .list_dead_letters(page_size(limit), offset.max(0))
+            .await
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<Option<DeadLetter>, RepositoryError> {
+        self.repo.find_dead_letter(id).await
+    }
+
+    /// Dispatches the original payload and waits for it, the dead letter is
+    /// removed on success and keeps the new errors, retries included,
+    /// otherwise. It stays claimed meanwhile, so a concurrent replay gets
+    /// [`RepositoryError::Locked`] instead of dispatching it again.
+    pub async fn replay(&self, id: Uuid) -> Result<(), CommandError> {
+        let claim = self.repo.claim_dead_letter(id).await?;
+        let dead_letter = claim.dead_letter();
+
+        let request = self
+            .decoders
+            .decode(&dead_letter.command_type, dead_letter.payload.clone());
+        let (result, errors) = match request {
+            Ok(request) => {
+                let result = self.bus.dispatch_request(request.clone()).await;
+                if let Err(e) = &result {
+                    request.record_failure(e);
+                }
+                let errors = request.failures().into_iter().map(|e| e.error).collect();
+                (result.map(|_| ()), errors)
+            }
+            Err(e) => {
+                let error = e.to_string();
+                (Err(e), vec![error])
+            }
+        };
+
+        match result {
+            Ok(()) => Ok(claim.complete().await?),
+            Err(e) => {
+                claim.fail(errors).await?;
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn discard(&self, id: Uuid) -> Result<(), RepositoryError> {
+        self.repo.delete_dead_letter(id).await
+    }
+}
@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    repositories::{IdempotencyRepository, RepositoryError},
+    PostgreSQL,
+};
+
+/// How long a key replays its original response.
+pub const IDEMPOTENCY_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long a request holds its key before it completes, a key left by a
+/// crashed request is taken over by the next one after that.
+pub const IDEMPOTENCY_LEASE: Duration = Duration::from_secs(30);
+
+pub enum Idempotency {
+    /// The key is new, run the request and `complete` it afterwards.
+    Fresh,
+    /// The key was already used for the same request.
+    Replay { response: Vec<u8> },
+    /// The key was already used for a different request.
+    Mismatch,
+    /// The original request has not finished yet, and its lease has not
+    /// ended.
+    InProgress,
+}
+
+#[derive(Clone, Debug)]
+pub struct IdempotencyService {
+    pub repo: PostgreSQL,
+    pub ttl: Duration,
+    pub lease: Duration,
+}
+
+impl IdempotencyService {
+    pub fn new(repo: PostgreSQL) -> Self {
+        Self {
+            repo,
+            ttl: IDEMPOTENCY_TTL,
+            lease: IDEMPOTENCY_LEASE,
+        }
+    }
+
+    pub async fn begin(
+        &self,
+        scope: &str,
+        key: &str,
+        request: &[u8],
+    ) -> Result<Idempotency, RepositoryError> {
+        let request_hash = format!("{:x}", Sha256::digest(request));
+
+        let Some(record) = self
+            .repo
+            .claim_idempotency_key(scope, key, &request_hash, self.ttl, self.lease)
+            .await?
+        else {
+            return Ok(Idempotency::Fresh);
+        };
+
+        if record.request_hash != request_hash {
+            return Ok(Idempotency::Mismatch);
+        }
+
+        match record.response {
+            Some(response) => Ok(Idempotency::Replay { response }),
+            None => Ok(Idempotency::InProgress),
+        }
+    }
+
+    pub async fn complete(
+        &self,
+        scope: &str,
+        key: &str,
+        response: &[u8],
+    ) -> Result<(), RepositoryError> {
+        self.repo
+            .complete_idempotency_key(scope, key, response)
+            .await
+    }
+
+    /// Forgets a key whose request failed so the client can retry it.
+    pub async fn release(&self, scope: &str, key: &str) -> Result<(), RepositoryError> {
+        self.repo.release_idempotency_key(scope, key).await
+    }
+}
@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+use tracing::info;
+
+use crate::commands::{CommandError, CommandHandler, CommandHandlers, SendWelcomeEmail};
+
+/// Delivers email. [`LogMailer`] only logs it, plug in a transport for
+/// real delivery.
+#[async_trait]
+pub trait Mailer: Send + Sync + 'static {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), CommandError>;
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, to: &str, subject: &str, _body: &str) -> Result<(), CommandError> {
+        info!("Mail to {}: {}", to, subject);
+        Ok(())
+    }
+}
+
+/// Handles the commands that send email.
+pub struct MailService<M> {
+    mailer: Arc<M>,
+}
+
+impl<M> Clone for MailService<M> {
+    fn clone(&self) -> Self {
+        Self {
+            mailer: self.mailer.clone(),
+        }
+    }
+}
+
+impl<M: Mailer> MailService<M> {
+    pub fn new(mailer: M) -> Self {
+        Self {
+            mailer: Arc::new(mailer),
+        }
+    }
+
+    /// Registers the handlers for every mail command.
+    pub fn register(self, handlers: CommandHandlers) -> CommandHandlers {
+        handlers.register::<SendWelcomeEmail, _>(self)
+    }
+}
+
+#[async_trait]
+impl<M: Mailer> CommandHandler<SendWelcomeEmail> for MailService<M> {
+    async fn handle(&self, command: SendWelcomeEmail) -> Result<(), CommandError> {
+        let body = format!("Hi {}, welcome aboard.", command.username);
+        self.mailer.send(&command.email, "Welcome", &body).await
+    }
+}
@@ -1,2 +1,19 @@
+mod dead_letter_service;
+mod idempotency_service;
+mod mail_service;
+mod schedule_service;
+mod user_import;
 mod user_service;
+mod workflow_service;
+
+pub use dead_letter_service::DeadLetterService;
+pub use idempotency_service::{
+    Idempotency, IdempotencyService, IDEMPOTENCY_LEASE, IDEMPOTENCY_TTL,
+};
+pub use mail_service::{LogMailer, MailService, Mailer};
+pub use schedule_service::{ScheduleService, Trigger};
+pub use user_import::{
+    import_batches, ImportOutcome, ImportRecord, ImportSummary, IMPORT_BATCH, IMPORT_BATCH_WAIT,
+};
 pub use user_service::UserService;
+pub use workflow_service::WorkflowService;
@@ -0,0 +1,114 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration, Utc};
+use cron::Schedule;
+use uuid::Uuid;
+
+use crate::{
+    commands::{CommandDecoders, CommandError, CommandRequest},
+    models::ScheduledCommand,
+    repositories::{page_size, RepositoryError, ScheduleRepository},
+    Command, PostgreSQL,
+};
+
+/// When a scheduled command runs.
+#[derive(Clone, Debug)]
+pub enum Trigger {
+    /// Once, at the given instant.
+    At(DateTime<Utc>),
+    /// On every occurrence of a cron expression, in UTC.
+    Cron(String),
+}
+
+impl Trigger {
+    /// Once, `secs` seconds from now, `None` when that instant is out of
+    /// range.
+    pub fn after_secs(secs: i64) -> Option<Self> {
+        let delay = Duration::try_seconds(secs)?;
+        Utc::now().checked_add_signed(delay).map(Trigger::At)
+    }
+}
+
+#[derive(Clone)]
+pub struct ScheduleService {
+    pub repo: PostgreSQL,
+    pub decoders: CommandDecoders,
+}
+
+impl ScheduleService {
+    pub fn new(repo: PostgreSQL, decoders: CommandDecoders) -> Self {
+        Self { repo, decoders }
+    }
+
+    pub async fn schedule<C: Command>(
+        &self,
+        command: C,
+        trigger: Trigger,
+    ) -> Result<ScheduledCommand, CommandError> {
+        self.schedule_request(CommandRequest::new(command), trigger)
+            .await
+    }
+
+    /// Schedules a command given by name, rejecting payloads no registered
+    /// handler could decode.
+    pub async fn schedule_serialized(
+        &self,
+        command_type: &str,
+        payload: serde_json::Value,
+        trigger: Trigger,
+    ) -> Result<ScheduledCommand, CommandError> {
+        let request = self.decoders.decode(command_type, payload)?;
+        self.schedule_request(request, trigger).await
+    }
+
+    async fn schedule_request(
+        &self,
+        request: CommandRequest,
+        trigger: Trigger,
+    ) -> Result<ScheduledCommand, CommandError> {
+        let now = Utc::now();
+        let (run_at, cron) = match trigger {
+            Trigger::At(run_at) => (run_at, None),
+            Trigger::Cron(expression) => {
+                let schedule = Schedule::from_str(&expression)
+                    .map_err(|e| CommandError::InvalidSchedule(e.to_string()))?;
+                let run_at = schedule.after(&now).next().ok_or_else(|| {
+                    CommandError::InvalidSchedule(format!("{expression} never occurs"))
+                })?;
+                (run_at, Some(expression))
+            }
+        };
+
+        let schedule = ScheduledCommand {
+            id: Uuid::now_v7(),
+            command_type: request.name().to_string(),
+            payload: request.payload(),
+            aggregate_id: request.aggregate_id(),
+            run_at,
+            cron,
+            last_run_at: None,
+            created_at: now,
+            leased_until: None,
+        };
+        self.repo.save_schedule(schedule.clone()).await?;
+        Ok(schedule)
+    }
+
+    pub async fn list(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<ScheduledCommand>, RepositoryError> {
+        self.repo
+            .list_schedules(page_size(limit), offset.max(0))
+            .await
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<Option<ScheduledCommand>, RepositoryError> {
+        self.repo.find_schedule(id).await
+    }
+
+    pub async fn cancel(&self, id: Uuid) -> Result<(), RepositoryError> {
+        self.repo.delete_schedule(id).await
+    }
+}
@@ -0,0 +1,194 @@
+use std::{collections::HashSet, pin::Pin, time::Duration};
+
+use futures_util::{stream, Stream, StreamExt};
+use serde::Serialize;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::{
+    commands::CreateUser,
+    models::User,
+    repositories::{RepositoryError, UnitOfWork, UserRepository, UserStore},
+};
+
+use super::UserService;
+
+/// Records are inserted at most this many per statement.
+pub const IMPORT_BATCH: usize = 500;
+
+/// How long a batch waits for more records after its first one.
+pub const IMPORT_BATCH_WAIT: Duration = Duration::from_millis(100);
+
+/// What became of one imported record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportOutcome {
+    Created(Uuid),
+    /// The username or email is taken, by an existing user or an earlier
+    /// record.
+    Duplicate,
+    Invalid(String),
+}
+
+/// Counts of [`ImportOutcome`]s.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub created: u64,
+    pub duplicate: u64,
+    pub invalid: u64,
+}
+
+impl ImportSummary {
+    pub fn add(&mut self, outcome: &ImportOutcome) {
+        match outcome {
+            ImportOutcome::Created(_) => self.created += 1,
+            ImportOutcome::Duplicate => self.duplicate += 1,
+            ImportOutcome::Invalid(_) => self.invalid += 1,
+        }
+    }
+}
+
+/// A record to import, or why it could not be read.
+pub type ImportRecord = Result<CreateUser, String>;
+
+/// `records` in batches of [`IMPORT_BATCH`], or of what arrived within
+/// [`IMPORT_BATCH_WAIT`] of the first record of a batch when they trickle
+/// in, such as over a network.
+pub fn import_batches<S>(records: S) -> impl Stream<Item = Vec<S::Item>> + Send + 'static
+where
+    S: Stream + Send + 'static,
+    S::Item: Send,
+{
+    stream::unfold(Some(Box::pin(records)), |records| async move {
+        let mut records = records?;
+        let mut batch = vec![records.next().await?];
+        let deadline = Instant::now() + IMPORT_BATCH_WAIT;
+        while batch.len() < IMPORT_BATCH {
+            match tokio::time::timeout_at(deadline, records.next()).await {
+                Ok(Some(record)) => batch.push(record),
+                Ok(None) => return Some((batch, None)),
+                Err(_) => break,
+            }
+        }
+        Some((batch, Some(records)))
+    })
+}
+
+/// A user for `record` if its username and email are valid.
+fn new_user(record: CreateUser) -> Result<User, String> {
+    record.user().map_err(|e| e.to_string())
+}
+
+impl<R: UserStore> UserService<R> {
+    /// Creates the users of `records` a batch at a time, yielding one
+    /// outcome per record in order. An error of `records` or of the
+    /// repository ends the import, the batches before it stay created.
+    /// A `dry_run` inserts each batch in a unit of work that is rolled back
+    /// right away, remembering the usernames and emails of the earlier
+    /// batches, so its outcomes are those the import would have.
+    pub fn import_users<S, E>(
+        &self,
+        records: S,
+        dry_run: bool,
+    ) -> impl Stream<Item = Result<ImportOutcome, E>> + Send + 'static
+    where
+        S: Stream<Item = Result<ImportRecord, E>> + Send + 'static,
+        E: From<RepositoryError> + Send + 'static,
+    {
+        let import = Import {
+            repo: self.repo.clone(),
+            batches: Box::pin(import_batches(records)),
+            dry_run,
+            taken: Taken::default(),
+        };
+        stream::unfold(Some(import), |import| async move {
+            let mut import = import?;
+            let records = import.batches.next().await?;
+            let outcomes = import.batch(records).await;
+            let failed = outcomes.last().is_some_and(Result::is_err);
+            Some((stream::iter(outcomes), (!failed).then_some(import)))
+        })
+        .flatten()
+    }
+}
+
+struct Import<R: UserStore, B> {
+    repo: R,
+    batches: Pin<Box<B>>,
+    dry_run: bool,
+    /// What the rolled back batches of a dry run would have taken.
+    taken: Taken,
+}
+
+#[derive(Default)]
+struct Taken {
+    usernames: HashSet<String>,
+    emails: HashSet<String>,
+}
+
+impl<R: UserStore, B> Import<R, B> {
+    /// Inserts the valid records up to the first error, the error if any
+    /// comes last.
+    async fn batch<E: From<RepositoryError>>(
+        &mut self,
+        records: Vec<Result<ImportRecord, E>>,
+    ) -> Vec<Result<ImportOutcome, E>> {
+        let mut failure = None;
+        let mut users = Vec::new();
+        let mut outcomes = Vec::new();
+        for record in records {
+            match record.map(|record| record.and_then(new_user)) {
+                Ok(Ok(user)) => {
+                    outcomes.push(ImportOutcome::Created(user.id));
+                    users.push(user);
+                }
+                Ok(Err(reason)) => outcomes.push(ImportOutcome::Invalid(reason)),
+                Err(e) => {
+                    failure = Some(e);
+                    break;
+                }
+            }
+        }
+
+        let created = match self.save(users).await {
+            Ok(created) => created,
+            Err(e) => return vec![Err(e.into())],
+        };
+        let mut results: Vec<_> = outcomes
+            .into_iter()
+            .map(|outcome| match outcome {
+                ImportOutcome::Created(id) if !created.contains(&id) => {
+                    Ok(ImportOutcome::Duplicate)
+                }
+                outcome => Ok(outcome),
+            })
+            .collect();
+        results.extend(failure.map(Err));
+        results
+    }
+
+    /// The ids of the users created, in a unit of work of their own that a
+    /// dry run rolls back.
+    async fn save(&mut self, mut users: Vec<User>) -> Result<HashSet<Uuid>, RepositoryError> {
+        if self.dry_run {
+            let taken = &self.taken;
+            users.retain(|user| {
+                !taken.usernames.contains(&user.username) && !taken.emails.contains(&user.email)
+            });
+        }
+        if users.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let uow = self.repo.begin().await?;
+        let created: HashSet<_> = uow.save_users(users.clone()).await?.into_iter().collect();
+        if !self.dry_run {
+            uow.commit().await?;
+            return Ok(created);
+        }
+        uow.rollback().await?;
+        for user in users.into_iter().filter(|user| created.contains(&user.id)) {
+            self.taken.usernames.insert(user.username);
+            self.taken.emails.insert(user.email);
+        }
+        Ok(created)
+    }
+}
@@ -1,40 +1,165 @@
-use tokio::sync::mpsc;
+use axum::async_trait;
+use futures_util::{future, stream, Stream, StreamExt};
 use uuid::Uuid;
 
 use crate::{
-    commands::{send_command, CommandMessage, CreateUser},
+    commands::{
+        CommandBus, CommandError, CommandHandler, CommandHandlers, CreateUser, DeleteUser,
+        UpdateUser,
+    },
+    event_feed,
+    events::{StoredEvent, USER_EVENT_TYPES},
     models::User,
-    repositories::UserRepository,
-    PostgreSQL,
+    repositories::{RepositoryError, UnitOfWork, UserRepository, UserStore},
+    EventBus, EventFilter, FeedError, PostgreSQL,
 };
 
+/// Users are read this many at a time by [`UserService::export_users`].
+const EXPORT_PAGE: i64 = 500;
+
+/// User commands and queries over any transactional backend.
 #[derive(Clone, Debug)]
-pub struct UserService {
-    pub repo: PostgreSQL,
-    pub sender: mpsc::Sender<CommandMessage>,
+pub struct UserService<R = PostgreSQL> {
+    pub repo: R,
+    pub bus: CommandBus,
+    events: Option<EventBus>,
 }
 
-impl UserService {
-    pub fn new(repo: PostgreSQL, sender: mpsc::Sender<CommandMessage>) -> Self {
-        Self { repo, sender }
+impl<R: UserStore> UserService<R> {
+    pub fn new(repo: R, bus: CommandBus) -> Self {
+        Self {
+            repo,
+            bus,
+            events: None,
+        }
+    }
+
+    /// Follows the events committed through `events` in [`Self::watch_users`].
+    pub fn with_event_bus(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Registers the handlers for every user command.
+    pub fn register(self, handlers: CommandHandlers) -> CommandHandlers {
+        handlers
+            .register::<CreateUser, _>(self.clone())
+            .register::<UpdateUser, _>(self.clone())
+            .register::<DeleteUser, _>(self)
+    }
+
+    pub async fn handle_create_user(&self, cmd: CreateUser) -> Result<(), CommandError> {
+        let user = cmd.user()?;
+
+        let uow = self.repo.begin().await?;
+        uow.save_user(user).await?;
+        Ok(uow.commit().await?)
     }
 
-    pub async fn handle_create_user(&self, cmd: CreateUser) -> Result<(), sqlx::Error> {
-        let user = User {
-            id: Uuid::now_v7(),
-            username: cmd.username,
-            email: cmd.email,
-        };
+    pub async fn handle_update_user(&self, cmd: UpdateUser) -> Result<User, CommandError> {
+        let user = cmd.user()?;
 
-        self.repo.save_user(user).await?;
-        Ok(())
+        let uow = self.repo.begin().await?;
+        let updated = uow.update_user(user, cmd.expected_version).await?;
+        uow.commit().await?;
+        Ok(updated)
     }
 
-    pub async fn handle_get_user_by_id(&self, id: Uuid) -> Result<Option<User>, sqlx::Error> {
+    pub async fn handle_delete_user(&self, cmd: DeleteUser) -> Result<(), RepositoryError> {
+        let uow = self.repo.begin().await?;
+        uow.delete_user(cmd.id, cmd.expected_version).await?;
+        uow.commit().await
+    }
+
+    pub async fn handle_get_user_by_id(&self, id: Uuid) -> Result<Option<User>, RepositoryError> {
         self.repo.find_user_by_id(id).await
     }
 
-    pub async fn create_user(&self, cmd: CreateUser) {
-        send_command(self.sender.clone(), CommandMessage::CreateUser(cmd)).await;
+    pub async fn handle_list_users(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<User>, RepositoryError> {
+        self.repo.list_users(after, limit).await
+    }
+
+    /// Every user in creation order, a page at a time so the table is
+    /// never held in memory.
+    pub fn export_users(
+        &self,
+    ) -> impl Stream<Item = Result<Vec<User>, RepositoryError>> + Send + 'static {
+        let repo = self.repo.clone();
+        stream::try_unfold(Some(None), move |after| {
+            let repo = repo.clone();
+            async move {
+                let Some(after) = after else {
+                    return Ok(None);
+                };
+                let users = repo.list_users(after, EXPORT_PAGE).await?;
+                let next = users
+                    .last()
+                    .filter(|_| users.len() as i64 == EXPORT_PAGE)
+                    .map(|user| Some(user.id));
+                Ok(Some((users, next)))
+            }
+        })
+    }
+
+    /// User events as they are committed, after replaying those stored
+    /// since `after`. Events of other types are skipped. `None` without an
+    /// event bus.
+    pub fn watch_users(
+        &self,
+        filter: EventFilter,
+        after: Option<Uuid>,
+    ) -> Option<impl Stream<Item = Result<StoredEvent, FeedError>> + Send + 'static> {
+        let events = self.events.as_ref()?;
+        let feed = event_feed(self.repo.clone(), events, filter, after).filter(|event| {
+            let skipped = matches!(event, Ok(event) if !USER_EVENT_TYPES.contains(&event.event_type.as_str()));
+            future::ready(!skipped)
+        });
+        Some(feed)
+    }
+
+    /// Queues `cmd` once its username and email are valid, so callers not
+    /// waiting for the handler still hear about them.
+    pub async fn create_user(&self, cmd: CreateUser) -> Result<(), CommandError> {
+        cmd.user()?;
+        self.bus.send(cmd).await
+    }
+
+    /// Dispatches `cmd` and waits for its handler, unlike [`Self::create_user`].
+    pub async fn create_user_and_wait(&self, cmd: CreateUser) -> Result<(), CommandError> {
+        cmd.user()?;
+        self.bus.dispatch(cmd).await
+    }
+
+    pub async fn update_user(&self, cmd: UpdateUser) -> Result<User, CommandError> {
+        self.bus.dispatch(cmd).await
+    }
+
+    pub async fn delete_user(&self, cmd: DeleteUser) -> Result<(), CommandError> {
+        self.bus.dispatch(cmd).await
+    }
+}
+
+#[async_trait]
+impl<R: UserStore> CommandHandler<CreateUser> for UserService<R> {
+    async fn handle(&self, command: CreateUser) -> Result<(), CommandError> {
+        self.handle_create_user(command).await
+    }
+}
+
+#[async_trait]
+impl<R: UserStore> CommandHandler<UpdateUser> for UserService<R> {
+    async fn handle(&self, command: UpdateUser) -> Result<User, CommandError> {
+        self.handle_update_user(command).await
+    }
+}
+
+#[async_trait]
+impl<R: UserStore> CommandHandler<DeleteUser> for UserService<R> {
+    async fn handle(&self, command: DeleteUser) -> Result<(), CommandError> {
+        Ok(self.handle_delete_user(command).await?)
     }
 }
@@ -0,0 +1,29 @@
+use uuid::Uuid;
+
+use crate::{
+    models::Workflow,
+    repositories::{page_size, RepositoryError, WorkflowRepository},
+    PostgreSQL,
+};
+
+/// Read access to workflow state for operators.
+#[derive(Clone, Debug)]
+pub struct WorkflowService {
+    pub repo: PostgreSQL,
+}
+
+impl WorkflowService {
+    pub fn new(repo: PostgreSQL) -> Self {
+        Self { repo }
+    }
+
+    pub async fn list(&self, limit: i64, offset: i64) -> Result<Vec<Workflow>, RepositoryError> {
+        self.repo
+            .list_workflows(page_size(limit), offset.max(0))
+            .await
+    }
+
+    pub async fn get(&self, id: Uuid) -> Result<Option<Workflow>, RepositoryError> {
+        self.repo.find_workflow(id).await
+    }
+}
@@ -0,0 +1,7 @@
+mod process_manager;
+mod runner;
+mod user_provisioning;
+
+pub use process_manager::{ProcessManager, Step};
+pub use runner::{WorkflowConfig, WorkflowRunner};
+pub use user_provisioning::UserProvisioning;
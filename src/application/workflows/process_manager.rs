@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::{
+    commands::{CommandError, CommandRequest},
+    events::StoredEvent,
+    Command,
+};
+
+type BuildCommand = Arc<dyn Fn(&Value) -> Result<CommandRequest, CommandError> + Send + Sync>;
+
+/// One step of a workflow: the command to send and, optionally, the
+/// command undoing it when a later step fails. Both are built from the
+/// workflow's context.
+#[derive(Clone)]
+pub struct Step {
+    pub name: &'static str,
+    action: BuildCommand,
+    compensation: Option<BuildCommand>,
+}
+
+impl Step {
+    pub fn new<C, F>(name: &'static str, action: F) -> Self
+    where
+        C: Command,
+        F: Fn(&Value) -> Result<C, CommandError> + Send + Sync + 'static,
+    {
+        Self {
+            name,
+            action: Arc::new(move |context| action(context).map(CommandRequest::new)),
+            compensation: None,
+        }
+    }
+
+    pub fn compensate_with<C, F>(mut self, compensation: F) -> Self
+    where
+        C: Command,
+        F: Fn(&Value) -> Result<C, CommandError> + Send + Sync + 'static,
+    {
+        self.compensation = Some(Arc::new(move |context| {
+            compensation(context).map(CommandRequest::new)
+        }));
+        self
+    }
+
+    pub(super) fn action(&self, context: &Value) -> Result<CommandRequest, CommandError> {
+        (self.action)(context)
+    }
+
+    pub(super) fn compensation(
+        &self,
+        context: &Value,
+    ) -> Option<Result<CommandRequest, CommandError>> {
+        self.compensation
+            .as_ref()
+            .map(|compensation| compensation(context))
+    }
+}
+
+/// Reacts to domain events by running a multi-step workflow through the
+/// command bus, one workflow per manager and correlated aggregate.
+pub trait ProcessManager: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+
+    /// The context of a new workflow if `event` starts one.
+    fn start(&self, event: &StoredEvent) -> Option<Value>;
+
+    /// Run in order, a failure compensates the finished ones in reverse.
+    fn steps(&self) -> Vec<Step>;
+}
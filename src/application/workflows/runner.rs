@@ -0,0 +1,501 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use serde_json::{json, Value};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    commands::{env_var, CommandBus, CommandError, CommandRequest},
+    events::StoredEvent,
+    models::{Workflow, WorkflowStatus},
+    repositories::{RepositoryError, WorkflowRepository},
+    EventBus, PostgreSQL,
+};
+
+use super::{ProcessManager, Step};
+
+/// Tuning of the workflow runner, read from `WORKFLOW_BATCH`,
+/// `WORKFLOW_POLL_INTERVAL_MS` and `WORKFLOW_LEASE_SECS`.
+#[derive(Clone, Debug)]
+pub struct WorkflowConfig {
+    /// Unfinished workflows claimed per poll.
+    pub batch_size: i64,
+    /// How often workflows whose lease ended are looked for.
+    pub poll_interval: Duration,
+    /// How long a runner holds a workflow without sending a command, it
+    /// should outlast the steps.
+    pub lease: Duration,
+}
+
+impl Default for WorkflowConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 32,
+            poll_interval: Duration::from_secs(5),
+            lease: Duration::from_secs(60),
+        }
+    }
+}
+
+impl WorkflowConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            batch_size: env_var("WORKFLOW_BATCH")
+                .map(|batch| batch.max(1) as i64)
+                .unwrap_or(default.batch_size),
+            poll_interval: env_var("WORKFLOW_POLL_INTERVAL_MS")
+                .map(|ms| Duration::from_millis(ms as u64))
+                .unwrap_or(default.poll_interval),
+            lease: env_var("WORKFLOW_LEASE_SECS")
+                .map(|secs| Duration::from_secs(secs as u64))
+                .unwrap_or(default.lease),
+        }
+    }
+}
+
+/// Stored events read per query while catching up.
+const PAGE: i64 = 100;
+
+struct Registered {
+    manager: Arc<dyn ProcessManager>,
+    steps: Arc<[Step]>,
+    /// The position of the last event given to the manager, loaded by the
+    /// first catch-up.
+    checkpoint: Option<(i64, i64)>,
+    saved: Option<(i64, i64)>,
+}
+
+/// Feeds events from the [`EventBus`] to every registered process manager
+/// and drives the workflows they start. It subscribes when created, so
+/// events published before it runs are not missed. Each manager has a
+/// checkpoint in the event store, on startup and whenever the runner lags
+/// behind the bus it reads the stored events after it, so no event is
+/// missed while it was down or slow. A new manager starts from the events
+/// to come. Starting the workflow of an event twice does nothing, so an
+/// event read both ways is harmless. A runner leases the
+/// workflows it drives and renews the lease before every command, so
+/// replicas never drive the same one, and one whose runner stopped, after
+/// a crash or a failed save, is resumed once its lease ends.
+pub struct WorkflowRunner {
+    repo: PostgreSQL,
+    bus: CommandBus,
+    events: broadcast::Receiver<StoredEvent>,
+    managers: Vec<Registered>,
+    config: WorkflowConfig,
+    /// Set until a catch-up read every stored event, live events do not
+    /// move the checkpoints meanwhile.
+    behind: bool,
+}
+
+impl WorkflowRunner {
+    pub fn new(repo: PostgreSQL, bus: CommandBus, events: EventBus) -> Self {
+        Self {
+            repo,
+            bus,
+            events: events.subscribe(),
+            managers: Vec::new(),
+            config: WorkflowConfig::default(),
+            behind: true,
+        }
+    }
+
+    pub fn with_config(mut self, config: WorkflowConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn register(mut self, manager: impl ProcessManager) -> Self {
+        let steps = manager.steps().into();
+        self.managers.push(Registered {
+            manager: Arc::new(manager),
+            steps,
+            checkpoint: None,
+            saved: None,
+        });
+        self
+    }
+
+    /// Starts workflows from the stored events after the checkpoints, then
+    /// from the live ones. Every `poll_interval` it saves the checkpoints,
+    /// retries a catch-up that failed, and resumes the unfinished workflows
+    /// no runner holds, starting with those a previous run left.
+    pub async fn run(mut self) {
+        self.catch_up().await;
+        let mut poll = tokio::time::interval(self.config.poll_interval);
+        loop {
+            let event = tokio::select! {
+                _ = poll.tick() => {
+                    if self.behind {
+                        self.catch_up().await;
+                    }
+                    self.save_checkpoints().await;
+                    self.resume().await;
+                    continue;
+                }
+                event = self.events.recv() => event,
+            };
+            match event {
+                Ok(event) => self.handle(&event).await,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Workflow runner skipped {} events, reading them from the store",
+                        skipped
+                    );
+                    self.behind = true;
+                    self.catch_up().await;
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+
+    /// Gives a live event to every manager, a workflow that could not be
+    /// started is left to the next catch-up.
+    async fn handle(&mut self, event: &StoredEvent) {
+        for index in 0..self.managers.len() {
+            if let Err(e) = self.start(&self.managers[index], event).await {
+                error!(
+                    "Failed to start {} workflow: {}",
+                    self.managers[index].manager.name(),
+                    e
+                );
+                self.behind = true;
+            }
+            if !self.behind {
+                let registered = &mut self.managers[index];
+                registered.checkpoint = registered.checkpoint.max(Some(event.position()));
+            }
+        }
+    }
+
+    /// Reads the stored events after the oldest checkpoint and gives each
+    /// to the managers that have not seen it.
+    async fn catch_up(&mut self) {
+        match self.read_stored_events().await {
+            Ok(()) => {
+                self.behind = false;
+                self.save_checkpoints().await;
+            }
+            Err(e) => error!("Failed to catch up on events for workflows: {}", e),
+        }
+    }
+
+    async fn read_stored_events(&mut self) -> Result<(), RepositoryError> {
+        for registered in &mut self.managers {
+            if registered.checkpoint.is_some() {
+                continue;
+            }
+            let name = registered.manager.name();
+            let checkpoint = match self.repo.workflow_checkpoint(name).await? {
+                Some(checkpoint) => checkpoint,
+                None => {
+                    let last = self.repo.last_event_position().await?;
+                    self.repo.save_workflow_checkpoint(name, last).await?;
+                    last
+                }
+            };
+            registered.checkpoint = Some(checkpoint);
+            registered.saved = Some(checkpoint);
+        }
+
+        let Some(mut after) = self.managers.iter().filter_map(|r| r.checkpoint).min() else {
+            return Ok(());
+        };
+        loop {
+            let page = self.repo.find_events_after_position(after, PAGE).await?;
+            let full = page.len() as i64 == PAGE;
+            for event in page {
+                after = event.position();
+                for index in 0..self.managers.len() {
+                    if self.managers[index].checkpoint >= Some(after) {
+                        continue;
+                    }
+                    self.start(&self.managers[index], &event).await?;
+                    self.managers[index].checkpoint = Some(after);
+                }
+            }
+            if !full {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn save_checkpoints(&mut self) {
+        for registered in &mut self.managers {
+            let Some(checkpoint) = registered.checkpoint else {
+                continue;
+            };
+            if registered.saved == Some(checkpoint) {
+                continue;
+            }
+            let name = registered.manager.name();
+            match self.repo.save_workflow_checkpoint(name, checkpoint).await {
+                Ok(()) => registered.saved = Some(checkpoint),
+                Err(e) => error!("Failed to save the checkpoint of {}: {}", name, e),
+            }
+        }
+    }
+
+    /// Picks each workflow up at the step its record is at, a step whose
+    /// command ran but was not recorded yet runs again.
+    async fn resume(&self) {
+        let names: Vec<&str> = self
+            .managers
+            .iter()
+            .map(|registered| registered.manager.name())
+            .collect();
+        let workflows = match self
+            .repo
+            .claim_unfinished_workflows(&names, self.config.batch_size, self.config.lease)
+            .await
+        {
+            Ok(workflows) => workflows,
+            Err(e) => return error!("Failed to claim unfinished workflows: {}", e),
+        };
+        for workflow in workflows {
+            let Some(registered) = self
+                .managers
+                .iter()
+                .find(|registered| registered.manager.name() == workflow.name)
+            else {
+                continue;
+            };
+            info!("Resuming {} workflow {}", workflow.name, workflow.id);
+            tokio::spawn(drive(
+                self.repo.clone(),
+                self.bus.clone(),
+                registered.steps.clone(),
+                workflow,
+                self.config.lease,
+            ));
+        }
+    }
+
+    /// Starts the workflow `event` calls for, if any.
+    async fn start(
+        &self,
+        registered: &Registered,
+        event: &StoredEvent,
+    ) -> Result<(), RepositoryError> {
+        let Some(context) = registered.manager.start(event) else {
+            return Ok(());
+        };
+        let now = Utc::now();
+        let workflow = Workflow {
+            id: Uuid::now_v7(),
+            name: registered.manager.name().to_string(),
+            correlation_id: event.aggregate_id,
+            status: WorkflowStatus::Running,
+            step: 0,
+            context,
+            history: json!([]),
+            error: None,
+            created_at: now,
+            updated_at: now,
+        };
+
+        if self
+            .repo
+            .start_workflow(workflow.clone(), self.config.lease)
+            .await?
+        {
+            info!("Started {} workflow {}", workflow.name, workflow.id);
+            tokio::spawn(drive(
+                self.repo.clone(),
+                self.bus.clone(),
+                registered.steps.clone(),
+                workflow,
+                self.config.lease,
+            ));
+        }
+        Ok(())
+    }
+}
+
+async fn drive(
+    repo: PostgreSQL,
+    bus: CommandBus,
+    steps: Arc<[Step]>,
+    mut workflow: Workflow,
+    lease: Duration,
+) {
+    let start = (workflow.step.max(0) as usize).min(steps.len());
+    if workflow.status == WorkflowStatus::Compensating {
+        return compensate(&repo, &bus, &steps[..start], &mut workflow, lease).await;
+    }
+    for (index, step) in steps.iter().enumerate().skip(start) {
+        if !renew(&repo, &workflow, lease).await {
+            return;
+        }
+        let result = send(&bus, step.action(&workflow.context)).await;
+        let next = index as i32 + 1;
+        match result {
+            Ok(()) => {
+                let entry = entry(step.name, "action", None);
+                let status = if next as usize == steps.len() {
+                    WorkflowStatus::Completed
+                } else {
+                    WorkflowStatus::Running
+                };
+                if !record(&repo, &mut workflow, status, next, entry, None).await {
+                    return;
+                }
+            }
+            Err(e) => {
+                warn!(
+                    "{} workflow {} failed at {}: {}",
+                    workflow.name, workflow.id, step.name, e
+                );
+                let error = e.to_string();
+                let entry = entry(step.name, "action", Some(&error));
+                if record(
+                    &repo,
+                    &mut workflow,
+                    WorkflowStatus::Compensating,
+                    index as i32,
+                    entry,
+                    Some(&error),
+                )
+                .await
+                {
+                    compensate(&repo, &bus, &steps[..index], &mut workflow, lease).await;
+                }
+                return;
+            }
+        }
+    }
+}
+
+/// Undoes the finished steps, newest first, stopping at the first
+/// compensation that fails.
+async fn compensate(
+    repo: &PostgreSQL,
+    bus: &CommandBus,
+    done: &[Step],
+    workflow: &mut Workflow,
+    lease: Duration,
+) {
+    for (index, step) in done.iter().enumerate().rev() {
+        let Some(compensation) = step.compensation(&workflow.context) else {
+            continue;
+        };
+        if !renew(repo, workflow, lease).await {
+            return;
+        }
+        if let Err(e) = send(bus, compensation).await {
+            error!(
+                "{} workflow {} could not compensate {}: {}",
+                workflow.name, workflow.id, step.name, e
+            );
+            let error = e.to_string();
+            let entry = entry(step.name, "compensation", Some(&error));
+            record(
+                repo,
+                workflow,
+                WorkflowStatus::Failed,
+                index as i32,
+                entry,
+                Some(&error),
+            )
+            .await;
+            return;
+        }
+        let entry = entry(step.name, "compensation", None);
+        if !record(
+            repo,
+            workflow,
+            WorkflowStatus::Compensating,
+            index as i32,
+            entry,
+            None,
+        )
+        .await
+        {
+            return;
+        }
+    }
+    let entry = json!({ "outcome": "compensated", "at": Utc::now() });
+    record(repo, workflow, WorkflowStatus::Compensated, 0, entry, None).await;
+}
+
+async fn send(
+    bus: &CommandBus,
+    request: Result<CommandRequest, CommandError>,
+) -> Result<(), CommandError> {
+    bus.dispatch_request(request?).await.map(|_| ())
+}
+
+fn entry(step: &str, action: &str, error: Option<&str>) -> Value {
+    json!({
+        "step": step,
+        "action": action,
+        "outcome": if error.is_some() { "failed" } else { "succeeded" },
+        "error": error,
+        "at": Utc::now(),
+    })
+}
+
+/// Extends the lease before a command is sent, a workflow whose lease
+/// cannot be renewed stops and is resumed by whichever runner claims it
+/// next.
+async fn renew(repo: &PostgreSQL, workflow: &Workflow, lease: Duration) -> bool {
+    let at = (workflow.status, workflow.step);
+    match repo.renew_workflow_lease(workflow.id, at, lease).await {
+        Ok(()) => true,
+        Err(RepositoryError::NotFound) => {
+            warn!(
+                "{} workflow {} was moved on elsewhere, stopping",
+                workflow.name, workflow.id
+            );
+            false
+        }
+        Err(e) => {
+            error!(
+                "Failed to lease {} workflow {}: {}",
+                workflow.name, workflow.id, e
+            );
+            false
+        }
+    }
+}
+
+/// Persists progress, a workflow whose state cannot be saved stops so it
+/// is never further along than its record says, and is resumed from there
+/// once its lease ends. One another runner moved on stops too.
+async fn record(
+    repo: &PostgreSQL,
+    workflow: &mut Workflow,
+    status: WorkflowStatus,
+    step: i32,
+    entry: Value,
+    error: Option<&str>,
+) -> bool {
+    let from = (workflow.status, workflow.step);
+    match repo
+        .advance_workflow(workflow.id, from, status, step, entry, error)
+        .await
+    {
+        Ok(()) => {
+            workflow.status = status;
+            workflow.step = step;
+            true
+        }
+        Err(RepositoryError::NotFound) => {
+            warn!(
+                "{} workflow {} was moved on elsewhere, stopping",
+                workflow.name, workflow.id
+            );
+            false
+        }
+        Err(e) => {
+            error!(
+                "Failed to save {} workflow {}: {}",
+                workflow.name, workflow.id, e
+            );
+            false
+        }
+    }
+}
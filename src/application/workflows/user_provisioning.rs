@@ -0,0 +1,37 @@
+use serde_json::{json, Value};
+
+use crate::{
+    commands::{CommandError, SendWelcomeEmail},
+    events::{user_upcasters, StoredEvent, UserCreated},
+    Event,
+};
+
+use super::{ProcessManager, Step};
+
+/// Provisions every created user, starting with the welcome email.
+#[derive(Clone, Debug, Default)]
+pub struct UserProvisioning;
+
+impl ProcessManager for UserProvisioning {
+    fn name(&self) -> &'static str {
+        "user_provisioning"
+    }
+
+    fn start(&self, event: &StoredEvent) -> Option<Value> {
+        if event.event_type != UserCreated::EVENT_TYPE {
+            return None;
+        }
+        let user: UserCreated = event.clone().decode(&user_upcasters()).ok()?;
+        Some(json!({
+            "user_id": user.id,
+            "username": user.username,
+            "email": user.email,
+        }))
+    }
+
+    fn steps(&self) -> Vec<Step> {
+        vec![Step::new("send_welcome_email", |context| {
+            Ok::<SendWelcomeEmail, CommandError>(serde_json::from_value(context.clone())?)
+        })]
+    }
+}
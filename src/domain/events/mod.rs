@@ -1,2 +1,7 @@
+mod stored_event;
+mod upcasters;
 mod user_events;
-pub use user_events::UserCreated;
+
+pub use stored_event::StoredEvent;
+pub use upcasters::{EventError, Upcast, Upcasters};
+pub use user_events::{user_upcasters, UserCreated, UserDeleted, UserUpdated, USER_EVENT_TYPES};
@@ -0,0 +1,55 @@
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::domain::Event;
+
+use super::{EventError, Upcasters};
+
+/// An event as persisted in the event store: a type tag, the schema version
+/// it was written with and the raw JSON payload.
+#[derive(Debug, Clone)]
+pub struct StoredEvent {
+    pub id: Uuid,
+    pub aggregate_id: Uuid,
+    pub event_type: String,
+    pub version: i32,
+    pub payload: Value,
+    /// Numbers events as they are saved, 0 until it is stored.
+    pub sequence: i64,
+    /// The Postgres transaction that saved it, 0 in stores saving one
+    /// transaction at a time.
+    pub transaction: i64,
+}
+
+impl StoredEvent {
+    pub fn new<E: Event>(aggregate_id: Uuid, event: &E) -> Self {
+        Self {
+            id: Uuid::now_v7(),
+            aggregate_id,
+            event_type: E::EVENT_TYPE.to_string(),
+            version: E::VERSION,
+            payload: serde_json::to_value(event).unwrap(),
+            sequence: 0,
+            transaction: 0,
+        }
+    }
+
+    /// Its place in the order of the event feeds, which stored events
+    /// never change: by transaction, then by sequence within one.
+    pub fn position(&self) -> (i64, i64) {
+        (self.transaction, self.sequence)
+    }
+
+    /// Upcasts the payload to `E::VERSION` and deserializes it.
+    pub fn decode<E: Event>(self, upcasters: &Upcasters) -> Result<E, EventError> {
+        if self.event_type != E::EVENT_TYPE {
+            return Err(EventError::TypeMismatch {
+                expected: E::EVENT_TYPE.to_string(),
+                found: self.event_type,
+            });
+        }
+
+        let payload = upcasters.upcast(&self.event_type, self.version, E::VERSION, self.payload)?;
+        Ok(serde_json::from_value(payload)?)
+    }
+}
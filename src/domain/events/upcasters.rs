@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use derive_more::{Display, Error, From};
+use serde_json::Value;
+
+/// Migrates a payload from one schema version to the next.
+pub type Upcast = fn(Value) -> Value;
+
+#[derive(Debug, Display, Error, From)]
+pub enum EventError {
+    #[display("no upcaster registered for {event_type} v{version}")]
+    #[from(ignore)]
+    MissingUpcaster { event_type: String, version: i32 },
+    #[display("{event_type} v{version} is newer than the supported v{current}")]
+    #[from(ignore)]
+    UnsupportedVersion {
+        event_type: String,
+        version: i32,
+        current: i32,
+    },
+    #[display("expected event {expected}, found {found}")]
+    #[from(ignore)]
+    TypeMismatch { expected: String, found: String },
+    #[display("invalid event payload: {_0}")]
+    Payload(serde_json::Error),
+    #[display("unknown event type {_0}")]
+    #[from(ignore)]
+    UnknownType(#[error(not(source))] String),
+}
+
+/// Registry of upcasters keyed by event type and the version they upgrade from.
+#[derive(Debug, Default)]
+pub struct Upcasters {
+    steps: HashMap<(String, i32), Upcast>,
+}
+
+impl Upcasters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the step that turns a `from_version` payload into `from_version + 1`.
+    pub fn register(mut self, event_type: &str, from_version: i32, upcast: Upcast) -> Self {
+        self.steps
+            .insert((event_type.to_string(), from_version), upcast);
+        self
+    }
+
+    /// Applies every step from `version` up to `target_version`.
+    pub fn upcast(
+        &self,
+        event_type: &str,
+        version: i32,
+        target_version: i32,
+        payload: Value,
+    ) -> Result<Value, EventError> {
+        if version > target_version {
+            return Err(EventError::UnsupportedVersion {
+                event_type: event_type.to_string(),
+                version,
+                current: target_version,
+            });
+        }
+
+        (version..target_version).try_fold(payload, |payload, from_version| {
+            let upcast = self
+                .steps
+                .get(&(event_type.to_string(), from_version))
+                .ok_or_else(|| EventError::MissingUpcaster {
+                    event_type: event_type.to_string(),
+                    version: from_version,
+                })?;
+            Ok(upcast(payload))
+        })
+    }
+}
@@ -3,10 +3,49 @@ use uuid::Uuid;
 
 use crate::domain::Event;
 
+use super::Upcasters;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct UserCreated {
     pub id: Uuid,
     pub username: String,
     pub email: String,
 }
-impl Event for UserCreated {}
+impl Event for UserCreated {
+    const EVENT_TYPE: &'static str = "UserCreated";
+    const VERSION: i32 = 1;
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserUpdated {
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub version: i64,
+}
+impl Event for UserUpdated {
+    const EVENT_TYPE: &'static str = "UserUpdated";
+    const VERSION: i32 = 1;
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UserDeleted {
+    pub id: Uuid,
+    pub version: i64,
+}
+impl Event for UserDeleted {
+    const EVENT_TYPE: &'static str = "UserDeleted";
+    const VERSION: i32 = 1;
+}
+
+/// The type of every user event, the only ones the user feeds deliver.
+pub const USER_EVENT_TYPES: [&str; 3] = [
+    UserCreated::EVENT_TYPE,
+    UserUpdated::EVENT_TYPE,
+    UserDeleted::EVENT_TYPE,
+];
+
+/// Upcasters for every user event, register new steps here when a schema changes.
+pub fn user_upcasters() -> Upcasters {
+    Upcasters::new()
+}
@@ -1,10 +1,27 @@
 use serde::{de::DeserializeOwned, ser::Serialize};
+use uuid::Uuid;
 
 #[allow(dead_code)]
-pub trait Event: DeserializeOwned + Serialize + Unpin + Send + Sync + 'static {}
+pub trait Event: DeserializeOwned + Serialize + Unpin + Send + Sync + 'static {
+    /// Name stored alongside every payload so it can be decoded on read.
+    const EVENT_TYPE: &'static str;
+    /// Current schema version, bump it and register an upcaster on change.
+    const VERSION: i32;
+}
 
 #[allow(dead_code)]
-pub trait Command: DeserializeOwned {}
+pub trait Command: Serialize + DeserializeOwned + Clone + Send + 'static {
+    /// Stable name used when the command is persisted.
+    const NAME: &'static str;
+
+    /// What the command's handler returns to the dispatcher.
+    type Output: Serialize + Send + 'static;
+
+    /// Commands for the same aggregate are handled one at a time, in order.
+    fn aggregate_id(&self) -> Option<Uuid> {
+        None
+    }
+}
 
 #[allow(dead_code)]
 pub trait Model: Serialize + DeserializeOwned + Unpin + Send + Sync + 'static {}
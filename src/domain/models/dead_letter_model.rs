@@ -0,0 +1,70 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::domain::Model;
+
+/// A command that kept failing, kept with its original payload and one
+/// `{attempt, error, at}` entry in `errors` per failed attempt.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeadLetter {
+    pub id: Uuid,
+    pub command_type: String,
+    pub payload: Value,
+    pub aggregate_id: Option<Uuid>,
+    pub attempts: i32,
+    pub last_error: String,
+    pub errors: Value,
+    pub created_at: DateTime<Utc>,
+    pub failed_at: DateTime<Utc>,
+}
+
+/// One failed attempt at running a command.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AttemptError {
+    pub attempt: i32,
+    pub error: String,
+    pub at: DateTime<Utc>,
+}
+
+impl AttemptError {
+    pub fn new(attempt: i32, error: String) -> Self {
+        Self {
+            attempt,
+            error,
+            at: Utc::now(),
+        }
+    }
+}
+
+impl DeadLetter {
+    /// A command that failed on every attempt in `errors`, which holds at
+    /// least one.
+    pub fn new(
+        command_type: String,
+        payload: Value,
+        aggregate_id: Option<Uuid>,
+        errors: Vec<AttemptError>,
+    ) -> Self {
+        let first = errors
+            .first()
+            .expect("a dead letter has failed at least once");
+        let last = errors
+            .last()
+            .expect("a dead letter has failed at least once");
+        Self {
+            id: Uuid::now_v7(),
+            command_type,
+            payload,
+            aggregate_id,
+            attempts: errors.len() as i32,
+            last_error: last.error.clone(),
+            created_at: first.at,
+            failed_at: last.at,
+            errors: serde_json::to_value(&errors).unwrap_or_default(),
+        }
+    }
+}
+
+impl Model for DeadLetter {}
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+use crate::domain::Model;
+
+/// A stored idempotency key, `response` stays empty while the original
+/// request is still in flight.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IdempotencyRecord {
+    pub scope: String,
+    pub key: String,
+    pub request_hash: String,
+    pub response: Option<Vec<u8>>,
+}
+
+impl Model for IdempotencyRecord {}
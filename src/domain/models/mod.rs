@@ -1,2 +1,13 @@
+mod dead_letter_model;
+mod idempotency_model;
+mod queued_command_model;
+mod scheduled_command_model;
 mod user_model;
-pub use user_model::User;
+mod workflow_model;
+
+pub use dead_letter_model::{AttemptError, DeadLetter};
+pub use idempotency_model::IdempotencyRecord;
+pub use queued_command_model::QueuedCommand;
+pub use scheduled_command_model::ScheduledCommand;
+pub use user_model::{Email, EmailError, InvalidUser, User, Username, UsernameError};
+pub use workflow_model::{Workflow, WorkflowStatus};
@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::domain::Model;
+
+/// A command waiting in the durable queue.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueuedCommand {
+    pub id: Uuid,
+    pub command_type: String,
+    pub payload: Value,
+    pub aggregate_id: Option<Uuid>,
+    pub attempts: i32,
+    pub max_attempts: i32,
+}
+
+impl Model for QueuedCommand {}
@@ -0,0 +1,36 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::domain::Model;
+
+/// A command due at `run_at`, recurring when it has a `cron` expression
+/// (`sec min hour day-of-month month day-of-week [year]`, in UTC).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduledCommand {
+    pub id: Uuid,
+    pub command_type: String,
+    pub payload: Value,
+    pub aggregate_id: Option<Uuid>,
+    pub run_at: DateTime<Utc>,
+    pub cron: Option<String>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    /// Set while a scheduler runs the occurrence, others pass over it until
+    /// then.
+    pub leased_until: Option<DateTime<Utc>>,
+}
+
+impl ScheduledCommand {
+    /// The occurrence following `after`, `None` once a one-off command ran.
+    pub fn next_run_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let schedule = Schedule::from_str(self.cron.as_deref()?).ok()?;
+        schedule.after(&after).next()
+    }
+}
+
+impl Model for ScheduledCommand {}
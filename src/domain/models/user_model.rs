@@ -1,13 +1,89 @@
+use derive_more::{Display, Error};
+use nutype::nutype;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::domain::Model;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct User {
     pub id: Uuid,
     pub username: String,
     pub email: String,
+    pub version: i64,
 }
 
 impl Model for User {}
+
+/// A username, trimmed, at most as long as the `users.username` column.
+#[nutype(
+    sanitize(trim),
+    validate(not_empty, len_char_max = 255),
+    derive(Debug, Clone, PartialEq, Eq, AsRef, Display)
+)]
+pub struct Username(String);
+
+/// An email address, trimmed and lowercased, with one `@` between a local
+/// part and a dotted domain.
+#[nutype(
+    sanitize(trim, lowercase),
+    validate(len_char_max = 255, predicate = is_email),
+    derive(Debug, Clone, PartialEq, Eq, AsRef, Display)
+)]
+pub struct Email(String);
+
+fn is_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain
+                    .split('.')
+                    .all(|label| !label.is_empty() && !label.contains(char::is_whitespace))
+                && domain.contains('.')
+                && !local.contains(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+/// Why a username or email was refused.
+#[derive(Debug, Display, Error, Clone, Copy, PartialEq, Eq)]
+#[display("{_0}")]
+pub struct InvalidUser(#[error(not(source))] &'static str);
+
+impl From<UsernameError> for InvalidUser {
+    fn from(e: UsernameError) -> Self {
+        InvalidUser(match e {
+            UsernameError::NotEmptyViolated => "username is empty",
+            UsernameError::LenCharMaxViolated => "username is too long",
+        })
+    }
+}
+
+impl From<EmailError> for InvalidUser {
+    fn from(e: EmailError) -> Self {
+        InvalidUser(match e {
+            EmailError::LenCharMaxViolated => "email is too long",
+            EmailError::PredicateViolated => "email is not an address",
+        })
+    }
+}
+
+impl User {
+    /// `username` and `email` as their value objects, for every command
+    /// that sets them.
+    pub fn validate(username: &str, email: &str) -> Result<(Username, Email), InvalidUser> {
+        Ok((Username::try_new(username)?, Email::try_new(email)?))
+    }
+
+    /// A new user at version 1.
+    pub fn new(username: Username, email: Email) -> Self {
+        User {
+            id: Uuid::now_v7(),
+            username: username.into_inner(),
+            email: email.into_inner(),
+            version: 1,
+        }
+    }
+}
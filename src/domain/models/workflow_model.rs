@@ -0,0 +1,47 @@
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::domain::Model;
+
+/// Stored as text in `workflows.status`.
+#[derive(Serialize, Deserialize, Display, sqlx::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum WorkflowStatus {
+    #[display("running")]
+    Running,
+    #[display("completed")]
+    Completed,
+    /// A step failed and the finished ones are being undone.
+    #[display("compensating")]
+    Compensating,
+    /// A step failed and every finished step was undone.
+    #[display("compensated")]
+    Compensated,
+    /// A compensation failed too, needs a human.
+    #[display("failed")]
+    Failed,
+}
+
+/// The persisted state of one process manager run, `history` holds a
+/// `{step, action, outcome, error, at}` entry per command sent.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Workflow {
+    pub id: Uuid,
+    pub name: String,
+    /// The aggregate whose event started the workflow.
+    pub correlation_id: Uuid,
+    pub status: WorkflowStatus,
+    /// Index of the next step to run.
+    pub step: i32,
+    pub context: Value,
+    pub history: Value,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Model for Workflow {}
@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+use axum::async_trait;
+use uuid::Uuid;
+
+use crate::models::QueuedCommand;
+
+use super::RepositoryError;
+
+#[async_trait]
+pub trait CommandQueueRepository {
+    async fn enqueue_command(&self, command: QueuedCommand) -> Result<(), RepositoryError>;
+    /// Leases up to `limit` visible commands for `visibility_timeout`,
+    /// skipping any whose aggregate still has an older command queued.
+    async fn claim_commands(
+        &self,
+        limit: i64,
+        visibility_timeout: Duration,
+    ) -> Result<Vec<QueuedCommand>, RepositoryError>;
+    async fn complete_command(&self, id: Uuid) -> Result<(), RepositoryError>;
+    /// Records `error` and hides the command again until `retry_in` has passed.
+    async fn retry_command(
+        &self,
+        id: Uuid,
+        error: &str,
+        retry_in: Duration,
+    ) -> Result<(), RepositoryError>;
+    /// Moves the command to the dead-letter table.
+    async fn dead_letter_command(&self, id: Uuid, error: &str) -> Result<(), RepositoryError>;
+}
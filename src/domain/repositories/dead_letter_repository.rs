@@ -0,0 +1,35 @@
+use axum::async_trait;
+use uuid::Uuid;
+
+use crate::models::DeadLetter;
+
+use super::RepositoryError;
+
+/// A dead letter taken by [`DeadLetterRepository::claim_dead_letter`], other
+/// claims cannot take it until it completes or fails. Dropping the claim
+/// leaves the dead letter as it was.
+#[async_trait]
+pub trait DeadLetterClaim: Send + Sync {
+    fn dead_letter(&self) -> &DeadLetter;
+    /// Removes the dead letter for good.
+    async fn complete(self) -> Result<(), RepositoryError>;
+    /// Keeps the dead letter with an attempt appended per error.
+    async fn fail(self, errors: Vec<String>) -> Result<(), RepositoryError>;
+}
+
+#[async_trait]
+pub trait DeadLetterRepository {
+    type Claim: DeadLetterClaim;
+
+    async fn save_dead_letter(&self, dead_letter: DeadLetter) -> Result<(), RepositoryError>;
+    async fn list_dead_letters(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DeadLetter>, RepositoryError>;
+    async fn find_dead_letter(&self, id: Uuid) -> Result<Option<DeadLetter>, RepositoryError>;
+    async fn delete_dead_letter(&self, id: Uuid) -> Result<(), RepositoryError>;
+    /// Takes the dead letter to replay it, [`RepositoryError::Locked`] when
+    /// another claim holds it.
+    async fn claim_dead_letter(&self, id: Uuid) -> Result<Self::Claim, RepositoryError>;
+}
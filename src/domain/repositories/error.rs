@@ -0,0 +1,71 @@
+use derive_more::{Display, Error};
+use sqlx::error::DatabaseError;
+
+#[derive(Debug, Display, Error)]
+pub enum RepositoryError {
+    #[display("version conflict: expected {expected}, found {actual}")]
+    Conflict { expected: i64, actual: i64 },
+    #[display("{_0} is already taken")]
+    Duplicate(#[error(not(source))] String),
+    #[display("record not found")]
+    NotFound,
+    /// Another operation holds the record, e.g. a dead letter being
+    /// replayed.
+    #[display("record is held by another operation")]
+    Locked,
+    /// The store was written to while a unit of work was open, which then
+    /// cannot commit. Running it again may succeed.
+    #[display("the store changed since the unit of work began")]
+    Serialization,
+    #[display("{_0}")]
+    Database(sqlx::Error),
+}
+
+impl RepositoryError {
+    /// Whether retrying the same operation may succeed, e.g. a dropped
+    /// connection, a pool timeout, a serialization failure or a deadlock.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            RepositoryError::Serialization => true,
+            RepositoryError::Database(sqlx::Error::Database(e)) => {
+                matches!(e.code().as_deref(), Some("40001" | "40P01"))
+            }
+            RepositoryError::Database(e) => matches!(
+                e,
+                sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut | sqlx::Error::WorkerCrashed
+            ),
+            _ => false,
+        }
+    }
+}
+
+/// Unique violations become [`RepositoryError::Duplicate`] so every backend
+/// reports them the same way.
+impl From<sqlx::Error> for RepositoryError {
+    fn from(e: sqlx::Error) -> Self {
+        match &e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                RepositoryError::Duplicate(unique_column(db.as_ref()))
+            }
+            _ => RepositoryError::Database(e),
+        }
+    }
+}
+
+/// Postgres names the constraint, e.g. `users_email_key`, SQLite only says
+/// `UNIQUE constraint failed: users.email`.
+fn unique_column(e: &dyn DatabaseError) -> String {
+    if let Some(constraint) = e.constraint() {
+        let column = constraint.trim_end_matches("_key");
+        return e
+            .table()
+            .and_then(|table| column.strip_prefix(table)?.strip_prefix('_'))
+            .unwrap_or(column)
+            .to_string();
+    }
+    let message = e.message();
+    message
+        .rsplit_once('.')
+        .map_or(message, |(_, column)| column)
+        .to_string()
+}
@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+use axum::async_trait;
+
+use crate::models::IdempotencyRecord;
+
+use super::RepositoryError;
+
+#[async_trait]
+pub trait IdempotencyRepository {
+    /// Claims `key` for a new request until `lease` ends, returns the
+    /// existing record instead when the key was used within `ttl` and was
+    /// either completed or is still leased.
+    async fn claim_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        request_hash: &str,
+        ttl: Duration,
+        lease: Duration,
+    ) -> Result<Option<IdempotencyRecord>, RepositoryError>;
+    async fn complete_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        response: &[u8],
+    ) -> Result<(), RepositoryError>;
+    async fn release_idempotency_key(&self, scope: &str, key: &str) -> Result<(), RepositoryError>;
+}
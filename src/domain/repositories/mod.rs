@@ -1,2 +1,19 @@
+mod command_queue_repository;
+mod dead_letter_repository;
+mod error;
+mod idempotency_repository;
+mod pagination;
+mod schedule_repository;
+mod unit_of_work;
 mod user_repository;
+mod workflow_repository;
+
+pub use command_queue_repository::CommandQueueRepository;
+pub use dead_letter_repository::{DeadLetterClaim, DeadLetterRepository};
+pub use error::RepositoryError;
+pub use idempotency_repository::IdempotencyRepository;
+pub use pagination::{page_size, MAX_PAGE_SIZE};
+pub use schedule_repository::{ScheduleClaim, ScheduleRepository};
+pub use unit_of_work::{Transactional, UnitOfWork, UserStore};
 pub use user_repository::UserRepository;
+pub use workflow_repository::WorkflowRepository;
@@ -0,0 +1,8 @@
+/// The most rows one page of a list holds.
+pub const MAX_PAGE_SIZE: i64 = 500;
+
+/// `limit` brought into `1..=MAX_PAGE_SIZE`, applied by every backend so
+/// they agree on out-of-range limits.
+pub fn page_size(limit: i64) -> i64 {
+    limit.clamp(1, MAX_PAGE_SIZE)
+}
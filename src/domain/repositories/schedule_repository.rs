@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::models::ScheduledCommand;
+
+use super::RepositoryError;
+
+/// Due commands leased by [`ScheduleRepository::claim_due_schedules`]. The
+/// lease is committed as they are claimed, other claims pass over them
+/// until it ends, and those not completed by then are claimed again, so an
+/// occurrence runs at least once.
+#[async_trait]
+pub trait ScheduleClaim: Send + Sync {
+    fn schedules(&self) -> &[ScheduledCommand];
+    /// Moves a claimed recurring command to its occurrence after `fired_at`
+    /// and removes a one-off one, on its own. Does nothing once the lease
+    /// ended and another claim took the command.
+    async fn complete(
+        &self,
+        schedule: &ScheduledCommand,
+        fired_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError>;
+}
+
+#[async_trait]
+pub trait ScheduleRepository {
+    type Claim: ScheduleClaim;
+
+    async fn save_schedule(&self, schedule: ScheduledCommand) -> Result<(), RepositoryError>;
+    async fn list_schedules(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<ScheduledCommand>, RepositoryError>;
+    async fn find_schedule(&self, id: Uuid) -> Result<Option<ScheduledCommand>, RepositoryError>;
+    async fn delete_schedule(&self, id: Uuid) -> Result<(), RepositoryError>;
+    /// Claims up to `limit` due commands, skipping those another claim
+    /// leased. Ones left uncompleted are due again after `lease`.
+    async fn claim_due_schedules(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Self::Claim, RepositoryError>;
+}
@@ -0,0 +1,26 @@
+use std::fmt::Debug;
+
+use axum::async_trait;
+
+use super::{RepositoryError, UserRepository};
+
+/// Repository handles sharing one transaction. Nothing is visible to
+/// others until `commit`, dropping the unit of work rolls it back.
+#[async_trait]
+pub trait UnitOfWork: UserRepository + Send + Sync + Sized {
+    async fn commit(self) -> Result<(), RepositoryError>;
+    async fn rollback(self) -> Result<(), RepositoryError>;
+}
+
+/// A backend able to open units of work.
+#[async_trait]
+pub trait Transactional: Send + Sync {
+    type UnitOfWork: UnitOfWork;
+
+    async fn begin(&self) -> Result<Self::UnitOfWork, RepositoryError>;
+}
+
+/// A backend the user services can run on.
+pub trait UserStore: Transactional + UserRepository + Clone + Debug + 'static {}
+
+impl<T> UserStore for T where T: Transactional + UserRepository + Clone + Debug + 'static {}
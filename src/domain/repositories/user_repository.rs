@@ -1,11 +1,49 @@
 use axum::async_trait;
 use uuid::Uuid;
 
-use crate::{events::UserCreated, models::User};
+use crate::{events::StoredEvent, models::User};
+
+use super::RepositoryError;
 
 #[async_trait]
 pub trait UserRepository {
-    async fn save_user(&self, user: User) -> Result<(), sqlx::Error>;
-    async fn save_event(&self, event: UserCreated) -> Result<(), sqlx::Error>;
-    async fn find_user_by_id(&self, id: Uuid) -> Result<Option<User>, sqlx::Error>;
+    async fn save_user(&self, user: User) -> Result<(), RepositoryError>;
+    /// Inserts `users` in one batch, skipping those whose id, username or
+    /// email is taken, and returns the ids of the users created in order.
+    async fn save_users(&self, users: Vec<User>) -> Result<Vec<Uuid>, RepositoryError>;
+    /// Compare-and-swap update, `expected_version` of `None` skips the check.
+    async fn update_user(
+        &self,
+        user: User,
+        expected_version: Option<i64>,
+    ) -> Result<User, RepositoryError>;
+    /// Soft delete, the user stops being found or listed but keeps its
+    /// username and email reserved.
+    async fn delete_user(
+        &self,
+        id: Uuid,
+        expected_version: Option<i64>,
+    ) -> Result<(), RepositoryError>;
+    async fn save_event(&self, event: StoredEvent) -> Result<(), RepositoryError>;
+    /// Users ordered by id, i.e. by creation time, starting after the `after` cursor.
+    async fn list_users(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<User>, RepositoryError>;
+    async fn find_user_by_id(&self, id: Uuid) -> Result<Option<User>, RepositoryError>;
+    async fn find_events_by_aggregate_id(
+        &self,
+        id: Uuid,
+    ) -> Result<Vec<StoredEvent>, RepositoryError>;
+    /// Up to `limit` committed events of every aggregate after the `after`
+    /// event, ordered by [`StoredEvent::position`]: the `tx` and `seq`
+    /// columns in Postgres, the `rowid` in SQLite. Events committed later
+    /// never come before those returned. An `after` that is not stored,
+    /// such as a nil id, starts from the first event.
+    async fn find_events_after(
+        &self,
+        after: Uuid,
+        limit: i64,
+    ) -> Result<Vec<StoredEvent>, RepositoryError>;
 }
@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+use axum::async_trait;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::models::{Workflow, WorkflowStatus};
+
+use super::RepositoryError;
+
+#[async_trait]
+pub trait WorkflowRepository {
+    /// Returns `false` when `workflow.name` already runs for its correlation id.
+    /// The starting runner holds it for `lease`.
+    async fn start_workflow(
+        &self,
+        workflow: Workflow,
+        lease: Duration,
+    ) -> Result<bool, RepositoryError>;
+    /// Moves the workflow from `from` to `status` and `step`, appending
+    /// `entry` to its history. [`RepositoryError::NotFound`] when it is no
+    /// longer at `from`, e.g. because another runner moved it on.
+    async fn advance_workflow(
+        &self,
+        id: Uuid,
+        from: (WorkflowStatus, i32),
+        status: WorkflowStatus,
+        step: i32,
+        entry: Value,
+        error: Option<&str>,
+    ) -> Result<(), RepositoryError>;
+    async fn list_workflows(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Workflow>, RepositoryError>;
+    async fn find_workflow(&self, id: Uuid) -> Result<Option<Workflow>, RepositoryError>;
+    /// Leases up to `limit` of the workflows of `names` still running or
+    /// compensating, oldest first, skipping those whose lease has not ended.
+    async fn claim_unfinished_workflows(
+        &self,
+        names: &[&str],
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Vec<Workflow>, RepositoryError>;
+    /// The [`StoredEvent::position`](crate::events::StoredEvent::position)
+    /// of the last event the process manager `name` read, `None` before it
+    /// first ran.
+    async fn workflow_checkpoint(&self, name: &str) -> Result<Option<(i64, i64)>, RepositoryError>;
+    /// Moves the checkpoint of `name` to `position`, never back.
+    async fn save_workflow_checkpoint(
+        &self,
+        name: &str,
+        position: (i64, i64),
+    ) -> Result<(), RepositoryError>;
+    /// Extends the lease of a workflow still at `at`, [`RepositoryError::NotFound`]
+    /// when another runner moved it on.
+    async fn renew_workflow_lease(
+        &self,
+        id: Uuid,
+        at: (WorkflowStatus, i32),
+        lease: Duration,
+    ) -> Result<(), RepositoryError>;
+}
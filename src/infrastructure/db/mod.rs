@@ -1,3 +1,7 @@
 pub mod pgpool;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
 
-pub use pgpool::pgpool_connections;
+pub use pgpool::{pg_pools, pgpool_connections, PgPools};
+#[cfg(feature = "sqlite")]
+pub use sqlite::sqlite_connections;
@@ -2,14 +2,38 @@ use std::time::Duration;
 
 use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
 
+/// The primary pool for writes and an optional read replica pool for
+/// queries, from `DATABASE_URL` and `DATABASE_READ_URL`.
+#[derive(Clone, Debug)]
+pub struct PgPools {
+    pub writer: Pool<Postgres>,
+    pub reader: Option<Pool<Postgres>>,
+}
+
 pub async fn pgpool_connections() -> Pool<Postgres> {
     let db_connection_str = std::env::var("DATABASE_URL")
         .unwrap_or_else(|_| "postgres://postgres@localhost/coqrs".to_string());
 
+    connect(&db_connection_str).await
+}
+
+pub async fn pg_pools() -> PgPools {
+    let reader = match std::env::var("DATABASE_READ_URL") {
+        Ok(url) if !url.is_empty() => Some(connect(&url).await),
+        _ => None,
+    };
+
+    PgPools {
+        writer: pgpool_connections().await,
+        reader,
+    }
+}
+
+async fn connect(url: &str) -> Pool<Postgres> {
     PgPoolOptions::new()
         .max_connections(5)
         .acquire_timeout(Duration::from_secs(3))
-        .connect(&db_connection_str)
+        .connect(url)
         .await
         .expect("can't connect to database")
 }
@@ -0,0 +1,28 @@
+use std::str::FromStr;
+
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    Pool, Sqlite,
+};
+
+/// Opens (creating if needed) the SQLite database at `url` and applies
+/// `migrations/sqlite`. In-memory databases get a single connection, as
+/// each connection would otherwise see its own empty database.
+pub async fn sqlite_connections(url: &str) -> Pool<Sqlite> {
+    let options = SqliteConnectOptions::from_str(url)
+        .expect("invalid sqlite DATABASE_URL")
+        .create_if_missing(true);
+    let max_connections = if url.contains(":memory:") { 1 } else { 5 };
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(max_connections)
+        .connect_with(options)
+        .await
+        .expect("can't open sqlite database");
+
+    sqlx::migrate!("migrations/sqlite")
+        .run(&pool)
+        .await
+        .expect("can't migrate sqlite database");
+    pool
+}
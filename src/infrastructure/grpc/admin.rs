@@ -0,0 +1,164 @@
+use tonic::{Request, Response, Status};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    commands::{CommandBus, CommandDecoders},
+    models::{DeadLetter, Workflow},
+    proto::{
+        admin_service_server::AdminService, DiscardDeadLetterRequest, DiscardDeadLetterResponse,
+        GetDeadLetterRequest, GetDeadLetterResponse, GetWorkflowRequest, GetWorkflowResponse,
+        ListDeadLettersRequest, ListDeadLettersResponse, ListWorkflowsRequest,
+        ListWorkflowsResponse, ReplayDeadLetterRequest, ReplayDeadLetterResponse,
+    },
+    repositories::RepositoryError,
+    services::{DeadLetterService, WorkflowService},
+    PostgreSQL,
+};
+
+pub struct GrpcAdminServiceImpl {
+    dead_letters: DeadLetterService,
+    workflows: WorkflowService,
+}
+
+impl GrpcAdminServiceImpl {
+    pub fn new(repo: PostgreSQL, bus: CommandBus, decoders: CommandDecoders) -> Self {
+        GrpcAdminServiceImpl {
+            dead_letters: DeadLetterService::new(repo.clone(), bus, decoders),
+            workflows: WorkflowService::new(repo),
+        }
+    }
+}
+
+impl From<DeadLetter> for GetDeadLetterResponse {
+    fn from(dead_letter: DeadLetter) -> Self {
+        GetDeadLetterResponse {
+            id: dead_letter.id.to_string(),
+            command_type: dead_letter.command_type,
+            payload: dead_letter.payload.to_string(),
+            aggregate_id: dead_letter.aggregate_id.map(|id| id.to_string()),
+            attempts: dead_letter.attempts,
+            last_error: dead_letter.last_error,
+            errors: dead_letter.errors.to_string(),
+            created_at: dead_letter.created_at.to_rfc3339(),
+            failed_at: dead_letter.failed_at.to_rfc3339(),
+        }
+    }
+}
+
+impl From<Workflow> for GetWorkflowResponse {
+    fn from(workflow: Workflow) -> Self {
+        GetWorkflowResponse {
+            id: workflow.id.to_string(),
+            name: workflow.name,
+            correlation_id: workflow.correlation_id.to_string(),
+            status: workflow.status.to_string(),
+            step: workflow.step,
+            context: workflow.context.to_string(),
+            history: workflow.history.to_string(),
+            error: workflow.error,
+            created_at: workflow.created_at.to_rfc3339(),
+            updated_at: workflow.updated_at.to_rfc3339(),
+        }
+    }
+}
+
+fn invalid_id(_: uuid::Error) -> Status {
+    Status::invalid_argument("Invalid id")
+}
+
+fn repository_error(e: RepositoryError) -> Status {
+    match e {
+        RepositoryError::NotFound => Status::not_found("Not found"),
+        e => {
+            error!("{}", e);
+            Status::internal("Internal server error")
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl AdminService for GrpcAdminServiceImpl {
+    async fn list_dead_letters(
+        &self,
+        request: Request<ListDeadLettersRequest>,
+    ) -> Result<Response<ListDeadLettersResponse>, Status> {
+        let request = request.into_inner();
+        let limit = if request.limit > 0 { request.limit } else { 50 };
+
+        let dead_letters = self
+            .dead_letters
+            .list(limit, request.offset)
+            .await
+            .map_err(repository_error)?;
+        Ok(Response::new(ListDeadLettersResponse {
+            dead_letters: dead_letters.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn get_dead_letter(
+        &self,
+        request: Request<GetDeadLetterRequest>,
+    ) -> Result<Response<GetDeadLetterResponse>, Status> {
+        let id = Uuid::parse_str(&request.into_inner().id).map_err(invalid_id)?;
+
+        match self.dead_letters.get(id).await.map_err(repository_error)? {
+            Some(dead_letter) => Ok(Response::new(dead_letter.into())),
+            None => Err(Status::not_found("Dead letter not found")),
+        }
+    }
+
+    async fn replay_dead_letter(
+        &self,
+        request: Request<ReplayDeadLetterRequest>,
+    ) -> Result<Response<ReplayDeadLetterResponse>, Status> {
+        let id = Uuid::parse_str(&request.into_inner().id).map_err(invalid_id)?;
+
+        self.dead_letters.replay(id).await?;
+        info!("Replayed dead letter {}", id);
+        Ok(Response::new(ReplayDeadLetterResponse {}))
+    }
+
+    async fn discard_dead_letter(
+        &self,
+        request: Request<DiscardDeadLetterRequest>,
+    ) -> Result<Response<DiscardDeadLetterResponse>, Status> {
+        let id = Uuid::parse_str(&request.into_inner().id).map_err(invalid_id)?;
+
+        self.dead_letters
+            .discard(id)
+            .await
+            .map_err(repository_error)?;
+        info!("Discarded dead letter {}", id);
+        Ok(Response::new(DiscardDeadLetterResponse {}))
+    }
+
+    async fn list_workflows(
+        &self,
+        request: Request<ListWorkflowsRequest>,
+    ) -> Result<Response<ListWorkflowsResponse>, Status> {
+        let request = request.into_inner();
+        let limit = if request.limit > 0 { request.limit } else { 50 };
+
+        let workflows = self
+            .workflows
+            .list(limit, request.offset)
+            .await
+            .map_err(repository_error)?;
+        Ok(Response::new(ListWorkflowsResponse {
+            workflows: workflows.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn get_workflow(
+        &self,
+        request: Request<GetWorkflowRequest>,
+    ) -> Result<Response<GetWorkflowResponse>, Status> {
+        let id = Uuid::parse_str(&request.into_inner().id).map_err(invalid_id)?;
+
+        match self.workflows.get(id).await.map_err(repository_error)? {
+            Some(workflow) => Ok(Response::new(workflow.into())),
+            None => Err(Status::not_found("Workflow not found")),
+        }
+    }
+}
@@ -0,0 +1,535 @@
+//! The Connect protocol, served by the same service implementations as
+//! gRPC: unary procedures are a `POST` of one JSON or protobuf message,
+//! streaming ones exchange enveloped messages and end with an end-stream
+//! message carrying the status. `build.rs` generates the routes.
+
+// Every step fails with the `Status` the client is sent, as services do.
+#![allow(clippy::result_large_err)]
+
+use std::{convert::Infallible, future::Future, time::Duration};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::Request,
+    http::{
+        header::{self, HeaderMap, HeaderValue},
+        StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use futures_util::{stream, Stream, StreamExt};
+use http_body_util::{BodyExt, LengthLimitError, Limited};
+use prost::{bytes::Buf, Message};
+use serde_json::{json, Map, Value};
+use tokio::time::Instant;
+use tonic::{
+    codec::{DecodeBuf, Decoder},
+    metadata::MetadataMap,
+    Code, Extensions, Status, Streaming,
+};
+
+use crate::proto::connect::PATHS;
+
+use super::transcoding::{forwarded, http_status, json, schema, with_metadata, Mapping};
+
+pub const PROTOCOL_VERSION: &str = "connect-protocol-version";
+pub const TIMEOUT_MS: &str = "connect-timeout-ms";
+
+const ACCEPTED: &str = "application/json, application/proto";
+const ACCEPTED_STREAMING: &str = "application/connect+json, application/connect+proto";
+
+/// The largest unary request body read, the default limit of gRPC.
+const MAX_MESSAGE_BYTES: usize = 4 * 1024 * 1024;
+
+/// Envelope flags.
+const COMPRESSED: u8 = 0b01;
+const END_STREAM: u8 = 0b10;
+
+/// One RPC as a Connect procedure, as generated by `build.rs`.
+#[derive(Clone, Debug)]
+pub struct Procedure {
+    /// e.g. `/users.UserService/GetUser`.
+    pub path: &'static str,
+    /// Fully qualified message names, e.g. `.users.GetUserRequest`.
+    pub input: &'static str,
+    pub output: &'static str,
+}
+
+/// Whether `request` speaks Connect: it names the protocol version, uses a
+/// Connect streaming content type, or posts a message to a procedure path.
+pub fn is_connect<B>(request: &axum::http::Request<B>) -> bool {
+    let content_type = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    request.headers().contains_key(PROTOCOL_VERSION)
+        || content_type.starts_with("application/connect+")
+        || (PATHS.contains(&request.uri().path()) && Codec::unary(content_type).is_some())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Codec {
+    Json,
+    Proto,
+}
+
+impl Codec {
+    fn unary(content_type: &str) -> Option<Self> {
+        match essence(content_type) {
+            "application/json" => Some(Codec::Json),
+            "application/proto" => Some(Codec::Proto),
+            _ => None,
+        }
+    }
+
+    fn streaming(content_type: &str) -> Option<Self> {
+        match essence(content_type) {
+            "application/connect+json" => Some(Codec::Json),
+            "application/connect+proto" => Some(Codec::Proto),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Codec::Json => "json",
+            Codec::Proto => "proto",
+        }
+    }
+
+    /// Decodes the message `name` as `T`, JSON through its descriptor so it
+    /// is read as proto3 JSON.
+    fn decode<T: Message + Default>(self, name: &str, bytes: &[u8]) -> Result<T, Status> {
+        let bytes = match self {
+            Codec::Json => {
+                let value: Value = serde_json::from_slice(bytes)
+                    .map_err(|e| Status::invalid_argument(format!("invalid JSON: {e}")))?;
+                json::encode(schema(), name, &value)
+                    .map_err(|e| Status::invalid_argument(e.to_string()))?
+            }
+            Codec::Proto => bytes.to_vec(),
+        };
+        T::decode(bytes.as_slice()).map_err(|e| Status::invalid_argument(e.to_string()))
+    }
+
+    fn encode<T: Message>(self, name: &str, message: &T) -> Result<Vec<u8>, Status> {
+        let bytes = message.encode_to_vec();
+        match self {
+            Codec::Json => json::decode(schema(), name, &bytes, Mapping::Proto3)
+                .map(|value| value.to_string().into_bytes())
+                .map_err(|e| {
+                    tracing::error!("Failed to encode {}: {}", name, e);
+                    Status::internal("Failed to encode response")
+                }),
+            Codec::Proto => Ok(bytes),
+        }
+    }
+}
+
+fn essence(content_type: &str) -> &str {
+    content_type.split(';').next().unwrap_or_default().trim()
+}
+
+/// Serves a unary procedure with `call`.
+pub async fn unary<Req, Res, F, Fut>(
+    procedure: &'static Procedure,
+    request: Request,
+    call: F,
+) -> Response
+where
+    Req: Message + Default,
+    Res: Message,
+    F: FnOnce(tonic::Request<Req>) -> Fut,
+    Fut: Future<Output = Result<tonic::Response<Res>, Status>>,
+{
+    let Some(codec) = content_type(&request).and_then(Codec::unary) else {
+        return unsupported(ACCEPTED);
+    };
+    let (deadline, metadata) = match start(request.headers()) {
+        Ok(start) => start,
+        Err(status) => return error_response(&status),
+    };
+    let body = match body(request).await {
+        Ok(body) => body,
+        Err(status) => return error_response(&status),
+    };
+    let message = match codec.decode::<Req>(procedure.input, &body) {
+        Ok(message) => message,
+        Err(status) => return error_response(&status),
+    };
+
+    let request = tonic::Request::from_parts(metadata, Extensions::default(), message);
+    let response = until(deadline, call(request)).await.and_then(|response| {
+        let (metadata, message, _) = response.into_parts();
+        Ok((metadata, codec.encode(procedure.output, &message)?))
+    });
+    match response {
+        Ok((metadata, body)) => {
+            let content_type = format!("application/{}", codec.name());
+            let mut response = ([(header::CONTENT_TYPE, content_type)], body).into_response();
+            with_metadata(&mut response, metadata);
+            response
+        }
+        Err(status) => error_response(&status),
+    }
+}
+
+/// Serves a server streaming procedure with `call`.
+pub async fn server_streaming<Req, Res, St, F, Fut>(
+    procedure: &'static Procedure,
+    request: Request,
+    call: F,
+) -> Response
+where
+    Req: Message + Default,
+    Res: Message + 'static,
+    St: Stream<Item = Result<Res, Status>> + Send + 'static,
+    F: FnOnce(tonic::Request<Req>) -> Fut,
+    Fut: Future<Output = Result<tonic::Response<St>, Status>>,
+{
+    let Some(codec) = content_type(&request).and_then(Codec::streaming) else {
+        return unsupported(ACCEPTED_STREAMING);
+    };
+    let response = async {
+        let (deadline, metadata) = start(request.headers())?;
+        let body = body(request).await?;
+        let message = match envelope(&body)? {
+            (payload, []) => codec.decode::<Req>(procedure.input, payload)?,
+            _ => {
+                return Err(Status::invalid_argument(
+                    "server streaming procedures take one message",
+                ))
+            }
+        };
+        let request = tonic::Request::from_parts(metadata, Extensions::default(), message);
+        Ok((deadline, until(deadline, call(request)).await?))
+    }
+    .await;
+    streaming_response(codec, procedure, response)
+}
+
+/// Serves a client streaming procedure with `call`, the request messages
+/// are decoded as the service reads them.
+pub async fn client_streaming<Req, Res, F, Fut>(
+    procedure: &'static Procedure,
+    request: Request,
+    call: F,
+) -> Response
+where
+    Req: Message + Default + 'static,
+    Res: Message + 'static,
+    F: FnOnce(tonic::Request<Streaming<Req>>) -> Fut,
+    Fut: Future<Output = Result<tonic::Response<Res>, Status>>,
+{
+    let Some(codec) = content_type(&request).and_then(Codec::streaming) else {
+        return unsupported(ACCEPTED_STREAMING);
+    };
+    let response = async {
+        let (deadline, request) = streaming_request(codec, procedure, request)?;
+        let response = until(deadline, call(request)).await?;
+        Ok((
+            deadline,
+            response.map(|message| stream::iter([Ok(message)])),
+        ))
+    }
+    .await;
+    streaming_response(codec, procedure, response)
+}
+
+/// Serves a bidirectional streaming procedure with `call`. Over HTTP/1.1
+/// the request is read in full before any response message is sent.
+pub async fn bidi_streaming<Req, Res, St, F, Fut>(
+    procedure: &'static Procedure,
+    request: Request,
+    call: F,
+) -> Response
+where
+    Req: Message + Default + 'static,
+    Res: Message + 'static,
+    St: Stream<Item = Result<Res, Status>> + Send + 'static,
+    F: FnOnce(tonic::Request<Streaming<Req>>) -> Fut,
+    Fut: Future<Output = Result<tonic::Response<St>, Status>>,
+{
+    let Some(codec) = content_type(&request).and_then(Codec::streaming) else {
+        return unsupported(ACCEPTED_STREAMING);
+    };
+    let response = async {
+        let (deadline, request) = streaming_request(codec, procedure, request)?;
+        Ok((deadline, until(deadline, call(request)).await?))
+    }
+    .await;
+    streaming_response(codec, procedure, response)
+}
+
+fn content_type(request: &Request) -> Option<&str> {
+    request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// The deadline and metadata of a request, rejecting other protocol
+/// versions and compressed bodies.
+fn start(headers: &HeaderMap) -> Result<(Option<Instant>, MetadataMap), Status> {
+    if let Some(version) = headers.get(PROTOCOL_VERSION) {
+        if version != "1" {
+            return Err(Status::invalid_argument(format!(
+                "unsupported {PROTOCOL_VERSION}, expected 1"
+            )));
+        }
+    }
+    let encoding = headers
+        .get(header::CONTENT_ENCODING)
+        .or_else(|| headers.get("connect-content-encoding"));
+    if encoding.is_some_and(|encoding| encoding != "identity") {
+        return Err(Status::unimplemented(
+            "compressed requests are not supported",
+        ));
+    }
+    let deadline = match headers.get(TIMEOUT_MS) {
+        // At most 10 digits, as the protocol allows.
+        Some(timeout) => Some(
+            timeout
+                .to_str()
+                .ok()
+                .filter(|timeout| {
+                    (1..=10).contains(&timeout.len()) && timeout.bytes().all(|b| b.is_ascii_digit())
+                })
+                .and_then(|timeout| timeout.parse().ok())
+                .and_then(|timeout| Instant::now().checked_add(Duration::from_millis(timeout)))
+                .ok_or_else(|| Status::invalid_argument(format!("invalid {TIMEOUT_MS}")))?,
+        ),
+        None => None,
+    };
+
+    let mut metadata = HeaderMap::new();
+    for (name, value) in headers {
+        if forwarded(name) && !name.as_str().starts_with("connect-") {
+            metadata.append(name, value.clone());
+        }
+    }
+    Ok((deadline, MetadataMap::from_headers(metadata)))
+}
+
+async fn body(request: Request) -> Result<Bytes, Status> {
+    let body = Limited::new(request.into_body(), MAX_MESSAGE_BYTES);
+    match body.collect().await {
+        Ok(body) => Ok(body.to_bytes()),
+        Err(e) if e.is::<LengthLimitError>() => Err(Status::resource_exhausted(format!(
+            "message larger than {MAX_MESSAGE_BYTES} bytes"
+        ))),
+        Err(e) => Err(Status::invalid_argument(e.to_string())),
+    }
+}
+
+/// Runs `future`, failing with `DeadlineExceeded` once `deadline` passes.
+async fn until<T>(
+    deadline: Option<Instant>,
+    future: impl Future<Output = Result<T, Status>>,
+) -> Result<T, Status> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future)
+            .await
+            .unwrap_or_else(|_| Err(Status::deadline_exceeded("deadline exceeded"))),
+        None => future.await,
+    }
+}
+
+/// Splits the first enveloped message off `bytes`.
+fn envelope(bytes: &[u8]) -> Result<(&[u8], &[u8]), Status> {
+    let invalid = || Status::invalid_argument("invalid envelope");
+    let (header, rest) = bytes.split_at_checked(5).ok_or_else(invalid)?;
+    if header[0] & COMPRESSED != 0 {
+        return Err(Status::unimplemented(
+            "compressed messages are not supported",
+        ));
+    }
+    let length = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    rest.split_at_checked(length).ok_or_else(invalid)
+}
+
+fn enveloped(flags: u8, payload: &[u8]) -> Bytes {
+    let mut bytes = Vec::with_capacity(payload.len() + 5);
+    bytes.push(flags);
+    bytes.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(payload);
+    bytes.into()
+}
+
+/// Reads the request messages the way gRPC does, the envelope of Connect
+/// is the gRPC length-prefixed message.
+fn streaming_request<Req: Message + Default + 'static>(
+    codec: Codec,
+    procedure: &'static Procedure,
+    request: Request,
+) -> Result<(Option<Instant>, tonic::Request<Streaming<Req>>), Status> {
+    let (deadline, metadata) = start(request.headers())?;
+    let decoder = MessageDecoder {
+        codec,
+        name: procedure.input,
+        message: std::marker::PhantomData,
+    };
+    // The constructor tonic's generated servers use for request streams.
+    let messages = Streaming::new_request(decoder, request.into_body(), None, None);
+    Ok((
+        deadline,
+        tonic::Request::from_parts(metadata, Extensions::default(), messages),
+    ))
+}
+
+struct MessageDecoder<T> {
+    codec: Codec,
+    name: &'static str,
+    message: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T: Message + Default> Decoder for MessageDecoder<T> {
+    type Item = T;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<T>, Status> {
+        let bytes = src.copy_to_bytes(src.remaining());
+        self.codec.decode(self.name, &bytes).map(Some)
+    }
+}
+
+/// Response messages in envelopes, then the end-stream message. Streaming
+/// procedures always answer `200 OK`, failures are reported at the end.
+fn streaming_response<Res, St>(
+    codec: Codec,
+    procedure: &'static Procedure,
+    response: Result<(Option<Instant>, tonic::Response<St>), Status>,
+) -> Response
+where
+    Res: Message + 'static,
+    St: Stream<Item = Result<Res, Status>> + Send + 'static,
+{
+    let content_type = format!("application/connect+{}", codec.name());
+    let (deadline, response) = match response {
+        Ok(response) => response,
+        Err(status) => {
+            let body = end_stream(Some(&status));
+            let mut response = ([(header::CONTENT_TYPE, content_type)], body).into_response();
+            with_metadata(&mut response, status.metadata().clone());
+            return response;
+        }
+    };
+    let (metadata, messages, _) = response.into_parts();
+
+    let messages = stream::unfold(Some(Box::pin(messages)), move |messages| async move {
+        let mut messages = messages?;
+        let next = until(deadline, async { messages.next().await.transpose() }).await;
+        let (frame, messages) = match next.and_then(|message| {
+            message
+                .map(|message| codec.encode(procedure.output, &message))
+                .transpose()
+        }) {
+            Ok(Some(payload)) => (enveloped(0, &payload), Some(messages)),
+            Ok(None) => (end_stream(None), None),
+            Err(status) => (end_stream(Some(&status)), None),
+        };
+        Some((Ok::<_, Infallible>(frame), messages))
+    });
+
+    let mut response = (
+        [(header::CONTENT_TYPE, content_type)],
+        Body::from_stream(messages),
+    )
+        .into_response();
+    with_metadata(&mut response, metadata);
+    response
+}
+
+/// The end-stream message, `{}` for success or the error and its metadata.
+fn end_stream(status: Option<&Status>) -> Bytes {
+    let end = match status {
+        Some(status) => {
+            let mut end = Map::new();
+            end.insert("error".into(), error_json(status));
+            let metadata = metadata_json(status.metadata());
+            if !metadata.is_empty() {
+                end.insert("metadata".into(), Value::Object(metadata));
+            }
+            Value::Object(end)
+        }
+        None => json!({}),
+    };
+    enveloped(END_STREAM, end.to_string().as_bytes())
+}
+
+fn metadata_json(metadata: &MetadataMap) -> Map<String, Value> {
+    let mut json = Map::new();
+    let headers = metadata.clone().into_headers();
+    for name in headers.keys() {
+        if !forwarded(name) || name.as_str().starts_with("grpc-") {
+            continue;
+        }
+        let values = headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .map(|value| Value::String(value.into()))
+            .collect();
+        json.insert(name.to_string(), Value::Array(values));
+    }
+    json
+}
+
+/// A unary error: the HTTP status of its code and a JSON body naming it.
+fn error_response(status: &Status) -> Response {
+    let mut response = (
+        http_status(status.code()),
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        )],
+        error_json(status).to_string(),
+    )
+        .into_response();
+    with_metadata(&mut response, status.metadata().clone());
+    response
+}
+
+fn error_json(status: &Status) -> Value {
+    let mut error = Map::new();
+    error.insert(
+        "code".into(),
+        Value::String(code_name(status.code()).into()),
+    );
+    if !status.message().is_empty() {
+        error.insert("message".into(), Value::String(status.message().into()));
+    }
+    Value::Object(error)
+}
+
+fn unsupported(accepted: &'static str) -> Response {
+    (
+        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        [("accept-post", accepted)],
+    )
+        .into_response()
+}
+
+/// The Connect name of a gRPC code.
+fn code_name(code: Code) -> &'static str {
+    match code {
+        Code::Ok => "ok",
+        Code::Cancelled => "canceled",
+        Code::Unknown => "unknown",
+        Code::InvalidArgument => "invalid_argument",
+        Code::DeadlineExceeded => "deadline_exceeded",
+        Code::NotFound => "not_found",
+        Code::AlreadyExists => "already_exists",
+        Code::PermissionDenied => "permission_denied",
+        Code::ResourceExhausted => "resource_exhausted",
+        Code::FailedPrecondition => "failed_precondition",
+        Code::Aborted => "aborted",
+        Code::OutOfRange => "out_of_range",
+        Code::Unimplemented => "unimplemented",
+        Code::Internal => "internal",
+        Code::Unavailable => "unavailable",
+        Code::DataLoss => "data_loss",
+        Code::Unauthenticated => "unauthenticated",
+    }
+}
@@ -0,0 +1,46 @@
+use tonic::{metadata::MetadataValue, Status};
+use tracing::error;
+
+use crate::{commands::CommandError, repositories::RepositoryError};
+
+impl From<CommandError> for Status {
+    fn from(e: CommandError) -> Self {
+        match e {
+            // A 503 over REST, see `transcoding::unary`.
+            CommandError::Overloaded { retry_after } => {
+                let mut status = Status::resource_exhausted("Server is busy, try again later");
+                status.metadata_mut().insert(
+                    "retry-after",
+                    MetadataValue::from(retry_after.as_secs().max(1)),
+                );
+                status
+            }
+            CommandError::Timeout => Status::deadline_exceeded("Command timed out"),
+            CommandError::Closed => Status::unavailable("Server is shutting down"),
+            CommandError::Unknown(_)
+            | CommandError::Payload(_)
+            | CommandError::InvalidSchedule(_)
+            | CommandError::InvalidUser(_) => Status::invalid_argument(e.to_string()),
+            CommandError::Repository(RepositoryError::NotFound) => Status::not_found("Not found"),
+            CommandError::Repository(RepositoryError::Conflict { expected, actual }) => {
+                Status::aborted(format!("Version is {actual}, expected {expected}"))
+            }
+            CommandError::Repository(RepositoryError::Duplicate(_)) => {
+                Status::already_exists(e.to_string())
+            }
+            CommandError::Repository(RepositoryError::Locked) => {
+                Status::aborted("Held by another request")
+            }
+            e => {
+                error!("{}", e);
+                Status::internal("Internal server error")
+            }
+        }
+    }
+}
+
+impl From<RepositoryError> for Status {
+    fn from(e: RepositoryError) -> Self {
+        CommandError::Repository(e).into()
+    }
+}
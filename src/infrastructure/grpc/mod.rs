@@ -1,2 +1,7 @@
+pub mod admin;
+pub mod connect;
+pub mod errors;
+pub mod schedules;
 pub mod services;
+pub mod transcoding;
 pub mod users;
@@ -0,0 +1,135 @@
+use chrono::{DateTime, Utc};
+use tonic::{Request, Response, Status};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    commands::CommandDecoders,
+    models::ScheduledCommand,
+    proto::{
+        schedule_service_server::ScheduleService as GrpcScheduleService, CancelScheduleRequest,
+        CancelScheduleResponse, CreateScheduleRequest, GetScheduleRequest, GetScheduleResponse,
+        ListSchedulesRequest, ListSchedulesResponse,
+    },
+    repositories::RepositoryError,
+    services::{ScheduleService, Trigger},
+    PostgreSQL,
+};
+
+pub struct GrpcScheduleServiceImpl {
+    schedules: ScheduleService,
+}
+
+impl GrpcScheduleServiceImpl {
+    pub fn new(repo: PostgreSQL, decoders: CommandDecoders) -> Self {
+        GrpcScheduleServiceImpl {
+            schedules: ScheduleService::new(repo, decoders),
+        }
+    }
+}
+
+impl From<ScheduledCommand> for GetScheduleResponse {
+    fn from(schedule: ScheduledCommand) -> Self {
+        GetScheduleResponse {
+            id: schedule.id.to_string(),
+            command_type: schedule.command_type,
+            payload: schedule.payload.to_string(),
+            aggregate_id: schedule.aggregate_id.map(|id| id.to_string()),
+            run_at: schedule.run_at.to_rfc3339(),
+            cron: schedule.cron,
+            last_run_at: schedule.last_run_at.map(|at| at.to_rfc3339()),
+            created_at: schedule.created_at.to_rfc3339(),
+        }
+    }
+}
+
+impl TryFrom<&CreateScheduleRequest> for Trigger {
+    type Error = &'static str;
+
+    fn try_from(request: &CreateScheduleRequest) -> Result<Self, Self::Error> {
+        match (&request.run_at, request.delay_secs, &request.cron) {
+            (Some(run_at), None, None) => DateTime::parse_from_rfc3339(run_at)
+                .map(|run_at| Trigger::At(run_at.with_timezone(&Utc)))
+                .map_err(|_| "run_at must be an RFC 3339 timestamp"),
+            (None, Some(delay), None) => {
+                Trigger::after_secs(delay).ok_or("delay_secs is out of range")
+            }
+            (None, None, Some(cron)) => Ok(Trigger::Cron(cron.clone())),
+            _ => Err("Exactly one of run_at, delay_secs or cron is required"),
+        }
+    }
+}
+
+fn invalid_id(_: uuid::Error) -> Status {
+    Status::invalid_argument("Invalid schedule id")
+}
+
+fn repository_error(e: RepositoryError) -> Status {
+    match e {
+        RepositoryError::NotFound => Status::not_found("Schedule not found"),
+        e => {
+            error!("{}", e);
+            Status::internal("Internal server error")
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl GrpcScheduleService for GrpcScheduleServiceImpl {
+    async fn create_schedule(
+        &self,
+        request: Request<CreateScheduleRequest>,
+    ) -> Result<Response<GetScheduleResponse>, Status> {
+        let request = request.into_inner();
+        let trigger = Trigger::try_from(&request).map_err(Status::invalid_argument)?;
+        let payload = serde_json::from_str(&request.payload)
+            .map_err(|_| Status::invalid_argument("payload must be a JSON document"))?;
+
+        let schedule = self
+            .schedules
+            .schedule_serialized(&request.command_type, payload, trigger)
+            .await?;
+        info!("Scheduled {} {}", schedule.command_type, schedule.id);
+        Ok(Response::new(schedule.into()))
+    }
+
+    async fn list_schedules(
+        &self,
+        request: Request<ListSchedulesRequest>,
+    ) -> Result<Response<ListSchedulesResponse>, Status> {
+        let request = request.into_inner();
+        let limit = if request.limit > 0 { request.limit } else { 50 };
+
+        let schedules = self
+            .schedules
+            .list(limit, request.offset)
+            .await
+            .map_err(repository_error)?;
+        Ok(Response::new(ListSchedulesResponse {
+            schedules: schedules.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn get_schedule(
+        &self,
+        request: Request<GetScheduleRequest>,
+    ) -> Result<Response<GetScheduleResponse>, Status> {
+        let id = Uuid::parse_str(&request.into_inner().id).map_err(invalid_id)?;
+
+        match self.schedules.get(id).await.map_err(repository_error)? {
+            Some(schedule) => Ok(Response::new(schedule.into())),
+            None => Err(Status::not_found("Schedule not found")),
+        }
+    }
+
+    async fn cancel_schedule(
+        &self,
+        request: Request<CancelScheduleRequest>,
+    ) -> Result<Response<CancelScheduleResponse>, Status> {
+        let id = Uuid::parse_str(&request.into_inner().id).map_err(invalid_id)?;
+
+        self.schedules.cancel(id).await.map_err(repository_error)?;
+        info!("Cancelled schedule {}", id);
+        Ok(Response::new(CancelScheduleResponse {}))
+    }
+}
@@ -1,14 +1,30 @@
-use sqlx::{Pool, Postgres};
-use tokio::sync::mpsc;
+use axum::middleware::from_fn_with_state;
 use tonic_reflection::pb::v1alpha::FILE_DESCRIPTOR_SET;
 
-use crate::commands::CommandMessage;
+use crate::{
+    commands::{CommandBus, CommandDecoders},
+    proto::{
+        admin_service_server::AdminServiceServer, connect, rest,
+        schedule_service_server::ScheduleServiceServer, user_service_server::UserServiceServer,
+    },
+    repositories::UserStore,
+    require_api_token, require_bearer, ApiTokens, BearerTokens, EventBus, PostgreSQL,
+};
 
-use super::users::GrpcUserServiceImpl;
+use super::{
+    admin::GrpcAdminServiceImpl, schedules::GrpcScheduleServiceImpl, users::GrpcUserServiceImpl,
+};
 
+/// Every service, the user one only for the bearer tokens of `api_tokens`
+/// if there are any, the admin and schedule ones only for those of
+/// `admin_tokens`.
 pub fn services(
-    pool: Pool<Postgres>,
-    sender: mpsc::Sender<CommandMessage>,
+    db: PostgreSQL,
+    bus: CommandBus,
+    decoders: CommandDecoders,
+    events: EventBus,
+    api_tokens: BearerTokens,
+    admin_tokens: BearerTokens,
 ) -> axum::routing::Router {
     let reflection_service = tonic_reflection::server::Builder::configure()
         .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
@@ -18,9 +34,88 @@ pub fn services(
     tonic::transport::Server::builder()
         .accept_http1(true)
         .add_service(reflection_service)
-        .add_service(tonic_web::enable(GrpcUserServiceImpl::new(
-            pool.clone(),
-            sender,
+        .add_service(tonic_web::enable(AdminServiceServer::with_interceptor(
+            GrpcAdminServiceImpl::new(db.clone(), bus.clone(), decoders.clone()),
+            admin_tokens.clone(),
+        )))
+        .add_service(tonic_web::enable(ScheduleServiceServer::with_interceptor(
+            GrpcScheduleServiceImpl::new(db.clone(), decoders),
+            admin_tokens,
+        )))
+        .add_service(tonic_web::enable(UserServiceServer::with_interceptor(
+            GrpcUserServiceImpl::new(db, bus).with_event_bus(events),
+            ApiTokens(api_tokens),
         )))
         .into_router()
 }
+
+/// The user service alone, for backends other than Postgres, only for the
+/// bearer tokens of `api_tokens` if there are any.
+pub fn user_services<R: UserStore>(
+    repo: R,
+    bus: CommandBus,
+    events: EventBus,
+    api_tokens: BearerTokens,
+) -> axum::routing::Router {
+    let reflection_service = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
+        .build()
+        .unwrap();
+
+    tonic::transport::Server::builder()
+        .accept_http1(true)
+        .add_service(reflection_service)
+        .add_service(tonic_web::enable(UserServiceServer::with_interceptor(
+            GrpcUserServiceImpl::with_repository(repo, bus).with_event_bus(events),
+            ApiTokens(api_tokens),
+        )))
+        .into_router()
+}
+
+/// The user service over REST, at the routes `build.rs` generates from
+/// the `google.api.http` annotations of `users.proto`.
+pub fn rest_services(db: PostgreSQL, bus: CommandBus) -> axum::routing::Router {
+    rest::user_service_routes(GrpcUserServiceImpl::new(db, bus))
+}
+
+/// The user service alone over REST, for backends other than Postgres.
+pub fn user_rest_services<R: UserStore>(repo: R, bus: CommandBus) -> axum::routing::Router {
+    rest::user_service_routes(GrpcUserServiceImpl::with_repository(repo, bus))
+}
+
+/// The schedule and user services over the Connect protocol, the user one
+/// only for the bearer tokens of `api_tokens` if there are any, the
+/// schedule one only for those of `admin_tokens`. The admin service is
+/// left to the guarded `/admin` routes and gRPC.
+pub fn connect_services(
+    db: PostgreSQL,
+    bus: CommandBus,
+    decoders: CommandDecoders,
+    events: EventBus,
+    api_tokens: BearerTokens,
+    admin_tokens: BearerTokens,
+) -> axum::routing::Router {
+    axum::Router::new()
+        .merge(
+            connect::schedule_service_routes(GrpcScheduleServiceImpl::new(db.clone(), decoders))
+                .route_layer(from_fn_with_state(admin_tokens, require_bearer)),
+        )
+        .merge(
+            connect::user_service_routes(GrpcUserServiceImpl::new(db, bus).with_event_bus(events))
+                .route_layer(from_fn_with_state(api_tokens, require_api_token)),
+        )
+}
+
+/// The user service alone over Connect, for backends other than Postgres,
+/// only for the bearer tokens of `api_tokens` if there are any.
+pub fn user_connect_services<R: UserStore>(
+    repo: R,
+    bus: CommandBus,
+    events: EventBus,
+    api_tokens: BearerTokens,
+) -> axum::routing::Router {
+    connect::user_service_routes(
+        GrpcUserServiceImpl::with_repository(repo, bus).with_event_bus(events),
+    )
+    .route_layer(from_fn_with_state(api_tokens, require_api_token))
+}
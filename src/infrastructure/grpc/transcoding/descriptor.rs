@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use prost::Message;
+use prost_types::{
+    field_descriptor_proto::{Label, Type},
+    DescriptorProto, EnumDescriptorProto, FieldDescriptorProto,
+};
+
+/// Messages and enums by fully qualified name, e.g. `.users.GetUserRequest`.
+#[derive(Debug, Default)]
+pub struct Schema {
+    messages: HashMap<String, DescriptorProto>,
+    enums: HashMap<String, EnumDescriptorProto>,
+}
+
+impl Schema {
+    pub fn new(descriptor_set: &[u8]) -> Result<Self, prost::DecodeError> {
+        let set = prost_types::FileDescriptorSet::decode(descriptor_set)?;
+        let mut schema = Schema::default();
+        for file in set.file {
+            let scope = match file.package() {
+                "" => String::new(),
+                package => format!(".{package}"),
+            };
+            schema.add_enums(&scope, file.enum_type);
+            for message in file.message_type {
+                schema.add_message(&scope, message);
+            }
+        }
+        Ok(schema)
+    }
+
+    fn add_message(&mut self, scope: &str, mut message: DescriptorProto) {
+        let name = format!("{scope}.{}", message.name());
+        self.add_enums(&name, std::mem::take(&mut message.enum_type));
+        for nested in std::mem::take(&mut message.nested_type) {
+            self.add_message(&name, nested);
+        }
+        self.messages.insert(name, message);
+    }
+
+    fn add_enums(&mut self, scope: &str, enums: Vec<EnumDescriptorProto>) {
+        for e in enums {
+            self.enums.insert(format!("{scope}.{}", e.name()), e);
+        }
+    }
+
+    pub fn message(&self, name: &str) -> Option<&DescriptorProto> {
+        self.messages.get(name)
+    }
+
+    pub fn enumeration(&self, name: &str) -> Option<&EnumDescriptorProto> {
+        self.enums.get(name)
+    }
+}
+
+/// Name of `field` in proto3 JSON, `lowerCamelCase` unless overridden.
+pub fn json_name(field: &FieldDescriptorProto) -> &str {
+    match field.json_name() {
+        "" => field.name(),
+        name => name,
+    }
+}
+
+pub fn is_repeated(field: &FieldDescriptorProto) -> bool {
+    field.label() == Label::Repeated
+}
+
+/// Fields that are omitted rather than written with their default value,
+/// messages and `optional` or oneof scalars.
+pub fn has_presence(field: &FieldDescriptorProto) -> bool {
+    field.r#type() == Type::Message || field.proto3_optional() || field.oneof_index.is_some()
+}
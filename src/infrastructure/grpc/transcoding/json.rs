@@ -0,0 +1,534 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use derive_more::{Display, Error};
+use prost::Message;
+use prost::{
+    bytes::{Buf, BufMut},
+    encoding::WireType,
+    encoding::{decode_key, decode_varint, encode_key, encode_varint, skip_field, DecodeContext},
+};
+use prost_types::{
+    field_descriptor_proto::Type, DescriptorProto, Duration, FieldDescriptorProto, FieldMask,
+    ListValue, Struct, Timestamp,
+};
+use serde_json::{Map, Number, Value};
+
+use crate::proto::well_known::WellKnown;
+
+use super::descriptor::{has_presence, is_repeated, json_name, Schema};
+
+/// A message that does not match its descriptor.
+#[derive(Debug, Display, Error)]
+#[display("{_0}")]
+pub struct InvalidMessage(#[error(not(source))] pub String);
+
+fn invalid(field: &FieldDescriptorProto, reason: &str) -> InvalidMessage {
+    InvalidMessage(format!("{}: {reason}", json_name(field)))
+}
+
+impl From<prost::DecodeError> for InvalidMessage {
+    fn from(e: prost::DecodeError) -> Self {
+        InvalidMessage(e.to_string())
+    }
+}
+
+/// Encodes a proto3 JSON object as the protobuf message `name`. Numbers
+/// and booleans may also be given as strings, as they are in query
+/// strings and path parameters.
+pub fn encode(schema: &Schema, name: &str, value: &Value) -> Result<Vec<u8>, InvalidMessage> {
+    let mut buf = Vec::new();
+    encode_message(schema, name, value, &mut buf)?;
+    Ok(buf)
+}
+
+fn message<'a>(schema: &'a Schema, name: &str) -> Result<&'a DescriptorProto, InvalidMessage> {
+    schema
+        .message(name)
+        .ok_or_else(|| InvalidMessage(format!("unknown message {name}")))
+}
+
+fn encode_message(
+    schema: &Schema,
+    name: &str,
+    value: &Value,
+    buf: &mut Vec<u8>,
+) -> Result<(), InvalidMessage> {
+    let descriptor = message(schema, name)?;
+    let Value::Object(object) = value else {
+        return Err(InvalidMessage(format!("expected an object for {name}")));
+    };
+
+    for (key, value) in object {
+        let field = descriptor
+            .field
+            .iter()
+            .find(|field| json_name(field) == key || field.name() == key)
+            .ok_or_else(|| InvalidMessage(format!("unknown field {key}")))?;
+        match value {
+            Value::Null => {}
+            Value::Object(entries) if is_map(schema, field) => {
+                for (key, value) in entries {
+                    let entry = Value::Object(Map::from_iter([
+                        ("key".to_string(), Value::String(key.clone())),
+                        ("value".to_string(), value.clone()),
+                    ]));
+                    encode_value(schema, field, &entry, buf)?;
+                }
+            }
+            Value::Array(items) if is_repeated(field) => {
+                for item in items {
+                    encode_value(schema, field, item, buf)?;
+                }
+            }
+            value => encode_value(schema, field, value, buf)?,
+        }
+    }
+    Ok(())
+}
+
+fn is_map(schema: &Schema, field: &FieldDescriptorProto) -> bool {
+    field.r#type() == Type::Message
+        && schema
+            .message(field.type_name())
+            .and_then(|entry| entry.options.as_ref())
+            .is_some_and(|options| options.map_entry())
+}
+
+fn encode_value(
+    schema: &Schema,
+    field: &FieldDescriptorProto,
+    value: &Value,
+    buf: &mut Vec<u8>,
+) -> Result<(), InvalidMessage> {
+    let tag = field.number() as u32;
+    match field.r#type() {
+        Type::Double => {
+            encode_key(tag, WireType::SixtyFourBit, buf);
+            buf.put_f64_le(float(field, value)?);
+        }
+        Type::Float => {
+            encode_key(tag, WireType::ThirtyTwoBit, buf);
+            buf.put_f32_le(float(field, value)? as f32);
+        }
+        Type::Int64 | Type::Int32 => {
+            encode_key(tag, WireType::Varint, buf);
+            encode_varint(int(field, value)? as u64, buf);
+        }
+        Type::Uint64 | Type::Uint32 => {
+            encode_key(tag, WireType::Varint, buf);
+            encode_varint(uint(field, value)?, buf);
+        }
+        Type::Sint64 | Type::Sint32 => {
+            let n = int(field, value)?;
+            encode_key(tag, WireType::Varint, buf);
+            encode_varint(((n << 1) ^ (n >> 63)) as u64, buf);
+        }
+        Type::Fixed64 => {
+            encode_key(tag, WireType::SixtyFourBit, buf);
+            buf.put_u64_le(uint(field, value)?);
+        }
+        Type::Sfixed64 => {
+            encode_key(tag, WireType::SixtyFourBit, buf);
+            buf.put_i64_le(int(field, value)?);
+        }
+        Type::Fixed32 => {
+            encode_key(tag, WireType::ThirtyTwoBit, buf);
+            buf.put_u32_le(uint(field, value)? as u32);
+        }
+        Type::Sfixed32 => {
+            encode_key(tag, WireType::ThirtyTwoBit, buf);
+            buf.put_i32_le(int(field, value)? as i32);
+        }
+        Type::Bool => {
+            let b = match value {
+                Value::Bool(b) => *b,
+                Value::String(s) => s.parse().map_err(|_| invalid(field, "expected a bool"))?,
+                _ => return Err(invalid(field, "expected a bool")),
+            };
+            encode_key(tag, WireType::Varint, buf);
+            encode_varint(b as u64, buf);
+        }
+        Type::Enum => {
+            let number = match value {
+                Value::String(name) => schema
+                    .enumeration(field.type_name())
+                    .and_then(|e| e.value.iter().find(|v| v.name() == name))
+                    .map(|v| v.number() as i64)
+                    .map_or_else(|| int(field, value), Ok)?,
+                value => int(field, value)?,
+            };
+            encode_key(tag, WireType::Varint, buf);
+            encode_varint(number as u64, buf);
+        }
+        Type::String => {
+            let Value::String(s) = value else {
+                return Err(invalid(field, "expected a string"));
+            };
+            encode_bytes(tag, s.as_bytes(), buf);
+        }
+        Type::Bytes => {
+            let Value::String(s) = value else {
+                return Err(invalid(field, "expected a base64 string"));
+            };
+            let bytes = STANDARD
+                .decode(s)
+                .map_err(|_| invalid(field, "expected a base64 string"))?;
+            encode_bytes(tag, &bytes, buf);
+        }
+        Type::Message => {
+            let nested = match encode_well_known(field.type_name(), value) {
+                Some(nested) => nested.map_err(|e| invalid(field, &e))?,
+                None => {
+                    let mut nested = Vec::new();
+                    encode_message(schema, field.type_name(), value, &mut nested)?;
+                    nested
+                }
+            };
+            encode_bytes(tag, &nested, buf);
+        }
+        Type::Group => return Err(invalid(field, "groups are not supported")),
+    }
+    Ok(())
+}
+
+/// The `google.protobuf` message `name` from its own JSON form, `None` for
+/// other messages.
+fn encode_well_known(name: &str, value: &Value) -> Option<Result<Vec<u8>, String>> {
+    fn encode<T: WellKnown + Message>(value: &Value) -> Result<Vec<u8>, String> {
+        T::from_json(value.clone()).map(|message| message.encode_to_vec())
+    }
+
+    Some(match name {
+        ".google.protobuf.Timestamp" => encode::<Timestamp>(value),
+        ".google.protobuf.Duration" => encode::<Duration>(value),
+        ".google.protobuf.FieldMask" => encode::<FieldMask>(value),
+        ".google.protobuf.Struct" => encode::<Struct>(value),
+        ".google.protobuf.ListValue" => encode::<ListValue>(value),
+        ".google.protobuf.Value" => encode::<prost_types::Value>(value),
+        ".google.protobuf.Empty" => Ok(Vec::new()),
+        ".google.protobuf.Any" => Err("google.protobuf.Any is not supported".into()),
+        name => {
+            // Wrappers are their `value` field.
+            let field = wrapped(name)?;
+            let mut buf = Vec::new();
+            encode_value(&Schema::default(), &field, value, &mut buf)
+                .map(|()| buf)
+                .map_err(|e| e.to_string())
+        }
+    })
+}
+
+/// The `value` field of the wrapper message `name`, e.g.
+/// `.google.protobuf.Int64Value`.
+fn wrapped(name: &str) -> Option<FieldDescriptorProto> {
+    let r#type = match name.strip_prefix(".google.protobuf.")? {
+        "DoubleValue" => Type::Double,
+        "FloatValue" => Type::Float,
+        "Int64Value" => Type::Int64,
+        "UInt64Value" => Type::Uint64,
+        "Int32Value" => Type::Int32,
+        "UInt32Value" => Type::Uint32,
+        "BoolValue" => Type::Bool,
+        "StringValue" => Type::String,
+        "BytesValue" => Type::Bytes,
+        _ => return None,
+    };
+    Some(FieldDescriptorProto {
+        name: Some("value".into()),
+        number: Some(1),
+        r#type: Some(r#type.into()),
+        ..Default::default()
+    })
+}
+
+fn encode_bytes(tag: u32, bytes: &[u8], buf: &mut Vec<u8>) {
+    encode_key(tag, WireType::LengthDelimited, buf);
+    encode_varint(bytes.len() as u64, buf);
+    buf.put_slice(bytes);
+}
+
+fn int(field: &FieldDescriptorProto, value: &Value) -> Result<i64, InvalidMessage> {
+    match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| invalid(field, "expected an integer"))
+}
+
+fn uint(field: &FieldDescriptorProto, value: &Value) -> Result<u64, InvalidMessage> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| invalid(field, "expected an unsigned integer"))
+}
+
+fn float(field: &FieldDescriptorProto, value: &Value) -> Result<f64, InvalidMessage> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| invalid(field, "expected a number"))
+}
+
+/// The JSON written by [`decode`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mapping {
+    /// Proto3 JSON, `lowerCamelCase` names and 64-bit integers as strings.
+    Proto3,
+    /// Proto field names and 64-bit integers as numbers, the shape `serde`
+    /// expects of Rust types with the same fields.
+    Rust,
+}
+
+/// Decodes the protobuf message `name` into JSON, fields without presence
+/// are written even when they hold their default value.
+pub fn decode(
+    schema: &Schema,
+    name: &str,
+    mut buf: &[u8],
+    mapping: Mapping,
+) -> Result<Value, InvalidMessage> {
+    Decoder { schema, mapping }
+        .message(name, &mut buf)
+        .map(Value::Object)
+}
+
+struct Decoder<'a> {
+    schema: &'a Schema,
+    mapping: Mapping,
+}
+
+impl Decoder<'_> {
+    fn key(&self, field: &FieldDescriptorProto) -> String {
+        match self.mapping {
+            Mapping::Proto3 => json_name(field).to_string(),
+            Mapping::Rust => field.name().to_string(),
+        }
+    }
+
+    fn int64(&self, n: impl ToString + Into<Value>) -> Value {
+        match self.mapping {
+            Mapping::Proto3 => Value::String(n.to_string()),
+            Mapping::Rust => n.into(),
+        }
+    }
+
+    fn message(&self, name: &str, buf: &mut &[u8]) -> Result<Map<String, Value>, InvalidMessage> {
+        let descriptor = message(self.schema, name)?;
+        let mut object = Map::new();
+
+        while buf.has_remaining() {
+            let (tag, wire_type) = decode_key(buf)?;
+            let Some(field) = descriptor
+                .field
+                .iter()
+                .find(|field| field.number() as u32 == tag)
+            else {
+                skip_field(wire_type, tag, buf, DecodeContext::default())?;
+                continue;
+            };
+            let key = self.key(field);
+
+            if is_map(self.schema, field) {
+                let mut entry = length_delimited(buf)?;
+                let entry = self.message(field.type_name(), &mut entry)?;
+                let key_string = match entry.get("key") {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(other) => other.to_string(),
+                    None => String::new(),
+                };
+                let value = entry.get("value").cloned().unwrap_or(Value::Null);
+                object
+                    .entry(key)
+                    .or_insert_with(|| Value::Object(Map::new()))
+                    .as_object_mut()
+                    .expect("map fields are objects")
+                    .insert(key_string, value);
+            } else if is_repeated(field) {
+                let mut values = Vec::new();
+                match (wire_type, scalar_wire_type(field.r#type())) {
+                    (WireType::LengthDelimited, Some(packed)) => {
+                        let mut items = length_delimited(buf)?;
+                        while items.has_remaining() {
+                            values.push(self.value(field, packed, &mut items)?);
+                        }
+                    }
+                    _ => values.push(self.value(field, wire_type, buf)?),
+                }
+                object
+                    .entry(key)
+                    .or_insert_with(|| Value::Array(Vec::new()))
+                    .as_array_mut()
+                    .expect("repeated fields are arrays")
+                    .extend(values);
+            } else {
+                object.insert(key, self.value(field, wire_type, buf)?);
+            }
+        }
+
+        for field in &descriptor.field {
+            let key = self.key(field);
+            if object.contains_key(&key) || (has_presence(field) && !is_repeated(field)) {
+                continue;
+            }
+            let default = if is_map(self.schema, field) {
+                Value::Object(Map::new())
+            } else if is_repeated(field) {
+                Value::Array(Vec::new())
+            } else {
+                self.default_value(field)
+            };
+            object.insert(key, default);
+        }
+        Ok(object)
+    }
+
+    fn value(
+        &self,
+        field: &FieldDescriptorProto,
+        wire_type: WireType,
+        buf: &mut &[u8],
+    ) -> Result<Value, InvalidMessage> {
+        let expected = scalar_wire_type(field.r#type()).unwrap_or(WireType::LengthDelimited);
+        if wire_type != expected {
+            return Err(invalid(field, "unexpected wire type"));
+        }
+        let width = match wire_type {
+            WireType::SixtyFourBit => 8,
+            WireType::ThirtyTwoBit => 4,
+            _ => 0,
+        };
+        if buf.remaining() < width {
+            return Err(InvalidMessage("truncated message".into()));
+        }
+
+        Ok(match field.r#type() {
+            Type::Double => float_value(buf.get_f64_le()),
+            Type::Float => float_value(buf.get_f32_le() as f64),
+            Type::Int64 => self.int64(decode_varint(buf)? as i64),
+            Type::Uint64 => self.int64(decode_varint(buf)?),
+            Type::Int32 => Value::from(decode_varint(buf)? as i32),
+            Type::Uint32 => Value::from(decode_varint(buf)? as u32),
+            Type::Sint64 => self.int64(zigzag(decode_varint(buf)?)),
+            Type::Sint32 => Value::from(zigzag(decode_varint(buf)?) as i32),
+            Type::Fixed64 => self.int64(buf.get_u64_le()),
+            Type::Sfixed64 => self.int64(buf.get_i64_le()),
+            Type::Fixed32 => Value::from(buf.get_u32_le()),
+            Type::Sfixed32 => Value::from(buf.get_i32_le()),
+            Type::Bool => Value::Bool(decode_varint(buf)? != 0),
+            Type::Enum => enum_value(self.schema, field, decode_varint(buf)? as i32),
+            Type::String => {
+                let bytes = length_delimited(buf)?;
+                Value::String(
+                    String::from_utf8(bytes.to_vec())
+                        .map_err(|_| invalid(field, "invalid UTF-8"))?,
+                )
+            }
+            Type::Bytes => Value::String(STANDARD.encode(length_delimited(buf)?)),
+            Type::Message => {
+                let mut nested = length_delimited(buf)?;
+                match self.well_known(field.type_name(), nested) {
+                    Some(value) => value.map_err(|e| invalid(field, &e.0))?,
+                    None => Value::Object(self.message(field.type_name(), &mut nested)?),
+                }
+            }
+            Type::Group => return Err(invalid(field, "groups are not supported")),
+        })
+    }
+
+    /// The `google.protobuf` message `name` in its own JSON form, `None`
+    /// for other messages.
+    fn well_known(&self, name: &str, buf: &[u8]) -> Option<Result<Value, InvalidMessage>> {
+        fn decode<T: WellKnown + Message + Default>(buf: &[u8]) -> Result<Value, InvalidMessage> {
+            Ok(T::decode(buf)?.to_json())
+        }
+
+        Some(match name {
+            ".google.protobuf.Timestamp" => decode::<Timestamp>(buf),
+            ".google.protobuf.Duration" => decode::<Duration>(buf),
+            ".google.protobuf.FieldMask" => decode::<FieldMask>(buf),
+            ".google.protobuf.Struct" => decode::<Struct>(buf),
+            ".google.protobuf.ListValue" => decode::<ListValue>(buf),
+            ".google.protobuf.Value" => decode::<prost_types::Value>(buf),
+            ".google.protobuf.Empty" => Ok(Value::Object(Map::new())),
+            ".google.protobuf.Any" => Err(InvalidMessage(
+                "google.protobuf.Any is not supported".into(),
+            )),
+            // Wrappers are their `value` field.
+            name => self.wrapper(&wrapped(name)?, buf),
+        })
+    }
+
+    fn wrapper(
+        &self,
+        field: &FieldDescriptorProto,
+        mut buf: &[u8],
+    ) -> Result<Value, InvalidMessage> {
+        let mut value = self.default_value(field);
+        while buf.has_remaining() {
+            let (tag, wire_type) = decode_key(&mut buf)?;
+            match tag {
+                1 => value = self.value(field, wire_type, &mut buf)?,
+                _ => skip_field(wire_type, tag, &mut buf, DecodeContext::default())?,
+            }
+        }
+        Ok(value)
+    }
+
+    fn default_value(&self, field: &FieldDescriptorProto) -> Value {
+        match field.r#type() {
+            Type::Int64 | Type::Uint64 | Type::Sint64 | Type::Fixed64 | Type::Sfixed64 => {
+                self.int64(0)
+            }
+            Type::Bool => Value::Bool(false),
+            Type::String | Type::Bytes => Value::String(String::new()),
+            Type::Enum => enum_value(self.schema, field, 0),
+            Type::Message | Type::Group => Value::Null,
+            _ => Value::from(0),
+        }
+    }
+}
+
+/// Wire type of a packable scalar, `None` for strings, bytes and messages.
+fn scalar_wire_type(ty: Type) -> Option<WireType> {
+    match ty {
+        Type::Double | Type::Fixed64 | Type::Sfixed64 => Some(WireType::SixtyFourBit),
+        Type::Float | Type::Fixed32 | Type::Sfixed32 => Some(WireType::ThirtyTwoBit),
+        Type::String | Type::Bytes | Type::Message | Type::Group => None,
+        _ => Some(WireType::Varint),
+    }
+}
+
+fn length_delimited<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8], InvalidMessage> {
+    let len = decode_varint(buf)? as usize;
+    if buf.len() < len {
+        return Err(InvalidMessage("truncated message".into()));
+    }
+    let (bytes, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(bytes)
+}
+
+fn zigzag(n: u64) -> i64 {
+    ((n >> 1) as i64) ^ -((n & 1) as i64)
+}
+
+/// `NaN` and the infinities are strings in proto3 JSON.
+fn float_value(f: f64) -> Value {
+    match Number::from_f64(f) {
+        Some(n) => Value::Number(n),
+        None if f.is_nan() => Value::String("NaN".into()),
+        None if f > 0.0 => Value::String("Infinity".into()),
+        None => Value::String("-Infinity".into()),
+    }
+}
+
+fn enum_value(schema: &Schema, field: &FieldDescriptorProto, number: i32) -> Value {
+    schema
+        .enumeration(field.type_name())
+        .and_then(|e| e.value.iter().find(|v| v.number() == number))
+        .map_or_else(|| Value::from(number), |v| Value::String(v.name().into()))
+}
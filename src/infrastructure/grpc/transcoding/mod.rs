@@ -0,0 +1,415 @@
+//! Runtime support for the REST routes `build.rs` generates from the gRPC
+//! services: HTTP requests are translated into request messages and handed
+//! to the service implementation, using the proto3 JSON mapping. Bodies
+//! may also be protobuf or MessagePack, negotiated like the other REST
+//! endpoints.
+
+mod descriptor;
+pub(crate) mod json;
+
+use std::{collections::HashMap, future::Future, sync::OnceLock};
+
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Path, Query, Request},
+    http::{
+        header::{self, HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, IF_MATCH, VARY},
+        StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Json,
+};
+use prost::Message;
+use serde::{
+    de::{DeserializeOwned, Error as _},
+    ser::Error as _,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use serde_json::{json, Map, Value};
+use tonic::{metadata::MetadataMap, Code, Extensions, Status};
+use tracing::error;
+
+use crate::{proto::FILE_DESCRIPTOR_SET, Format};
+
+use super::users::IDEMPOTENCY_KEY;
+
+use descriptor::{json_name, Schema};
+pub use json::InvalidMessage;
+pub(crate) use json::Mapping;
+
+/// One REST endpoint of a unary RPC, as generated by `build.rs`.
+#[derive(Clone, Debug)]
+pub struct Binding {
+    /// gRPC path, e.g. `/users.UserService/GetUser`.
+    pub rpc: &'static str,
+    /// The axum route of the path template, e.g. `/v1/users/:id`.
+    pub route: &'static str,
+    /// Route parameters and the request fields they are copied to.
+    pub variables: &'static [(&'static str, &'static str)],
+    pub body: &'static str,
+    pub response_body: &'static str,
+    /// Fully qualified message names, e.g. `.users.GetUserRequest`.
+    pub input: &'static str,
+    pub output: &'static str,
+}
+
+pub(crate) fn schema() -> &'static Schema {
+    static SCHEMA: OnceLock<Schema> = OnceLock::new();
+    SCHEMA.get_or_init(|| Schema::new(FILE_DESCRIPTOR_SET).expect("valid descriptor set"))
+}
+
+/// The parts of an HTTP request a [`Binding`] maps onto a message.
+#[derive(Debug)]
+pub struct RestRequest {
+    path: HashMap<String, String>,
+    query: Vec<(String, String)>,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequest<S> for RestRequest {
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let (mut parts, body) = request.into_parts();
+        let path = Option::<Path<HashMap<String, String>>>::from_request_parts(&mut parts, state)
+            .await
+            .ok()
+            .flatten()
+            .map(|Path(path)| path)
+            .unwrap_or_default();
+        let Query(query) = Query::from_request_parts(&mut parts, state)
+            .await
+            .map_err(|e| status_response(Code::InvalidArgument, &e.body_text()))?;
+        let headers = parts.headers.clone();
+        let body = Bytes::from_request(Request::from_parts(parts, body), state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        Ok(Self {
+            path,
+            query,
+            headers,
+            body,
+        })
+    }
+}
+
+/// Serves `request` with `call`, the service method of `binding`. Headers
+/// are passed on as metadata and response metadata comes back as headers.
+/// The body is read by its `Content-Type` and the response written as the
+/// `Accept` header prefers, errors are always JSON.
+pub async fn unary<Req, Res, F, Fut>(binding: &Binding, request: RestRequest, call: F) -> Response
+where
+    Req: Message + Default,
+    Res: Message,
+    F: FnOnce(tonic::Request<Req>) -> Fut,
+    Fut: Future<Output = Result<tonic::Response<Res>, Status>>,
+{
+    let Some(format) = Format::from_accept(&request.headers) else {
+        return (
+            StatusCode::NOT_ACCEPTABLE,
+            "Responses are application/json, application/x-protobuf or application/msgpack",
+        )
+            .into_response();
+    };
+    let body_format = match request.headers.get(CONTENT_TYPE) {
+        Some(content_type) if !request.body.is_empty() => {
+            let body_format = content_type.to_str().ok().and_then(Format::from_media_type);
+            let Some(body_format) = body_format else {
+                return (
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    "Expected application/json, application/x-protobuf or application/msgpack",
+                )
+                    .into_response();
+            };
+            body_format
+        }
+        _ => Format::Json,
+    };
+    // A version conflict of a conditional request fails its precondition.
+    let conditional = request.headers.contains_key(IF_MATCH);
+    // An idempotency key reused for another request is unprocessable.
+    let idempotent = request.headers.contains_key(IDEMPOTENCY_KEY);
+
+    let message = body_value(binding, body_format, &request.body)
+        .and_then(|body| request_message(binding, request.path, request.query, body))
+        .and_then(|message| {
+            json::encode(schema(), binding.input, &message).map_err(|e| e.to_string())
+        })
+        .and_then(|message| Req::decode(message.as_slice()).map_err(|e| e.to_string()));
+    let message = match message {
+        Ok(message) => message,
+        Err(e) => return status_response(Code::InvalidArgument, &e),
+    };
+
+    let mut metadata = HeaderMap::new();
+    for (name, value) in &request.headers {
+        if forwarded(name) {
+            metadata.append(name, value.clone());
+        }
+    }
+    let request = tonic::Request::from_parts(
+        MetadataMap::from_headers(metadata),
+        Extensions::default(),
+        message,
+    );
+
+    let (metadata, message) = match call(request).await {
+        Ok(response) => {
+            let (metadata, message, _) = response.into_parts();
+            (metadata, message)
+        }
+        Err(status) => {
+            let mut response = status_response(status.code(), status.message());
+            if conditional && status.code() == Code::Aborted {
+                *response.status_mut() = StatusCode::PRECONDITION_FAILED;
+            }
+            if idempotent && status.code() == Code::FailedPrecondition {
+                *response.status_mut() = StatusCode::UNPROCESSABLE_ENTITY;
+            }
+            // A full command bus, retryable after `retry-after` seconds.
+            if status.code() == Code::ResourceExhausted
+                && status.metadata().contains_key("retry-after")
+            {
+                *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            }
+            with_metadata(&mut response, status.metadata().clone());
+            return response;
+        }
+    };
+
+    let mut response = match response_body(binding, format, message) {
+        Ok(body) => (
+            [
+                (
+                    CONTENT_TYPE,
+                    HeaderValue::from_static(format.content_type()),
+                ),
+                (VARY, HeaderValue::from_static("accept")),
+            ],
+            body,
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Failed to transcode {} response: {}", binding.rpc, e);
+            return status_response(Code::Internal, "Internal server error");
+        }
+    };
+    with_metadata(&mut response, metadata);
+    response
+}
+
+/// The body as proto3 JSON, an empty body is an empty object.
+fn body_value(binding: &Binding, format: Format, body: &[u8]) -> Result<Value, String> {
+    if body.is_empty() || binding.body.is_empty() {
+        return Ok(Value::Object(Map::new()));
+    }
+    match format {
+        Format::Json => serde_json::from_slice(body).map_err(|e| format!("invalid JSON body: {e}")),
+        Format::MessagePack => {
+            rmp_serde::from_slice(body).map_err(|e| format!("invalid MessagePack body: {e}"))
+        }
+        Format::Protobuf => {
+            let name = match binding.body {
+                "*" => binding.input,
+                field => message_field(binding.input, field)
+                    .ok_or_else(|| format!("{field} is not a message field"))?,
+            };
+            json::decode(schema(), name, body, Mapping::Proto3)
+                .map_err(|e| format!("invalid protobuf body: {e}"))
+        }
+    }
+}
+
+/// The response in `format`, or its `response_body` field when set.
+fn response_body<Res: Message>(
+    binding: &Binding,
+    format: Format,
+    message: Res,
+) -> Result<Vec<u8>, String> {
+    if format == Format::Protobuf && binding.response_body.is_empty() {
+        return Ok(message.encode_to_vec());
+    }
+    let mut value = json::decode(
+        schema(),
+        binding.output,
+        &message.encode_to_vec(),
+        Mapping::Proto3,
+    )
+    .map_err(|e| e.to_string())?;
+    if !binding.response_body.is_empty() {
+        value = value
+            .get_mut(binding.response_body)
+            .map(Value::take)
+            .unwrap_or_default();
+    }
+    match format {
+        Format::Json => serde_json::to_vec(&value).map_err(|e| e.to_string()),
+        Format::MessagePack => rmp_serde::to_vec_named(&value).map_err(|e| e.to_string()),
+        Format::Protobuf => {
+            let name = message_field(binding.output, binding.response_body)
+                .ok_or_else(|| format!("{} is not a message field", binding.response_body))?;
+            json::encode(schema(), name, &value).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// The message type of `field` in the message `name`.
+fn message_field(name: &str, field: &str) -> Option<&'static str> {
+    schema()
+        .message(name)?
+        .field
+        .iter()
+        .find(|f| f.name() == field || json_name(f) == field)
+        .filter(|f| f.type_name().starts_with('.') && schema().message(f.type_name()).is_some())
+        .map(|f| f.type_name())
+}
+
+/// Converts `message`, of the protobuf type `name`, into the command with
+/// the same fields.
+pub fn to_command<M: Message, C: DeserializeOwned>(
+    message: &M,
+    name: &str,
+) -> Result<C, InvalidMessage> {
+    let value = json::decode(schema(), name, &message.encode_to_vec(), Mapping::Rust)?;
+    serde_json::from_value(value).map_err(|e| InvalidMessage(e.to_string()))
+}
+
+/// `message`, of the protobuf type `name`, in proto3 JSON. `build.rs`
+/// implements `Serialize` for the generated messages with this.
+pub fn serialize<M: Message, S: Serializer>(
+    message: &M,
+    name: &str,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    json::decode(schema(), name, &message.encode_to_vec(), Mapping::Proto3)
+        .map_err(S::Error::custom)?
+        .serialize(serializer)
+}
+
+/// The protobuf message `name` from proto3 JSON, which field names may also
+/// be given as in the proto file. `build.rs` implements `Deserialize` for
+/// the generated messages with this.
+pub fn deserialize<'de, M: Message + Default, D: Deserializer<'de>>(
+    name: &str,
+    deserializer: D,
+) -> Result<M, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    let buf = json::encode(schema(), name, &value).map_err(D::Error::custom)?;
+    M::decode(buf.as_slice()).map_err(D::Error::custom)
+}
+
+/// Adds response `metadata` as headers.
+pub(crate) fn with_metadata(response: &mut Response, metadata: MetadataMap) {
+    for (name, value) in &metadata.into_headers() {
+        if forwarded(name) && !name.as_str().starts_with("grpc-") {
+            response.headers_mut().append(name, value.clone());
+        }
+    }
+}
+
+/// Builds the request message from the body as mapped by `body`, then the
+/// query string for fields not bound otherwise, then path variables which
+/// take precedence.
+fn request_message(
+    binding: &Binding,
+    path: HashMap<String, String>,
+    query: Vec<(String, String)>,
+    body: Value,
+) -> Result<Value, String> {
+    let mut message = Value::Object(Map::new());
+    match binding.body {
+        "" => {}
+        "*" => message = body,
+        field => set(&mut message, field, body, false)?,
+    }
+    if binding.body != "*" {
+        for (key, value) in query {
+            set(&mut message, &key, Value::String(value), true)?;
+        }
+    }
+    for (param, field) in binding.variables {
+        if let Some(value) = path.get(*param) {
+            set(&mut message, field, Value::String(value.clone()), false)?;
+        }
+    }
+    Ok(message)
+}
+
+/// Sets the dotted `field` path. With `append` a repeated key appends to
+/// it, as for repeated query parameters, otherwise it is replaced.
+fn set(message: &mut Value, field: &str, value: Value, append: bool) -> Result<(), String> {
+    let mut target = message;
+    let mut segments = field.split('.').peekable();
+    while let Some(segment) = segments.next() {
+        let Value::Object(object) = target else {
+            return Err(format!("{field} is not a message"));
+        };
+        if segments.peek().is_some() {
+            target = object
+                .entry(segment)
+                .or_insert_with(|| Value::Object(Map::new()));
+            continue;
+        }
+        match object.get_mut(segment) {
+            Some(Value::Array(items)) if append => items.push(value),
+            Some(existing @ Value::String(_)) if append => {
+                *existing = Value::Array(vec![existing.take(), value]);
+            }
+            _ => {
+                object.insert(segment.to_string(), value);
+            }
+        }
+        break;
+    }
+    Ok(())
+}
+
+/// Request headers passed on as gRPC metadata, and response metadata
+/// passed back as headers.
+pub(crate) fn forwarded(name: &HeaderName) -> bool {
+    !matches!(
+        *name,
+        header::CONTENT_TYPE
+            | header::CONTENT_LENGTH
+            | header::ACCEPT
+            | header::ACCEPT_ENCODING
+            | header::CONTENT_ENCODING
+            | header::CONNECTION
+            | header::HOST
+            | header::TE
+            | header::TRANSFER_ENCODING
+    )
+}
+
+/// A `google.rpc.Status` JSON body with the HTTP status of `code`.
+fn status_response(code: Code, message: &str) -> Response {
+    (
+        http_status(code),
+        Json(json!({ "code": code as i32, "message": message })),
+    )
+        .into_response()
+}
+
+/// The HTTP status of a gRPC `code`, as mapped by `google.api.http` and
+/// Connect alike.
+pub(crate) fn http_status(code: Code) -> StatusCode {
+    match code {
+        Code::Ok => StatusCode::OK,
+        Code::Cancelled => StatusCode::from_u16(499).expect("valid status"),
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => {
+            StatusCode::BAD_REQUEST
+        }
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::Unknown | Code::Internal | Code::DataLoss => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
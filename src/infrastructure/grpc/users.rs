@@ -1,43 +1,354 @@
-use sqlx::{Pool, Postgres};
-use tokio::sync::mpsc;
-use tonic::{Request, Response, Status};
+use std::{pin::Pin, time::Duration};
+
+use axum::http::{header::ETAG, HeaderMap};
+use futures_util::{stream, Stream, StreamExt, TryStreamExt};
+use prost::Message;
+use tonic::{
+    metadata::{MetadataMap, MetadataValue},
+    Code, Extensions, Request, Response, Status, Streaming,
+};
 use tracing::{error, info};
 use uuid::Uuid;
 
 use crate::{
-    commands::{CommandMessage, CreateUser},
+    commands::{CommandBus, CommandError, CreateUser, DeleteUser, UpdateUser},
+    events::{user_upcasters, EventError, StoredEvent, UserCreated, UserDeleted, UserUpdated},
+    infrastructure::http::headers::{etag, if_match},
+    models::User,
     proto::{
-        user_service_server::{UserService as GrpcUserService, UserServiceServer},
-        CreateUserRequest, CreateUserResponse, GetUserRequest, GetUserResponse,
+        user_service_server::UserService as GrpcUserService, CreateUserRequest, CreateUserResponse,
+        DeleteUserRequest, DeleteUserResponse, GetUserRequest, GetUserResponse, ImportStatus,
+        ImportUserResult, ImportUsersResponse, ListUsersRequest, ListUsersResponse,
+        UpdateUserRequest, UpdateUserResponse, UserEvent, WatchUsersRequest,
     },
-    services::UserService,
-    PostgreSQL,
+    repositories::{page_size, RepositoryError, UserStore},
+    services::{
+        Idempotency, IdempotencyService, ImportOutcome, ImportRecord, ImportSummary, UserService,
+    },
+    Event, EventBus, EventFilter, FeedError, PostgreSQL,
 };
 
+/// Metadata key carrying the client supplied idempotency key.
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+/// Set on responses replayed for a repeated idempotency key.
+pub const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+const CREATE_USER_SCOPE: &str = "/users.UserService/CreateUser";
+/// Heartbeat interval of `WatchUsers` when the request sets none.
+const HEARTBEAT: Duration = Duration::from_secs(15);
+
+impl From<User> for GetUserResponse {
+    fn from(user: User) -> Self {
+        GetUserResponse {
+            id: user.id.to_string(),
+            username: user.username,
+            email: user.email,
+            version: user.version,
+        }
+    }
+}
+
+impl From<User> for UpdateUserResponse {
+    fn from(user: User) -> Self {
+        UpdateUserResponse {
+            id: user.id.to_string(),
+            username: user.username,
+            email: user.email,
+            version: user.version,
+        }
+    }
+}
+
+impl From<ImportSummary> for ImportUsersResponse {
+    fn from(summary: ImportSummary) -> Self {
+        ImportUsersResponse {
+            created: summary.created,
+            duplicate: summary.duplicate,
+            invalid: summary.invalid,
+        }
+    }
+}
+
+/// The `etag` of a user version, a header of REST responses.
+fn etag_metadata(version: i64) -> MetadataMap {
+    MetadataMap::from_headers(HeaderMap::from_iter([(ETAG, etag(version))]))
+}
+
+fn with_etag<T>(message: T, version: i64) -> Response<T> {
+    Response::from_parts(etag_metadata(version), message, Extensions::default())
+}
+
+/// `expected_version` when set, else the version of an `if-match`, as
+/// REST clients send `If-Match`.
+// Service methods answer with a `Status` error.
+#[allow(clippy::result_large_err)]
+fn expected_version(
+    metadata: &MetadataMap,
+    expected_version: Option<i64>,
+) -> Result<Option<i64>, Status> {
+    if expected_version.is_some() {
+        return Ok(expected_version);
+    }
+    if_match(&metadata.clone().into_headers())
+        .map_err(|_| Status::invalid_argument("Invalid If-Match header"))
+}
+
+/// A version conflict, with the `etag` of the stored version.
+fn conflict(expected: i64, actual: i64) -> Status {
+    Status::with_metadata(
+        Code::Aborted,
+        format!("User is at version {actual}, expected {expected}"),
+        etag_metadata(actual),
+    )
+}
+
+// Stream items of tonic responses are results with a `Status` error.
+#[allow(clippy::result_large_err)]
+fn import_result(
+    (index, outcome): (usize, Result<ImportOutcome, Status>),
+) -> Result<ImportUserResult, Status> {
+    let result = ImportUserResult {
+        index: index as u64,
+        ..Default::default()
+    };
+    Ok(match outcome? {
+        ImportOutcome::Created(id) => ImportUserResult {
+            status: ImportStatus::Created.into(),
+            id: id.to_string(),
+            ..result
+        },
+        ImportOutcome::Duplicate => ImportUserResult {
+            status: ImportStatus::Duplicate.into(),
+            ..result
+        },
+        ImportOutcome::Invalid(error) => ImportUserResult {
+            status: ImportStatus::Invalid.into(),
+            error,
+            ..result
+        },
+    })
+}
+
+/// The records of an import stream as the commands creating them.
+fn import_records(
+    request: Request<Streaming<CreateUserRequest>>,
+) -> impl Stream<Item = Result<ImportRecord, Status>> + Send + 'static {
+    request.into_inner().map_ok(|record| {
+        Ok(CreateUser {
+            username: record.username,
+            email: record.email,
+        })
+    })
+}
+
+impl TryFrom<StoredEvent> for UserEvent {
+    type Error = EventError;
+
+    fn try_from(event: StoredEvent) -> Result<Self, Self::Error> {
+        let upcasters = user_upcasters();
+        let header = UserEvent {
+            id: event.id.to_string(),
+            event_type: event.event_type.clone(),
+            user_id: event.aggregate_id.to_string(),
+            ..Default::default()
+        };
+        Ok(match event.event_type.as_str() {
+            UserCreated::EVENT_TYPE => {
+                let created: UserCreated = event.decode(&upcasters)?;
+                UserEvent {
+                    username: created.username,
+                    email: created.email,
+                    version: 1,
+                    ..header
+                }
+            }
+            UserUpdated::EVENT_TYPE => {
+                let updated: UserUpdated = event.decode(&upcasters)?;
+                UserEvent {
+                    username: updated.username,
+                    email: updated.email,
+                    version: updated.version,
+                    ..header
+                }
+            }
+            UserDeleted::EVENT_TYPE => {
+                let deleted: UserDeleted = event.decode(&upcasters)?;
+                UserEvent {
+                    version: deleted.version,
+                    ..header
+                }
+            }
+            other => return Err(EventError::UnknownType(other.to_string())),
+        })
+    }
+}
+
+impl TryFrom<WatchUsersRequest> for EventFilter {
+    type Error = Status;
+
+    fn try_from(request: WatchUsersRequest) -> Result<Self, Self::Error> {
+        let aggregate_ids = request
+            .user_ids
+            .iter()
+            .map(|id| Uuid::parse_str(id))
+            .collect::<Result<_, _>>()
+            .map_err(|_| Status::invalid_argument("Invalid User Id"))?;
+        Ok(EventFilter {
+            event_types: request.event_types,
+            aggregate_ids,
+        })
+    }
+}
+
+impl From<FeedError> for Status {
+    fn from(e: FeedError) -> Self {
+        match e {
+            FeedError::Lagged(_) => {
+                Status::aborted("Fell behind the event feed, resume from the last event seen")
+            }
+            FeedError::Repository(e) => {
+                error!("Failed to replay events: {}", e);
+                Status::internal("Failed to replay events")
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
-pub struct GrpcUserServiceImpl {
-    repo: UserService,
+pub struct GrpcUserServiceImpl<R = PostgreSQL> {
+    repo: UserService<R>,
+    idempotency: Option<IdempotencyService>,
 }
 
 impl GrpcUserServiceImpl {
-    pub fn new(
-        pool: Pool<Postgres>,
-        sender: mpsc::Sender<CommandMessage>,
-    ) -> UserServiceServer<GrpcUserServiceImpl> {
-        let user_service = UserService::new(PostgreSQL::new(pool.clone()), sender.clone());
-        UserServiceServer::new(GrpcUserServiceImpl { repo: user_service })
+    pub fn new(db: PostgreSQL, bus: CommandBus) -> Self {
+        let idempotency = IdempotencyService::new(db.clone());
+        let user_service = UserService::new(db, bus);
+        GrpcUserServiceImpl {
+            repo: user_service,
+            idempotency: Some(idempotency),
+        }
+    }
+}
+
+impl<R: UserStore> GrpcUserServiceImpl<R> {
+    /// Serves users from `repo`, without idempotency keys as those are
+    /// stored in Postgres.
+    pub fn with_repository(repo: R, bus: CommandBus) -> Self {
+        GrpcUserServiceImpl {
+            repo: UserService::new(repo, bus),
+            idempotency: None,
+        }
+    }
+
+    /// Serves `WatchUsers` from the events committed through `events`,
+    /// without it the RPC is unavailable.
+    pub fn with_event_bus(mut self, events: EventBus) -> Self {
+        self.repo = self.repo.with_event_bus(events);
+        self
+    }
+
+    async fn create_user_once(
+        &self,
+        idempotency: &IdempotencyService,
+        key: &str,
+        request: CreateUserRequest,
+    ) -> Result<Response<CreateUserResponse>, Status> {
+        let outcome = idempotency
+            .begin(CREATE_USER_SCOPE, key, &request.encode_to_vec())
+            .await
+            .map_err(|e| {
+                error!("Failed to claim idempotency key: {}", e);
+                Status::internal("Failed to process request")
+            })?;
+
+        match outcome {
+            Idempotency::Fresh => {}
+            Idempotency::Replay { response } => {
+                let mut response = CreateUserResponse::decode(response.as_slice())
+                    .map(Response::new)
+                    .map_err(|_| Status::internal("Failed to replay response"))?;
+                response
+                    .metadata_mut()
+                    .insert(IDEMPOTENT_REPLAYED, MetadataValue::from_static("true"));
+                return Ok(response);
+            }
+            Idempotency::Mismatch => {
+                return Err(Status::failed_precondition(
+                    "Idempotency key was already used with a different payload",
+                ));
+            }
+            Idempotency::InProgress => {
+                return Err(Status::aborted(
+                    "A request with this idempotency key is still in progress",
+                ));
+            }
+        }
+
+        // Waits for the handler, so only a created user is replayed.
+        let created = match CreateUser::try_from(request) {
+            Ok(command) => self
+                .repo
+                .create_user_and_wait(command)
+                .await
+                .map_err(Status::from),
+            Err(e) => Err(Status::invalid_argument(e.to_string())),
+        };
+        if let Err(status) = created {
+            if let Err(e) = idempotency.release(CREATE_USER_SCOPE, key).await {
+                error!("Failed to release idempotency key: {}", e);
+            }
+            return Err(status);
+        }
+        let response = CreateUserResponse {};
+        if let Err(e) = idempotency
+            .complete(CREATE_USER_SCOPE, key, &response.encode_to_vec())
+            .await
+        {
+            error!("Failed to store idempotent response: {}", e);
+        }
+        Ok(Response::new(response))
     }
 }
 
+// Stream items of tonic responses are results with a `Status` error.
+#[allow(clippy::result_large_err)]
+fn user_event(event: Result<StoredEvent, FeedError>) -> Result<UserEvent, Status> {
+    UserEvent::try_from(event?).map_err(|e| {
+        error!("Failed to decode event: {}", e);
+        Status::internal("Failed to decode event")
+    })
+}
+
+fn queue_error(e: CommandError) -> Status {
+    error!("Failed to queue CreateUser: {}", e);
+    e.into()
+}
+
 #[tonic::async_trait]
-impl GrpcUserService for GrpcUserServiceImpl {
+impl<R: UserStore> GrpcUserService for GrpcUserServiceImpl<R> {
+    type WatchUsersStream = Pin<Box<dyn Stream<Item = Result<UserEvent, Status>> + Send>>;
+    type ImportUsersStreamStream =
+        Pin<Box<dyn Stream<Item = Result<ImportUserResult, Status>> + Send>>;
+
     async fn create_user(
         &self,
         request: Request<CreateUserRequest>,
     ) -> Result<Response<CreateUserResponse>, Status> {
-        let command = CreateUser::from(request.into_inner());
+        let key = request
+            .metadata()
+            .get(IDEMPOTENCY_KEY)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        let request = request.into_inner();
 
-        self.repo.create_user(command).await;
+        if let (Some(idempotency), Some(key)) = (&self.idempotency, key) {
+            return self.create_user_once(idempotency, &key, request).await;
+        }
+
+        let command =
+            CreateUser::try_from(request).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        self.repo.create_user(command).await.map_err(queue_error)?;
         Ok(Response::new(CreateUserResponse {}))
     }
 
@@ -45,18 +356,14 @@ impl GrpcUserService for GrpcUserServiceImpl {
         &self,
         request: Request<GetUserRequest>,
     ) -> Result<Response<GetUserResponse>, Status> {
-        let id = Uuid::parse_str(&request.into_inner().id).unwrap();
+        let id = Uuid::parse_str(&request.into_inner().id)
+            .map_err(|_| Status::invalid_argument("Invalid User Id"))?;
 
         match self.repo.handle_get_user_by_id(id).await {
             Ok(Some(user)) => {
                 info!("User Found:\n{:#?}", user);
-                let response = Response::new(GetUserResponse {
-                    id: user.id.to_string(),
-                    email: user.email,
-                    username: user.username,
-                });
-
-                Ok(response)
+                let version = user.version;
+                Ok(with_etag(user.into(), version))
             }
             Ok(None) => Err(Status::not_found("User Not Found")),
             Err(e) => {
@@ -65,4 +372,148 @@ impl GrpcUserService for GrpcUserServiceImpl {
             }
         }
     }
+
+    async fn update_user(
+        &self,
+        request: Request<UpdateUserRequest>,
+    ) -> Result<Response<UpdateUserResponse>, Status> {
+        let (metadata, _, mut request) = request.into_parts();
+        request.expected_version = expected_version(&metadata, request.expected_version)?;
+        let command = UpdateUser::try_from(request)
+            .map_err(|_| Status::invalid_argument("Invalid User Id"))?;
+
+        match self.repo.update_user(command).await {
+            Ok(user) => {
+                info!("User Updated:\n{:#?}", user);
+                let version = user.version;
+                Ok(with_etag(user.into(), version))
+            }
+            Err(CommandError::Repository(RepositoryError::Conflict { expected, actual })) => {
+                Err(conflict(expected, actual))
+            }
+            Err(CommandError::Repository(RepositoryError::NotFound)) => {
+                Err(Status::not_found("User Not Found"))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete_user(
+        &self,
+        request: Request<DeleteUserRequest>,
+    ) -> Result<Response<DeleteUserResponse>, Status> {
+        let (metadata, _, mut request) = request.into_parts();
+        request.expected_version = expected_version(&metadata, request.expected_version)?;
+        let command = DeleteUser::try_from(request)
+            .map_err(|_| Status::invalid_argument("Invalid User Id"))?;
+
+        match self.repo.delete_user(command).await {
+            Ok(()) => Ok(Response::new(DeleteUserResponse {})),
+            Err(CommandError::Repository(RepositoryError::Conflict { expected, actual })) => {
+                Err(conflict(expected, actual))
+            }
+            Err(CommandError::Repository(RepositoryError::NotFound)) => {
+                Err(Status::not_found("User Not Found"))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn list_users(
+        &self,
+        request: Request<ListUsersRequest>,
+    ) -> Result<Response<ListUsersResponse>, Status> {
+        let request = request.into_inner();
+        let after = request
+            .after
+            .as_deref()
+            .map(Uuid::parse_str)
+            .transpose()
+            .map_err(|_| Status::invalid_argument("Invalid cursor"))?;
+        let limit = if request.limit > 0 {
+            page_size(request.limit)
+        } else {
+            50
+        };
+
+        let users = self
+            .repo
+            .handle_list_users(after, limit)
+            .await
+            .map_err(|e| {
+                error!("{}", e);
+                Status::internal("Failed to list users")
+            })?;
+        let next_after = users
+            .last()
+            .filter(|_| users.len() as i64 == limit)
+            .map(|user| user.id.to_string());
+        Ok(Response::new(ListUsersResponse {
+            users: users.into_iter().map(Into::into).collect(),
+            next_after,
+        }))
+    }
+
+    async fn watch_users(
+        &self,
+        request: Request<WatchUsersRequest>,
+    ) -> Result<Response<Self::WatchUsersStream>, Status> {
+        let request = request.into_inner();
+        let after = request
+            .after
+            .as_deref()
+            .map(Uuid::parse_str)
+            .transpose()
+            .map_err(|_| Status::invalid_argument("Invalid event id"))?;
+        let heartbeat = request
+            .heartbeat_seconds
+            .map_or(HEARTBEAT, |seconds| Duration::from_secs(seconds.into()));
+        let events = self
+            .repo
+            .watch_users(EventFilter::try_from(request)?, after)
+            .ok_or_else(|| Status::unavailable("User events are not published"))?
+            .map(user_event);
+
+        // A heartbeat whenever no event came for a whole interval.
+        let events = stream::unfold(Box::pin(events), move |mut events| async move {
+            let event = if heartbeat.is_zero() {
+                events.next().await?
+            } else {
+                match tokio::time::timeout(heartbeat, events.next()).await {
+                    Ok(event) => event?,
+                    Err(_) => Ok(UserEvent {
+                        heartbeat: true,
+                        ..Default::default()
+                    }),
+                }
+            };
+            Some((event, events))
+        });
+        Ok(Response::new(Box::pin(events)))
+    }
+
+    async fn import_users(
+        &self,
+        request: Request<Streaming<CreateUserRequest>>,
+    ) -> Result<Response<ImportUsersResponse>, Status> {
+        let mut outcomes = Box::pin(self.repo.import_users(import_records(request), false));
+        let mut summary = ImportSummary::default();
+        while let Some(outcome) = outcomes.next().await {
+            summary.add(&outcome?);
+        }
+        info!("Users Imported: {:?}", summary);
+        Ok(Response::new(summary.into()))
+    }
+
+    async fn import_users_stream(
+        &self,
+        request: Request<Streaming<CreateUserRequest>>,
+    ) -> Result<Response<Self::ImportUsersStreamStream>, Status> {
+        let results = self
+            .repo
+            .import_users(import_records(request), false)
+            .enumerate()
+            .map(import_result);
+        Ok(Response::new(Box::pin(results)))
+    }
 }
@@ -0,0 +1,163 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{Query, Request, State},
+    http::{
+        header::{AUTHORIZATION, WWW_AUTHENTICATE},
+        StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use tonic::{service::Interceptor, Status};
+
+/// The bearer tokens a set of routes accepts. Only their SHA-256 digests
+/// are kept, so looking one up takes the same time whatever it matches.
+#[derive(Clone, Debug, Default)]
+pub struct BearerTokens(Arc<HashSet<[u8; 32]>>);
+
+impl BearerTokens {
+    pub fn new<I, T>(tokens: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        let digests = tokens
+            .into_iter()
+            .filter(|token| !token.as_ref().is_empty())
+            .map(|token| digest(token.as_ref()))
+            .collect();
+        Self(Arc::new(digests))
+    }
+
+    /// The comma-separated tokens of `var`. Without any [`require_bearer`]
+    /// refuses every request and [`require_api_token`] and [`ApiTokens`]
+    /// let every one in.
+    pub fn from_env(var: &str) -> Self {
+        let tokens = std::env::var(var).unwrap_or_default();
+        Self::new(tokens.split(',').map(str::trim))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn allows(&self, token: &str) -> bool {
+        self.0.contains(&digest(token))
+    }
+
+    /// Whether an `Authorization` value is `Bearer` and an accepted token.
+    pub fn allows_authorization(&self, authorization: &str) -> bool {
+        authorization
+            .strip_prefix("Bearer ")
+            .is_some_and(|token| self.allows(token.trim()))
+    }
+
+    /// Whether the `Authorization: Bearer` header holds an accepted token.
+    pub fn authorizes(&self, request: &Request) -> bool {
+        request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|authorization| self.allows_authorization(authorization))
+    }
+
+    /// Whether the bearer token or the `access_token` query parameter is
+    /// accepted, for clients such as browsers opening a WebSocket that
+    /// cannot set headers.
+    pub fn authorizes_with_query(&self, request: &Request) -> bool {
+        let query = Query::<HashMap<String, String>>::try_from_uri(request.uri());
+        self.authorizes(request)
+            || query.is_ok_and(|Query(params)| {
+                params
+                    .get("access_token")
+                    .is_some_and(|token| self.allows(token))
+            })
+    }
+}
+
+/// Answers `unauthenticated` to calls without an accepted bearer token in
+/// their `authorization` metadata.
+impl Interceptor for BearerTokens {
+    fn call(&mut self, request: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
+        let authorized = request
+            .metadata()
+            .get(AUTHORIZATION.as_str())
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|authorization| self.allows_authorization(authorization));
+        if authorized {
+            Ok(request)
+        } else {
+            Err(Status::unauthenticated("missing or invalid bearer token"))
+        }
+    }
+}
+
+/// The authentication of the user RPCs: lets every call in when there are
+/// no tokens, otherwise checks them like [`BearerTokens`].
+#[derive(Clone, Debug, Default)]
+pub struct ApiTokens(pub BearerTokens);
+
+impl Interceptor for ApiTokens {
+    fn call(&mut self, request: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
+        if self.0.is_empty() {
+            Ok(request)
+        } else {
+            self.0.call(request)
+        }
+    }
+}
+
+fn digest(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+/// Middleware answering `401 Unauthorized` to requests without an
+/// accepted bearer token, for `axum::middleware::from_fn_with_state`.
+pub async fn require_bearer(
+    State(tokens): State<BearerTokens>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if tokens.authorizes(&request) {
+        next.run(request).await
+    } else {
+        unauthorized()
+    }
+}
+
+/// The authentication of the user routes: open when no tokens are
+/// configured, otherwise [`require_bearer`].
+pub async fn require_api_token(
+    State(tokens): State<BearerTokens>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if tokens.is_empty() || tokens.authorizes(&request) {
+        next.run(request).await
+    } else {
+        unauthorized()
+    }
+}
+
+/// [`require_api_token`] for `/ws`, also taking the token from an
+/// `access_token` query parameter.
+pub async fn require_websocket_token(
+    State(tokens): State<BearerTokens>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if tokens.is_empty() || tokens.authorizes_with_query(&request) {
+        next.run(request).await
+    } else {
+        unauthorized()
+    }
+}
+
+fn unauthorized() -> Response {
+    (StatusCode::UNAUTHORIZED, [(WWW_AUTHENTICATE, "Bearer")]).into_response()
+}
@@ -0,0 +1,83 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{repositories::RepositoryError, services::DeadLetterService};
+
+use super::Page;
+
+pub async fn list_dead_letters(
+    State(service): State<DeadLetterService>,
+    Query(page): Query<Page>,
+) -> impl IntoResponse {
+    match service.list(page.limit, page.offset).await {
+        Ok(dead_letters) => Json(dead_letters).into_response(),
+        Err(e) => {
+            error!("Failed to list dead letters: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to list dead letters",
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn get_dead_letter(
+    State(service): State<DeadLetterService>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match service.get(id).await {
+        Ok(Some(dead_letter)) => Json(dead_letter).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Dead letter not found").into_response(),
+        Err(e) => {
+            error!("Failed to fetch dead letter {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to get dead letter",
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn replay_dead_letter(
+    State(service): State<DeadLetterService>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match service.replay(id).await {
+        Ok(()) => {
+            info!("Replayed dead letter {}", id);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn discard_dead_letter(
+    State(service): State<DeadLetterService>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match service.discard(id).await {
+        Ok(()) => {
+            info!("Discarded dead letter {}", id);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(RepositoryError::NotFound) => {
+            (StatusCode::NOT_FOUND, "Dead letter not found").into_response()
+        }
+        Err(e) => {
+            error!("Failed to discard dead letter {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to discard dead letter",
+            )
+                .into_response()
+        }
+    }
+}
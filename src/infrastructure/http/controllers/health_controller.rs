@@ -0,0 +1,24 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde_json::json;
+
+use crate::commands::CommandBus;
+
+/// Not ready while any command shard is full, so load balancers back off
+/// before requests start being shed.
+pub async fn ready(State(bus): State<CommandBus>) -> impl IntoResponse {
+    let depth = bus.queue_depth();
+    let status = if depth.full_shards == 0 {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(json!({
+            "ready": status == StatusCode::OK,
+            "queue": depth,
+            "shards": bus.shard_metrics(),
+        })),
+    )
+}
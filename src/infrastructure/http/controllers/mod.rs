@@ -1,2 +1,16 @@
+mod dead_letter_controller;
+mod health_controller;
+mod pagination;
+mod schedule_controller;
+mod user_bulk_controller;
 mod user_controller;
+mod websocket_controller;
+mod workflow_controller;
+pub use dead_letter_controller::*;
+pub use health_controller::*;
+pub use pagination::*;
+pub use schedule_controller::*;
+pub use user_bulk_controller::*;
 pub use user_controller::*;
+pub use websocket_controller::*;
+pub use workflow_controller::*;
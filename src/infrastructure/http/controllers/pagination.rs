@@ -0,0 +1,14 @@
+use serde::Deserialize;
+
+/// `?limit=&offset=` of list endpoints.
+#[derive(Deserialize, Debug)]
+pub struct Page {
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    #[serde(default)]
+    pub offset: i64,
+}
+
+fn default_limit() -> i64 {
+    50
+}
@@ -0,0 +1,117 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    repositories::RepositoryError,
+    services::{ScheduleService, Trigger},
+};
+
+use super::Page;
+
+/// A command to run once at `run_at` or after `delay_secs`, or on every
+/// occurrence of `cron`.
+#[derive(Deserialize, Debug)]
+pub struct CreateSchedule {
+    pub command_type: String,
+    pub payload: Value,
+    pub run_at: Option<DateTime<Utc>>,
+    pub delay_secs: Option<i64>,
+    pub cron: Option<String>,
+}
+
+impl CreateSchedule {
+    fn trigger(&self) -> Result<Trigger, &'static str> {
+        match (self.run_at, self.delay_secs, &self.cron) {
+            (Some(run_at), None, None) => Ok(Trigger::At(run_at)),
+            (None, Some(delay), None) => {
+                Trigger::after_secs(delay).ok_or("delay_secs is out of range")
+            }
+            (None, None, Some(cron)) => Ok(Trigger::Cron(cron.clone())),
+            _ => Err("Exactly one of run_at, delay_secs or cron is required"),
+        }
+    }
+}
+
+pub async fn create_schedule(
+    State(service): State<ScheduleService>,
+    Json(payload): Json<CreateSchedule>,
+) -> impl IntoResponse {
+    let trigger = match payload.trigger() {
+        Ok(trigger) => trigger,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+
+    match service
+        .schedule_serialized(&payload.command_type, payload.payload, trigger)
+        .await
+    {
+        Ok(schedule) => {
+            info!("Scheduled {} {}", schedule.command_type, schedule.id);
+            (StatusCode::CREATED, Json(schedule)).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn list_schedules(
+    State(service): State<ScheduleService>,
+    Query(page): Query<Page>,
+) -> impl IntoResponse {
+    match service.list(page.limit, page.offset).await {
+        Ok(schedules) => Json(schedules).into_response(),
+        Err(e) => {
+            error!("Failed to list schedules: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to list schedules",
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn get_schedule(
+    State(service): State<ScheduleService>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match service.get(id).await {
+        Ok(Some(schedule)) => Json(schedule).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Schedule not found").into_response(),
+        Err(e) => {
+            error!("Failed to fetch schedule {}: {}", id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to get schedule").into_response()
+        }
+    }
+}
+
+pub async fn cancel_schedule(
+    State(service): State<ScheduleService>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match service.cancel(id).await {
+        Ok(()) => {
+            info!("Cancelled schedule {}", id);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(RepositoryError::NotFound) => {
+            (StatusCode::NOT_FOUND, "Schedule not found").into_response()
+        }
+        Err(e) => {
+            error!("Failed to cancel schedule {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to cancel schedule",
+            )
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use std::io::{self, BufRead, BufReader, Read};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    response::IntoResponse,
+    Json,
+};
+use derive_more::{Display, From};
+use futures_util::{stream, Stream, StreamExt, TryStreamExt};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio_util::io::{StreamReader, SyncIoBridge};
+use tracing::{error, info};
+
+use crate::{
+    commands::CreateUser,
+    models::User,
+    proto::GetUserResponse,
+    repositories::{RepositoryError, UserStore},
+    services::{ImportOutcome, ImportRecord, ImportSummary, UserService, IMPORT_BATCH},
+};
+
+/// Formats of `POST /users/import` and `GET /users/export`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransferFormat {
+    /// A header row naming the columns, then one user per row.
+    Csv,
+    /// One JSON object per line.
+    Ndjson,
+    /// `GetUserResponse` messages, each prefixed with its varint length.
+    /// Exports only.
+    Protobuf,
+}
+
+impl TransferFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            TransferFormat::Csv => "text/csv",
+            TransferFormat::Ndjson => "application/x-ndjson",
+            TransferFormat::Protobuf => "application/x-protobuf; delimited=true",
+        }
+    }
+
+    /// The format of a `Content-Type`, parameters such as `charset` ignored.
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        let essence = media_type.split(';').next()?.trim().to_ascii_lowercase();
+        match essence.as_str() {
+            "text/csv" => Some(TransferFormat::Csv),
+            "application/x-ndjson" | "application/jsonl" | "application/jsonlines" => {
+                Some(TransferFormat::Ndjson)
+            }
+            _ => None,
+        }
+    }
+
+    /// The format named by `?format=`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "csv" => Some(TransferFormat::Csv),
+            "ndjson" => Some(TransferFormat::Ndjson),
+            "protobuf" => Some(TransferFormat::Protobuf),
+            _ => None,
+        }
+    }
+
+    fn file_name(self) -> &'static str {
+        match self {
+            TransferFormat::Csv => "users.csv",
+            TransferFormat::Ndjson => "users.ndjson",
+            TransferFormat::Protobuf => "users.pb",
+        }
+    }
+
+    /// One chunk of the export body.
+    fn encode(self, users: Vec<User>) -> Bytes {
+        match self {
+            TransferFormat::Csv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(Vec::new());
+                for user in users {
+                    let version = user.version.to_string();
+                    writer
+                        .write_record([&user.id.to_string(), &user.username, &user.email, &version])
+                        .expect("writing to memory cannot fail");
+                }
+                writer
+                    .into_inner()
+                    .expect("writing to memory cannot fail")
+                    .into()
+            }
+            TransferFormat::Ndjson => {
+                let mut bytes = Vec::new();
+                for user in users {
+                    serde_json::to_writer(&mut bytes, &GetUserResponse::from(user))
+                        .expect("users serialize to JSON");
+                    bytes.push(b'\n');
+                }
+                bytes.into()
+            }
+            TransferFormat::Protobuf => {
+                let mut bytes = Vec::new();
+                for user in users {
+                    GetUserResponse::from(user)
+                        .encode_length_delimited(&mut bytes)
+                        .expect("a Vec grows to fit");
+                }
+                bytes.into()
+            }
+        }
+    }
+}
+
+const CSV_HEADER: &[u8] = b"id,username,email,version\n";
+
+/// `?dry_run=` of `POST /users/import`.
+#[derive(Deserialize, Debug)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// `?format=` of `GET /users/export`, CSV when absent.
+#[derive(Deserialize, Debug)]
+pub struct ExportQuery {
+    pub format: Option<String>,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct ImportReport {
+    #[serde(flatten)]
+    summary: ImportSummary,
+    dry_run: bool,
+    /// Why the invalid records were skipped.
+    errors: Vec<RecordError>,
+    /// What ended the import early, the records before it are imported.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct RecordError {
+    /// Counted from 1, the CSV header row excluded.
+    record: u64,
+    error: String,
+}
+
+#[derive(Debug, Display, From)]
+enum ImportError {
+    #[display("Failed to read the body: {_0}")]
+    Read(io::Error),
+    #[display("{_0}")]
+    Repository(RepositoryError),
+}
+
+/// Creates the users of a CSV or NDJSON body as it is received, each
+/// record validated like the `CreateUser` RPCs. Answers with a report of
+/// the records created, skipped as duplicates and invalid, which a
+/// `dry_run` gives without creating anyone.
+pub async fn import_users<R: UserStore>(
+    State(state): State<UserService<R>>,
+    Query(query): Query<ImportQuery>,
+    headers: HeaderMap,
+    body: Body,
+) -> impl IntoResponse {
+    let format = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(TransferFormat::from_media_type);
+    let Some(format) = format else {
+        return (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Imports are text/csv or application/x-ndjson",
+        )
+            .into_response();
+    };
+
+    let mut report = ImportReport {
+        dry_run: query.dry_run,
+        ..Default::default()
+    };
+    let mut outcomes = Box::pin(state.import_users(read_records(format, body), query.dry_run));
+    let mut status = StatusCode::OK;
+    let mut record = 0;
+    while let Some(outcome) = outcomes.next().await {
+        record += 1;
+        match outcome {
+            Ok(outcome) => {
+                report.summary.add(&outcome);
+                if let ImportOutcome::Invalid(error) = outcome {
+                    report.errors.push(RecordError { record, error });
+                }
+            }
+            Err(e) => {
+                status = match e {
+                    ImportError::Read(_) => StatusCode::BAD_REQUEST,
+                    ImportError::Repository(_) => {
+                        error!("Failed to import users: {}", e);
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    }
+                };
+                report.error = Some(e.to_string());
+            }
+        }
+    }
+    info!("Users Imported: {:?}", report.summary);
+    (status, Json(report)).into_response()
+}
+
+/// The records of `body`, parsed on a blocking thread as it arrives.
+fn read_records(
+    format: TransferFormat,
+    body: Body,
+) -> impl Stream<Item = Result<ImportRecord, ImportError>> + Send + 'static {
+    let body = StreamReader::new(body.into_data_stream().map_err(io::Error::other));
+    let reader = SyncIoBridge::new(body);
+    let (records, mut received) = mpsc::channel(IMPORT_BATCH);
+    tokio::task::spawn_blocking(move || match format {
+        TransferFormat::Csv => read_csv(reader, records),
+        _ => read_ndjson(reader, records),
+    });
+    stream::poll_fn(move |cx| received.poll_recv(cx)).map(|record| record.map_err(Into::into))
+}
+
+/// Rows by their header, so the columns may come in any order and others
+/// are ignored.
+fn read_csv(reader: impl Read, records: mpsc::Sender<io::Result<ImportRecord>>) {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let header = match reader.headers() {
+        Ok(header) => header,
+        Err(e) => {
+            let _ = records.blocking_send(Err(io::Error::other(e)));
+            return;
+        }
+    };
+    if !["username", "email"]
+        .iter()
+        .all(|column| header.iter().any(|name| name == *column))
+    {
+        let e = io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the CSV header must name the username and email columns",
+        );
+        let _ = records.blocking_send(Err(e));
+        return;
+    }
+
+    for record in reader.deserialize::<CreateUser>() {
+        let record = match record {
+            Ok(record) => Ok(Ok(record)),
+            Err(e) if e.is_io_error() => Err(io::Error::other(e)),
+            Err(e) => Ok(Err(e.to_string())),
+        };
+        let failed = record.is_err();
+        if records.blocking_send(record).is_err() || failed {
+            return;
+        }
+    }
+}
+
+/// Blank lines are skipped.
+fn read_ndjson(reader: impl Read, records: mpsc::Sender<io::Result<ImportRecord>>) {
+    for line in BufReader::new(reader).lines() {
+        let record = match line {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => Ok(serde_json::from_str(&line).map_err(|e| e.to_string())),
+            Err(e) => Err(e),
+        };
+        let failed = record.is_err();
+        if records.blocking_send(record).is_err() || failed {
+            return;
+        }
+    }
+}
+
+/// Streams every user a page at a time, as `?format=csv` (the default),
+/// `ndjson` or `protobuf` for length-delimited `GetUserResponse`s.
+pub async fn export_users<R: UserStore>(
+    State(state): State<UserService<R>>,
+    Query(query): Query<ExportQuery>,
+) -> impl IntoResponse {
+    let format = match query.format.as_deref() {
+        None => Some(TransferFormat::Csv),
+        Some(name) => TransferFormat::from_name(name),
+    };
+    let Some(format) = format else {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Exports are csv, ndjson or protobuf",
+        )
+            .into_response();
+    };
+
+    let header = (format == TransferFormat::Csv).then_some(Ok(Bytes::from_static(CSV_HEADER)));
+    let pages = state
+        .export_users()
+        .map_ok(move |users| format.encode(users))
+        .inspect_err(|e| error!("Failed to export users: {}", e));
+    let disposition = format!("attachment; filename=\"{}\"", format.file_name());
+    (
+        [
+            (CONTENT_TYPE, format.content_type().to_string()),
+            (CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(stream::iter(header).chain(pages)),
+    )
+        .into_response()
+}
@@ -1,36 +1,91 @@
+use std::convert::Infallible;
+
 use axum::{
-    extract::{Path, State},
-    response::IntoResponse,
-    Json,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
 };
-use tracing::{error, info};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use tracing::error;
 use uuid::Uuid;
 
-use crate::{commands, services::UserService};
+use crate::{proto::UserEvent, repositories::UserStore, services::UserService, EventFilter};
 
-pub async fn create_user(
-    State(handler): State<UserService>,
-    Json(payload): Json<commands::CreateUser>,
-) -> impl IntoResponse {
-    handler.create_user(payload).await;
-    "User creation initiated".into_response()
+/// `?types=&ids=&after=` of the user event feed, `types` and `ids` are
+/// comma separated and match everything when absent.
+#[derive(Deserialize, Debug)]
+pub struct UserEventQuery {
+    pub types: Option<String>,
+    pub ids: Option<String>,
+    pub after: Option<Uuid>,
 }
-pub async fn get_user_by_id(
-    State(state): State<UserService>,
-    Path(id): Path<Uuid>,
+
+/// User events as Server-Sent Events, each with its event id as `id` and
+/// its type as `event`. A reconnecting `EventSource` sends the last id it
+/// saw as `Last-Event-ID`, which takes precedence over `after`.
+pub async fn watch_users<R: UserStore>(
+    State(state): State<UserService<R>>,
+    Query(query): Query<UserEventQuery>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    match state.handle_get_user_by_id(id).await {
-        Ok(Some(user)) => {
-            info!("User Found:\n {:#?}", user);
-            Json(user).into_response()
+    let last_event_id = headers
+        .get("last-event-id")
+        .map(|value| value.to_str().ok().and_then(|id| Uuid::parse_str(id).ok()));
+    let after = match last_event_id {
+        Some(Some(id)) => Some(id),
+        Some(None) => {
+            return (StatusCode::BAD_REQUEST, "Invalid Last-Event-ID header").into_response()
         }
-        Ok(None) => {
-            info!("User Not Found");
-            "User not found".into_response()
-        }
-        Err(_) => {
-            error!("Failed to Fetch User");
-            "Failed to get user".into_response()
-        }
-    }
+        None => query.after,
+    };
+    let split = |list: Option<String>| -> Vec<String> {
+        list.iter()
+            .flat_map(|list| list.split(','))
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_owned)
+            .collect()
+    };
+    let Ok(aggregate_ids) = split(query.ids)
+        .iter()
+        .map(|id| Uuid::parse_str(id))
+        .collect::<Result<_, _>>()
+    else {
+        return (StatusCode::BAD_REQUEST, "Invalid User Id").into_response();
+    };
+    let filter = EventFilter {
+        event_types: split(query.types),
+        aggregate_ids,
+    };
+
+    let Some(events) = state.watch_users(filter, after) else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "User events are not published",
+        )
+            .into_response();
+    };
+    // Errors are sent as `error` events, the feed ends after those it cannot recover from.
+    let events = events.map(|event| {
+        let event = event.map_err(|e| e.to_string()).and_then(|event| {
+            let (id, event_type) = (event.id, event.event_type.clone());
+            let data = UserEvent::try_from(event).map_err(|e| e.to_string())?;
+            Ok(Event::default()
+                .id(id.to_string())
+                .event(event_type)
+                .json_data(data)
+                .expect("user events serialize to JSON"))
+        });
+        Ok::<_, Infallible>(event.unwrap_or_else(|e| {
+            error!("Failed to stream user event: {}", e);
+            Event::default().event("error").data(e)
+        }))
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
 }
@@ -0,0 +1,246 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        State, WebSocketUpgrade,
+    },
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{broadcast::error::RecvError, mpsc, Semaphore};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    commands::{CommandBus, CommandDecoders, CommandError, CreateUser, DeleteUser, UpdateUser},
+    events::{StoredEvent, USER_EVENT_TYPES},
+    proto::UserEvent,
+    Command, EventBus, EventFilter,
+};
+
+/// The commands `/ws` dispatches, others are unknown to it.
+const COMMANDS: [&str; 3] = [CreateUser::NAME, UpdateUser::NAME, DeleteUser::NAME];
+
+/// Commands still running, and their results, are limited to this many per
+/// connection. Further commands are refused until some are done.
+const PENDING_RESULTS: usize = 64;
+
+/// What `/ws` connections dispatch commands to and take events from.
+#[derive(Clone)]
+pub struct WebSocketState {
+    pub bus: CommandBus,
+    pub decoders: CommandDecoders,
+    pub events: EventBus,
+}
+
+/// Messages from the client, `id` is echoed back in the reply.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    /// Dispatches the user command named `command`, e.g. `UpdateUser`.
+    Command {
+        id: String,
+        command: String,
+        #[serde(default)]
+        payload: Value,
+    },
+    Subscribe {
+        id: String,
+        topic: String,
+    },
+    Unsubscribe {
+        id: String,
+        topic: String,
+    },
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Reply {
+    /// The handler's output of a command.
+    Result {
+        id: String,
+        output: Value,
+    },
+    /// A failed request, without `id` when the request could not be read
+    /// or events were missed.
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        message: String,
+    },
+    Subscribed {
+        id: String,
+        topic: String,
+    },
+    Unsubscribed {
+        id: String,
+        topic: String,
+    },
+    Event {
+        topic: String,
+        event: UserEvent,
+    },
+}
+
+impl Reply {
+    fn error(id: impl Into<Option<String>>, message: impl ToString) -> Self {
+        Reply::Error {
+            id: id.into(),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// `users` for every user event, `users/{id}` for the events of one user,
+/// or an event type such as `UserUpdated`.
+fn topic_filter(topic: &str) -> Option<EventFilter> {
+    match topic.split_once('/') {
+        None if topic == "users" => Some(EventFilter::default()),
+        None => USER_EVENT_TYPES.contains(&topic).then(|| EventFilter {
+            event_types: vec![topic.to_string()],
+            ..Default::default()
+        }),
+        Some(("users", id)) => Some(EventFilter {
+            aggregate_ids: vec![Uuid::parse_str(id).ok()?],
+            ..Default::default()
+        }),
+        Some(_) => None,
+    }
+}
+
+/// Submits commands and streams events over one connection, speaking the
+/// JSON envelopes of [`Request`] and [`Reply`].
+pub async fn websocket(
+    State(state): State<WebSocketState>,
+    upgrade: WebSocketUpgrade,
+) -> impl IntoResponse {
+    upgrade.on_upgrade(move |socket| serve(socket, state))
+}
+
+async fn serve(mut socket: WebSocket, state: WebSocketState) {
+    let mut events = state.events.subscribe();
+    let mut topics: HashMap<String, EventFilter> = HashMap::new();
+    let (results, mut finished) = mpsc::channel(PENDING_RESULTS);
+    let pending = Arc::new(Semaphore::new(PENDING_RESULTS));
+
+    loop {
+        let replies = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    match serde_json::from_str(&text) {
+                        Ok(request) => handle(request, &state, &mut topics, &results, &pending),
+                        Err(e) => vec![Reply::error(None, format!("invalid message: {e}"))],
+                    }
+                }
+                Some(Ok(Message::Binary(_))) => {
+                    vec![Reply::error(None, "messages are JSON text")]
+                }
+                Some(Ok(_)) => continue,
+                Some(Err(_)) | None => break,
+            },
+            Some(reply) = finished.recv() => vec![reply],
+            event = events.recv() => match event {
+                Ok(event) => publish(event, &topics),
+                Err(RecvError::Lagged(skipped)) => {
+                    vec![Reply::error(None, format!("missed {skipped} events"))]
+                }
+                Err(RecvError::Closed) => break,
+            },
+        };
+
+        for reply in replies {
+            let text = serde_json::to_string(&reply).expect("replies serialize to JSON");
+            if socket.send(Message::Text(text)).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Replies right away to subscriptions, command results are sent to
+/// `results` once their handler is done. Commands are refused while
+/// `pending` has no permit left.
+fn handle(
+    request: Request,
+    state: &WebSocketState,
+    topics: &mut HashMap<String, EventFilter>,
+    results: &mpsc::Sender<Reply>,
+    pending: &Arc<Semaphore>,
+) -> Vec<Reply> {
+    match request {
+        Request::Command {
+            id,
+            command,
+            payload,
+        } => {
+            if !COMMANDS.contains(&command.as_str()) {
+                return vec![Reply::error(id, CommandError::Unknown(command))];
+            }
+            let request = match state.decoders.decode(&command, payload) {
+                Ok(request) => request,
+                Err(e) => return vec![Reply::error(id, e)],
+            };
+            let Ok(permit) = pending.clone().try_acquire_owned() else {
+                let message = format!("{PENDING_RESULTS} commands are running, retry later");
+                return vec![Reply::error(id, message)];
+            };
+            let (bus, results) = (state.bus.clone(), results.clone());
+            tokio::spawn(async move {
+                let reply = match bus.dispatch_json(request).await {
+                    Ok(output) => Reply::Result { id, output },
+                    Err(e) => {
+                        info!("Command {} failed: {}", command, e);
+                        Reply::error(id, e)
+                    }
+                };
+                // The connection is gone when nobody receives the reply.
+                let _ = results.send(reply).await;
+                drop(permit);
+            });
+            vec![]
+        }
+        Request::Subscribe { id, topic } => match topic_filter(&topic) {
+            Some(filter) => {
+                topics.insert(topic.clone(), filter);
+                vec![Reply::Subscribed { id, topic }]
+            }
+            None => vec![Reply::error(id, format!("unknown topic {topic}"))],
+        },
+        Request::Unsubscribe { id, topic } => {
+            topics.remove(&topic);
+            vec![Reply::Unsubscribed { id, topic }]
+        }
+    }
+}
+
+/// One reply per subscribed topic the event matches, none for events
+/// other than user ones.
+fn publish(event: StoredEvent, topics: &HashMap<String, EventFilter>) -> Vec<Reply> {
+    if !USER_EVENT_TYPES.contains(&event.event_type.as_str()) {
+        return vec![];
+    }
+    let matching: Vec<_> = topics
+        .iter()
+        .filter(|(_, filter)| filter.matches(&event))
+        .map(|(topic, _)| topic.clone())
+        .collect();
+    if matching.is_empty() {
+        return vec![];
+    }
+    match UserEvent::try_from(event) {
+        Ok(event) => matching
+            .into_iter()
+            .map(|topic| Reply::Event {
+                topic,
+                event: event.clone(),
+            })
+            .collect(),
+        Err(e) => {
+            error!("Failed to decode event: {}", e);
+            vec![]
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::services::WorkflowService;
+
+use super::Page;
+
+pub async fn list_workflows(
+    State(service): State<WorkflowService>,
+    Query(page): Query<Page>,
+) -> impl IntoResponse {
+    match service.list(page.limit, page.offset).await {
+        Ok(workflows) => Json(workflows).into_response(),
+        Err(e) => {
+            error!("Failed to list workflows: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to list workflows",
+            )
+                .into_response()
+        }
+    }
+}
+
+pub async fn get_workflow(
+    State(service): State<WorkflowService>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match service.get(id).await {
+        Ok(Some(workflow)) => Json(workflow).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Workflow not found").into_response(),
+        Err(e) => {
+            error!("Failed to fetch workflow {}: {}", id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to get workflow").into_response()
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use axum::{
+    http::{header::RETRY_AFTER, StatusCode},
+    response::{IntoResponse, Response},
+};
+use tracing::error;
+
+use crate::{commands::CommandError, repositories::RepositoryError};
+
+impl IntoResponse for CommandError {
+    fn into_response(self) -> Response {
+        match self {
+            CommandError::Overloaded { retry_after } => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(RETRY_AFTER, retry_after.as_secs().max(1).to_string())],
+                "Server is busy, try again later",
+            )
+                .into_response(),
+            CommandError::Timeout => {
+                (StatusCode::GATEWAY_TIMEOUT, "Command timed out").into_response()
+            }
+            CommandError::Closed => {
+                (StatusCode::SERVICE_UNAVAILABLE, "Server is shutting down").into_response()
+            }
+            CommandError::Unknown(_)
+            | CommandError::Payload(_)
+            | CommandError::InvalidSchedule(_)
+            | CommandError::InvalidUser(_) => {
+                (StatusCode::BAD_REQUEST, self.to_string()).into_response()
+            }
+            CommandError::Repository(RepositoryError::NotFound) => {
+                (StatusCode::NOT_FOUND, "Not found").into_response()
+            }
+            CommandError::Repository(RepositoryError::Conflict { .. }) => {
+                (StatusCode::CONFLICT, "Modified by another request").into_response()
+            }
+            CommandError::Repository(RepositoryError::Duplicate(_)) => {
+                (StatusCode::CONFLICT, self.to_string()).into_response()
+            }
+            CommandError::Repository(RepositoryError::Locked) => {
+                (StatusCode::CONFLICT, "Held by another request").into_response()
+            }
+            e => {
+                error!("{}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error").into_response()
+            }
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use axum::http::{header::IF_MATCH, HeaderMap, HeaderValue};
+
+/// Strong `ETag` for an aggregate version, e.g. `"3"`.
+pub fn etag(version: i64) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{version}\"")).unwrap()
+}
+
+#[derive(Debug)]
+pub struct InvalidIfMatch;
+
+/// Parses `If-Match` into an expected version, `*` or a missing header means any version.
+pub fn if_match(headers: &HeaderMap) -> Result<Option<i64>, InvalidIfMatch> {
+    let Some(value) = headers.get(IF_MATCH) else {
+        return Ok(None);
+    };
+
+    let value = value.to_str().map_err(|_| InvalidIfMatch)?.trim();
+    if value == "*" {
+        return Ok(None);
+    }
+
+    value
+        .trim_start_matches("W/")
+        .trim_matches('"')
+        .parse()
+        .map(Some)
+        .map_err(|_| InvalidIfMatch)
+}
@@ -1,3 +1,9 @@
+pub mod auth;
 pub mod controllers;
+pub mod errors;
+pub mod headers;
+pub mod negotiation;
+pub mod protocol_router;
+pub mod read_your_writes;
 pub mod router;
 pub mod routes;
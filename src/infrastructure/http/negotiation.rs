@@ -0,0 +1,192 @@
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{
+        header::{ACCEPT, CONTENT_TYPE, VARY},
+        request::Parts,
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use derive_more::Display;
+use prost::Message;
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::error;
+
+/// Body formats of the REST API, all carrying the prost types.
+#[derive(Clone, Copy, Debug, Default, Display, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    #[display("JSON")]
+    Json,
+    #[display("protobuf")]
+    Protobuf,
+    #[display("MessagePack")]
+    MessagePack,
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Protobuf => "application/x-protobuf",
+            Format::MessagePack => "application/msgpack",
+        }
+    }
+
+    /// The format of a media type, parameters such as `charset` ignored.
+    pub fn from_media_type(media_type: &str) -> Option<Self> {
+        let essence = media_type.split(';').next()?.trim().to_ascii_lowercase();
+        match essence.as_str() {
+            "application/json" => Some(Format::Json),
+            "application/x-protobuf"
+            | "application/protobuf"
+            | "application/vnd.google.protobuf" => Some(Format::Protobuf),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(Format::MessagePack)
+            }
+            essence if essence.ends_with("+json") => Some(Format::Json),
+            _ => None,
+        }
+    }
+
+    /// The supported format `Accept` prefers, JSON when the header is
+    /// missing or allows anything.
+    pub fn from_accept(headers: &HeaderMap) -> Option<Self> {
+        let mut ranges = headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .peekable();
+        if ranges.peek().is_none() {
+            return Some(Format::Json);
+        }
+
+        let mut best: Option<(f32, Format)> = None;
+        for range in ranges {
+            let mut params = range.split(';');
+            let media_type = params.next().unwrap_or_default().trim();
+            // A weight that is not a number excludes the range.
+            let quality = match params.find_map(|param| param.trim().strip_prefix("q=")) {
+                Some(q) => q
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|q| q.is_finite())
+                    .map_or(0.0, |q| q.clamp(0.0, 1.0)),
+                None => 1.0,
+            };
+            let format = match media_type {
+                "*/*" | "application/*" => Some(Format::Json),
+                media_type => Format::from_media_type(media_type),
+            };
+            if let Some(format) = format.filter(|_| quality > 0.0) {
+                if !matches!(best, Some((q, _)) if q >= quality) {
+                    best = Some((quality, format));
+                }
+            }
+        }
+        best.map(|(_, format)| format)
+    }
+
+    pub fn encode<T: Message + Serialize>(self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Format::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            Format::Protobuf => Ok(value.encode_to_vec()),
+            Format::MessagePack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn decode<T: Message + Default + DeserializeOwned>(self, body: &[u8]) -> Result<T, String> {
+        match self {
+            Format::Json => serde_json::from_slice(body).map_err(|e| e.to_string()),
+            Format::Protobuf => T::decode(body).map_err(|e| e.to_string()),
+            Format::MessagePack => rmp_serde::from_slice(body).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// `value` encoded in this format, with `Vary: Accept` as the body
+    /// depends on it.
+    pub fn respond<T: Message + Serialize>(self, value: &T) -> Response {
+        match self.encode(value) {
+            Ok(body) => (
+                [
+                    (CONTENT_TYPE, HeaderValue::from_static(self.content_type())),
+                    (VARY, HeaderValue::from_static("accept")),
+                ],
+                body,
+            )
+                .into_response(),
+            Err(e) => {
+                error!("Failed to encode {} response: {}", self, e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to encode response",
+                )
+                    .into_response()
+            }
+        }
+    }
+}
+
+/// A request body decoded from the format of its `Content-Type`.
+#[derive(Debug)]
+pub struct Negotiated<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Negotiated<T>
+where
+    T: Message + Default + DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let format = request
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Format::from_media_type)
+            .ok_or_else(|| {
+                (
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    "Expected application/json, application/x-protobuf or application/msgpack",
+                )
+                    .into_response()
+            })?;
+        let body = Bytes::from_request(request, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        format.decode(&body).map(Negotiated).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid {format} body: {e}"),
+            )
+                .into_response()
+        })
+    }
+}
+
+/// The response format the request's `Accept` header asks for, answer with
+/// [`Format::respond`].
+#[derive(Clone, Copy, Debug)]
+pub struct Accept(pub Format);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Accept {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Format::from_accept(&parts.headers)
+            .map(Accept)
+            .ok_or_else(|| {
+                (
+                    StatusCode::NOT_ACCEPTABLE,
+                    "Responses are application/json, application/x-protobuf or application/msgpack",
+                )
+                    .into_response()
+            })
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    convert::Infallible,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    extract::Request,
+    http::{header::CONTENT_TYPE, HeaderName, HeaderValue, StatusCode, Version},
+    response::{IntoResponse, Response},
+};
+use tower::{util::BoxCloneService, Service, ServiceExt};
+
+/// The protocol TLS negotiated for a connection. Servers terminating TLS
+/// insert it into request extensions for [`Predicate::alpn`] to see.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Alpn(pub Vec<u8>);
+
+/// What a request must look like for a [`ProtocolRouter`] backend.
+#[derive(Clone)]
+pub enum Predicate {
+    /// `Content-Type` starts with this, case insensitively.
+    ContentType(&'static str),
+    /// The path is this or below it, by whole segments.
+    PathPrefix(&'static str),
+    /// The header is present.
+    Header(HeaderName),
+    /// The header has this value.
+    HeaderValue(HeaderName, HeaderValue),
+    Version(Version),
+    Alpn(&'static [u8]),
+    Any(Vec<Predicate>),
+    All(Vec<Predicate>),
+    Not(Box<Predicate>),
+    Custom(Arc<dyn Fn(&Request) -> bool + Send + Sync>),
+}
+
+impl Predicate {
+    pub fn content_type(prefix: &'static str) -> Self {
+        Predicate::ContentType(prefix)
+    }
+
+    pub fn path_prefix(prefix: &'static str) -> Self {
+        Predicate::PathPrefix(prefix)
+    }
+
+    pub fn header(name: HeaderName) -> Self {
+        Predicate::Header(name)
+    }
+
+    pub fn header_value(name: HeaderName, value: &'static str) -> Self {
+        Predicate::HeaderValue(name, HeaderValue::from_static(value))
+    }
+
+    pub fn version(version: Version) -> Self {
+        Predicate::Version(version)
+    }
+
+    pub fn alpn(protocol: &'static [u8]) -> Self {
+        Predicate::Alpn(protocol)
+    }
+
+    pub fn custom(predicate: impl Fn(&Request) -> bool + Send + Sync + 'static) -> Self {
+        Predicate::Custom(Arc::new(predicate))
+    }
+
+    pub fn or(self, other: Predicate) -> Self {
+        match self {
+            Predicate::Any(mut predicates) => {
+                predicates.push(other);
+                Predicate::Any(predicates)
+            }
+            predicate => Predicate::Any(vec![predicate, other]),
+        }
+    }
+
+    pub fn and(self, other: Predicate) -> Self {
+        match self {
+            Predicate::All(mut predicates) => {
+                predicates.push(other);
+                Predicate::All(predicates)
+            }
+            predicate => Predicate::All(vec![predicate, other]),
+        }
+    }
+
+    pub fn matches(&self, request: &Request) -> bool {
+        match self {
+            Predicate::ContentType(prefix) => request
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.get(..prefix.len()))
+                .is_some_and(|value| value.eq_ignore_ascii_case(prefix)),
+            Predicate::PathPrefix(prefix) => {
+                let prefix = prefix.trim_end_matches('/');
+                request
+                    .uri()
+                    .path()
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            }
+            Predicate::Header(name) => request.headers().contains_key(name),
+            Predicate::HeaderValue(name, value) => request.headers().get(name) == Some(value),
+            Predicate::Version(version) => request.version() == *version,
+            Predicate::Alpn(protocol) => request
+                .extensions()
+                .get::<Alpn>()
+                .is_some_and(|alpn| alpn.0 == *protocol),
+            Predicate::Any(predicates) => predicates.iter().any(|p| p.matches(request)),
+            Predicate::All(predicates) => predicates.iter().all(|p| p.matches(request)),
+            Predicate::Not(predicate) => !predicate.matches(request),
+            Predicate::Custom(predicate) => predicate(request),
+        }
+    }
+}
+
+impl std::ops::Not for Predicate {
+    type Output = Predicate;
+
+    fn not(self) -> Self::Output {
+        Predicate::Not(Box::new(self))
+    }
+}
+
+impl fmt::Debug for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Predicate::ContentType(prefix) => write!(f, "ContentType({prefix:?})"),
+            Predicate::PathPrefix(prefix) => write!(f, "PathPrefix({prefix:?})"),
+            Predicate::Header(name) => write!(f, "Header({name})"),
+            Predicate::HeaderValue(name, value) => write!(f, "HeaderValue({name}, {value:?})"),
+            Predicate::Version(version) => write!(f, "Version({version:?})"),
+            Predicate::Alpn(protocol) => {
+                write!(f, "Alpn({:?})", String::from_utf8_lossy(protocol))
+            }
+            Predicate::Any(predicates) => f.debug_tuple("Any").field(predicates).finish(),
+            Predicate::All(predicates) => f.debug_tuple("All").field(predicates).finish(),
+            Predicate::Not(predicate) => f.debug_tuple("Not").field(predicate).finish(),
+            Predicate::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+type Backend = BoxCloneService<Request, Response, Infallible>;
+
+/// Sends each request to the first backend whose predicate it matches,
+/// or the fallback. Without a fallback, requests matching no backend are
+/// answered with `415 Unsupported Media Type`.
+#[derive(Clone, Default)]
+pub struct ProtocolRouter {
+    routes: Vec<(Predicate, Backend)>,
+    fallback: Option<Backend>,
+}
+
+impl ProtocolRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn route<S>(mut self, predicate: Predicate, service: S) -> Self
+    where
+        S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+        S::Future: Send + 'static,
+    {
+        self.routes.push((predicate, BoxCloneService::new(service)));
+        self
+    }
+
+    pub fn fallback<S>(mut self, service: S) -> Self
+    where
+        S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+        S::Future: Send + 'static,
+    {
+        self.fallback = Some(BoxCloneService::new(service));
+        self
+    }
+}
+
+impl fmt::Debug for ProtocolRouter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProtocolRouter")
+            .field(
+                "routes",
+                &self.routes.iter().map(|(p, _)| p).collect::<Vec<_>>(),
+            )
+            .field("fallback", &self.fallback.is_some())
+            .finish()
+    }
+}
+
+impl Service<Request> for ProtocolRouter {
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+
+    /// Backends are readied when called, like the routes of an axum
+    /// `Router`.
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let backend = self
+            .routes
+            .iter()
+            .find(|(predicate, _)| predicate.matches(&request))
+            .map(|(_, backend)| backend)
+            .or(self.fallback.as_ref())
+            .cloned();
+        match backend {
+            Some(backend) => Box::pin(backend.oneshot(request)),
+            None => Box::pin(async {
+                Ok((StatusCode::UNSUPPORTED_MEDIA_TYPE, "Unsupported protocol").into_response())
+            }),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::http::{HeaderValue, Request, Response};
+use tower::{Layer, Service};
+
+use crate::consistency;
+
+/// Response header set after a request sent a command, holding the Unix
+/// time in milliseconds until which the client's reads go to the primary.
+/// Clients send it back on the following requests.
+pub const READ_PRIMARY_UNTIL: &str = "x-read-primary-until";
+
+/// Read-your-writes over a read replica, as headers are metadata in gRPC
+/// this wraps both the REST and the gRPC router.
+#[derive(Clone, Debug)]
+pub struct ReadYourWritesLayer {
+    window: Duration,
+}
+
+impl ReadYourWritesLayer {
+    pub fn new(window: Duration) -> Self {
+        Self { window }
+    }
+
+    /// `READ_YOUR_WRITES_MS`, 2 seconds by default.
+    pub fn from_env() -> Self {
+        let ms = std::env::var("READ_YOUR_WRITES_MS")
+            .ok()
+            .and_then(|ms| ms.parse().ok())
+            .unwrap_or(2000);
+        Self::new(Duration::from_millis(ms))
+    }
+}
+
+impl<S> Layer<S> for ReadYourWritesLayer {
+    type Service = ReadYourWrites<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ReadYourWrites {
+            inner,
+            window: self.window,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ReadYourWrites<S> {
+    inner: S,
+    window: Duration,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for ReadYourWrites<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let now = now_ms();
+        let window = self.window.as_millis() as u64;
+        // Deadlines further out than one window were not issued here.
+        let read_primary = request
+            .headers()
+            .get(READ_PRIMARY_UNTIL)
+            .and_then(|until| until.to_str().ok()?.parse::<u64>().ok())
+            .is_some_and(|until| until > now && until - now <= window);
+
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let (response, wrote) = consistency::scope(read_primary, response).await;
+            let mut response = response?;
+            if wrote && window > 0 {
+                response
+                    .headers_mut()
+                    .insert(READ_PRIMARY_UNTIL, HeaderValue::from(now_ms() + window));
+            }
+            Ok(response)
+        })
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
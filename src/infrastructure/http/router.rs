@@ -1,18 +1,130 @@
-use axum::{
-    routing::{get, post, Router as HttpRouter},
-    Router,
+use axum::middleware::from_fn_with_state;
+use axum::routing::{get, post, Router as HttpRouter};
+use axum::Router;
+
+use crate::{
+    commands::{CommandBus, CommandDecoders},
+    infrastructure::grpc::services::{rest_services, user_rest_services},
+    repositories::UserStore,
+    services::{DeadLetterService, ScheduleService, UserService, WorkflowService},
+    Api, EventBus, PostgreSQL,
 };
-use sqlx::{Pool, Postgres};
-use tokio::sync::mpsc;
 
-use crate::{commands::CommandMessage, services::UserService, Api, PostgreSQL};
+use super::auth::{require_api_token, require_bearer, require_websocket_token, BearerTokens};
+use super::controllers::{
+    cancel_schedule, create_schedule, discard_dead_letter, export_users, get_dead_letter,
+    get_schedule, get_workflow, import_users, list_dead_letters, list_schedules, list_workflows,
+    ready, replay_dead_letter, watch_users, websocket, WebSocketState,
+};
 
-use super::controllers::{create_user, get_user_by_id};
+/// The user and health routes alone, for backends other than Postgres
+/// where the idempotency, admin and scheduling tables are not available.
+/// The user routes need one of `api_tokens` if there are any.
+pub fn user_router<R: UserStore>(
+    repo: R,
+    bus: CommandBus,
+    events: EventBus,
+    api_tokens: BearerTokens,
+) -> HttpRouter {
+    let user_service = UserService::new(repo.clone(), bus.clone()).with_event_bus(events);
+    user_rest_services(repo, bus.clone())
+        .merge(user_routes(user_service))
+        .route_layer(from_fn_with_state(api_tokens, require_api_token))
+        .merge(health_routes(bus))
+}
 
-pub fn router(pool: Pool<Postgres>, sender: mpsc::Sender<CommandMessage>) -> HttpRouter {
-    let user_service = UserService::new(PostgreSQL::new(pool.clone()), sender);
+/// The user endpoints that are not RPCs, those are generated from
+/// `users.proto`.
+fn user_routes<R: UserStore>(user_service: UserService<R>) -> HttpRouter {
     Router::new()
-        .route(Api::CreateUser.into(), post(create_user))
-        .route(Api::GetUser.into(), get(get_user_by_id))
+        .route(Api::UserEvents.into(), get(watch_users::<R>))
+        .route(Api::ImportUsers.into(), post(import_users::<R>))
+        .route(Api::ExportUsers.into(), get(export_users::<R>))
         .with_state(user_service)
 }
+
+fn health_routes(bus: CommandBus) -> HttpRouter {
+    Router::new()
+        .route(Api::Ready.into(), get(ready))
+        .with_state(bus)
+}
+
+/// Every route but `/admin` and `/ws`. The user routes need one of
+/// `api_tokens` if there are any, the schedule ones one of `admin_tokens`.
+pub fn router(
+    db: PostgreSQL,
+    bus: CommandBus,
+    decoders: CommandDecoders,
+    events: EventBus,
+    api_tokens: BearerTokens,
+    admin_tokens: BearerTokens,
+) -> HttpRouter {
+    let user_service = UserService::new(db.clone(), bus.clone()).with_event_bus(events);
+    let schedule_service = ScheduleService::new(db.clone(), decoders.clone());
+    rest_services(db, bus.clone())
+        .merge(user_routes(user_service))
+        .route_layer(from_fn_with_state(api_tokens, require_api_token))
+        .merge(health_routes(bus))
+        .merge(
+            Router::new()
+                .route(
+                    Api::Schedules.into(),
+                    get(list_schedules).post(create_schedule),
+                )
+                .route(
+                    Api::Schedule.into(),
+                    get(get_schedule).delete(cancel_schedule),
+                )
+                .route_layer(from_fn_with_state(admin_tokens, require_bearer))
+                .with_state(schedule_service),
+        )
+}
+
+/// `/ws`, dispatching the user commands of `decoders` and pushing the
+/// events of `events`, with the authentication of the user routes or an
+/// `access_token` query parameter.
+pub fn websocket_router(
+    bus: CommandBus,
+    decoders: CommandDecoders,
+    events: EventBus,
+    api_tokens: BearerTokens,
+) -> HttpRouter {
+    Router::new()
+        .route(Api::WebSocket.into(), get(websocket))
+        .route_layer(from_fn_with_state(api_tokens, require_websocket_token))
+        .with_state(WebSocketState {
+            bus,
+            decoders,
+            events,
+        })
+}
+
+/// The dead letter and workflow routes under `/admin`, only for requests
+/// carrying one of `tokens`.
+pub fn admin_router(
+    db: PostgreSQL,
+    bus: CommandBus,
+    decoders: CommandDecoders,
+    tokens: BearerTokens,
+) -> HttpRouter {
+    let dead_letter_service = DeadLetterService::new(db.clone(), bus, decoders);
+    let workflow_service = WorkflowService::new(db);
+    Router::new()
+        .merge(
+            Router::new()
+                .route(Api::ListDeadLetters.into(), get(list_dead_letters))
+                .route(
+                    Api::DeadLetter.into(),
+                    get(get_dead_letter).delete(discard_dead_letter),
+                )
+                .route(Api::ReplayDeadLetter.into(), post(replay_dead_letter))
+                .with_state(dead_letter_service),
+        )
+        .merge(
+            Router::new()
+                .route(Api::ListWorkflows.into(), get(list_workflows))
+                .route(Api::Workflow.into(), get(get_workflow))
+                .with_state(workflow_service),
+        )
+        .route_layer(from_fn_with_state(tokens, require_bearer))
+}
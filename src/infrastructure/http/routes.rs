@@ -1,13 +1,33 @@
 pub enum Api {
-    CreateUser,
-    GetUser,
+    UserEvents,
+    ImportUsers,
+    ExportUsers,
+    WebSocket,
+    Ready,
+    ListDeadLetters,
+    DeadLetter,
+    ReplayDeadLetter,
+    Schedules,
+    Schedule,
+    ListWorkflows,
+    Workflow,
 }
 
 impl From<Api> for &'static str {
     fn from(value: Api) -> Self {
         match value {
-            Api::CreateUser => "/users",
-            Api::GetUser => "/users/:id",
+            Api::UserEvents => "/users/events",
+            Api::ImportUsers => "/users/import",
+            Api::ExportUsers => "/users/export",
+            Api::WebSocket => "/ws",
+            Api::Ready => "/health/ready",
+            Api::ListDeadLetters => "/admin/dead-letters",
+            Api::DeadLetter => "/admin/dead-letters/:id",
+            Api::ReplayDeadLetter => "/admin/dead-letters/:id/replay",
+            Api::Schedules => "/schedules",
+            Api::Schedule => "/schedules/:id",
+            Api::ListWorkflows => "/admin/workflows",
+            Api::Workflow => "/admin/workflows/:id",
         }
     }
 }
@@ -0,0 +1,398 @@
+// @generated by build.rs from the services in proto/, do not edit.
+
+use std::sync::Arc;
+
+use axum::{extract::Request, routing::post, Router};
+
+use crate::infrastructure::grpc::connect::{self, Procedure};
+
+/// `admin.AdminService.ListDeadLetters`, unary.
+pub static ADMIN_SERVICE_LIST_DEAD_LETTERS: Procedure = Procedure {
+    path: "/admin.AdminService/ListDeadLetters",
+    input: ".admin.ListDeadLettersRequest",
+    output: ".admin.ListDeadLettersResponse",
+};
+
+/// `admin.AdminService.GetDeadLetter`, unary.
+pub static ADMIN_SERVICE_GET_DEAD_LETTER: Procedure = Procedure {
+    path: "/admin.AdminService/GetDeadLetter",
+    input: ".admin.GetDeadLetterRequest",
+    output: ".admin.GetDeadLetterResponse",
+};
+
+/// `admin.AdminService.ReplayDeadLetter`, unary.
+pub static ADMIN_SERVICE_REPLAY_DEAD_LETTER: Procedure = Procedure {
+    path: "/admin.AdminService/ReplayDeadLetter",
+    input: ".admin.ReplayDeadLetterRequest",
+    output: ".admin.ReplayDeadLetterResponse",
+};
+
+/// `admin.AdminService.DiscardDeadLetter`, unary.
+pub static ADMIN_SERVICE_DISCARD_DEAD_LETTER: Procedure = Procedure {
+    path: "/admin.AdminService/DiscardDeadLetter",
+    input: ".admin.DiscardDeadLetterRequest",
+    output: ".admin.DiscardDeadLetterResponse",
+};
+
+/// `admin.AdminService.ListWorkflows`, unary.
+pub static ADMIN_SERVICE_LIST_WORKFLOWS: Procedure = Procedure {
+    path: "/admin.AdminService/ListWorkflows",
+    input: ".admin.ListWorkflowsRequest",
+    output: ".admin.ListWorkflowsResponse",
+};
+
+/// `admin.AdminService.GetWorkflow`, unary.
+pub static ADMIN_SERVICE_GET_WORKFLOW: Procedure = Procedure {
+    path: "/admin.AdminService/GetWorkflow",
+    input: ".admin.GetWorkflowRequest",
+    output: ".admin.GetWorkflowResponse",
+};
+
+/// Connect routes of `admin.AdminService`.
+pub fn admin_service_routes<S>(service: S) -> Router
+where
+    S: crate::proto::admin_service_server::AdminService,
+{
+    let service = Arc::new(service);
+    Router::new()
+        .route(
+            ADMIN_SERVICE_LIST_DEAD_LETTERS.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::unary(&ADMIN_SERVICE_LIST_DEAD_LETTERS, request, |request| async move {
+                        service.list_dead_letters(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            ADMIN_SERVICE_GET_DEAD_LETTER.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::unary(&ADMIN_SERVICE_GET_DEAD_LETTER, request, |request| async move {
+                        service.get_dead_letter(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            ADMIN_SERVICE_REPLAY_DEAD_LETTER.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::unary(&ADMIN_SERVICE_REPLAY_DEAD_LETTER, request, |request| async move {
+                        service.replay_dead_letter(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            ADMIN_SERVICE_DISCARD_DEAD_LETTER.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::unary(&ADMIN_SERVICE_DISCARD_DEAD_LETTER, request, |request| async move {
+                        service.discard_dead_letter(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            ADMIN_SERVICE_LIST_WORKFLOWS.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::unary(&ADMIN_SERVICE_LIST_WORKFLOWS, request, |request| async move {
+                        service.list_workflows(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            ADMIN_SERVICE_GET_WORKFLOW.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::unary(&ADMIN_SERVICE_GET_WORKFLOW, request, |request| async move {
+                        service.get_workflow(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+}
+
+/// `schedules.ScheduleService.CreateSchedule`, unary.
+pub static SCHEDULE_SERVICE_CREATE_SCHEDULE: Procedure = Procedure {
+    path: "/schedules.ScheduleService/CreateSchedule",
+    input: ".schedules.CreateScheduleRequest",
+    output: ".schedules.GetScheduleResponse",
+};
+
+/// `schedules.ScheduleService.ListSchedules`, unary.
+pub static SCHEDULE_SERVICE_LIST_SCHEDULES: Procedure = Procedure {
+    path: "/schedules.ScheduleService/ListSchedules",
+    input: ".schedules.ListSchedulesRequest",
+    output: ".schedules.ListSchedulesResponse",
+};
+
+/// `schedules.ScheduleService.GetSchedule`, unary.
+pub static SCHEDULE_SERVICE_GET_SCHEDULE: Procedure = Procedure {
+    path: "/schedules.ScheduleService/GetSchedule",
+    input: ".schedules.GetScheduleRequest",
+    output: ".schedules.GetScheduleResponse",
+};
+
+/// `schedules.ScheduleService.CancelSchedule`, unary.
+pub static SCHEDULE_SERVICE_CANCEL_SCHEDULE: Procedure = Procedure {
+    path: "/schedules.ScheduleService/CancelSchedule",
+    input: ".schedules.CancelScheduleRequest",
+    output: ".schedules.CancelScheduleResponse",
+};
+
+/// Connect routes of `schedules.ScheduleService`.
+pub fn schedule_service_routes<S>(service: S) -> Router
+where
+    S: crate::proto::schedule_service_server::ScheduleService,
+{
+    let service = Arc::new(service);
+    Router::new()
+        .route(
+            SCHEDULE_SERVICE_CREATE_SCHEDULE.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::unary(&SCHEDULE_SERVICE_CREATE_SCHEDULE, request, |request| async move {
+                        service.create_schedule(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            SCHEDULE_SERVICE_LIST_SCHEDULES.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::unary(&SCHEDULE_SERVICE_LIST_SCHEDULES, request, |request| async move {
+                        service.list_schedules(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            SCHEDULE_SERVICE_GET_SCHEDULE.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::unary(&SCHEDULE_SERVICE_GET_SCHEDULE, request, |request| async move {
+                        service.get_schedule(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            SCHEDULE_SERVICE_CANCEL_SCHEDULE.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::unary(&SCHEDULE_SERVICE_CANCEL_SCHEDULE, request, |request| async move {
+                        service.cancel_schedule(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+}
+
+/// `users.UserService.CreateUser`, unary.
+pub static USER_SERVICE_CREATE_USER: Procedure = Procedure {
+    path: "/users.UserService/CreateUser",
+    input: ".users.CreateUserRequest",
+    output: ".users.CreateUserResponse",
+};
+
+/// `users.UserService.GetUser`, unary.
+pub static USER_SERVICE_GET_USER: Procedure = Procedure {
+    path: "/users.UserService/GetUser",
+    input: ".users.GetUserRequest",
+    output: ".users.GetUserResponse",
+};
+
+/// `users.UserService.UpdateUser`, unary.
+pub static USER_SERVICE_UPDATE_USER: Procedure = Procedure {
+    path: "/users.UserService/UpdateUser",
+    input: ".users.UpdateUserRequest",
+    output: ".users.UpdateUserResponse",
+};
+
+/// `users.UserService.DeleteUser`, unary.
+pub static USER_SERVICE_DELETE_USER: Procedure = Procedure {
+    path: "/users.UserService/DeleteUser",
+    input: ".users.DeleteUserRequest",
+    output: ".users.DeleteUserResponse",
+};
+
+/// `users.UserService.ListUsers`, unary.
+pub static USER_SERVICE_LIST_USERS: Procedure = Procedure {
+    path: "/users.UserService/ListUsers",
+    input: ".users.ListUsersRequest",
+    output: ".users.ListUsersResponse",
+};
+
+/// `users.UserService.WatchUsers`, server streaming.
+pub static USER_SERVICE_WATCH_USERS: Procedure = Procedure {
+    path: "/users.UserService/WatchUsers",
+    input: ".users.WatchUsersRequest",
+    output: ".users.UserEvent",
+};
+
+/// `users.UserService.ImportUsers`, client streaming.
+pub static USER_SERVICE_IMPORT_USERS: Procedure = Procedure {
+    path: "/users.UserService/ImportUsers",
+    input: ".users.CreateUserRequest",
+    output: ".users.ImportUsersResponse",
+};
+
+/// `users.UserService.ImportUsersStream`, bidirectional streaming.
+pub static USER_SERVICE_IMPORT_USERS_STREAM: Procedure = Procedure {
+    path: "/users.UserService/ImportUsersStream",
+    input: ".users.CreateUserRequest",
+    output: ".users.ImportUserResult",
+};
+
+/// Connect routes of `users.UserService`.
+pub fn user_service_routes<S>(service: S) -> Router
+where
+    S: crate::proto::user_service_server::UserService,
+{
+    let service = Arc::new(service);
+    Router::new()
+        .route(
+            USER_SERVICE_CREATE_USER.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::unary(&USER_SERVICE_CREATE_USER, request, |request| async move {
+                        service.create_user(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            USER_SERVICE_GET_USER.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::unary(&USER_SERVICE_GET_USER, request, |request| async move {
+                        service.get_user(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            USER_SERVICE_UPDATE_USER.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::unary(&USER_SERVICE_UPDATE_USER, request, |request| async move {
+                        service.update_user(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            USER_SERVICE_DELETE_USER.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::unary(&USER_SERVICE_DELETE_USER, request, |request| async move {
+                        service.delete_user(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            USER_SERVICE_LIST_USERS.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::unary(&USER_SERVICE_LIST_USERS, request, |request| async move {
+                        service.list_users(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            USER_SERVICE_WATCH_USERS.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::server_streaming(&USER_SERVICE_WATCH_USERS, request, |request| async move {
+                        service.watch_users(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            USER_SERVICE_IMPORT_USERS.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::client_streaming(&USER_SERVICE_IMPORT_USERS, request, |request| async move {
+                        service.import_users(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            USER_SERVICE_IMPORT_USERS_STREAM.path,
+            post({
+                let service = service.clone();
+                move |request: Request| async move {
+                    connect::bidi_streaming(&USER_SERVICE_IMPORT_USERS_STREAM, request, |request| async move {
+                        service.import_users_stream(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+}
+
+/// Paths of every procedure.
+pub static PATHS: &[&str] = &[
+    "/admin.AdminService/ListDeadLetters",
+    "/admin.AdminService/GetDeadLetter",
+    "/admin.AdminService/ReplayDeadLetter",
+    "/admin.AdminService/DiscardDeadLetter",
+    "/admin.AdminService/ListWorkflows",
+    "/admin.AdminService/GetWorkflow",
+    "/schedules.ScheduleService/CreateSchedule",
+    "/schedules.ScheduleService/ListSchedules",
+    "/schedules.ScheduleService/GetSchedule",
+    "/schedules.ScheduleService/CancelSchedule",
+    "/users.UserService/CreateUser",
+    "/users.UserService/GetUser",
+    "/users.UserService/UpdateUser",
+    "/users.UserService/DeleteUser",
+    "/users.UserService/ListUsers",
+    "/users.UserService/WatchUsers",
+    "/users.UserService/ImportUsers",
+    "/users.UserService/ImportUsersStream",
+];
@@ -0,0 +1,437 @@
+// @generated by build.rs from the messages in proto/, do not edit.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::infrastructure::grpc::transcoding;
+
+impl Serialize for crate::proto::ListDeadLettersRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".admin.ListDeadLettersRequest", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::ListDeadLettersRequest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".admin.ListDeadLettersRequest", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::ListDeadLettersResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".admin.ListDeadLettersResponse", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::ListDeadLettersResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".admin.ListDeadLettersResponse", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::GetDeadLetterRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".admin.GetDeadLetterRequest", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::GetDeadLetterRequest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".admin.GetDeadLetterRequest", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::GetDeadLetterResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".admin.GetDeadLetterResponse", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::GetDeadLetterResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".admin.GetDeadLetterResponse", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::ReplayDeadLetterRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".admin.ReplayDeadLetterRequest", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::ReplayDeadLetterRequest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".admin.ReplayDeadLetterRequest", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::ReplayDeadLetterResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".admin.ReplayDeadLetterResponse", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::ReplayDeadLetterResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".admin.ReplayDeadLetterResponse", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::DiscardDeadLetterRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".admin.DiscardDeadLetterRequest", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::DiscardDeadLetterRequest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".admin.DiscardDeadLetterRequest", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::DiscardDeadLetterResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".admin.DiscardDeadLetterResponse", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::DiscardDeadLetterResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".admin.DiscardDeadLetterResponse", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::ListWorkflowsRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".admin.ListWorkflowsRequest", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::ListWorkflowsRequest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".admin.ListWorkflowsRequest", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::ListWorkflowsResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".admin.ListWorkflowsResponse", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::ListWorkflowsResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".admin.ListWorkflowsResponse", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::GetWorkflowRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".admin.GetWorkflowRequest", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::GetWorkflowRequest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".admin.GetWorkflowRequest", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::GetWorkflowResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".admin.GetWorkflowResponse", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::GetWorkflowResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".admin.GetWorkflowResponse", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::CreateScheduleRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".schedules.CreateScheduleRequest", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::CreateScheduleRequest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".schedules.CreateScheduleRequest", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::ListSchedulesRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".schedules.ListSchedulesRequest", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::ListSchedulesRequest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".schedules.ListSchedulesRequest", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::ListSchedulesResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".schedules.ListSchedulesResponse", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::ListSchedulesResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".schedules.ListSchedulesResponse", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::GetScheduleRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".schedules.GetScheduleRequest", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::GetScheduleRequest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".schedules.GetScheduleRequest", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::GetScheduleResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".schedules.GetScheduleResponse", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::GetScheduleResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".schedules.GetScheduleResponse", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::CancelScheduleRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".schedules.CancelScheduleRequest", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::CancelScheduleRequest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".schedules.CancelScheduleRequest", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::CancelScheduleResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".schedules.CancelScheduleResponse", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::CancelScheduleResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".schedules.CancelScheduleResponse", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::google::api::Http {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".google.api.Http", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::google::api::Http {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".google.api.Http", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::google::api::HttpRule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".google.api.HttpRule", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::google::api::HttpRule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".google.api.HttpRule", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::google::api::CustomHttpPattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".google.api.CustomHttpPattern", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::google::api::CustomHttpPattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".google.api.CustomHttpPattern", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::CreateUserRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".users.CreateUserRequest", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::CreateUserRequest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".users.CreateUserRequest", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::CreateUserResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".users.CreateUserResponse", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::CreateUserResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".users.CreateUserResponse", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::GetUserRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".users.GetUserRequest", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::GetUserRequest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".users.GetUserRequest", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::GetUserResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".users.GetUserResponse", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::GetUserResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".users.GetUserResponse", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::UpdateUserRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".users.UpdateUserRequest", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::UpdateUserRequest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".users.UpdateUserRequest", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::UpdateUserResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".users.UpdateUserResponse", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::UpdateUserResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".users.UpdateUserResponse", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::DeleteUserRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".users.DeleteUserRequest", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::DeleteUserRequest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".users.DeleteUserRequest", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::DeleteUserResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".users.DeleteUserResponse", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::DeleteUserResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".users.DeleteUserResponse", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::ListUsersRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".users.ListUsersRequest", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::ListUsersRequest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".users.ListUsersRequest", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::ListUsersResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".users.ListUsersResponse", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::ListUsersResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".users.ListUsersResponse", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::WatchUsersRequest {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".users.WatchUsersRequest", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::WatchUsersRequest {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".users.WatchUsersRequest", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::UserEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".users.UserEvent", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::UserEvent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".users.UserEvent", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::ImportUsersResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".users.ImportUsersResponse", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::ImportUsersResponse {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".users.ImportUsersResponse", deserializer)
+    }
+}
+
+impl Serialize for crate::proto::ImportUserResult {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        transcoding::serialize(self, ".users.ImportUserResult", serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for crate::proto::ImportUserResult {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        transcoding::deserialize(".users.ImportUserResult", deserializer)
+    }
+}
@@ -1,3 +1,32 @@
+mod admin;
+mod schedules;
 mod users;
 
+pub use admin::*;
+pub use schedules::*;
 pub use users::*;
+
+/// Types of the vendored `google/api` protos, for reading `google.api.http`
+/// annotations.
+pub mod google {
+    pub mod api {
+        include!("google.api.rs");
+    }
+}
+
+pub mod well_known;
+
+/// `Serialize` and `Deserialize` of the messages, generated by `build.rs`.
+#[rustfmt::skip]
+mod json;
+
+/// REST routes and command conversions generated by `build.rs`.
+#[rustfmt::skip]
+pub mod rest;
+
+/// Connect routes generated by `build.rs`.
+#[rustfmt::skip]
+pub mod connect;
+
+/// Every compiled proto file and its imports, as written by `build.rs`.
+pub const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!("reflection_descriptor.bin");
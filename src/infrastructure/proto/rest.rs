@@ -0,0 +1,158 @@
+// @generated by build.rs from the services in proto/, do not edit.
+
+use std::sync::Arc;
+
+use axum::{
+    routing::{on, MethodFilter},
+    Router,
+};
+
+use crate::infrastructure::grpc::transcoding::{self, Binding, RestRequest};
+
+/// `POST /users`
+pub static USER_SERVICE_CREATE_USER: Binding = Binding {
+    rpc: "/users.UserService/CreateUser",
+    route: "/users",
+    variables: &[],
+    body: "*",
+    response_body: "",
+    input: ".users.CreateUserRequest",
+    output: ".users.CreateUserResponse",
+};
+
+/// `GET /users/{id}`
+pub static USER_SERVICE_GET_USER: Binding = Binding {
+    rpc: "/users.UserService/GetUser",
+    route: "/users/:id",
+    variables: &[("id", "id")],
+    body: "",
+    response_body: "",
+    input: ".users.GetUserRequest",
+    output: ".users.GetUserResponse",
+};
+
+/// `PUT /users/{id}`
+pub static USER_SERVICE_UPDATE_USER: Binding = Binding {
+    rpc: "/users.UserService/UpdateUser",
+    route: "/users/:id",
+    variables: &[("id", "id")],
+    body: "*",
+    response_body: "",
+    input: ".users.UpdateUserRequest",
+    output: ".users.UpdateUserResponse",
+};
+
+/// `DELETE /users/{id}`
+pub static USER_SERVICE_DELETE_USER: Binding = Binding {
+    rpc: "/users.UserService/DeleteUser",
+    route: "/users/:id",
+    variables: &[("id", "id")],
+    body: "",
+    response_body: "",
+    input: ".users.DeleteUserRequest",
+    output: ".users.DeleteUserResponse",
+};
+
+/// `GET /users`
+pub static USER_SERVICE_LIST_USERS: Binding = Binding {
+    rpc: "/users.UserService/ListUsers",
+    route: "/users",
+    variables: &[],
+    body: "",
+    response_body: "",
+    input: ".users.ListUsersRequest",
+    output: ".users.ListUsersResponse",
+};
+
+/// REST routes of `users.UserService`.
+pub fn user_service_routes<S>(service: S) -> Router
+where
+    S: crate::proto::user_service_server::UserService,
+{
+    let service = Arc::new(service);
+    Router::new()
+        .route(
+            USER_SERVICE_CREATE_USER.route,
+            on(MethodFilter::POST, {
+                let service = service.clone();
+                move |request: RestRequest| async move {
+                    transcoding::unary(&USER_SERVICE_CREATE_USER, request, |request| async move {
+                        service.create_user(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            USER_SERVICE_GET_USER.route,
+            on(MethodFilter::GET, {
+                let service = service.clone();
+                move |request: RestRequest| async move {
+                    transcoding::unary(&USER_SERVICE_GET_USER, request, |request| async move {
+                        service.get_user(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            USER_SERVICE_UPDATE_USER.route,
+            on(MethodFilter::PUT, {
+                let service = service.clone();
+                move |request: RestRequest| async move {
+                    transcoding::unary(&USER_SERVICE_UPDATE_USER, request, |request| async move {
+                        service.update_user(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            USER_SERVICE_DELETE_USER.route,
+            on(MethodFilter::DELETE, {
+                let service = service.clone();
+                move |request: RestRequest| async move {
+                    transcoding::unary(&USER_SERVICE_DELETE_USER, request, |request| async move {
+                        service.delete_user(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+        .route(
+            USER_SERVICE_LIST_USERS.route,
+            on(MethodFilter::GET, {
+                let service = service.clone();
+                move |request: RestRequest| async move {
+                    transcoding::unary(&USER_SERVICE_LIST_USERS, request, |request| async move {
+                        service.list_users(request).await
+                    })
+                    .await
+                }
+            }),
+        )
+}
+
+impl TryFrom<crate::proto::CreateUserRequest> for crate::commands::CreateUser {
+    type Error = transcoding::InvalidMessage;
+
+    fn try_from(message: crate::proto::CreateUserRequest) -> Result<Self, Self::Error> {
+        transcoding::to_command(&message, ".users.CreateUserRequest")
+    }
+}
+
+impl TryFrom<crate::proto::UpdateUserRequest> for crate::commands::UpdateUser {
+    type Error = transcoding::InvalidMessage;
+
+    fn try_from(message: crate::proto::UpdateUserRequest) -> Result<Self, Self::Error> {
+        transcoding::to_command(&message, ".users.UpdateUserRequest")
+    }
+}
+
+impl TryFrom<crate::proto::DeleteUserRequest> for crate::commands::DeleteUser {
+    type Error = transcoding::InvalidMessage;
+
+    fn try_from(message: crate::proto::DeleteUserRequest) -> Result<Self, Self::Error> {
+        transcoding::to_command(&message, ".users.DeleteUserRequest")
+    }
+}
@@ -0,0 +1,196 @@
+//! The proto3 JSON form of the `google.protobuf` types that have one of
+//! their own, used by the transcoder for fields of those types.
+
+use chrono::{DateTime, SecondsFormat};
+use prost_types::{value::Kind, Duration, FieldMask, ListValue, Struct, Timestamp};
+use serde_json::{Number, Value};
+
+/// A well-known type and its proto3 JSON value.
+pub trait WellKnown: Sized {
+    fn to_json(&self) -> Value;
+    fn from_json(json: Value) -> Result<Self, String>;
+}
+
+/// RFC 3339 in UTC, e.g. `"2024-08-26T09:00:00.5Z"`.
+impl WellKnown for Timestamp {
+    fn to_json(&self) -> Value {
+        DateTime::from_timestamp(self.seconds, self.nanos.max(0) as u32)
+            .map_or(Value::Null, |time| {
+                Value::String(time.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+            })
+    }
+
+    fn from_json(json: Value) -> Result<Self, String> {
+        let Value::String(text) = json else {
+            return Err("expected an RFC 3339 timestamp".into());
+        };
+        let time = DateTime::parse_from_rfc3339(&text).map_err(|e| e.to_string())?;
+        Ok(Timestamp {
+            seconds: time.timestamp(),
+            nanos: time.timestamp_subsec_nanos() as i32,
+        })
+    }
+}
+
+/// Seconds with up to nine fractional digits and an `s` suffix, e.g.
+/// `"1.5s"`.
+impl WellKnown for Duration {
+    fn to_json(&self) -> Value {
+        let sign = if self.seconds < 0 || self.nanos < 0 {
+            "-"
+        } else {
+            ""
+        };
+        let (seconds, nanos) = (self.seconds.unsigned_abs(), self.nanos.unsigned_abs());
+        let text = match nanos {
+            0 => format!("{sign}{seconds}s"),
+            nanos => {
+                let fraction = format!("{nanos:09}");
+                format!("{sign}{seconds}.{}s", fraction.trim_end_matches('0'))
+            }
+        };
+        Value::String(text)
+    }
+
+    fn from_json(json: Value) -> Result<Self, String> {
+        let invalid = || "expected a duration such as \"1.5s\"".to_string();
+        let Value::String(text) = json else {
+            return Err(invalid());
+        };
+        let text = text.strip_suffix('s').ok_or_else(invalid)?;
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(text) => (true, text),
+            None => (false, text),
+        };
+        let (seconds, fraction) = text.split_once('.').unwrap_or((text, ""));
+        if fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let seconds: i64 = seconds.parse().map_err(|_| invalid())?;
+        let nanos: i32 = format!("{fraction:0<9}").parse().map_err(|_| invalid())?;
+        Ok(match negative {
+            true => Duration {
+                seconds: -seconds,
+                nanos: -nanos,
+            },
+            false => Duration { seconds, nanos },
+        })
+    }
+}
+
+/// Comma separated `lowerCamelCase` paths, e.g. `"username,email"`.
+impl WellKnown for FieldMask {
+    fn to_json(&self) -> Value {
+        let paths: Vec<_> = self.paths.iter().map(|path| camel_case(path)).collect();
+        Value::String(paths.join(","))
+    }
+
+    fn from_json(json: Value) -> Result<Self, String> {
+        let Value::String(text) = json else {
+            return Err("expected comma separated field paths".into());
+        };
+        Ok(FieldMask {
+            paths: text
+                .split(',')
+                .filter(|path| !path.is_empty())
+                .map(snake_case)
+                .collect(),
+        })
+    }
+}
+
+/// Any JSON object.
+impl WellKnown for Struct {
+    fn to_json(&self) -> Value {
+        Value::Object(
+            self.fields
+                .iter()
+                .map(|(key, value)| (key.clone(), value.to_json()))
+                .collect(),
+        )
+    }
+
+    fn from_json(json: Value) -> Result<Self, String> {
+        let Value::Object(object) = json else {
+            return Err("expected an object".into());
+        };
+        Ok(Struct {
+            fields: object
+                .into_iter()
+                .map(|(key, value)| Ok((key, prost_types::Value::from_json(value)?)))
+                .collect::<Result<_, String>>()?,
+        })
+    }
+}
+
+/// Any JSON array.
+impl WellKnown for ListValue {
+    fn to_json(&self) -> Value {
+        Value::Array(self.values.iter().map(WellKnown::to_json).collect())
+    }
+
+    fn from_json(json: Value) -> Result<Self, String> {
+        let Value::Array(values) = json else {
+            return Err("expected an array".into());
+        };
+        Ok(ListValue {
+            values: values
+                .into_iter()
+                .map(prost_types::Value::from_json)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Any JSON value.
+impl WellKnown for prost_types::Value {
+    fn to_json(&self) -> Value {
+        match &self.kind {
+            None | Some(Kind::NullValue(_)) => Value::Null,
+            Some(Kind::NumberValue(n)) => Number::from_f64(*n).map_or(Value::Null, Value::Number),
+            Some(Kind::StringValue(s)) => Value::String(s.clone()),
+            Some(Kind::BoolValue(b)) => Value::Bool(*b),
+            Some(Kind::StructValue(s)) => s.to_json(),
+            Some(Kind::ListValue(l)) => l.to_json(),
+        }
+    }
+
+    fn from_json(json: Value) -> Result<Self, String> {
+        let kind = match json {
+            Value::Null => Kind::NullValue(0),
+            Value::Bool(b) => Kind::BoolValue(b),
+            Value::Number(n) => Kind::NumberValue(n.as_f64().unwrap_or_default()),
+            Value::String(s) => Kind::StringValue(s),
+            Value::Array(_) => Kind::ListValue(ListValue::from_json(json)?),
+            Value::Object(_) => Kind::StructValue(Struct::from_json(json)?),
+        };
+        Ok(prost_types::Value { kind: Some(kind) })
+    }
+}
+
+fn camel_case(path: &str) -> String {
+    let mut camel = String::new();
+    let mut upper = false;
+    for c in path.chars() {
+        match c {
+            '_' => upper = true,
+            c if upper => {
+                camel.extend(c.to_uppercase());
+                upper = false;
+            }
+            c => camel.push(c),
+        }
+    }
+    camel
+}
+
+fn snake_case(path: &str) -> String {
+    let mut snake = String::new();
+    for c in path.chars() {
+        if c.is_uppercase() {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use axum::async_trait;
+use uuid::Uuid;
+
+use crate::{
+    models::QueuedCommand,
+    repositories::{CommandQueueRepository, RepositoryError},
+    PostgreSQL,
+};
+
+#[async_trait]
+impl CommandQueueRepository for PostgreSQL {
+    async fn enqueue_command(&self, command: QueuedCommand) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            "INSERT INTO command_queue (id,command_type,payload,aggregate_id,attempts,max_attempts)
+             VALUES ($1,$2,$3,$4,$5,$6)",
+            command.id,
+            &command.command_type,
+            command.payload,
+            command.aggregate_id,
+            command.attempts,
+            command.max_attempts,
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    async fn claim_commands(
+        &self,
+        limit: i64,
+        visibility_timeout: Duration,
+    ) -> Result<Vec<QueuedCommand>, RepositoryError> {
+        Ok(sqlx::query_as!(
+            QueuedCommand,
+            "WITH claimable AS (
+                 SELECT id FROM command_queue q
+                 WHERE visible_at <= now()
+                   AND NOT EXISTS (
+                       SELECT 1 FROM command_queue older
+                       WHERE older.aggregate_id = q.aggregate_id AND older.id < q.id
+                   )
+                 ORDER BY id
+                 LIMIT $1
+                 FOR UPDATE SKIP LOCKED
+             )
+             UPDATE command_queue SET attempts = attempts + 1,
+                 visible_at = now() + make_interval(secs => $2)
+             FROM claimable WHERE command_queue.id = claimable.id
+             RETURNING command_queue.id, command_type, payload, aggregate_id, attempts, max_attempts",
+            limit,
+            visibility_timeout.as_secs_f64(),
+        )
+        .fetch_all(&self.db)
+        .await?)
+    }
+
+    async fn complete_command(&self, id: Uuid) -> Result<(), RepositoryError> {
+        sqlx::query!("DELETE FROM command_queue WHERE id = $1", id)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn retry_command(
+        &self,
+        id: Uuid,
+        error: &str,
+        retry_in: Duration,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            "UPDATE command_queue SET last_error = $2,
+                 visible_at = now() + make_interval(secs => $3),
+                 errors = errors || jsonb_build_array(jsonb_build_object(
+                     'attempt', attempts, 'error', $2::TEXT, 'at', now()))
+             WHERE id = $1",
+            id,
+            error,
+            retry_in.as_secs_f64(),
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    async fn dead_letter_command(&self, id: Uuid, error: &str) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            "WITH failed AS (DELETE FROM command_queue WHERE id = $1 RETURNING *)
+             INSERT INTO command_dead_letters
+                 (id,command_type,payload,aggregate_id,attempts,last_error,errors,created_at)
+             SELECT id,command_type,payload,aggregate_id,attempts,$2,
+                 errors || jsonb_build_array(jsonb_build_object(
+                     'attempt', attempts, 'error', $2::TEXT, 'at', now())),
+                 created_at
+             FROM failed",
+            id,
+            error,
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+}
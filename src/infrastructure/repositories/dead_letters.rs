@@ -0,0 +1,141 @@
+use axum::async_trait;
+use sqlx::{PgConnection, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    models::DeadLetter,
+    repositories::{DeadLetterClaim, DeadLetterRepository, RepositoryError},
+    PostgreSQL,
+};
+
+#[async_trait]
+impl DeadLetterRepository for PostgreSQL {
+    type Claim = PgDeadLetterClaim;
+
+    async fn save_dead_letter(&self, dead_letter: DeadLetter) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            "INSERT INTO command_dead_letters
+                 (id,command_type,payload,aggregate_id,attempts,last_error,errors,created_at,failed_at)
+             VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9)",
+            dead_letter.id,
+            &dead_letter.command_type,
+            dead_letter.payload,
+            dead_letter.aggregate_id,
+            dead_letter.attempts,
+            &dead_letter.last_error,
+            dead_letter.errors,
+            dead_letter.created_at,
+            dead_letter.failed_at,
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_dead_letters(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<DeadLetter>, RepositoryError> {
+        Ok(sqlx::query_as!(
+            DeadLetter,
+            "SELECT id,command_type,payload,aggregate_id,attempts,last_error,errors,created_at,failed_at
+             FROM command_dead_letters ORDER BY failed_at DESC LIMIT $1 OFFSET $2",
+            limit,
+            offset,
+        )
+        .fetch_all(&self.db)
+        .await?)
+    }
+
+    async fn find_dead_letter(&self, id: Uuid) -> Result<Option<DeadLetter>, RepositoryError> {
+        Ok(sqlx::query_as!(
+            DeadLetter,
+            "SELECT id,command_type,payload,aggregate_id,attempts,last_error,errors,created_at,failed_at
+             FROM command_dead_letters WHERE id = $1",
+            id,
+        )
+        .fetch_optional(&self.db)
+        .await?)
+    }
+
+    async fn delete_dead_letter(&self, id: Uuid) -> Result<(), RepositoryError> {
+        let result = sqlx::query!("DELETE FROM command_dead_letters WHERE id = $1", id)
+            .execute(&self.db)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn claim_dead_letter(&self, id: Uuid) -> Result<PgDeadLetterClaim, RepositoryError> {
+        let mut tx = self.db.begin().await?;
+        let dead_letter = sqlx::query_as!(
+            DeadLetter,
+            "SELECT id,command_type,payload,aggregate_id,attempts,last_error,errors,created_at,failed_at
+             FROM command_dead_letters WHERE id = $1 FOR UPDATE SKIP LOCKED",
+            id,
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        match dead_letter {
+            Some(dead_letter) => Ok(PgDeadLetterClaim { tx, dead_letter }),
+            None if self.find_dead_letter(id).await?.is_some() => Err(RepositoryError::Locked),
+            None => Err(RepositoryError::NotFound),
+        }
+    }
+}
+
+/// A [`DeadLetterClaim`] holding the row lock of its dead letter in an open
+/// transaction, which other claims skip. A crash mid-replay leaves the dead
+/// letter as it was.
+pub struct PgDeadLetterClaim {
+    tx: Transaction<'static, Postgres>,
+    dead_letter: DeadLetter,
+}
+
+#[async_trait]
+impl DeadLetterClaim for PgDeadLetterClaim {
+    fn dead_letter(&self) -> &DeadLetter {
+        &self.dead_letter
+    }
+
+    async fn complete(self) -> Result<(), RepositoryError> {
+        let mut tx = self.tx;
+        sqlx::query!(
+            "DELETE FROM command_dead_letters WHERE id = $1",
+            self.dead_letter.id
+        )
+        .execute(&mut *tx)
+        .await?;
+        Ok(tx.commit().await?)
+    }
+
+    async fn fail(self, errors: Vec<String>) -> Result<(), RepositoryError> {
+        let mut tx = self.tx;
+        for error in errors {
+            record_attempt(&mut tx, self.dead_letter.id, &error).await?;
+        }
+        Ok(tx.commit().await?)
+    }
+}
+
+async fn record_attempt(
+    db: &mut PgConnection,
+    id: Uuid,
+    error: &str,
+) -> Result<(), RepositoryError> {
+    sqlx::query!(
+        "UPDATE command_dead_letters SET attempts = attempts + 1, last_error = $2,
+             failed_at = now(),
+             errors = errors || jsonb_build_array(jsonb_build_object(
+                 'attempt', attempts + 1, 'error', $2::TEXT, 'at', now()))
+         WHERE id = $1",
+        id,
+        error,
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use sqlx::postgres::PgListener;
+use tracing::{error, warn};
+
+use crate::{repositories::RepositoryError, EventBus, PostgreSQL};
+
+use super::postgres::EVENTS_CHANNEL;
+
+/// Stored events read per query while catching up.
+const PAGE: i64 = 100;
+
+/// How long the listener waits for a notification before reading anyway.
+const POLL: Duration = Duration::from_secs(1);
+
+/// Publishes the events any process commits on an [`EventBus`]. Every
+/// transaction saving events sends a `NOTIFY` as it commits, the listener
+/// then reads the events after the last one it published in feed order,
+/// so a missed notification only delays them until the next one. Events
+/// are held back while an older transaction runs, which need not notify
+/// when it ends, so the listener also reads every [`POLL`].
+pub struct PgEventListener {
+    repo: PostgreSQL,
+    events: EventBus,
+}
+
+impl PgEventListener {
+    pub fn new(repo: PostgreSQL, events: EventBus) -> Self {
+        Self { repo, events }
+    }
+
+    /// Publishes the events committed from now on, reconnecting after
+    /// errors.
+    pub async fn run(self) {
+        let mut last = loop {
+            match self.repo.last_event_position().await {
+                Ok(last) => break last,
+                Err(e) => self.failed(e).await,
+            }
+        };
+        loop {
+            if let Err(e) = self.listen(&mut last).await {
+                self.failed(e).await;
+            }
+        }
+    }
+
+    async fn listen(&self, last: &mut (i64, i64)) -> Result<(), RepositoryError> {
+        let mut listener = PgListener::connect_with(&self.repo.db).await?;
+        listener.listen(EVENTS_CHANNEL).await?;
+        loop {
+            self.publish_after(last).await?;
+            // Read again on a notification or after `POLL`, whichever comes first.
+            if let Ok(received) = tokio::time::timeout(POLL, listener.try_recv()).await {
+                // `None` after a reconnect, events may have been committed meanwhile.
+                if received?.is_none() {
+                    warn!("Event listener reconnected");
+                }
+            }
+        }
+    }
+
+    async fn failed(&self, e: RepositoryError) {
+        error!("Event listener failed: {}", e);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+
+    async fn publish_after(&self, last: &mut (i64, i64)) -> Result<(), RepositoryError> {
+        loop {
+            let page = self.repo.find_events_after_position(*last, PAGE).await?;
+            let full = page.len() as i64 == PAGE;
+            for event in page {
+                *last = event.position();
+                self.events.publish(event);
+            }
+            if !full {
+                return Ok(());
+            }
+        }
+    }
+}
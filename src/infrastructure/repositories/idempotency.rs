@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use axum::async_trait;
+
+use crate::{
+    models::IdempotencyRecord,
+    repositories::{IdempotencyRepository, RepositoryError},
+    PostgreSQL,
+};
+
+#[async_trait]
+impl IdempotencyRepository for PostgreSQL {
+    async fn claim_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        request_hash: &str,
+        ttl: Duration,
+        lease: Duration,
+    ) -> Result<Option<IdempotencyRecord>, RepositoryError> {
+        // Expired keys, and those whose request never finished within its
+        // lease, are taken over as if they had never been used.
+        let claimed = sqlx::query!(
+            "INSERT INTO idempotency_keys (scope,key,request_hash,leased_until)
+             VALUES ($1,$2,$3,now() + make_interval(secs => $5))
+             ON CONFLICT (scope,key) DO UPDATE
+             SET request_hash = EXCLUDED.request_hash, response = NULL,
+                 leased_until = EXCLUDED.leased_until, created_at = now()
+             WHERE idempotency_keys.created_at <= now() - make_interval(secs => $4)
+                 OR (idempotency_keys.response IS NULL
+                     AND (idempotency_keys.leased_until IS NULL
+                          OR idempotency_keys.leased_until <= now()))
+             RETURNING key",
+            scope,
+            key,
+            request_hash,
+            ttl.as_secs_f64(),
+            lease.as_secs_f64(),
+        )
+        .fetch_optional(&self.db)
+        .await?;
+
+        if claimed.is_some() {
+            return Ok(None);
+        }
+
+        Ok(sqlx::query_as!(
+            IdempotencyRecord,
+            "SELECT scope,key,request_hash,response
+             FROM idempotency_keys WHERE scope = $1 AND key = $2",
+            scope,
+            key,
+        )
+        .fetch_optional(&self.db)
+        .await?)
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        response: &[u8],
+    ) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            "UPDATE idempotency_keys SET response = $3, leased_until = NULL
+             WHERE scope = $1 AND key = $2",
+            scope,
+            key,
+            response,
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    async fn release_idempotency_key(&self, scope: &str, key: &str) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            "DELETE FROM idempotency_keys WHERE scope = $1 AND key = $2",
+            scope,
+            key
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,424 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    ops::Bound,
+    sync::{Arc, Mutex},
+};
+
+use axum::async_trait;
+use tokio::sync::OwnedMutexGuard;
+use uuid::Uuid;
+
+use crate::{
+    events::{StoredEvent, UserCreated, UserDeleted, UserUpdated},
+    models::User,
+    repositories::{page_size, RepositoryError, Transactional, UnitOfWork, UserRepository},
+    EventBus,
+};
+
+#[derive(Clone, Debug, Default)]
+struct State {
+    users: BTreeMap<Uuid, User>,
+    deleted: HashSet<Uuid>,
+    events: Vec<StoredEvent>,
+    /// Bumped by every committed write.
+    revision: u64,
+}
+
+impl State {
+    /// Mirrors the unique constraints of the SQL schemas, deleted users
+    /// included.
+    fn check_unique(&self, user: &User) -> Result<(), RepositoryError> {
+        for other in self.users.values().filter(|other| other.id != user.id) {
+            if other.username == user.username {
+                return Err(RepositoryError::Duplicate("username".into()));
+            }
+            if other.email == user.email {
+                return Err(RepositoryError::Duplicate("email".into()));
+            }
+        }
+        Ok(())
+    }
+
+    fn find_user_by_id(&self, id: Uuid) -> Option<User> {
+        self.users
+            .get(&id)
+            .filter(|_| !self.deleted.contains(&id))
+            .cloned()
+    }
+
+    fn save_user(&mut self, user: User) -> Result<StoredEvent, RepositoryError> {
+        if self.users.contains_key(&user.id) {
+            return Err(RepositoryError::Duplicate("id".into()));
+        }
+        self.check_unique(&user)?;
+
+        let event = UserCreated {
+            id: user.id,
+            username: user.username.clone(),
+            email: user.email.clone(),
+        };
+        self.users.insert(user.id, user);
+        Ok(self.save_event(StoredEvent::new(event.id, &event)))
+    }
+
+    fn save_users(&mut self, users: Vec<User>) -> Result<Vec<StoredEvent>, RepositoryError> {
+        let mut events = Vec::new();
+        for user in users {
+            match self.save_user(user) {
+                Ok(event) => events.push(event),
+                Err(RepositoryError::Duplicate(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(events)
+    }
+
+    fn update_user(
+        &mut self,
+        user: User,
+        expected_version: Option<i64>,
+    ) -> Result<(User, StoredEvent), RepositoryError> {
+        if self.deleted.contains(&user.id) {
+            return Err(RepositoryError::NotFound);
+        }
+        self.check_unique(&user)?;
+        let current = self
+            .users
+            .get_mut(&user.id)
+            .ok_or(RepositoryError::NotFound)?;
+        if let Some(expected) = expected_version.filter(|&v| v != current.version) {
+            return Err(RepositoryError::Conflict {
+                expected,
+                actual: current.version,
+            });
+        }
+
+        current.username = user.username;
+        current.email = user.email;
+        current.version += 1;
+        let updated = current.clone();
+
+        let event = UserUpdated {
+            id: updated.id,
+            username: updated.username.clone(),
+            email: updated.email.clone(),
+            version: updated.version,
+        };
+        Ok((updated, self.save_event(StoredEvent::new(user.id, &event))))
+    }
+
+    fn delete_user(
+        &mut self,
+        id: Uuid,
+        expected_version: Option<i64>,
+    ) -> Result<StoredEvent, RepositoryError> {
+        let current = self
+            .users
+            .get_mut(&id)
+            .filter(|_| !self.deleted.contains(&id))
+            .ok_or(RepositoryError::NotFound)?;
+        if let Some(expected) = expected_version.filter(|&v| v != current.version) {
+            return Err(RepositoryError::Conflict {
+                expected,
+                actual: current.version,
+            });
+        }
+
+        current.version += 1;
+        let event = UserDeleted {
+            id,
+            version: current.version,
+        };
+        self.deleted.insert(id);
+        Ok(self.save_event(StoredEvent::new(id, &event)))
+    }
+
+    fn list_users(&self, after: Option<Uuid>, limit: i64) -> Vec<User> {
+        let start = after.map_or(Bound::Unbounded, Bound::Excluded);
+        self.users
+            .range((start, Bound::Unbounded))
+            .filter(|(id, _)| !self.deleted.contains(id))
+            .take(page_size(limit) as usize)
+            .map(|(_, user)| user.clone())
+            .collect()
+    }
+
+    fn save_event(&mut self, mut event: StoredEvent) -> StoredEvent {
+        event.sequence = self.events.len() as i64 + 1;
+        self.events.push(event.clone());
+        event
+    }
+
+    fn find_events_by_aggregate_id(&self, id: Uuid) -> Vec<StoredEvent> {
+        self.events
+            .iter()
+            .filter(|event| event.aggregate_id == id)
+            .cloned()
+            .collect()
+    }
+
+    /// Events are kept in commit order, those after `after` or all of them
+    /// when it is not stored.
+    fn find_events_after(&self, after: Uuid, limit: i64) -> Vec<StoredEvent> {
+        let start = self
+            .events
+            .iter()
+            .position(|event| event.id == after)
+            .map_or(0, |position| position + 1);
+        self.events
+            .iter()
+            .skip(start)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Repositories kept in process memory, for tests and trying things out
+/// without a database.
+#[derive(Clone, Debug, Default)]
+pub struct InMemory {
+    state: Arc<Mutex<State>>,
+    /// Held by the open unit of work.
+    units: Arc<tokio::sync::Mutex<()>>,
+    events: Option<EventBus>,
+}
+
+impl InMemory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_event_bus(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    fn read<T>(&self, f: impl FnOnce(&State) -> T) -> T {
+        f(&self.state.lock().unwrap())
+    }
+
+    fn write<T>(
+        &self,
+        f: impl FnOnce(&mut State) -> Result<T, RepositoryError>,
+    ) -> Result<T, RepositoryError> {
+        let mut state = self.state.lock().unwrap();
+        let written = f(&mut state)?;
+        state.revision += 1;
+        Ok(written)
+    }
+
+    fn publish(&self, event: StoredEvent) {
+        if let Some(events) = &self.events {
+            events.publish(event);
+        }
+    }
+}
+
+#[async_trait]
+impl Transactional for InMemory {
+    type UnitOfWork = InMemoryUnitOfWork;
+
+    /// Units of work run one at a time, like serializable transactions,
+    /// each on a snapshot of the store. Plain repository calls do not wait
+    /// for them: reads see the last commit, and a write landing while a
+    /// unit of work is open makes its commit fail with
+    /// [`RepositoryError::Serialization`].
+    async fn begin(&self) -> Result<InMemoryUnitOfWork, RepositoryError> {
+        let turn = self.units.clone().lock_owned().await;
+        Ok(InMemoryUnitOfWork {
+            working: Mutex::new(self.read(State::clone)),
+            pending: Mutex::new(Vec::new()),
+            store: self.state.clone(),
+            turn,
+            events: self.events.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl UserRepository for InMemory {
+    async fn save_user(&self, user: User) -> Result<(), RepositoryError> {
+        let event = self.write(|state| state.save_user(user))?;
+        self.publish(event);
+        Ok(())
+    }
+
+    async fn save_users(&self, users: Vec<User>) -> Result<Vec<Uuid>, RepositoryError> {
+        let events = self.write(|state| state.save_users(users))?;
+        let created = events.iter().map(|event| event.aggregate_id).collect();
+        for event in events {
+            self.publish(event);
+        }
+        Ok(created)
+    }
+
+    async fn update_user(
+        &self,
+        user: User,
+        expected_version: Option<i64>,
+    ) -> Result<User, RepositoryError> {
+        let (updated, event) = self.write(|state| state.update_user(user, expected_version))?;
+        self.publish(event);
+        Ok(updated)
+    }
+
+    async fn delete_user(
+        &self,
+        id: Uuid,
+        expected_version: Option<i64>,
+    ) -> Result<(), RepositoryError> {
+        let event = self.write(|state| state.delete_user(id, expected_version))?;
+        self.publish(event);
+        Ok(())
+    }
+
+    async fn save_event(&self, event: StoredEvent) -> Result<(), RepositoryError> {
+        let event = self.write(|state| Ok(state.save_event(event)))?;
+        self.publish(event);
+        Ok(())
+    }
+
+    async fn list_users(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<User>, RepositoryError> {
+        Ok(self.read(|state| state.list_users(after, limit)))
+    }
+
+    async fn find_user_by_id(&self, id: Uuid) -> Result<Option<User>, RepositoryError> {
+        Ok(self.read(|state| state.find_user_by_id(id)))
+    }
+
+    async fn find_events_by_aggregate_id(
+        &self,
+        id: Uuid,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        Ok(self.read(|state| state.find_events_by_aggregate_id(id)))
+    }
+
+    async fn find_events_after(
+        &self,
+        after: Uuid,
+        limit: i64,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        Ok(self.read(|state| state.find_events_after(after, limit)))
+    }
+}
+
+/// Works on a copy of the store that replaces it on commit, unless the
+/// store was written to since the copy was taken.
+pub struct InMemoryUnitOfWork {
+    working: Mutex<State>,
+    pending: Mutex<Vec<StoredEvent>>,
+    store: Arc<Mutex<State>>,
+    turn: OwnedMutexGuard<()>,
+    events: Option<EventBus>,
+}
+
+impl InMemoryUnitOfWork {
+    fn with_state<T>(&self, f: impl FnOnce(&mut State) -> T) -> T {
+        f(&mut self.working.lock().unwrap())
+    }
+
+    fn record(&self, event: StoredEvent) {
+        self.pending.lock().unwrap().push(event);
+    }
+}
+
+#[async_trait]
+impl UnitOfWork for InMemoryUnitOfWork {
+    async fn commit(self) -> Result<(), RepositoryError> {
+        let mut working = self.working.into_inner().unwrap();
+        {
+            let mut store = self.store.lock().unwrap();
+            if store.revision != working.revision {
+                return Err(RepositoryError::Serialization);
+            }
+            working.revision += 1;
+            *store = working;
+        }
+        drop(self.turn);
+        if let Some(events) = &self.events {
+            for event in self.pending.into_inner().unwrap() {
+                events.publish(event);
+            }
+        }
+        Ok(())
+    }
+
+    async fn rollback(self) -> Result<(), RepositoryError> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserRepository for InMemoryUnitOfWork {
+    async fn save_user(&self, user: User) -> Result<(), RepositoryError> {
+        let event = self.with_state(|state| state.save_user(user))?;
+        self.record(event);
+        Ok(())
+    }
+
+    async fn save_users(&self, users: Vec<User>) -> Result<Vec<Uuid>, RepositoryError> {
+        let events = self.with_state(|state| state.save_users(users))?;
+        let created = events.iter().map(|event| event.aggregate_id).collect();
+        self.pending.lock().unwrap().extend(events);
+        Ok(created)
+    }
+
+    async fn update_user(
+        &self,
+        user: User,
+        expected_version: Option<i64>,
+    ) -> Result<User, RepositoryError> {
+        let (updated, event) =
+            self.with_state(|state| state.update_user(user, expected_version))?;
+        self.record(event);
+        Ok(updated)
+    }
+
+    async fn delete_user(
+        &self,
+        id: Uuid,
+        expected_version: Option<i64>,
+    ) -> Result<(), RepositoryError> {
+        let event = self.with_state(|state| state.delete_user(id, expected_version))?;
+        self.record(event);
+        Ok(())
+    }
+
+    async fn save_event(&self, event: StoredEvent) -> Result<(), RepositoryError> {
+        let event = self.with_state(|state| state.save_event(event));
+        self.record(event);
+        Ok(())
+    }
+
+    async fn list_users(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<User>, RepositoryError> {
+        Ok(self.with_state(|state| state.list_users(after, limit)))
+    }
+
+    async fn find_user_by_id(&self, id: Uuid) -> Result<Option<User>, RepositoryError> {
+        Ok(self.with_state(|state| state.find_user_by_id(id)))
+    }
+
+    async fn find_events_by_aggregate_id(
+        &self,
+        id: Uuid,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        Ok(self.with_state(|state| state.find_events_by_aggregate_id(id)))
+    }
+
+    async fn find_events_after(
+        &self,
+        after: Uuid,
+        limit: i64,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        Ok(self.with_state(|state| state.find_events_after(after, limit)))
+    }
+}
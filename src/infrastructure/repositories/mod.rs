@@ -1,3 +1,18 @@
+mod command_queue;
+mod dead_letters;
+mod event_listener;
+mod idempotency;
+mod in_memory;
 mod postgres;
+mod schedules;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod workflows;
 
-pub use postgres::PostgreSQL;
+pub use dead_letters::PgDeadLetterClaim;
+pub use event_listener::PgEventListener;
+pub use in_memory::{InMemory, InMemoryUnitOfWork};
+pub use postgres::{PgUnitOfWork, PostgreSQL};
+pub use schedules::PgScheduleClaim;
+#[cfg(feature = "sqlite")]
+pub use sqlite::{SQLite, SqliteUnitOfWork};
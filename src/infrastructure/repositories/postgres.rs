@@ -1,58 +1,489 @@
+use std::collections::HashSet;
+
 use axum::async_trait;
-use sqlx::{Pool, Postgres};
+use sqlx::{PgConnection, Pool, Postgres, Transaction};
+use tokio::sync::Mutex;
 use uuid::Uuid;
 
-use crate::{events::UserCreated, models, repositories::UserRepository};
+use crate::{
+    consistency,
+    db::PgPools,
+    events::{StoredEvent, UserCreated, UserDeleted, UserUpdated},
+    models,
+    repositories::{page_size, RepositoryError, Transactional, UnitOfWork, UserRepository},
+};
+
+/// The channel notified by every transaction saving events, see
+/// [`super::PgEventListener`].
+pub(super) const EVENTS_CHANNEL: &str = "events";
 
 #[derive(Clone, Debug)]
 pub struct PostgreSQL {
-    db: Pool<Postgres>,
+    pub(super) db: Pool<Postgres>,
+    reader: Option<Pool<Postgres>>,
 }
 
 impl PostgreSQL {
     pub fn new(db: Pool<Postgres>) -> Self {
-        Self { db }
+        Self { db, reader: None }
+    }
+
+    /// Serves user queries from the read replica if there is one, unless
+    /// the current request has to read its own writes.
+    pub fn from_pools(pools: PgPools) -> Self {
+        Self {
+            db: pools.writer,
+            reader: pools.reader,
+        }
+    }
+
+    fn read_pool(&self) -> &Pool<Postgres> {
+        match &self.reader {
+            Some(reader) if !consistency::reads_primary() => reader,
+            _ => &self.db,
+        }
+    }
+
+    /// Events after the [`StoredEvent::position`] `after`, read from the
+    /// primary. See [`find_events_after_position`].
+    pub(crate) async fn find_events_after_position(
+        &self,
+        after: (i64, i64),
+        limit: i64,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        find_events_after_position(&mut *self.db.acquire().await?, after, limit).await
+    }
+
+    /// The position of the last event that can be read now, events of
+    /// transactions still running come after it.
+    pub(crate) async fn last_event_position(&self) -> Result<(i64, i64), RepositoryError> {
+        let last = sqlx::query!(
+            r#"SELECT tx::text::bigint AS "transaction!", seq AS "sequence!" FROM events
+               WHERE tx < pg_snapshot_xmin(pg_current_snapshot())
+               ORDER BY tx DESC, seq DESC LIMIT 1"#
+        )
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(last.map_or((0, 0), |last| (last.transaction, last.sequence)))
+    }
+}
+
+#[async_trait]
+impl Transactional for PostgreSQL {
+    type UnitOfWork = PgUnitOfWork;
+
+    async fn begin(&self) -> Result<PgUnitOfWork, RepositoryError> {
+        Ok(PgUnitOfWork {
+            tx: Mutex::new(self.db.begin().await?),
+        })
     }
 }
 
+/// Writes on [`PostgreSQL`] outside a unit of work each run in their own
+/// transaction.
 #[async_trait]
 impl UserRepository for PostgreSQL {
-    async fn save_user(&self, user: models::User) -> Result<(), sqlx::Error> {
+    async fn save_user(&self, user: models::User) -> Result<(), RepositoryError> {
+        let uow = self.begin().await?;
+        uow.save_user(user).await?;
+        uow.commit().await
+    }
+
+    async fn save_users(&self, users: Vec<models::User>) -> Result<Vec<Uuid>, RepositoryError> {
+        let uow = self.begin().await?;
+        let created = uow.save_users(users).await?;
+        uow.commit().await?;
+        Ok(created)
+    }
+
+    async fn update_user(
+        &self,
+        user: models::User,
+        expected_version: Option<i64>,
+    ) -> Result<models::User, RepositoryError> {
+        let uow = self.begin().await?;
+        let updated = uow.update_user(user, expected_version).await?;
+        uow.commit().await?;
+        Ok(updated)
+    }
+
+    async fn delete_user(
+        &self,
+        id: Uuid,
+        expected_version: Option<i64>,
+    ) -> Result<(), RepositoryError> {
+        let uow = self.begin().await?;
+        uow.delete_user(id, expected_version).await?;
+        uow.commit().await
+    }
+
+    async fn save_event(&self, event: StoredEvent) -> Result<(), RepositoryError> {
+        let uow = self.begin().await?;
+        uow.save_event(event).await?;
+        uow.commit().await
+    }
+
+    async fn list_users(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<models::User>, RepositoryError> {
+        list_users(&mut *self.read_pool().acquire().await?, after, limit).await
+    }
+
+    async fn find_user_by_id(&self, id: Uuid) -> Result<Option<models::User>, RepositoryError> {
+        find_user_by_id(&mut *self.read_pool().acquire().await?, id).await
+    }
+
+    async fn find_events_by_aggregate_id(
+        &self,
+        id: Uuid,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        find_events_by_aggregate_id(&mut *self.read_pool().acquire().await?, id).await
+    }
+
+    async fn find_events_after(
+        &self,
+        after: Uuid,
+        limit: i64,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        find_events_after(&mut *self.read_pool().acquire().await?, after, limit).await
+    }
+}
+
+/// A [`UnitOfWork`] over one `sqlx` transaction. Events saved in it are
+/// published by the [`super::PgEventListener`] of every process once it
+/// commits.
+pub struct PgUnitOfWork {
+    tx: Mutex<Transaction<'static, Postgres>>,
+}
+
+impl PgUnitOfWork {
+    async fn record(&self, event: StoredEvent) -> Result<(), RepositoryError> {
+        save_event(&mut **self.tx.lock().await, &event).await
+    }
+}
+
+#[async_trait]
+impl UnitOfWork for PgUnitOfWork {
+    async fn commit(self) -> Result<(), RepositoryError> {
+        Ok(self.tx.into_inner().commit().await?)
+    }
+
+    async fn rollback(self) -> Result<(), RepositoryError> {
+        Ok(self.tx.into_inner().rollback().await?)
+    }
+}
+
+#[async_trait]
+impl UserRepository for PgUnitOfWork {
+    async fn save_user(&self, user: models::User) -> Result<(), RepositoryError> {
         let result = sqlx::query!(
-            "INSERT INTO users (id,username,email) VALUES ($1,$2,$3)",
+            "INSERT INTO users (id,username,email,version) VALUES ($1,$2,$3,$4)",
             user.id,
             &user.username,
             &user.email,
+            user.version,
         )
-        .execute(&self.db)
+        .execute(&mut **self.tx.lock().await)
         .await?;
-        if result.rows_affected() > 1 {
+        if result.rows_affected() == 1 {
             let event = UserCreated {
                 id: user.id,
                 username: user.username.clone(),
                 email: user.email.clone(),
             };
-            self.save_event(event).await?
+            self.record(StoredEvent::new(user.id, &event)).await?
         }
-        // TODO: Need to Create Custom Error
         Ok(())
     }
 
-    async fn save_event(&self, event: UserCreated) -> Result<(), sqlx::Error> {
-        let payload = serde_json::to_value(&event).unwrap();
-        sqlx::query!(
-            "INSERT INTO events (id,payload) VALUES ($1,$2)",
-            Uuid::now_v7(),
-            payload
+    async fn save_users(&self, users: Vec<models::User>) -> Result<Vec<Uuid>, RepositoryError> {
+        let mut tx = self.tx.lock().await;
+        let ids: Vec<_> = users.iter().map(|user| user.id).collect();
+        let usernames: Vec<_> = users.iter().map(|user| user.username.clone()).collect();
+        let emails: Vec<_> = users.iter().map(|user| user.email.clone()).collect();
+        let versions: Vec<_> = users.iter().map(|user| user.version).collect();
+        let inserted: HashSet<Uuid> = sqlx::query_scalar!(
+            "INSERT INTO users (id,username,email,version)
+             SELECT * FROM UNNEST($1::UUID[], $2::VARCHAR[], $3::VARCHAR[], $4::BIGINT[])
+             ON CONFLICT DO NOTHING
+             RETURNING id",
+            &ids,
+            &usernames,
+            &emails,
+            &versions,
+        )
+        .fetch_all(&mut **tx)
+        .await?
+        .into_iter()
+        .collect();
+
+        let events: Vec<_> = users
+            .into_iter()
+            .filter(|user| inserted.contains(&user.id))
+            .map(|user| {
+                let event = UserCreated {
+                    id: user.id,
+                    username: user.username,
+                    email: user.email,
+                };
+                StoredEvent::new(user.id, &event)
+            })
+            .collect();
+        save_events(&mut tx, &events).await?;
+        Ok(events.iter().map(|event| event.aggregate_id).collect())
+    }
+
+    async fn update_user(
+        &self,
+        user: models::User,
+        expected_version: Option<i64>,
+    ) -> Result<models::User, RepositoryError> {
+        let mut tx = self.tx.lock().await;
+        let updated = sqlx::query_as!(
+            models::User,
+            "UPDATE users SET username = $2, email = $3, version = version + 1
+             WHERE id = $1 AND deleted_at IS NULL AND ($4::BIGINT IS NULL OR version = $4)
+             RETURNING id, username, email, version",
+            user.id,
+            &user.username,
+            &user.email,
+            expected_version,
         )
-        .execute(&self.db)
+        .fetch_optional(&mut **tx)
         .await?;
-        Ok(())
+
+        let Some(updated) = updated else {
+            return match (find_user_by_id(&mut tx, user.id).await?, expected_version) {
+                (Some(current), Some(expected)) => Err(RepositoryError::Conflict {
+                    expected,
+                    actual: current.version,
+                }),
+                _ => Err(RepositoryError::NotFound),
+            };
+        };
+        drop(tx);
+
+        let event = UserUpdated {
+            id: updated.id,
+            username: updated.username.clone(),
+            email: updated.email.clone(),
+            version: updated.version,
+        };
+        self.record(StoredEvent::new(updated.id, &event)).await?;
+        Ok(updated)
     }
 
-    async fn find_user_by_id(&self, id: Uuid) -> Result<Option<models::User>, sqlx::Error> {
-        sqlx::query_as!(models::User, "SELECT * from users WHERE id = $1", id)
-            .fetch_optional(&self.db)
+    async fn delete_user(
+        &self,
+        id: Uuid,
+        expected_version: Option<i64>,
+    ) -> Result<(), RepositoryError> {
+        let mut tx = self.tx.lock().await;
+        let version = sqlx::query_scalar!(
+            "UPDATE users SET deleted_at = now(), version = version + 1
+             WHERE id = $1 AND deleted_at IS NULL AND ($2::BIGINT IS NULL OR version = $2)
+             RETURNING version",
+            id,
+            expected_version,
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        let Some(version) = version else {
+            return match (find_user_by_id(&mut tx, id).await?, expected_version) {
+                (Some(current), Some(expected)) => Err(RepositoryError::Conflict {
+                    expected,
+                    actual: current.version,
+                }),
+                _ => Err(RepositoryError::NotFound),
+            };
+        };
+        drop(tx);
+
+        self.record(StoredEvent::new(id, &UserDeleted { id, version }))
             .await
     }
+
+    async fn save_event(&self, event: StoredEvent) -> Result<(), RepositoryError> {
+        self.record(event).await
+    }
+
+    async fn list_users(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<models::User>, RepositoryError> {
+        list_users(&mut **self.tx.lock().await, after, limit).await
+    }
+
+    async fn find_user_by_id(&self, id: Uuid) -> Result<Option<models::User>, RepositoryError> {
+        find_user_by_id(&mut **self.tx.lock().await, id).await
+    }
+
+    async fn find_events_by_aggregate_id(
+        &self,
+        id: Uuid,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        find_events_by_aggregate_id(&mut **self.tx.lock().await, id).await
+    }
+
+    async fn find_events_after(
+        &self,
+        after: Uuid,
+        limit: i64,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        find_events_after(&mut **self.tx.lock().await, after, limit).await
+    }
+}
+
+/// Notifies the listeners, who are woken once the transaction commits.
+async fn notify_listeners(conn: &mut PgConnection) -> Result<(), RepositoryError> {
+    sqlx::query!(
+        "SELECT 1 AS notified FROM pg_notify($1, '')",
+        EVENTS_CHANNEL,
+    )
+    .fetch_one(conn)
+    .await?;
+    Ok(())
+}
+
+async fn save_event(conn: &mut PgConnection, event: &StoredEvent) -> Result<(), RepositoryError> {
+    notify_listeners(&mut *conn).await?;
+    sqlx::query!(
+        "INSERT INTO events (id,aggregate_id,event_type,version,payload) VALUES ($1,$2,$3,$4,$5)",
+        event.id,
+        event.aggregate_id,
+        &event.event_type,
+        event.version,
+        event.payload
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// One statement for the whole batch rather than a round trip per event.
+async fn save_events(
+    conn: &mut PgConnection,
+    events: &[StoredEvent],
+) -> Result<(), RepositoryError> {
+    let ids: Vec<_> = events.iter().map(|event| event.id).collect();
+    let aggregate_ids: Vec<_> = events.iter().map(|event| event.aggregate_id).collect();
+    let event_types: Vec<_> = events
+        .iter()
+        .map(|event| event.event_type.clone())
+        .collect();
+    let versions: Vec<_> = events.iter().map(|event| event.version).collect();
+    let payloads: Vec<_> = events.iter().map(|event| event.payload.clone()).collect();
+    if events.is_empty() {
+        return Ok(());
+    }
+    notify_listeners(&mut *conn).await?;
+    sqlx::query!(
+        "INSERT INTO events (id,aggregate_id,event_type,version,payload)
+         SELECT * FROM UNNEST($1::UUID[], $2::UUID[], $3::VARCHAR[], $4::INT[], $5::JSONB[])",
+        &ids,
+        &aggregate_ids,
+        &event_types,
+        &versions,
+        &payloads,
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn find_user_by_id(
+    conn: &mut PgConnection,
+    id: Uuid,
+) -> Result<Option<models::User>, RepositoryError> {
+    Ok(sqlx::query_as!(
+        models::User,
+        "SELECT id, username, email, version FROM users WHERE id = $1 AND deleted_at IS NULL",
+        id
+    )
+    .fetch_optional(conn)
+    .await?)
+}
+
+async fn list_users(
+    conn: &mut PgConnection,
+    after: Option<Uuid>,
+    limit: i64,
+) -> Result<Vec<models::User>, RepositoryError> {
+    Ok(sqlx::query_as!(
+        models::User,
+        "SELECT id, username, email, version FROM users
+         WHERE deleted_at IS NULL AND ($1::UUID IS NULL OR id > $1)
+         ORDER BY id LIMIT $2",
+        after,
+        page_size(limit)
+    )
+    .fetch_all(conn)
+    .await?)
+}
+
+async fn find_events_by_aggregate_id(
+    conn: &mut PgConnection,
+    id: Uuid,
+) -> Result<Vec<StoredEvent>, RepositoryError> {
+    Ok(sqlx::query_as!(
+        StoredEvent,
+        r#"SELECT id,aggregate_id,event_type,version,payload,seq AS sequence,
+                  tx::text::bigint AS "transaction!"
+           FROM events WHERE aggregate_id = $1 ORDER BY seq"#,
+        id
+    )
+    .fetch_all(conn)
+    .await?)
+}
+
+/// Events committed after the `after` event, all of them when it is not
+/// stored. See [`find_events_after_position`].
+async fn find_events_after(
+    conn: &mut PgConnection,
+    after: Uuid,
+    limit: i64,
+) -> Result<Vec<StoredEvent>, RepositoryError> {
+    Ok(sqlx::query_as!(
+        StoredEvent,
+        r#"SELECT id,aggregate_id,event_type,version,payload,seq AS sequence,
+                  tx::text::bigint AS "transaction!"
+           FROM events
+           WHERE (tx, seq) > (COALESCE((SELECT tx FROM events WHERE id = $1), '0'::xid8),
+                              COALESCE((SELECT seq FROM events WHERE id = $1), 0))
+             AND tx < pg_snapshot_xmin(pg_current_snapshot())
+           ORDER BY tx, seq LIMIT $2"#,
+        after,
+        limit
+    )
+    .fetch_all(conn)
+    .await?)
+}
+
+/// Events after the [`StoredEvent::position`] `after`. Only those saved
+/// by transactions older than any still running are read: every one of
+/// those has ended, so no event can later show up before the last one
+/// read, without making writers wait for each other.
+async fn find_events_after_position(
+    conn: &mut PgConnection,
+    (transaction, sequence): (i64, i64),
+    limit: i64,
+) -> Result<Vec<StoredEvent>, RepositoryError> {
+    Ok(sqlx::query_as!(
+        StoredEvent,
+        r#"SELECT id,aggregate_id,event_type,version,payload,seq AS sequence,
+                  tx::text::bigint AS "transaction!"
+           FROM events
+           WHERE (tx, seq) > ($1::bigint::text::xid8, $2)
+             AND tx < pg_snapshot_xmin(pg_current_snapshot())
+           ORDER BY tx, seq LIMIT $3"#,
+        transaction,
+        sequence,
+        limit
+    )
+    .fetch_all(conn)
+    .await?)
 }
@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    models::ScheduledCommand,
+    repositories::{RepositoryError, ScheduleClaim, ScheduleRepository},
+    PostgreSQL,
+};
+
+#[async_trait]
+impl ScheduleRepository for PostgreSQL {
+    type Claim = PgScheduleClaim;
+
+    async fn save_schedule(&self, schedule: ScheduledCommand) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            "INSERT INTO scheduled_commands
+                 (id,command_type,payload,aggregate_id,run_at,cron,last_run_at,created_at,
+                  leased_until)
+             VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9)",
+            schedule.id,
+            &schedule.command_type,
+            schedule.payload,
+            schedule.aggregate_id,
+            schedule.run_at,
+            schedule.cron,
+            schedule.last_run_at,
+            schedule.created_at,
+            schedule.leased_until,
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_schedules(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<ScheduledCommand>, RepositoryError> {
+        Ok(sqlx::query_as!(
+            ScheduledCommand,
+            "SELECT id,command_type,payload,aggregate_id,run_at,cron,last_run_at,created_at,
+                 leased_until
+             FROM scheduled_commands ORDER BY run_at, id LIMIT $1 OFFSET $2",
+            limit,
+            offset,
+        )
+        .fetch_all(&self.db)
+        .await?)
+    }
+
+    async fn find_schedule(&self, id: Uuid) -> Result<Option<ScheduledCommand>, RepositoryError> {
+        Ok(sqlx::query_as!(
+            ScheduledCommand,
+            "SELECT id,command_type,payload,aggregate_id,run_at,cron,last_run_at,created_at,
+                 leased_until
+             FROM scheduled_commands WHERE id = $1",
+            id,
+        )
+        .fetch_optional(&self.db)
+        .await?)
+    }
+
+    async fn delete_schedule(&self, id: Uuid) -> Result<(), RepositoryError> {
+        let result = sqlx::query!("DELETE FROM scheduled_commands WHERE id = $1", id)
+            .execute(&self.db)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn claim_due_schedules(
+        &self,
+        limit: i64,
+        lease: Duration,
+    ) -> Result<PgScheduleClaim, RepositoryError> {
+        let mut tx = self.db.begin().await?;
+        let mut schedules = sqlx::query_as!(
+            ScheduledCommand,
+            "SELECT id,command_type,payload,aggregate_id,run_at,cron,last_run_at,created_at,
+                 leased_until
+             FROM scheduled_commands
+             WHERE run_at <= now() AND (leased_until IS NULL OR leased_until <= now())
+             ORDER BY run_at LIMIT $1
+             FOR UPDATE SKIP LOCKED",
+            limit,
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let leased_until = sqlx::query_scalar!(
+            r#"SELECT now() + make_interval(secs => $1) AS "leased_until!""#,
+            lease.as_secs_f64(),
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        let ids: Vec<Uuid> = schedules.iter().map(|schedule| schedule.id).collect();
+        sqlx::query!(
+            "UPDATE scheduled_commands SET leased_until = $2 WHERE id = ANY($1)",
+            &ids,
+            leased_until,
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        for schedule in &mut schedules {
+            schedule.leased_until = Some(leased_until);
+        }
+        Ok(PgScheduleClaim {
+            db: self.db.clone(),
+            leased_until,
+            schedules,
+        })
+    }
+}
+
+/// A [`ScheduleClaim`] whose lease already committed. A claim taking one
+/// of its schedules after the lease ended sets another `leased_until`, so
+/// completions match this one and leave a schedule alone once it changed.
+pub struct PgScheduleClaim {
+    db: PgPool,
+    leased_until: DateTime<Utc>,
+    schedules: Vec<ScheduledCommand>,
+}
+
+#[async_trait]
+impl ScheduleClaim for PgScheduleClaim {
+    fn schedules(&self) -> &[ScheduledCommand] {
+        &self.schedules
+    }
+
+    async fn complete(
+        &self,
+        schedule: &ScheduledCommand,
+        fired_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        match schedule.next_run_after(fired_at) {
+            Some(next) => {
+                sqlx::query!(
+                    "UPDATE scheduled_commands
+                     SET run_at = $2, last_run_at = $3, leased_until = NULL
+                     WHERE id = $1 AND leased_until = $4",
+                    schedule.id,
+                    next,
+                    fired_at,
+                    self.leased_until,
+                )
+                .execute(&self.db)
+                .await?;
+            }
+            None => {
+                sqlx::query!(
+                    "DELETE FROM scheduled_commands WHERE id = $1 AND leased_until = $2",
+                    schedule.id,
+                    self.leased_until,
+                )
+                .execute(&self.db)
+                .await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,446 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::async_trait;
+use sqlx::{sqlite::SqliteRow, Pool, QueryBuilder, Row, Sqlite, SqliteConnection, Transaction};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::{
+    events::{StoredEvent, UserCreated, UserDeleted, UserUpdated},
+    models,
+    repositories::{page_size, RepositoryError, Transactional, UnitOfWork, UserRepository},
+    EventBus,
+};
+
+/// SQLite implementation of the user repository and event store. Queries
+/// are checked at runtime since the `query!` macros verify against the
+/// Postgres schema.
+#[derive(Clone, Debug)]
+pub struct SQLite {
+    db: Pool<Sqlite>,
+    events: Option<EventBus>,
+}
+
+impl SQLite {
+    pub fn new(db: Pool<Sqlite>) -> Self {
+        Self { db, events: None }
+    }
+
+    /// Publishes events once the transaction saving them commits.
+    pub fn with_event_bus(mut self, events: EventBus) -> Self {
+        self.events = Some(events);
+        self
+    }
+}
+
+#[async_trait]
+impl Transactional for SQLite {
+    type UnitOfWork = SqliteUnitOfWork;
+
+    async fn begin(&self) -> Result<SqliteUnitOfWork, RepositoryError> {
+        Ok(SqliteUnitOfWork {
+            tx: Mutex::new(self.db.begin().await?),
+            pending: Mutex::new(Vec::new()),
+            events: self.events.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl UserRepository for SQLite {
+    async fn save_user(&self, user: models::User) -> Result<(), RepositoryError> {
+        let uow = self.begin().await?;
+        uow.save_user(user).await?;
+        uow.commit().await
+    }
+
+    async fn save_users(&self, users: Vec<models::User>) -> Result<Vec<Uuid>, RepositoryError> {
+        let uow = self.begin().await?;
+        let created = uow.save_users(users).await?;
+        uow.commit().await?;
+        Ok(created)
+    }
+
+    async fn update_user(
+        &self,
+        user: models::User,
+        expected_version: Option<i64>,
+    ) -> Result<models::User, RepositoryError> {
+        let uow = self.begin().await?;
+        let updated = uow.update_user(user, expected_version).await?;
+        uow.commit().await?;
+        Ok(updated)
+    }
+
+    async fn delete_user(
+        &self,
+        id: Uuid,
+        expected_version: Option<i64>,
+    ) -> Result<(), RepositoryError> {
+        let uow = self.begin().await?;
+        uow.delete_user(id, expected_version).await?;
+        uow.commit().await
+    }
+
+    async fn save_event(&self, event: StoredEvent) -> Result<(), RepositoryError> {
+        let uow = self.begin().await?;
+        uow.save_event(event).await?;
+        uow.commit().await
+    }
+
+    async fn list_users(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<models::User>, RepositoryError> {
+        list_users(&mut *self.db.acquire().await?, after, limit).await
+    }
+
+    async fn find_user_by_id(&self, id: Uuid) -> Result<Option<models::User>, RepositoryError> {
+        find_user_by_id(&mut *self.db.acquire().await?, id).await
+    }
+
+    async fn find_events_by_aggregate_id(
+        &self,
+        id: Uuid,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        find_events_by_aggregate_id(&mut *self.db.acquire().await?, id).await
+    }
+
+    async fn find_events_after(
+        &self,
+        after: Uuid,
+        limit: i64,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        find_events_after(&mut *self.db.acquire().await?, after, limit).await
+    }
+}
+
+pub struct SqliteUnitOfWork {
+    tx: Mutex<Transaction<'static, Sqlite>>,
+    pending: Mutex<Vec<StoredEvent>>,
+    events: Option<EventBus>,
+}
+
+impl SqliteUnitOfWork {
+    async fn record(&self, mut event: StoredEvent) -> Result<(), RepositoryError> {
+        save_event(&mut **self.tx.lock().await, &mut event).await?;
+        self.pending.lock().await.push(event);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UnitOfWork for SqliteUnitOfWork {
+    async fn commit(self) -> Result<(), RepositoryError> {
+        self.tx.into_inner().commit().await?;
+        if let Some(events) = &self.events {
+            for event in self.pending.into_inner() {
+                events.publish(event);
+            }
+        }
+        Ok(())
+    }
+
+    async fn rollback(self) -> Result<(), RepositoryError> {
+        Ok(self.tx.into_inner().rollback().await?)
+    }
+}
+
+#[async_trait]
+impl UserRepository for SqliteUnitOfWork {
+    async fn save_user(&self, user: models::User) -> Result<(), RepositoryError> {
+        let result = sqlx::query("INSERT INTO users (id,username,email,version) VALUES (?,?,?,?)")
+            .bind(user.id)
+            .bind(&user.username)
+            .bind(&user.email)
+            .bind(user.version)
+            .execute(&mut **self.tx.lock().await)
+            .await?;
+        if result.rows_affected() == 1 {
+            let event = UserCreated {
+                id: user.id,
+                username: user.username.clone(),
+                email: user.email.clone(),
+            };
+            self.record(StoredEvent::new(user.id, &event)).await?
+        }
+        Ok(())
+    }
+
+    async fn save_users(&self, users: Vec<models::User>) -> Result<Vec<Uuid>, RepositoryError> {
+        if users.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut tx = self.tx.lock().await;
+        let inserted: HashSet<Uuid> =
+            QueryBuilder::new("INSERT OR IGNORE INTO users (id,username,email,version) ")
+                .push_values(&users, |mut row, user| {
+                    row.push_bind(user.id)
+                        .push_bind(&user.username)
+                        .push_bind(&user.email)
+                        .push_bind(user.version);
+                })
+                .push(" RETURNING id")
+                .build_query_scalar()
+                .fetch_all(&mut **tx)
+                .await?
+                .into_iter()
+                .collect();
+
+        let mut events: Vec<_> = users
+            .into_iter()
+            .filter(|user| inserted.contains(&user.id))
+            .map(|user| {
+                let event = UserCreated {
+                    id: user.id,
+                    username: user.username,
+                    email: user.email,
+                };
+                StoredEvent::new(user.id, &event)
+            })
+            .collect();
+        save_events(&mut tx, &mut events).await?;
+        drop(tx);
+
+        let created = events.iter().map(|event| event.aggregate_id).collect();
+        self.pending.lock().await.extend(events);
+        Ok(created)
+    }
+
+    async fn update_user(
+        &self,
+        user: models::User,
+        expected_version: Option<i64>,
+    ) -> Result<models::User, RepositoryError> {
+        let mut tx = self.tx.lock().await;
+        let updated = sqlx::query(
+            "UPDATE users SET username = ?2, email = ?3, version = version + 1
+             WHERE id = ?1 AND deleted_at IS NULL AND (?4 IS NULL OR version = ?4)
+             RETURNING id, username, email, version",
+        )
+        .bind(user.id)
+        .bind(&user.username)
+        .bind(&user.email)
+        .bind(expected_version)
+        .fetch_optional(&mut **tx)
+        .await?
+        .map(|row| user_from_row(&row))
+        .transpose()?;
+
+        let Some(updated) = updated else {
+            return match (find_user_by_id(&mut tx, user.id).await?, expected_version) {
+                (Some(current), Some(expected)) => Err(RepositoryError::Conflict {
+                    expected,
+                    actual: current.version,
+                }),
+                _ => Err(RepositoryError::NotFound),
+            };
+        };
+        drop(tx);
+
+        let event = UserUpdated {
+            id: updated.id,
+            username: updated.username.clone(),
+            email: updated.email.clone(),
+            version: updated.version,
+        };
+        self.record(StoredEvent::new(updated.id, &event)).await?;
+        Ok(updated)
+    }
+
+    async fn delete_user(
+        &self,
+        id: Uuid,
+        expected_version: Option<i64>,
+    ) -> Result<(), RepositoryError> {
+        let mut tx = self.tx.lock().await;
+        let version: Option<i64> = sqlx::query_scalar(
+            "UPDATE users SET deleted_at = CURRENT_TIMESTAMP, version = version + 1
+             WHERE id = ?1 AND deleted_at IS NULL AND (?2 IS NULL OR version = ?2)
+             RETURNING version",
+        )
+        .bind(id)
+        .bind(expected_version)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        let Some(version) = version else {
+            return match (find_user_by_id(&mut tx, id).await?, expected_version) {
+                (Some(current), Some(expected)) => Err(RepositoryError::Conflict {
+                    expected,
+                    actual: current.version,
+                }),
+                _ => Err(RepositoryError::NotFound),
+            };
+        };
+        drop(tx);
+
+        self.record(StoredEvent::new(id, &UserDeleted { id, version }))
+            .await
+    }
+
+    async fn save_event(&self, event: StoredEvent) -> Result<(), RepositoryError> {
+        self.record(event).await
+    }
+
+    async fn list_users(
+        &self,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<models::User>, RepositoryError> {
+        list_users(&mut **self.tx.lock().await, after, limit).await
+    }
+
+    async fn find_user_by_id(&self, id: Uuid) -> Result<Option<models::User>, RepositoryError> {
+        find_user_by_id(&mut **self.tx.lock().await, id).await
+    }
+
+    async fn find_events_by_aggregate_id(
+        &self,
+        id: Uuid,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        find_events_by_aggregate_id(&mut **self.tx.lock().await, id).await
+    }
+
+    async fn find_events_after(
+        &self,
+        after: Uuid,
+        limit: i64,
+    ) -> Result<Vec<StoredEvent>, RepositoryError> {
+        find_events_after(&mut **self.tx.lock().await, after, limit).await
+    }
+}
+
+fn user_from_row(row: &SqliteRow) -> Result<models::User, sqlx::Error> {
+    Ok(models::User {
+        id: row.try_get("id")?,
+        username: row.try_get("username")?,
+        email: row.try_get("email")?,
+        version: row.try_get("version")?,
+    })
+}
+
+fn event_from_row(row: &SqliteRow) -> Result<StoredEvent, sqlx::Error> {
+    let payload: String = row.try_get("payload")?;
+    Ok(StoredEvent {
+        id: row.try_get("id")?,
+        aggregate_id: row.try_get("aggregate_id")?,
+        event_type: row.try_get("event_type")?,
+        version: row.try_get("version")?,
+        payload: serde_json::from_str(&payload).map_err(|e| sqlx::Error::Decode(e.into()))?,
+        sequence: row.try_get("sequence")?,
+        transaction: 0,
+    })
+}
+
+/// Sets the `sequence` of `event` to its rowid. Events are never deleted
+/// and writers take turns, so rowids follow the commit order.
+async fn save_event(
+    conn: &mut SqliteConnection,
+    event: &mut StoredEvent,
+) -> Result<(), RepositoryError> {
+    event.sequence = sqlx::query_scalar(
+        "INSERT INTO events (id,aggregate_id,event_type,version,payload) VALUES (?,?,?,?,?)
+         RETURNING rowid",
+    )
+    .bind(event.id)
+    .bind(event.aggregate_id)
+    .bind(&event.event_type)
+    .bind(event.version)
+    .bind(event.payload.to_string())
+    .fetch_one(conn)
+    .await?;
+    Ok(())
+}
+
+/// One multi-row `INSERT` for the whole batch, setting sequences like
+/// [`save_event`].
+async fn save_events(
+    conn: &mut SqliteConnection,
+    events: &mut [StoredEvent],
+) -> Result<(), RepositoryError> {
+    if events.is_empty() {
+        return Ok(());
+    }
+    let sequences: HashMap<Uuid, i64> =
+        QueryBuilder::new("INSERT INTO events (id,aggregate_id,event_type,version,payload) ")
+            .push_values(events.iter(), |mut row, event| {
+                row.push_bind(event.id)
+                    .push_bind(event.aggregate_id)
+                    .push_bind(&event.event_type)
+                    .push_bind(event.version)
+                    .push_bind(event.payload.to_string());
+            })
+            .push(" RETURNING id, rowid")
+            .build_query_as()
+            .fetch_all(conn)
+            .await?
+            .into_iter()
+            .collect();
+    for event in events {
+        event.sequence = sequences[&event.id];
+    }
+    Ok(())
+}
+
+async fn find_user_by_id(
+    conn: &mut SqliteConnection,
+    id: Uuid,
+) -> Result<Option<models::User>, RepositoryError> {
+    let row = sqlx::query(
+        "SELECT id, username, email, version FROM users WHERE id = ? AND deleted_at IS NULL",
+    )
+    .bind(id)
+    .fetch_optional(conn)
+    .await?;
+    Ok(row.map(|row| user_from_row(&row)).transpose()?)
+}
+
+async fn list_users(
+    conn: &mut SqliteConnection,
+    after: Option<Uuid>,
+    limit: i64,
+) -> Result<Vec<models::User>, RepositoryError> {
+    let rows = sqlx::query(
+        "SELECT id, username, email, version FROM users
+         WHERE deleted_at IS NULL AND (?1 IS NULL OR id > ?1)
+         ORDER BY id LIMIT ?2",
+    )
+    .bind(after)
+    .bind(page_size(limit))
+    .fetch_all(conn)
+    .await?;
+    Ok(rows.iter().map(user_from_row).collect::<Result<_, _>>()?)
+}
+
+async fn find_events_by_aggregate_id(
+    conn: &mut SqliteConnection,
+    id: Uuid,
+) -> Result<Vec<StoredEvent>, RepositoryError> {
+    let rows = sqlx::query(
+        "SELECT id,aggregate_id,event_type,version,payload,rowid AS sequence FROM events
+         WHERE aggregate_id = ? ORDER BY rowid",
+    )
+    .bind(id)
+    .fetch_all(conn)
+    .await?;
+    Ok(rows.iter().map(event_from_row).collect::<Result<_, _>>()?)
+}
+
+async fn find_events_after(
+    conn: &mut SqliteConnection,
+    after: Uuid,
+    limit: i64,
+) -> Result<Vec<StoredEvent>, RepositoryError> {
+    let rows = sqlx::query(
+        "SELECT id,aggregate_id,event_type,version,payload,rowid AS sequence FROM events
+         WHERE rowid > COALESCE((SELECT rowid FROM events WHERE id = ?), 0)
+         ORDER BY rowid LIMIT ?",
+    )
+    .bind(after)
+    .bind(limit)
+    .fetch_all(conn)
+    .await?;
+    Ok(rows.iter().map(event_from_row).collect::<Result<_, _>>()?)
+}
@@ -0,0 +1,180 @@
+use std::time::Duration;
+
+use axum::async_trait;
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::{
+    models::{Workflow, WorkflowStatus},
+    repositories::{RepositoryError, WorkflowRepository},
+    PostgreSQL,
+};
+
+#[async_trait]
+impl WorkflowRepository for PostgreSQL {
+    async fn start_workflow(
+        &self,
+        workflow: Workflow,
+        lease: Duration,
+    ) -> Result<bool, RepositoryError> {
+        let result = sqlx::query!(
+            "INSERT INTO workflows
+                 (id,name,correlation_id,status,step,context,history,error,created_at,updated_at,
+                  leased_until)
+             VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,now() + make_interval(secs => $11))
+             ON CONFLICT (name, correlation_id) DO NOTHING",
+            workflow.id,
+            &workflow.name,
+            workflow.correlation_id,
+            workflow.status.to_string(),
+            workflow.step,
+            workflow.context,
+            workflow.history,
+            workflow.error,
+            workflow.created_at,
+            workflow.updated_at,
+            lease.as_secs_f64(),
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn advance_workflow(
+        &self,
+        id: Uuid,
+        (from_status, from_step): (WorkflowStatus, i32),
+        status: WorkflowStatus,
+        step: i32,
+        entry: Value,
+        error: Option<&str>,
+    ) -> Result<(), RepositoryError> {
+        let result = sqlx::query!(
+            "UPDATE workflows SET status = $4, step = $5,
+                 history = history || jsonb_build_array($6::JSONB),
+                 error = COALESCE($7, error), updated_at = now()
+             WHERE id = $1 AND status = $2 AND step = $3",
+            id,
+            from_status.to_string(),
+            from_step,
+            status.to_string(),
+            step,
+            entry,
+            error,
+        )
+        .execute(&self.db)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn list_workflows(
+        &self,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Workflow>, RepositoryError> {
+        Ok(sqlx::query_as!(
+            Workflow,
+            "SELECT id,name,correlation_id,status AS \"status: _\",step,context,history,error,created_at,updated_at
+             FROM workflows ORDER BY id DESC LIMIT $1 OFFSET $2",
+            limit,
+            offset,
+        )
+        .fetch_all(&self.db)
+        .await?)
+    }
+
+    async fn find_workflow(&self, id: Uuid) -> Result<Option<Workflow>, RepositoryError> {
+        Ok(sqlx::query_as!(
+            Workflow,
+            "SELECT id,name,correlation_id,status AS \"status: _\",step,context,history,error,created_at,updated_at
+             FROM workflows WHERE id = $1",
+            id,
+        )
+        .fetch_optional(&self.db)
+        .await?)
+    }
+
+    async fn claim_unfinished_workflows(
+        &self,
+        names: &[&str],
+        limit: i64,
+        lease: Duration,
+    ) -> Result<Vec<Workflow>, RepositoryError> {
+        let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        let mut workflows = sqlx::query_as!(
+            Workflow,
+            "UPDATE workflows SET leased_until = now() + make_interval(secs => $5)
+             WHERE id IN (
+                 SELECT id FROM workflows
+                 WHERE name = ANY($1) AND status IN ($2, $3)
+                     AND (leased_until IS NULL OR leased_until <= now())
+                 ORDER BY id LIMIT $4
+                 FOR UPDATE SKIP LOCKED)
+             RETURNING id,name,correlation_id,status AS \"status: _\",step,context,history,error,created_at,
+                 updated_at",
+            &names,
+            WorkflowStatus::Running.to_string(),
+            WorkflowStatus::Compensating.to_string(),
+            limit,
+            lease.as_secs_f64(),
+        )
+        .fetch_all(&self.db)
+        .await?;
+        workflows.sort_by_key(|workflow| workflow.id);
+        Ok(workflows)
+    }
+
+    async fn workflow_checkpoint(&self, name: &str) -> Result<Option<(i64, i64)>, RepositoryError> {
+        let checkpoint = sqlx::query!(
+            "SELECT tx, seq FROM workflow_checkpoints WHERE name = $1",
+            name,
+        )
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(checkpoint.map(|checkpoint| (checkpoint.tx, checkpoint.seq)))
+    }
+
+    async fn save_workflow_checkpoint(
+        &self,
+        name: &str,
+        (transaction, sequence): (i64, i64),
+    ) -> Result<(), RepositoryError> {
+        sqlx::query!(
+            "INSERT INTO workflow_checkpoints (name, tx, seq) VALUES ($1, $2, $3)
+             ON CONFLICT (name) DO UPDATE SET tx = EXCLUDED.tx, seq = EXCLUDED.seq
+             WHERE (workflow_checkpoints.tx, workflow_checkpoints.seq)
+                 < (EXCLUDED.tx, EXCLUDED.seq)",
+            name,
+            transaction,
+            sequence,
+        )
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    async fn renew_workflow_lease(
+        &self,
+        id: Uuid,
+        (status, step): (WorkflowStatus, i32),
+        lease: Duration,
+    ) -> Result<(), RepositoryError> {
+        let result = sqlx::query!(
+            "UPDATE workflows SET leased_until = now() + make_interval(secs => $4)
+             WHERE id = $1 AND status = $2 AND step = $3",
+            id,
+            status.to_string(),
+            step,
+            lease.as_secs_f64(),
+        )
+        .execute(&self.db)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+}
@@ -3,19 +3,41 @@ mod domain;
 mod infrastructure;
 
 pub use application::commands;
+pub use application::consistency;
+pub use application::event_bus::EventBus;
+pub use application::event_feed::{event_feed, EventFilter, FeedError};
 pub use application::services;
+pub use application::workflows;
 /// ---
 pub use domain::events;
 pub use domain::models;
+pub use domain::{Command, Event, Model};
 
 pub use domain::repositories;
 pub use infrastructure::db;
+pub use infrastructure::http::auth::{
+    require_api_token, require_bearer, require_websocket_token, ApiTokens, BearerTokens,
+};
 pub use infrastructure::http::controllers;
-pub use infrastructure::http::router::router;
+pub use infrastructure::http::negotiation::{Accept, Format, Negotiated};
+pub use infrastructure::http::protocol_router::{Alpn, Predicate, ProtocolRouter};
+pub use infrastructure::http::read_your_writes::{ReadYourWritesLayer, READ_PRIMARY_UNTIL};
+pub use infrastructure::http::router::{admin_router, router, user_router, websocket_router};
 /// ---
 pub use infrastructure::http::routes::Api;
 pub use infrastructure::logger::init_logger;
 pub use infrastructure::proto;
-pub use infrastructure::repositories::PostgreSQL;
+pub use infrastructure::repositories::{
+    InMemory, InMemoryUnitOfWork, PgDeadLetterClaim, PgEventListener, PgScheduleClaim,
+    PgUnitOfWork, PostgreSQL,
+};
+#[cfg(feature = "sqlite")]
+pub use infrastructure::repositories::{SQLite, SqliteUnitOfWork};
 
+pub use infrastructure::grpc::connect;
+pub use infrastructure::grpc::services::connect_services as grpc_connect_services;
+pub use infrastructure::grpc::services::rest_services as grpc_rest_services;
 pub use infrastructure::grpc::services::services as grpc_services;
+pub use infrastructure::grpc::services::user_connect_services as grpc_user_connect_services;
+pub use infrastructure::grpc::services::user_rest_services as grpc_user_rest_services;
+pub use infrastructure::grpc::services::user_services as grpc_user_services;
@@ -1,10 +1,35 @@
-use axum::{extract::Request, http::header::CONTENT_TYPE};
+use axum::{
+    http::{header::CONTENT_TYPE, Version},
+    Router,
+};
+use std::time::Duration;
+
 use coqrs::{
-    commands::CommandHandler, db, grpc_services, init_logger, router, services::UserService,
-    PostgreSQL,
+    admin_router,
+    commands::{
+        command_bus, CommandBus, CommandBusConfig, CommandHandlers, CommandReceiver,
+        CommandScheduler, CommandWorker, DurableCommandQueue, DurableQueueConfig, DurableWorker,
+        RetryLayer, SchedulerConfig, TimeoutLayer, TracingLayer,
+    },
+    connect::is_connect,
+    controllers::TransferFormat,
+    db, grpc_connect_services, grpc_services, init_logger, router,
+    services::{LogMailer, MailService, UserService},
+    websocket_router,
+    workflows::{UserProvisioning, WorkflowConfig, WorkflowRunner},
+    BearerTokens, EventBus, Format, PgEventListener, PostgreSQL, Predicate, ProtocolRouter,
+    ReadYourWritesLayer,
 };
-use tokio::sync::mpsc;
-use tower::{make::Shared, steer::Steer};
+use tower::{make::Shared, Layer};
+
+/// The services behind each protocol.
+struct Backends {
+    rest: Router,
+    grpc: Router,
+    connect: Router,
+    /// Only with Postgres.
+    admin: Option<Router>,
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<(), anyhow::Error> {
@@ -12,37 +37,57 @@ async fn main() -> anyhow::Result<(), anyhow::Error> {
 
     init_logger();
 
-    // Create a channel for sending commands with a buffer size of 32
-    let (sender, receiver) = mpsc::channel(32);
-
-    let pool = db::pgpool_connections().await;
+    // Shard commands across COMMAND_WORKERS workers, each buffering COMMAND_QUEUE_CAPACITY
+    let config = CommandBusConfig::from_env();
+    let (bus, receiver) = command_bus(&config);
 
-    let user_service = UserService::new(PostgreSQL::new(pool.clone()), sender.clone());
+    // The DATABASE_URL scheme picks the backend
+    let database_url = std::env::var("DATABASE_URL").unwrap_or_default();
+    let backends = if database_url.starts_with("sqlite:") {
+        sqlite(&database_url, bus, receiver).await
+    } else {
+        postgres(&config, bus, receiver).await
+    };
 
-    let handler = CommandHandler::new(receiver);
-
-    tokio::spawn(handler.run(user_service.clone()));
-
-    let lb = Steer::new(
-        [
-            router(pool.clone(), sender.clone()),
-            grpc_services(pool.clone(), sender.clone()),
-        ],
-        |req: &Request, _services: &[_]| {
-            req.headers()
+    // First match wins, requests no backend speaks get a 415
+    let mut protocols = ProtocolRouter::new()
+        // tonic_web translates gRPC-Web, over HTTP/1.1 too
+        .route(
+            Predicate::content_type("application/grpc-web"),
+            backends.grpc.clone(),
+        )
+        .route(
+            Predicate::content_type("application/grpc").and(Predicate::version(Version::HTTP_2)),
+            backends.grpc,
+        )
+        // A Connect-Protocol-Version header, a Connect streaming content type, or a procedure path
+        .route(Predicate::custom(is_connect), backends.connect);
+    if let Some(admin) = backends.admin {
+        protocols = protocols.route(Predicate::path_prefix("/admin"), admin);
+    }
+    // Bodies the REST controllers negotiate or import, or none at all
+    let protocols = protocols.route(
+        (!Predicate::header(CONTENT_TYPE)).or(Predicate::custom(|request| {
+            request
+                .headers()
                 .get(CONTENT_TYPE)
-                .map(|content_type| content_type.as_bytes())
-                .filter(|content_type| content_type.starts_with(b"application/grpc"))
-                .map(|_| 1)
-                .unwrap_or(0)
-        },
+                .and_then(|content_type| content_type.to_str().ok())
+                .is_some_and(|content_type| {
+                    Format::from_media_type(content_type).is_some()
+                        || TransferFormat::from_media_type(content_type).is_some()
+                })
+        })),
+        backends.rest,
     );
 
     let listener = tokio::net::TcpListener::bind("[::]:80").await.unwrap();
 
     tracing::debug!("listening on {:?}", listener.local_addr().unwrap());
 
-    let server = axum::serve(listener, Shared::new(lb)).await;
+    // Clients that just sent a command read from the primary for a while
+    let protocols = ReadYourWritesLayer::from_env().layer(protocols);
+
+    let server = axum::serve(listener, Shared::new(protocols)).await;
 
     if let Err(err) = server {
         tracing::error!("server error: {:?}", err);
@@ -50,3 +95,166 @@ async fn main() -> anyhow::Result<(), anyhow::Error> {
 
     Ok(())
 }
+
+fn layered(handlers: CommandHandlers) -> CommandHandlers {
+    handlers
+        .layer(TracingLayer)
+        .layer(TimeoutLayer::new(Duration::from_secs(10)))
+        .layer(RetryLayer::new(3, Duration::from_millis(100)))
+}
+
+async fn postgres(
+    config: &CommandBusConfig,
+    bus: CommandBus,
+    receiver: CommandReceiver,
+) -> Backends {
+    // Queries use the DATABASE_READ_URL replica when set
+    let pools = db::pg_pools().await;
+    let pool = pools.writer.clone();
+
+    // Persist commands in Postgres when COMMAND_QUEUE=durable or COMMAND_OVERFLOW=spill
+    let durable = config
+        .uses_durable_queue()
+        .then(DurableQueueConfig::from_env);
+    let bus = match &durable {
+        Some(durable) => bus.with_durable_queue(DurableCommandQueue::new(
+            PostgreSQL::new(pool.clone()),
+            durable.clone(),
+        )),
+        None => bus,
+    };
+
+    // Events committed by any instance are published to in-process subscribers
+    let events = EventBus::default();
+    tokio::spawn(PgEventListener::new(PostgreSQL::new(pool.clone()), events.clone()).run());
+    let user_service = UserService::new(PostgreSQL::from_pools(pools.clone()), bus.clone());
+
+    let handlers = MailService::new(LogMailer).register(CommandHandlers::new());
+    let pipeline = layered(user_service.register(handlers)).build();
+
+    if let Some(durable) = durable {
+        tokio::spawn(
+            DurableWorker::new(PostgreSQL::new(pool.clone()), pipeline.clone(), durable).run(),
+        );
+    }
+
+    let decoders = pipeline.decoders.clone();
+
+    // Process managers, resuming the workflows a previous run left unfinished
+    tokio::spawn(
+        WorkflowRunner::new(PostgreSQL::new(pool.clone()), bus.clone(), events.clone())
+            .register(UserProvisioning)
+            .with_config(WorkflowConfig::from_env())
+            .run(),
+    );
+
+    tokio::spawn(
+        CommandScheduler::new(
+            PostgreSQL::new(pool.clone()),
+            bus.clone(),
+            decoders.clone(),
+            SchedulerConfig::from_env(),
+        )
+        .run(),
+    );
+
+    // Failed fire-and-forget commands are kept for inspection and replay
+    tokio::spawn(
+        CommandWorker::new(receiver, pipeline)
+            .with_dead_letters(PostgreSQL::new(pool.clone()))
+            .run(),
+    );
+
+    // Schedules, dead letters and workflows, for the bearer tokens in ADMIN_TOKENS
+    let admin_tokens = BearerTokens::from_env("ADMIN_TOKENS");
+    // The user routes, RPCs and /ws, for the bearer tokens in API_TOKENS if it is set
+    let api_tokens = BearerTokens::from_env("API_TOKENS");
+
+    // The UserService RPCs are served as REST at their google.api.http routes
+    let rest = router(
+        PostgreSQL::from_pools(pools.clone()),
+        bus.clone(),
+        decoders.clone(),
+        events.clone(),
+        api_tokens.clone(),
+        admin_tokens.clone(),
+    )
+    // Commands and events over one connection, behind the same layers as REST
+    .merge(websocket_router(
+        bus.clone(),
+        decoders.clone(),
+        events.clone(),
+        api_tokens.clone(),
+    ));
+
+    let admin = admin_router(
+        PostgreSQL::from_pools(pools.clone()),
+        bus.clone(),
+        decoders.clone(),
+        admin_tokens.clone(),
+    );
+
+    Backends {
+        rest,
+        grpc: grpc_services(
+            PostgreSQL::from_pools(pools.clone()),
+            bus.clone(),
+            decoders.clone(),
+            events.clone(),
+            api_tokens.clone(),
+            admin_tokens.clone(),
+        ),
+        connect: grpc_connect_services(
+            PostgreSQL::from_pools(pools),
+            bus,
+            decoders,
+            events,
+            api_tokens,
+            admin_tokens,
+        ),
+        admin: Some(admin),
+    }
+}
+
+/// Users only: the durable queue, dead letters, schedules and workflows
+/// need Postgres.
+#[cfg(feature = "sqlite")]
+async fn sqlite(url: &str, bus: CommandBus, receiver: CommandReceiver) -> Backends {
+    use coqrs::{grpc_user_connect_services, grpc_user_services, user_router, SQLite};
+
+    let events = EventBus::default();
+    let repo = SQLite::new(db::sqlite_connections(url).await).with_event_bus(events.clone());
+    let user_service = UserService::new(repo.clone(), bus.clone());
+    let pipeline = layered(user_service.register(CommandHandlers::new())).build();
+    let decoders = pipeline.decoders.clone();
+    tokio::spawn(CommandWorker::new(receiver, pipeline).run());
+
+    let api_tokens = BearerTokens::from_env("API_TOKENS");
+    Backends {
+        rest: user_router(
+            repo.clone(),
+            bus.clone(),
+            events.clone(),
+            api_tokens.clone(),
+        )
+        .merge(websocket_router(
+            bus.clone(),
+            decoders,
+            events.clone(),
+            api_tokens.clone(),
+        )),
+        grpc: grpc_user_services(
+            repo.clone(),
+            bus.clone(),
+            events.clone(),
+            api_tokens.clone(),
+        ),
+        connect: grpc_user_connect_services(repo, bus, events, api_tokens),
+        admin: None,
+    }
+}
+
+#[cfg(not(feature = "sqlite"))]
+async fn sqlite(_url: &str, _bus: CommandBus, _receiver: CommandReceiver) -> Backends {
+    panic!("DATABASE_URL is a sqlite URL but coqrs was built without the `sqlite` feature")
+}
@@ -0,0 +1,455 @@
+//! The command bus: dispatching and sending commands to their handlers
+//! through a worker, decoding commands by their registered name, the
+//! layers wrapping dispatch, the ordering of commands per aggregate, and
+//! what full queues do.
+
+use std::sync::{
+    atomic::{AtomicI64, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
+use axum::{
+    async_trait,
+    body::Body,
+    http::{header::RETRY_AFTER, Request, StatusCode},
+};
+use coqrs::{
+    commands::{
+        command_bus, CommandBus, CommandBusConfig, CommandDecoders, CommandError, CommandHandler,
+        CommandHandlers, CommandReceiver, CommandRequest, CommandWorker, DurableQueue,
+        OverflowPolicy, RetryLayer, TimeoutLayer,
+    },
+    grpc_user_services,
+    proto::{user_service_client::UserServiceClient, CreateUserRequest},
+    repositories::RepositoryError,
+    user_router, BearerTokens, Command, EventBus, InMemory,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{net::TcpListener, sync::mpsc};
+use tonic::Code;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Add {
+    n: i64,
+}
+
+impl Command for Add {
+    const NAME: &'static str = "Add";
+    type Output = i64;
+}
+
+/// Never registered.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Subtract {
+    n: i64,
+}
+
+impl Command for Subtract {
+    const NAME: &'static str = "Subtract";
+    type Output = i64;
+}
+
+/// Keeps a running total and reports every command it handles.
+struct Adder {
+    total: AtomicI64,
+    handled: mpsc::UnboundedSender<i64>,
+}
+
+#[async_trait]
+impl CommandHandler<Add> for Arc<Adder> {
+    async fn handle(&self, command: Add) -> Result<i64, CommandError> {
+        let total = self.total.fetch_add(command.n, Ordering::SeqCst) + command.n;
+        let _ = self.handled.send(command.n);
+        Ok(total)
+    }
+}
+
+/// Sleeps for `ms` milliseconds.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Sleep {
+    ms: u64,
+}
+
+impl Command for Sleep {
+    const NAME: &'static str = "Sleep";
+    type Output = ();
+}
+
+struct Sleeper;
+
+#[async_trait]
+impl CommandHandler<Sleep> for Sleeper {
+    async fn handle(&self, command: Sleep) -> Result<(), CommandError> {
+        tokio::time::sleep(Duration::from_millis(command.ms)).await;
+        Ok(())
+    }
+}
+
+/// Answers with the attempt it succeeded on.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Flake;
+
+impl Command for Flake {
+    const NAME: &'static str = "Flake";
+    type Output = usize;
+}
+
+/// Fails its first `failures` attempts with `error`.
+struct Flaky {
+    failures: usize,
+    error: fn() -> RepositoryError,
+    attempts: AtomicUsize,
+}
+
+#[async_trait]
+impl CommandHandler<Flake> for Arc<Flaky> {
+    async fn handle(&self, _: Flake) -> Result<usize, CommandError> {
+        let attempt = self.attempts.fetch_add(1, Ordering::SeqCst) + 1;
+        if attempt <= self.failures {
+            return Err((self.error)().into());
+        }
+        Ok(attempt)
+    }
+}
+
+fn flaky(failures: usize, error: fn() -> RepositoryError) -> Arc<Flaky> {
+    Arc::new(Flaky {
+        failures,
+        error,
+        attempts: AtomicUsize::new(0),
+    })
+}
+
+/// The `seq`th command for the aggregate `id`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Append {
+    id: Uuid,
+    seq: usize,
+}
+
+impl Command for Append {
+    const NAME: &'static str = "Append";
+    type Output = ();
+
+    fn aggregate_id(&self) -> Option<Uuid> {
+        Some(self.id)
+    }
+}
+
+/// Records the commands in the order they are handled, taking longer for
+/// earlier ones so any reordering would show, and the most it handled at
+/// once.
+#[derive(Default)]
+struct Log {
+    handled: Mutex<Vec<Append>>,
+    running: AtomicUsize,
+    most_running: AtomicUsize,
+}
+
+#[async_trait]
+impl CommandHandler<Append> for Arc<Log> {
+    async fn handle(&self, command: Append) -> Result<(), CommandError> {
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.most_running.fetch_max(running, Ordering::SeqCst);
+        let ms = 5u64.saturating_sub(command.seq as u64);
+        tokio::time::sleep(Duration::from_millis(ms)).await;
+        self.handled.lock().unwrap().push(command);
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// A bus with a worker running `handlers`, and their decoders.
+fn running(handlers: CommandHandlers) -> (CommandBus, CommandDecoders) {
+    let (bus, commands) = command_bus(&CommandBusConfig::default());
+    let pipeline = handlers.build();
+    let decoders = pipeline.decoders.clone();
+    tokio::spawn(CommandWorker::new(commands, pipeline).run());
+    (bus, decoders)
+}
+
+/// A bus with a worker running the [`Adder`], its decoders, and the
+/// commands it handles as they are handled.
+fn adder_bus() -> (CommandBus, CommandDecoders, mpsc::UnboundedReceiver<i64>) {
+    let (handled, receiver) = mpsc::unbounded_channel();
+    let adder = Arc::new(Adder {
+        total: AtomicI64::new(0),
+        handled,
+    });
+    let (bus, decoders) = running(CommandHandlers::new().register::<Add, _>(adder));
+    (bus, decoders, receiver)
+}
+
+#[tokio::test]
+async fn dispatch_returns_the_handler_output() {
+    let (bus, _, _) = adder_bus();
+
+    assert_eq!(bus.dispatch(Add { n: 2 }).await.unwrap(), 2);
+    assert_eq!(bus.dispatch(Add { n: 3 }).await.unwrap(), 5);
+}
+
+#[tokio::test]
+async fn sent_commands_are_handled_in_the_background() {
+    let (bus, _, mut handled) = adder_bus();
+
+    bus.send(Add { n: 7 }).await.unwrap();
+    let n = tokio::time::timeout(Duration::from_secs(5), handled.recv())
+        .await
+        .expect("the command was not handled");
+    assert_eq!(n, Some(7));
+}
+
+#[tokio::test]
+async fn commands_without_a_handler_are_refused() {
+    let (bus, _, _) = adder_bus();
+
+    let error = bus.dispatch(Subtract { n: 1 }).await.unwrap_err();
+    assert!(matches!(error, CommandError::Unregistered("Subtract")));
+}
+
+#[tokio::test]
+async fn commands_decode_by_their_registered_name() {
+    let (bus, decoders, _) = adder_bus();
+
+    let request = decoders.decode("Add", json!({ "n": 4 })).unwrap();
+    assert_eq!(request.name(), "Add");
+    assert_eq!(bus.dispatch_json(request).await.unwrap(), json!(4));
+
+    let error = decoders.decode("Subtract", json!({ "n": 4 })).err();
+    assert!(matches!(error, Some(CommandError::Unknown(name)) if name == "Subtract"));
+    let error = decoders.decode("Add", json!({ "n": "four" })).err();
+    assert!(matches!(error, Some(CommandError::Payload(_))));
+}
+
+#[tokio::test]
+async fn a_bus_without_a_worker_is_closed() {
+    let (bus, commands) = command_bus(&CommandBusConfig::default());
+    drop(commands);
+
+    let error = bus.dispatch(Add { n: 1 }).await.unwrap_err();
+    assert!(matches!(error, CommandError::Closed));
+    let error = bus
+        .send_request(CommandRequest::new(Add { n: 1 }))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, CommandError::Closed));
+}
+
+#[tokio::test]
+async fn slow_commands_time_out() {
+    let handlers = CommandHandlers::new()
+        .layer(TimeoutLayer::new(Duration::from_millis(50)))
+        .register(Sleeper);
+    let (bus, _) = running(handlers);
+
+    bus.dispatch(Sleep { ms: 1 }).await.unwrap();
+    let error = bus.dispatch(Sleep { ms: 1000 }).await.unwrap_err();
+    assert!(matches!(error, CommandError::Timeout));
+}
+
+#[tokio::test]
+async fn transient_failures_are_retried() {
+    let pool_timeout = || RepositoryError::Database(sqlx::Error::PoolTimedOut);
+    let recovering = flaky(2, pool_timeout);
+    let handlers = CommandHandlers::new()
+        .layer(RetryLayer::new(2, Duration::from_millis(1)))
+        .register::<Flake, _>(recovering);
+    let (bus, _) = running(handlers);
+    assert_eq!(bus.dispatch(Flake).await.unwrap(), 3);
+
+    let failing = flaky(usize::MAX, pool_timeout);
+    let handlers = CommandHandlers::new()
+        .layer(RetryLayer::new(2, Duration::from_millis(1)))
+        .register::<Flake, _>(failing.clone());
+    let (bus, _) = running(handlers);
+    let error = bus.dispatch(Flake).await.unwrap_err();
+    assert!(error.is_transient());
+    assert_eq!(failing.attempts.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn other_failures_are_not_retried() {
+    let missing = flaky(1, || RepositoryError::NotFound);
+    let handlers = CommandHandlers::new()
+        .layer(RetryLayer::new(3, Duration::from_millis(1)))
+        .register::<Flake, _>(missing.clone());
+    let (bus, _) = running(handlers);
+
+    let error = bus.dispatch(Flake).await.unwrap_err();
+    assert!(matches!(
+        error,
+        CommandError::Repository(RepositoryError::NotFound)
+    ));
+    assert_eq!(missing.attempts.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn timeouts_bound_every_retry_together() {
+    // The outer timeout covers the retries and their backoff.
+    let failing = flaky(usize::MAX, || {
+        RepositoryError::Database(sqlx::Error::PoolTimedOut)
+    });
+    let handlers = CommandHandlers::new()
+        .layer(TimeoutLayer::new(Duration::from_millis(50)))
+        .layer(RetryLayer::new(10, Duration::from_millis(20)))
+        .register::<Flake, _>(failing.clone());
+    let (bus, _) = running(handlers);
+
+    let error = bus.dispatch(Flake).await.unwrap_err();
+    assert!(matches!(error, CommandError::Timeout));
+    assert!(failing.attempts.load(Ordering::SeqCst) < 10);
+}
+
+#[tokio::test]
+async fn commands_for_one_aggregate_keep_their_order() {
+    let log = Arc::new(Log::default());
+    let (bus, _) = running(CommandHandlers::new().register::<Append, _>(log.clone()));
+
+    let aggregates: Vec<_> = (0..8).map(|_| Uuid::now_v7()).collect();
+    let senders: Vec<_> = aggregates
+        .iter()
+        .map(|&id| {
+            let bus = bus.clone();
+            tokio::spawn(async move {
+                for seq in 0..20 {
+                    bus.send(Append { id, seq }).await.unwrap();
+                }
+            })
+        })
+        .collect();
+    for sender in senders {
+        sender.await.unwrap();
+    }
+    // Queued behind the sends of its aggregate, so they are all handled once
+    // it answers.
+    for &id in &aggregates {
+        bus.dispatch(Append { id, seq: 20 }).await.unwrap();
+    }
+
+    let handled = log.handled.lock().unwrap();
+    assert_eq!(handled.len(), aggregates.len() * 21);
+    for id in aggregates {
+        let seqs: Vec<_> = handled
+            .iter()
+            .filter(|command| command.id == id)
+            .map(|command| command.seq)
+            .collect();
+        assert_eq!(seqs, (0..=20).collect::<Vec<_>>());
+    }
+    assert!(log.most_running.load(Ordering::SeqCst) > 1);
+}
+
+/// One shard holding one command, its receiver to keep with no worker
+/// taking from it.
+fn full_bus(overflow: OverflowPolicy) -> (CommandBus, CommandReceiver) {
+    let config = CommandBusConfig {
+        workers: 1,
+        capacity: 1,
+        overflow,
+        retry_after: Duration::from_secs(7),
+        ..Default::default()
+    };
+    command_bus(&config)
+}
+
+#[tokio::test]
+async fn full_queues_reject_with_a_retry_hint() {
+    for overflow in [
+        OverflowPolicy::Reject,
+        OverflowPolicy::Wait(Duration::from_millis(20)),
+        OverflowPolicy::Spill,
+    ] {
+        let (bus, _commands) = full_bus(overflow);
+        bus.send(Add { n: 1 }).await.unwrap();
+
+        let error = bus.send(Add { n: 2 }).await.unwrap_err();
+        assert!(
+            matches!(error, CommandError::Overloaded { retry_after } if retry_after.as_secs() == 7),
+            "{overflow:?}"
+        );
+        let depth = bus.queue_depth();
+        assert_eq!((depth.queued, depth.full_shards), (1, 1));
+        assert_eq!(bus.shard_metrics()[0].shed, 1);
+    }
+}
+
+/// Counts the commands pushed to it.
+#[derive(Debug, Default, Clone)]
+struct Spilled(Arc<Mutex<Vec<&'static str>>>);
+
+#[async_trait]
+impl DurableQueue for Spilled {
+    async fn push(&self, request: CommandRequest) -> Result<(), CommandError> {
+        self.0.lock().unwrap().push(request.name());
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn full_queues_spill_sent_commands_to_the_durable_queue() {
+    let spilled = Spilled::default();
+    let (bus, _commands) = full_bus(OverflowPolicy::Spill);
+    let bus = bus.with_durable_queue(spilled.clone());
+    bus.send(Add { n: 1 }).await.unwrap();
+
+    bus.send(Add { n: 2 }).await.unwrap();
+    assert_eq!(*spilled.0.lock().unwrap(), ["Add"]);
+
+    // Dispatched commands wait for an answer, they cannot be spilled.
+    let error = bus.dispatch(Add { n: 3 }).await.unwrap_err();
+    assert!(matches!(error, CommandError::Overloaded { .. }));
+}
+
+#[tokio::test]
+async fn overloaded_rest_requests_get_503_with_retry_after() {
+    let (bus, _commands) = full_bus(OverflowPolicy::Reject);
+    let app = user_router(
+        InMemory::new(),
+        bus,
+        EventBus::default(),
+        BearerTokens::default(),
+    );
+    let create = |name: &str| {
+        let body = json!({ "username": name, "email": format!("{name}@example.com") });
+        Request::post("/users")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(create("alice")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app.oneshot(create("bob")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()[RETRY_AFTER], "7");
+}
+
+#[tokio::test]
+async fn overloaded_rpcs_are_resource_exhausted_with_retry_after() {
+    let (bus, _commands) = full_bus(OverflowPolicy::Reject);
+    let app = grpc_user_services(
+        InMemory::new(),
+        bus,
+        EventBus::default(),
+        BearerTokens::default(),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let mut client = UserServiceClient::connect(format!("http://{address}"))
+        .await
+        .unwrap();
+    let create = |name: &str| CreateUserRequest {
+        username: name.into(),
+        email: format!("{name}@example.com"),
+    };
+
+    client.create_user(create("alice")).await.unwrap();
+    let status = client.create_user(create("bob")).await.unwrap_err();
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert_eq!(status.metadata().get("retry-after").unwrap(), "7");
+}
@@ -0,0 +1,78 @@
+//! Fixtures shared by the integration tests, each test crate using some.
+#![allow(dead_code)]
+
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, Request, StatusCode},
+    Router,
+};
+use coqrs::{
+    commands::{
+        command_bus, CommandBus, CommandBusConfig, CommandDecoders, CommandHandlers, CommandWorker,
+    },
+    models::User,
+    repositories::{UserRepository, UserStore},
+    services::UserService,
+    InMemory, PostgreSQL,
+};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+/// A new user named `name` with an `example.com` address.
+pub fn user(name: &str) -> User {
+    User {
+        id: Uuid::now_v7(),
+        username: name.into(),
+        email: format!("{name}@example.com"),
+        version: 1,
+    }
+}
+
+pub fn renamed(user: &User, name: &str) -> User {
+    User {
+        username: name.into(),
+        ..user.clone()
+    }
+}
+
+/// Saves a [`user`] named `name` in `repo`.
+pub async fn saved_user(repo: &InMemory, name: &str) -> User {
+    let user = user(name);
+    repo.save_user(user.clone()).await.unwrap();
+    user
+}
+
+/// A command bus whose worker runs the user commands against `repo`, and
+/// the decoders of those commands.
+pub fn running_bus<R: UserStore>(repo: &R) -> (CommandBus, CommandDecoders) {
+    let (bus, receiver) = command_bus(&CommandBusConfig::default());
+    let pipeline = UserService::new(repo.clone(), bus.clone())
+        .register(CommandHandlers::new())
+        .build();
+    let decoders = pipeline.decoders.clone();
+    tokio::spawn(CommandWorker::new(receiver, pipeline).run());
+    (bus, decoders)
+}
+
+/// The database of `DATABASE_URL`, `None` when it is not a Postgres URL so
+/// the tests needing it are skipped. A URL that cannot be reached fails
+/// them instead.
+pub async fn postgres() -> Option<PostgreSQL> {
+    let url = std::env::var("DATABASE_URL").ok()?;
+    if !url.starts_with("postgres") {
+        return None;
+    }
+    let pool = sqlx::PgPool::connect(&url)
+        .await
+        .unwrap_or_else(|e| panic!("DATABASE_URL is set but unreachable: {e}"));
+    Some(PostgreSQL::new(pool))
+}
+
+/// Sends `request` to `app` and collects the response.
+pub async fn call(app: &Router, request: Request<Body>) -> (StatusCode, HeaderMap, Bytes) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let (parts, body) = response.into_parts();
+    let bytes = body.collect().await.unwrap().to_bytes();
+    (parts.status, parts.headers, bytes)
+}
@@ -0,0 +1,406 @@
+//! The Connect protocol over the gRPC user service and the in-memory
+//! backend, and the streaming runtime the generated routes call.
+
+mod common;
+
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header::CONTENT_TYPE, StatusCode},
+    routing::post,
+    Router,
+};
+use common::{call, running_bus, saved_user};
+use coqrs::{
+    connect::{self, is_connect, Procedure},
+    grpc_user_connect_services,
+    proto::{GetUserRequest, GetUserResponse, ListUsersRequest, ListUsersResponse},
+    BearerTokens, EventBus, InMemory,
+};
+use futures_util::{stream, StreamExt};
+use prost::Message;
+use serde_json::{json, Value};
+use tonic::{Status, Streaming};
+use uuid::Uuid;
+
+fn app() -> (Router, InMemory) {
+    let repo = InMemory::new();
+    let (bus, _) = running_bus(&repo);
+    (
+        grpc_user_connect_services(
+            repo.clone(),
+            bus,
+            EventBus::default(),
+            BearerTokens::default(),
+        ),
+        repo,
+    )
+}
+
+fn rpc(path: &str, content_type: &str, body: impl Into<Body>) -> Request {
+    Request::post(path)
+        .header(CONTENT_TYPE, content_type)
+        .header(connect::PROTOCOL_VERSION, "1")
+        .body(body.into())
+        .unwrap()
+}
+
+fn enveloped(flags: u8, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![flags];
+    bytes.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+/// Splits a streaming body into its flags and payloads.
+fn envelopes(mut bytes: &[u8]) -> Vec<(u8, Vec<u8>)> {
+    let mut envelopes = Vec::new();
+    while !bytes.is_empty() {
+        let length = u32::from_be_bytes(bytes[1..5].try_into().unwrap()) as usize;
+        envelopes.push((bytes[0], bytes[5..5 + length].to_vec()));
+        bytes = &bytes[5 + length..];
+    }
+    envelopes
+}
+
+#[tokio::test]
+async fn unary_json_uses_proto3_json() {
+    let (app, repo) = app();
+    let alice = saved_user(&repo, "alice").await;
+
+    let body = json!({ "id": alice.id.to_string() }).to_string();
+    let (status, headers, body) = call(
+        &app,
+        rpc("/users.UserService/GetUser", "application/json", body),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[CONTENT_TYPE], "application/json");
+    assert_eq!(
+        serde_json::from_slice::<Value>(&body).unwrap(),
+        json!({
+            "id": alice.id.to_string(),
+            "username": "alice",
+            "email": "alice@example.com",
+            "version": "1",
+        })
+    );
+}
+
+#[tokio::test]
+async fn unary_proto_round_trips_messages() {
+    let (app, repo) = app();
+    let alice = saved_user(&repo, "alice").await;
+
+    let request = GetUserRequest {
+        id: alice.id.to_string(),
+    };
+    let (status, headers, body) = call(
+        &app,
+        rpc(
+            "/users.UserService/GetUser",
+            "application/proto",
+            request.encode_to_vec(),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[CONTENT_TYPE], "application/proto");
+    assert_eq!(GetUserResponse::decode(body).unwrap().username, "alice");
+}
+
+#[tokio::test]
+async fn commands_are_served_by_the_same_service() {
+    let (app, _) = app();
+    let body = json!({ "username": "alice", "email": "alice@example.com" }).to_string();
+    let (status, _, _) = call(
+        &app,
+        rpc("/users.UserService/CreateUser", "application/json", body),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut page = ListUsersResponse::default();
+    for _ in 0..50 {
+        let request = ListUsersRequest::default().encode_to_vec();
+        let (_, _, body) = call(
+            &app,
+            rpc("/users.UserService/ListUsers", "application/proto", request),
+        )
+        .await;
+        page = ListUsersResponse::decode(body).unwrap();
+        if !page.users.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(page.users[0].username, "alice");
+}
+
+#[tokio::test]
+async fn errors_name_their_code() {
+    let (app, _) = app();
+
+    let body = json!({ "id": Uuid::now_v7().to_string() }).to_string();
+    let (status, headers, body) = call(
+        &app,
+        rpc("/users.UserService/GetUser", "application/json", body),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(headers[CONTENT_TYPE], "application/json");
+    assert_eq!(
+        serde_json::from_slice::<Value>(&body).unwrap(),
+        json!({ "code": "not_found", "message": "User Not Found" })
+    );
+
+    let (status, _, body) = call(
+        &app,
+        rpc(
+            "/users.UserService/GetUser",
+            "application/json",
+            "{\"nope\": 1}",
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        serde_json::from_slice::<Value>(&body).unwrap()["code"],
+        "invalid_argument"
+    );
+
+    let mut request = rpc("/users.UserService/GetUser", "application/json", "{}");
+    request
+        .headers_mut()
+        .insert(connect::PROTOCOL_VERSION, "2".parse().unwrap());
+    let (status, _, _) = call(&app, request).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn unsupported_content_types_are_refused() {
+    let (app, _) = app();
+
+    for content_type in ["text/plain", "application/connect+json"] {
+        let (status, headers, _) =
+            call(&app, rpc("/users.UserService/GetUser", content_type, "{}")).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(
+            headers["accept-post"],
+            "application/json, application/proto"
+        );
+    }
+}
+
+#[test]
+fn connect_requests_are_recognized() {
+    let request = |path: &str, content_type: &str| {
+        Request::post(path)
+            .header(CONTENT_TYPE, content_type)
+            .body(())
+            .unwrap()
+    };
+
+    assert!(is_connect(&request(
+        "/users.UserService/GetUser",
+        "application/json"
+    )));
+    assert!(is_connect(&request(
+        "/users.UserService/GetUser",
+        "application/proto"
+    )));
+    assert!(is_connect(&request("/any", "application/connect+proto")));
+    assert!(is_connect(
+        &Request::post("/any")
+            .header(connect::PROTOCOL_VERSION, "1")
+            .body(())
+            .unwrap()
+    ));
+    assert!(!is_connect(&request("/users", "application/json")));
+    assert!(!is_connect(&request(
+        "/users.UserService/GetUser",
+        "application/grpc"
+    )));
+}
+
+static WATCH: Procedure = Procedure {
+    path: "/users.UserService/Watch",
+    input: ".users.GetUserRequest",
+    output: ".users.GetUserResponse",
+};
+
+static COUNT: Procedure = Procedure {
+    path: "/users.UserService/Count",
+    input: ".users.GetUserRequest",
+    output: ".users.ListUsersResponse",
+};
+
+/// A server streaming procedure answering with one message per username
+/// in the request id, then failing.
+fn streaming_app() -> Router {
+    Router::new()
+        .route(
+            WATCH.path,
+            post(|request: Request| {
+                connect::server_streaming(
+                    &WATCH,
+                    request,
+                    |request: tonic::Request<GetUserRequest>| async move {
+                        let users: Vec<_> = request
+                            .into_inner()
+                            .id
+                            .split(',')
+                            .map(|name| GetUserResponse {
+                                username: name.into(),
+                                version: 1,
+                                ..Default::default()
+                            })
+                            .collect();
+                        let messages = stream::iter(users.into_iter().map(Ok))
+                            .chain(stream::iter([Err(Status::aborted("done"))]));
+                        Ok(tonic::Response::new(messages))
+                    },
+                )
+            }),
+        )
+        .route(
+            COUNT.path,
+            post(|request: Request| {
+                connect::client_streaming(
+                    &COUNT,
+                    request,
+                    |request: tonic::Request<Streaming<GetUserRequest>>| async move {
+                        let mut messages = request.into_inner();
+                        let mut users = Vec::new();
+                        while let Some(message) = messages.next().await {
+                            users.push(GetUserResponse {
+                                id: message?.id,
+                                ..Default::default()
+                            });
+                        }
+                        Ok(tonic::Response::new(ListUsersResponse {
+                            users,
+                            next_after: None,
+                        }))
+                    },
+                )
+            }),
+        )
+}
+
+#[tokio::test]
+async fn server_streams_end_with_their_status() {
+    let app = streaming_app();
+    let request = json!({ "id": "alice,bob" }).to_string();
+
+    let (status, headers, body) = call(
+        &app,
+        rpc(
+            WATCH.path,
+            "application/connect+json",
+            enveloped(0, request.as_bytes()),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[CONTENT_TYPE], "application/connect+json");
+
+    let envelopes = envelopes(&body);
+    assert_eq!(envelopes.len(), 3);
+    let first: Value = serde_json::from_slice(&envelopes[0].1).unwrap();
+    assert_eq!(envelopes[0].0, 0);
+    assert_eq!(first["username"], "alice");
+    assert_eq!(first["version"], "1");
+    let end: Value = serde_json::from_slice(&envelopes[2].1).unwrap();
+    assert_eq!(envelopes[2].0, 2);
+    assert_eq!(
+        end,
+        json!({ "error": { "code": "aborted", "message": "done" } })
+    );
+}
+
+#[tokio::test]
+async fn client_streams_read_every_envelope() {
+    let app = streaming_app();
+    let mut body = Vec::new();
+    for id in ["a", "b", "c"] {
+        body.extend(enveloped(
+            0,
+            &GetUserRequest { id: id.into() }.encode_to_vec(),
+        ));
+    }
+
+    let (status, _, body) = call(&app, rpc(COUNT.path, "application/connect+proto", body)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let envelopes = envelopes(&body);
+    assert_eq!(envelopes.len(), 2);
+    let response = ListUsersResponse::decode(envelopes[0].1.as_slice()).unwrap();
+    let ids: Vec<_> = response.users.iter().map(|user| user.id.as_str()).collect();
+    assert_eq!(ids, ["a", "b", "c"]);
+    assert_eq!(envelopes[1], (2, b"{}".to_vec()));
+}
+
+#[tokio::test]
+async fn timeouts_end_calls_with_deadline_exceeded() {
+    static SLOW: Procedure = Procedure {
+        path: "/users.UserService/Slow",
+        input: ".users.GetUserRequest",
+        output: ".users.GetUserResponse",
+    };
+    let app = Router::new().route(
+        SLOW.path,
+        post(|request: Request| {
+            connect::unary(&SLOW, request, |_: tonic::Request<GetUserRequest>| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(tonic::Response::new(GetUserResponse::default()))
+            })
+        }),
+    );
+
+    let mut request = rpc(SLOW.path, "application/json", "{}");
+    request
+        .headers_mut()
+        .insert(connect::TIMEOUT_MS, "10".parse().unwrap());
+    let (status, _, body) = call(&app, request).await;
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(
+        serde_json::from_slice::<Value>(&body).unwrap()["code"],
+        "deadline_exceeded"
+    );
+}
+
+#[tokio::test]
+async fn malformed_timeouts_and_oversized_bodies_are_refused() {
+    let (app, _) = app();
+    let code = |body: &[u8]| serde_json::from_slice::<Value>(body).unwrap()["code"].clone();
+
+    for timeout in [
+        "18446744073709551615",
+        "99999999999",
+        "-1",
+        "1.5",
+        "",
+        "abc",
+    ] {
+        let mut request = rpc("/users.UserService/GetUser", "application/json", "{}");
+        request
+            .headers_mut()
+            .insert(connect::TIMEOUT_MS, timeout.parse().unwrap());
+        let (status, _, body) = call(&app, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{timeout}");
+        assert_eq!(code(&body), "invalid_argument");
+    }
+
+    let id = "a".repeat(4 * 1024 * 1024);
+    let body = json!({ "id": id }).to_string();
+    let (status, _, body) = call(
+        &app,
+        rpc("/users.UserService/GetUser", "application/json", body),
+    )
+    .await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(code(&body), "resource_exhausted");
+}
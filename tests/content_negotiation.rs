@@ -0,0 +1,191 @@
+//! `Content-Type` and `Accept` negotiation of the user REST routes
+//! between JSON, protobuf and MessagePack.
+
+mod common;
+
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    http::{
+        header::{ACCEPT, CONTENT_TYPE, ETAG},
+        HeaderMap, HeaderValue, Request, StatusCode,
+    },
+    Router,
+};
+use common::{call, running_bus, saved_user};
+use coqrs::{
+    proto::{CreateUserRequest, GetUserResponse, ListUsersResponse, UpdateUserRequest},
+    user_router, BearerTokens, EventBus, Format, InMemory,
+};
+use prost::Message;
+
+fn app() -> (Router, InMemory) {
+    let repo = InMemory::new();
+    let (bus, _) = running_bus(&repo);
+    (
+        user_router(
+            repo.clone(),
+            bus,
+            EventBus::default(),
+            BearerTokens::default(),
+        ),
+        repo,
+    )
+}
+
+fn get(uri: &str, accept: &str) -> Request<Body> {
+    Request::get(uri)
+        .header(ACCEPT, accept)
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn protobuf_bodies_create_users() {
+    let (app, _) = app();
+    let request = CreateUserRequest {
+        username: "alice".into(),
+        email: "alice@example.com".into(),
+    };
+    let (status, _, _) = call(
+        &app,
+        Request::post("/users")
+            .header(CONTENT_TYPE, "application/x-protobuf")
+            .body(Body::from(request.encode_to_vec()))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut page = ListUsersResponse::default();
+    for _ in 0..50 {
+        let (_, headers, body) = call(&app, get("/users", "application/x-protobuf")).await;
+        assert_eq!(headers[CONTENT_TYPE], "application/x-protobuf");
+        page = ListUsersResponse::decode(body).unwrap();
+        if !page.users.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(page.users.len(), 1);
+    assert_eq!(page.users[0].username, "alice");
+}
+
+#[tokio::test]
+async fn responses_follow_accept() {
+    let (app, repo) = app();
+    let alice = saved_user(&repo, "alice").await;
+    let uri = format!("/users/{}", alice.id);
+    let expected = GetUserResponse {
+        id: alice.id.to_string(),
+        username: "alice".into(),
+        email: "alice@example.com".into(),
+        version: 1,
+    };
+
+    let (_, headers, body) = call(&app, get(&uri, "application/x-protobuf")).await;
+    assert_eq!(GetUserResponse::decode(body).unwrap(), expected);
+    assert_eq!(headers[ETAG], "\"1\"");
+    assert_eq!(headers["vary"], "accept");
+
+    let (_, headers, body) = call(&app, get(&uri, "application/msgpack")).await;
+    assert_eq!(headers[CONTENT_TYPE], "application/msgpack");
+    let user: GetUserResponse = rmp_serde::from_slice(&body).unwrap();
+    assert_eq!(user, expected);
+
+    let (_, headers, body) = call(&app, get(&uri, "*/*")).await;
+    assert_eq!(headers[CONTENT_TYPE], "application/json");
+    let user: GetUserResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(user, expected);
+}
+
+#[tokio::test]
+async fn accept_quality_picks_the_format() {
+    let (app, repo) = app();
+    let alice = saved_user(&repo, "alice").await;
+
+    let accept = "application/json;q=0.5, application/msgpack;q=0.9, text/html";
+    let (status, headers, _) = call(&app, get(&format!("/users/{}", alice.id), accept)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[CONTENT_TYPE], "application/msgpack");
+}
+
+#[test]
+fn malformed_or_out_of_range_qualities_are_bounded() {
+    let preferred = |accept: &str| {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_str(accept).unwrap());
+        Format::from_accept(&headers)
+    };
+
+    for q in ["abc", "", "NaN", "-1"] {
+        let accept = format!("application/msgpack;q={q}");
+        assert_eq!(preferred(&accept), None, "{q}");
+    }
+    assert_eq!(
+        preferred("application/msgpack;q=oops, application/json;q=0.1"),
+        Some(Format::Json)
+    );
+    // Above 1 counts as 1, so the earlier range keeps the tie.
+    assert_eq!(
+        preferred("application/json, application/msgpack;q=5"),
+        Some(Format::Json)
+    );
+}
+
+#[tokio::test]
+async fn msgpack_bodies_update_users() {
+    let (app, repo) = app();
+    let alice = saved_user(&repo, "alice").await;
+    let request = UpdateUserRequest {
+        username: "alicia".into(),
+        email: "alicia@example.com".into(),
+        ..Default::default()
+    };
+
+    let (status, headers, body) = call(
+        &app,
+        Request::put(format!("/users/{}", alice.id))
+            .header(CONTENT_TYPE, "application/msgpack")
+            .header(ACCEPT, "application/json")
+            .header("if-match", "\"1\"")
+            .body(Body::from(rmp_serde::to_vec_named(&request).unwrap()))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[ETAG], "\"2\"");
+    let user: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(user["username"], "alicia");
+    assert_eq!(user["version"], "2");
+}
+
+#[tokio::test]
+async fn unsupported_formats_are_refused() {
+    let (app, repo) = app();
+    let alice = saved_user(&repo, "alice").await;
+
+    let (status, _, _) = call(
+        &app,
+        Request::post("/users")
+            .header(CONTENT_TYPE, "text/plain")
+            .body(Body::from("alice"))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let (status, _, _) = call(
+        &app,
+        Request::post("/users")
+            .header(CONTENT_TYPE, "application/x-protobuf")
+            .body(Body::from(vec![0xff, 0xff]))
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, _) = call(&app, get(&format!("/users/{}", alice.id), "text/html")).await;
+    assert_eq!(status, StatusCode::NOT_ACCEPTABLE);
+}
@@ -0,0 +1,335 @@
+//! Dead letters: the attempts kept for commands that failed after their
+//! retries, and the `/admin` routes and admin RPCs replaying and discarding
+//! them behind bearer tokens. These need `DATABASE_URL` and are skipped otherwise.
+
+mod common;
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::{
+    async_trait,
+    body::Body,
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        Request, StatusCode,
+    },
+    Router,
+};
+use common::{call, postgres};
+use coqrs::{
+    admin_router,
+    commands::{
+        command_bus, CommandBus, CommandBusConfig, CommandDecoders, CommandError, CommandHandler,
+        CommandHandlers, CommandWorker, RetryLayer,
+    },
+    grpc_connect_services, grpc_services,
+    models::{AttemptError, DeadLetter},
+    proto::{admin_service_client::AdminServiceClient, ListDeadLettersRequest},
+    repositories::{DeadLetterRepository, RepositoryError},
+    BearerTokens, Command, EventBus, PostgreSQL,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::net::TcpListener;
+use tonic::Code;
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Flake {
+    id: Uuid,
+}
+
+impl Command for Flake {
+    const NAME: &'static str = "Flake";
+    type Output = ();
+
+    fn aggregate_id(&self) -> Option<Uuid> {
+        Some(self.id)
+    }
+}
+
+/// Fails its first `failures` attempts with a transient error.
+struct Flaky {
+    failures: usize,
+    attempts: AtomicUsize,
+}
+
+#[async_trait]
+impl CommandHandler<Flake> for Arc<Flaky> {
+    async fn handle(&self, _: Flake) -> Result<(), CommandError> {
+        let attempt = self.attempts.fetch_add(1, Ordering::SeqCst) + 1;
+        if attempt <= self.failures {
+            return Err(RepositoryError::Database(sqlx::Error::PoolTimedOut).into());
+        }
+        Ok(())
+    }
+}
+
+/// Counts its attempts and takes a while over each.
+struct Slow(AtomicUsize);
+
+#[async_trait]
+impl CommandHandler<Flake> for Arc<Slow> {
+    async fn handle(&self, _: Flake) -> Result<(), CommandError> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        Ok(())
+    }
+}
+
+/// A bus retrying `Flake` twice and dead-lettering it into `db` after that.
+fn running(db: &PostgreSQL, failures: usize) -> (CommandBus, CommandDecoders) {
+    let (bus, receiver) = command_bus(&CommandBusConfig::default());
+    let flaky = Arc::new(Flaky {
+        failures,
+        attempts: AtomicUsize::new(0),
+    });
+    let pipeline = CommandHandlers::new()
+        .layer(RetryLayer::new(2, Duration::from_millis(1)))
+        .register::<Flake, _>(flaky)
+        .build();
+    let decoders = pipeline.decoders.clone();
+    tokio::spawn(
+        CommandWorker::new(receiver, pipeline)
+            .with_dead_letters(db.clone())
+            .run(),
+    );
+    (bus, decoders)
+}
+
+fn admin(db: &PostgreSQL, failures: usize) -> Router {
+    let (bus, decoders) = running(db, failures);
+    admin_router(db.clone(), bus, decoders, BearerTokens::new(["secret"]))
+}
+
+fn request(method: &str, uri: &str, token: Option<&str>) -> Request<Body> {
+    let mut request = Request::builder().method(method).uri(uri);
+    if let Some(token) = token {
+        request = request.header(AUTHORIZATION, format!("Bearer {token}"));
+    }
+    request.body(Body::empty()).unwrap()
+}
+
+async fn saved_dead_letter(db: &PostgreSQL) -> DeadLetter {
+    let id = Uuid::now_v7();
+    let dead_letter = DeadLetter::new(
+        Flake::NAME.into(),
+        json!({ "id": id }),
+        Some(id),
+        vec![AttemptError::new(1, "boom".into())],
+    );
+    db.save_dead_letter(dead_letter.clone()).await.unwrap();
+    dead_letter
+}
+
+#[tokio::test]
+async fn admin_routes_need_a_bearer_token() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let app = admin(&db, 0);
+
+    for token in [None, Some("wrong"), Some("")] {
+        for uri in ["/admin/dead-letters", "/admin/workflows"] {
+            let (status, headers, _) = call(&app, request("GET", uri, token)).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{uri} {token:?}");
+            assert_eq!(headers["www-authenticate"], "Bearer");
+        }
+    }
+
+    let (status, _, _) = call(&app, request("GET", "/admin/dead-letters", Some("secret"))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let refusing = admin_router(
+        db.clone(),
+        command_bus(&CommandBusConfig::default()).0,
+        CommandDecoders::default(),
+        BearerTokens::default(),
+    );
+    let (status, _, _) = call(
+        &refusing,
+        request("GET", "/admin/dead-letters", Some("secret")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn admin_rpcs_need_a_bearer_token() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let (bus, decoders) = running(&db, 0);
+    let events = EventBus::default();
+
+    let app = grpc_services(
+        db.clone(),
+        bus.clone(),
+        decoders.clone(),
+        events.clone(),
+        BearerTokens::default(),
+        BearerTokens::new(["secret"]),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let mut client = AdminServiceClient::connect(format!("http://{address}"))
+        .await
+        .unwrap();
+    let list = |token: Option<&str>| {
+        let mut request = tonic::Request::new(ListDeadLettersRequest {
+            limit: 1,
+            offset: 0,
+        });
+        if let Some(token) = token {
+            let value = format!("Bearer {token}").parse().unwrap();
+            request.metadata_mut().insert("authorization", value);
+        }
+        request
+    };
+    for token in [None, Some("wrong")] {
+        let status = client.list_dead_letters(list(token)).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated, "{token:?}");
+    }
+    client
+        .list_dead_letters(list(Some("secret")))
+        .await
+        .unwrap();
+
+    // Connect does not serve the admin service at all.
+    let connect = grpc_connect_services(
+        db,
+        bus,
+        decoders,
+        events,
+        BearerTokens::default(),
+        BearerTokens::new(["secret"]),
+    );
+    let request = Request::post("/admin.AdminService/ListDeadLetters")
+        .header(CONTENT_TYPE, "application/json")
+        .header(AUTHORIZATION, "Bearer secret")
+        .body(Body::from("{}"))
+        .unwrap();
+    let (status, _, _) = call(&connect, request).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn dead_letters_keep_every_failed_attempt() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let (bus, _) = running(&db, usize::MAX);
+    let id = Uuid::now_v7();
+    bus.send(Flake { id }).await.unwrap();
+
+    let dead_letter = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let dead_letters = db.list_dead_letters(500, 0).await.unwrap();
+            if let Some(dead_letter) = dead_letters
+                .into_iter()
+                .find(|dead_letter| dead_letter.aggregate_id == Some(id))
+            {
+                return dead_letter;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("the command was not dead-lettered");
+
+    assert_eq!(dead_letter.command_type, "Flake");
+    assert_eq!(dead_letter.payload, json!({ "id": id }));
+    assert_eq!(dead_letter.attempts, 3);
+    let errors = dead_letter.errors.as_array().unwrap();
+    assert_eq!(errors.len(), 3);
+    for (i, error) in errors.iter().enumerate() {
+        assert_eq!(error["attempt"], i + 1);
+    }
+    assert!(dead_letter.last_error.contains("pool timed out"));
+    assert!(dead_letter.created_at <= dead_letter.failed_at);
+    db.delete_dead_letter(dead_letter.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn replays_keep_failing_dead_letters_until_they_succeed() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    // The first replay fails all three of its attempts, the second succeeds.
+    let app = admin(&db, 3);
+    let dead_letter = saved_dead_letter(&db).await;
+    let replay = format!("/admin/dead-letters/{}/replay", dead_letter.id);
+
+    let (status, _, _) = call(&app, request("POST", &replay, Some("secret"))).await;
+    assert!(!status.is_success());
+    let kept = db.find_dead_letter(dead_letter.id).await.unwrap().unwrap();
+    assert_eq!(kept.attempts, 4);
+    assert_eq!(kept.errors.as_array().unwrap().len(), 4);
+
+    let (status, _, _) = call(&app, request("POST", &replay, Some("secret"))).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(db.find_dead_letter(dead_letter.id).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn a_dead_letter_is_replayed_once_at_a_time() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let (bus, receiver) = command_bus(&CommandBusConfig::default());
+    let slow = Arc::new(Slow(AtomicUsize::new(0)));
+    let pipeline = CommandHandlers::new()
+        .register::<Flake, _>(slow.clone())
+        .build();
+    let decoders = pipeline.decoders.clone();
+    tokio::spawn(CommandWorker::new(receiver, pipeline).run());
+    let app = admin_router(db.clone(), bus, decoders, BearerTokens::new(["secret"]));
+    let dead_letter = saved_dead_letter(&db).await;
+    let replay = format!("/admin/dead-letters/{}/replay", dead_letter.id);
+
+    let first = tokio::spawn({
+        let (app, replay) = (app.clone(), replay.clone());
+        async move { call(&app, request("POST", &replay, Some("secret"))).await.0 }
+    });
+    while slow.0.load(Ordering::SeqCst) == 0 {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    let (status, _, _) = call(&app, request("POST", &replay, Some("secret"))).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    // Still there while the first replay runs.
+    assert!(db.find_dead_letter(dead_letter.id).await.unwrap().is_some());
+
+    assert_eq!(first.await.unwrap(), StatusCode::NO_CONTENT);
+    assert_eq!(slow.0.load(Ordering::SeqCst), 1);
+    assert!(db.find_dead_letter(dead_letter.id).await.unwrap().is_none());
+    let (status, _, _) = call(&app, request("POST", &replay, Some("secret"))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn discarded_dead_letters_are_gone() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let app = admin(&db, 0);
+    let dead_letter = saved_dead_letter(&db).await;
+    let uri = format!("/admin/dead-letters/{}", dead_letter.id);
+
+    let (status, _, body) = call(&app, request("GET", &uri, Some("secret"))).await;
+    assert_eq!(status, StatusCode::OK);
+    let fetched: DeadLetter = serde_json::from_slice(&body).unwrap();
+    assert_eq!(fetched.attempts, 1);
+    assert_eq!(fetched.last_error, "boom");
+
+    let (status, _, _) = call(&app, request("DELETE", &uri, Some("secret"))).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _, _) = call(&app, request("GET", &uri, Some("secret"))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
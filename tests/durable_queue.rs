@@ -0,0 +1,156 @@
+//! The Postgres command queue: leasing, acknowledging and retrying
+//! commands, and the worker running them. These need `DATABASE_URL` and
+//! are skipped otherwise.
+
+mod common;
+
+use std::time::Duration;
+
+use axum::async_trait;
+use common::postgres;
+use coqrs::{
+    commands::{CommandError, CommandHandler, CommandHandlers, DurableQueueConfig, DurableWorker},
+    models::QueuedCommand,
+    repositories::{CommandQueueRepository, DeadLetterRepository, RepositoryError},
+    Command, PostgreSQL,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::{Mutex, MutexGuard};
+use uuid::Uuid;
+
+/// Claims take any visible command, so the tests sharing the queue take
+/// turns.
+static QUEUE: Mutex<()> = Mutex::const_new(());
+
+async fn queue() -> Option<(PostgreSQL, MutexGuard<'static, ()>)> {
+    let db = postgres().await?;
+    Some((db, QUEUE.lock().await))
+}
+
+fn queued(command_type: &str, aggregate_id: Option<Uuid>) -> QueuedCommand {
+    QueuedCommand {
+        id: Uuid::now_v7(),
+        command_type: command_type.into(),
+        payload: json!({}),
+        aggregate_id,
+        attempts: 0,
+        max_attempts: 2,
+    }
+}
+
+/// The ids of the commands a claim leased.
+async fn claim(db: &PostgreSQL, visibility_timeout: Duration) -> Vec<Uuid> {
+    let commands = db.claim_commands(100, visibility_timeout).await.unwrap();
+    commands.into_iter().map(|command| command.id).collect()
+}
+
+#[tokio::test]
+async fn leased_commands_stay_hidden_until_retried_or_acked() {
+    let Some((db, _turn)) = queue().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let command = queued("Noop", None);
+    db.enqueue_command(command.clone()).await.unwrap();
+
+    let leased = db
+        .claim_commands(100, Duration::from_secs(30))
+        .await
+        .unwrap();
+    let lease = leased.iter().find(|c| c.id == command.id).unwrap();
+    assert_eq!(lease.attempts, 1);
+    assert!(!claim(&db, Duration::from_secs(30))
+        .await
+        .contains(&command.id));
+
+    db.retry_command(command.id, "boom", Duration::ZERO)
+        .await
+        .unwrap();
+    let leased = db
+        .claim_commands(100, Duration::from_secs(30))
+        .await
+        .unwrap();
+    let lease = leased.iter().find(|c| c.id == command.id).unwrap();
+    assert_eq!(lease.attempts, 2);
+
+    db.complete_command(command.id).await.unwrap();
+    db.retry_command(command.id, "gone", Duration::ZERO)
+        .await
+        .unwrap();
+    assert!(!claim(&db, Duration::ZERO).await.contains(&command.id));
+}
+
+#[tokio::test]
+async fn commands_of_one_aggregate_are_leased_one_at_a_time() {
+    let Some((db, _turn)) = queue().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let aggregate = Some(Uuid::now_v7());
+    let first = queued("Noop", aggregate);
+    let second = queued("Noop", aggregate);
+    db.enqueue_command(first.clone()).await.unwrap();
+    db.enqueue_command(second.clone()).await.unwrap();
+
+    let leased = claim(&db, Duration::ZERO).await;
+    assert!(leased.contains(&first.id));
+    assert!(!leased.contains(&second.id));
+
+    db.complete_command(first.id).await.unwrap();
+    assert!(claim(&db, Duration::ZERO).await.contains(&second.id));
+    db.complete_command(second.id).await.unwrap();
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Fail {
+    id: Uuid,
+}
+
+impl Command for Fail {
+    const NAME: &'static str = "Fail";
+    type Output = ();
+}
+
+struct Failing;
+
+#[async_trait]
+impl CommandHandler<Fail> for Failing {
+    async fn handle(&self, _: Fail) -> Result<(), CommandError> {
+        Err(RepositoryError::NotFound.into())
+    }
+}
+
+#[tokio::test]
+async fn the_worker_retries_then_dead_letters_failing_commands() {
+    let Some((db, _turn)) = queue().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let mut command = queued("Fail", None);
+    command.payload = json!({ "id": Uuid::now_v7() });
+    db.enqueue_command(command.clone()).await.unwrap();
+
+    let config = DurableQueueConfig {
+        poll_interval: Duration::from_millis(10),
+        ..Default::default()
+    };
+    let pipeline = CommandHandlers::new().register(Failing).build();
+    let worker = tokio::spawn(DurableWorker::new(db.clone(), pipeline, config).run());
+
+    // The retry waits out a backoff of two seconds.
+    let dead_letter = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            if let Some(dead_letter) = db.find_dead_letter(command.id).await.unwrap() {
+                return dead_letter;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("the command was not dead-lettered");
+    worker.abort();
+
+    assert_eq!(dead_letter.command_type, "Fail");
+    assert_eq!(dead_letter.attempts, 2);
+    assert_eq!(dead_letter.errors.as_array().unwrap().len(), 2);
+    assert_eq!(dead_letter.last_error, "record not found");
+    db.delete_dead_letter(command.id).await.unwrap();
+}
@@ -0,0 +1,154 @@
+//! Optimistic concurrency of user updates and deletes: `ETag` and
+//! `If-Match` over REST, `expected_version` and `ABORTED` over gRPC.
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{
+        header::{CONTENT_TYPE, ETAG, IF_MATCH},
+        Request, StatusCode,
+    },
+    Router,
+};
+use common::{call, running_bus, saved_user};
+use coqrs::{
+    grpc_user_services,
+    proto::{user_service_client::UserServiceClient, DeleteUserRequest, UpdateUserRequest},
+    repositories::UserRepository,
+    user_router, BearerTokens, EventBus, InMemory,
+};
+use serde_json::json;
+use tokio::net::TcpListener;
+use tonic::{transport::Channel, Code};
+
+fn app() -> (Router, InMemory) {
+    let repo = InMemory::new();
+    let (bus, _) = running_bus(&repo);
+    (
+        user_router(
+            repo.clone(),
+            bus,
+            EventBus::default(),
+            BearerTokens::default(),
+        ),
+        repo,
+    )
+}
+
+fn update(uri: &str, if_match: &str) -> Request<Body> {
+    let body = json!({ "username": "alicia", "email": "alicia@example.com" });
+    Request::put(uri)
+        .header(CONTENT_TYPE, "application/json")
+        .header(IF_MATCH, if_match)
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+/// Serves the gRPC user service on a free port.
+async fn client() -> (UserServiceClient<Channel>, InMemory) {
+    let repo = InMemory::new();
+    let (bus, _) = running_bus(&repo);
+    let app = grpc_user_services(
+        repo.clone(),
+        bus,
+        EventBus::default(),
+        BearerTokens::default(),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let client = UserServiceClient::connect(format!("http://{address}"))
+        .await
+        .unwrap();
+    (client, repo)
+}
+
+#[tokio::test]
+async fn stale_if_match_is_a_failed_precondition() {
+    let (app, repo) = app();
+    let alice = saved_user(&repo, "alice").await;
+    let uri = format!("/users/{}", alice.id);
+
+    let (status, headers, _) = call(&app, update(&uri, "\"2\"")).await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+    assert_eq!(headers[ETAG], "\"1\"");
+
+    let delete = Request::delete(&uri)
+        .header(IF_MATCH, "W/\"0\"")
+        .body(Body::empty())
+        .unwrap();
+    let (status, _, _) = call(&app, delete).await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+    let stored = repo.find_user_by_id(alice.id).await.unwrap().unwrap();
+    assert_eq!(stored.version, 1);
+
+    let (status, headers, _) = call(&app, update(&uri, "\"1\"")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[ETAG], "\"2\"");
+}
+
+#[tokio::test]
+async fn malformed_if_match_is_a_bad_request() {
+    let (app, repo) = app();
+    let alice = saved_user(&repo, "alice").await;
+    let uri = format!("/users/{}", alice.id);
+
+    for if_match in ["\"one\"", "1, 2"] {
+        let (status, _, _) = call(&app, update(&uri, if_match)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{if_match}");
+    }
+    let stored = repo.find_user_by_id(alice.id).await.unwrap().unwrap();
+    assert_eq!(stored.version, 1);
+
+    // `*` matches any version.
+    let (status, _, _) = call(&app, update(&uri, "*")).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn stale_expected_versions_are_aborted_over_grpc() {
+    let (mut client, repo) = client().await;
+    let alice = saved_user(&repo, "alice").await;
+
+    let status = client
+        .update_user(UpdateUserRequest {
+            id: alice.id.to_string(),
+            username: "alicia".into(),
+            email: "alicia@example.com".into(),
+            expected_version: Some(2),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Aborted);
+    assert_eq!(status.metadata().get("etag").unwrap(), "\"1\"");
+
+    let status = client
+        .delete_user(DeleteUserRequest {
+            id: alice.id.to_string(),
+            expected_version: Some(0),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Aborted);
+
+    let mut request = tonic::Request::new(DeleteUserRequest {
+        id: alice.id.to_string(),
+        expected_version: None,
+    });
+    request
+        .metadata_mut()
+        .insert("if-match", "not-a-version".parse().unwrap());
+    let status = client.delete_user(request).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let response = client
+        .delete_user(DeleteUserRequest {
+            id: alice.id.to_string(),
+            expected_version: Some(1),
+        })
+        .await;
+    assert!(response.is_ok());
+    assert!(repo.find_user_by_id(alice.id).await.unwrap().is_none());
+}
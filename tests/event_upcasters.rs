@@ -0,0 +1,123 @@
+use coqrs::{
+    events::{EventError, StoredEvent, Upcasters},
+    Event,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// v1 stored `name`, v2 split it into `first_name`/`last_name`, v3 added `active`.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct AccountOpened {
+    first_name: String,
+    last_name: String,
+    active: bool,
+}
+
+impl Event for AccountOpened {
+    const EVENT_TYPE: &'static str = "AccountOpened";
+    const VERSION: i32 = 3;
+}
+
+fn v1_to_v2(mut payload: Value) -> Value {
+    let name = payload["name"].as_str().unwrap_or_default().to_string();
+    let (first, last) = name.split_once(' ').unwrap_or((&name, ""));
+    payload["first_name"] = json!(first);
+    payload["last_name"] = json!(last);
+    payload.as_object_mut().unwrap().remove("name");
+    payload
+}
+
+fn v2_to_v3(mut payload: Value) -> Value {
+    payload["active"] = json!(true);
+    payload
+}
+
+fn upcasters() -> Upcasters {
+    Upcasters::new()
+        .register(AccountOpened::EVENT_TYPE, 1, v1_to_v2)
+        .register(AccountOpened::EVENT_TYPE, 2, v2_to_v3)
+}
+
+fn stored(version: i32, payload: Value) -> StoredEvent {
+    StoredEvent {
+        id: Uuid::now_v7(),
+        aggregate_id: Uuid::now_v7(),
+        event_type: AccountOpened::EVENT_TYPE.to_string(),
+        version,
+        payload,
+        sequence: 0,
+        transaction: 0,
+    }
+}
+
+fn expected() -> AccountOpened {
+    AccountOpened {
+        first_name: "Ada".to_string(),
+        last_name: "Lovelace".to_string(),
+        active: true,
+    }
+}
+
+#[test]
+fn upcasts_v1_through_v3() {
+    let event = stored(1, json!({ "name": "Ada Lovelace" }));
+
+    assert_eq!(
+        event.decode::<AccountOpened>(&upcasters()).unwrap(),
+        expected()
+    );
+}
+
+#[test]
+fn upcasts_v2_to_v3() {
+    let event = stored(2, json!({ "first_name": "Ada", "last_name": "Lovelace" }));
+
+    assert_eq!(
+        event.decode::<AccountOpened>(&upcasters()).unwrap(),
+        expected()
+    );
+}
+
+#[test]
+fn current_version_is_decoded_as_is() {
+    let event = StoredEvent::new(Uuid::now_v7(), &expected());
+
+    assert_eq!(event.version, 3);
+    assert_eq!(
+        event.decode::<AccountOpened>(&upcasters()).unwrap(),
+        expected()
+    );
+}
+
+#[test]
+fn missing_step_is_an_error() {
+    let upcasters = Upcasters::new().register(AccountOpened::EVENT_TYPE, 1, v1_to_v2);
+    let event = stored(1, json!({ "name": "Ada Lovelace" }));
+
+    assert!(matches!(
+        event.decode::<AccountOpened>(&upcasters),
+        Err(EventError::MissingUpcaster { version: 2, .. })
+    ));
+}
+
+#[test]
+fn newer_version_is_rejected() {
+    let event = stored(4, json!({}));
+
+    assert!(matches!(
+        event.decode::<AccountOpened>(&upcasters()),
+        Err(EventError::UnsupportedVersion { version: 4, .. })
+    ));
+}
+
+#[test]
+fn other_event_type_is_rejected() {
+    let mut event = stored(3, json!({}));
+    event.event_type = "AccountClosed".to_string();
+
+    assert!(matches!(
+        event.decode::<AccountOpened>(&upcasters()),
+        Err(EventError::TypeMismatch { .. })
+    ));
+}
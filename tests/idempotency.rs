@@ -0,0 +1,150 @@
+//! `Idempotency-Key` on user creation: replaying the first response, keys
+//! reused for another request, keys expiring and keys left by crashed
+//! requests. Keys are stored in Postgres, so these need `DATABASE_URL` and
+//! are skipped otherwise.
+
+mod common;
+
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    http::{header::CONTENT_TYPE, Request, StatusCode},
+};
+use common::{call, postgres, running_bus};
+use coqrs::{
+    commands::{command_bus, CommandBusConfig},
+    grpc_rest_services,
+    services::{Idempotency, IdempotencyService},
+};
+use serde_json::json;
+use uuid::Uuid;
+
+fn create(key: &str, username: &str) -> Request<Body> {
+    let body = json!({ "username": username, "email": format!("{username}@example.com") });
+    Request::post("/users")
+        .header(CONTENT_TYPE, "application/json")
+        .header("idempotency-key", key)
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn retried_creates_replay_the_first_response() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let (bus, _) = running_bus(&db);
+    let app = grpc_rest_services(db, bus);
+    let key = Uuid::now_v7().to_string();
+    let username = format!("idem-{}", Uuid::now_v7().simple());
+
+    let (status, headers, body) = call(&app, create(&key, &username)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers.get("idempotent-replayed").is_none());
+
+    // Creating the same username twice would fail, the replay does not run it.
+    let (status, headers, replayed) = call(&app, create(&key, &username)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["idempotent-replayed"], "true");
+    assert_eq!(replayed, body);
+
+    let (status, _, _) = call(&app, create(&key, "someone-else")).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn failed_creates_release_their_key() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let key = Uuid::now_v7().to_string();
+    let username = format!("idem-{}", Uuid::now_v7().simple());
+
+    // Without a worker the command cannot be queued.
+    let (closed, _) = command_bus(&CommandBusConfig::default());
+    let app = grpc_rest_services(db.clone(), closed);
+    let (status, _, _) = call(&app, create(&key, &username)).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+    let (bus, _) = running_bus(&db);
+    let app = grpc_rest_services(db, bus);
+    let (status, headers, _) = call(&app, create(&key, &username)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers.get("idempotent-replayed").is_none());
+}
+
+#[tokio::test]
+async fn rejected_creates_are_not_replayed_as_successes() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let (bus, _) = running_bus(&db);
+    let app = grpc_rest_services(db, bus);
+    let username = format!("idem-{}", Uuid::now_v7().simple());
+    let (status, _, _) = call(&app, create(&Uuid::now_v7().to_string(), &username)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // The handler rejects the taken username, so the key is released.
+    let key = Uuid::now_v7().to_string();
+    for _ in 0..2 {
+        let (status, headers, _) = call(&app, create(&key, &username)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(headers.get("idempotent-replayed").is_none());
+    }
+}
+
+#[tokio::test]
+async fn keys_replay_until_they_expire() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let service = IdempotencyService::new(db);
+    let key = Uuid::now_v7().to_string();
+
+    let outcome = service.begin("test", &key, b"request").await.unwrap();
+    assert!(matches!(outcome, Idempotency::Fresh));
+    let outcome = service.begin("test", &key, b"request").await.unwrap();
+    assert!(matches!(outcome, Idempotency::InProgress));
+
+    service.complete("test", &key, b"response").await.unwrap();
+    let outcome = service.begin("test", &key, b"request").await.unwrap();
+    assert!(matches!(
+        outcome,
+        Idempotency::Replay { response } if response == b"response"
+    ));
+    let outcome = service.begin("test", &key, b"other").await.unwrap();
+    assert!(matches!(outcome, Idempotency::Mismatch));
+
+    let expired = IdempotencyService {
+        ttl: Duration::ZERO,
+        ..service
+    };
+    let outcome = expired.begin("test", &key, b"other").await.unwrap();
+    assert!(matches!(outcome, Idempotency::Fresh));
+}
+
+#[tokio::test]
+async fn keys_of_unfinished_requests_are_taken_over_once_their_lease_ends() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let crashed = IdempotencyService {
+        lease: Duration::ZERO,
+        ..IdempotencyService::new(db.clone())
+    };
+    let service = IdempotencyService::new(db);
+    let key = Uuid::now_v7().to_string();
+
+    // Never completed nor released, as by a request that crashed.
+    let outcome = crashed.begin("test", &key, b"request").await.unwrap();
+    assert!(matches!(outcome, Idempotency::Fresh));
+    let outcome = service.begin("test", &key, b"request").await.unwrap();
+    assert!(matches!(outcome, Idempotency::Fresh));
+    let outcome = service.begin("test", &key, b"request").await.unwrap();
+    assert!(matches!(outcome, Idempotency::InProgress));
+
+    service.complete("test", &key, b"response").await.unwrap();
+    let outcome = crashed.begin("test", &key, b"request").await.unwrap();
+    assert!(matches!(outcome, Idempotency::Replay { .. }));
+}
@@ -0,0 +1,402 @@
+//! Bulk user imports and exports: batching and outcomes in the service,
+//! the `ImportUsers` and `ImportUsersStream` RPCs over Connect, and the
+//! CSV, NDJSON and protobuf endpoints.
+
+mod common;
+
+use std::time::Duration;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::Request,
+    http::{header::CONTENT_TYPE, StatusCode},
+    Router,
+};
+use common::saved_user;
+use coqrs::{
+    commands::{command_bus, CommandBusConfig, CreateUser},
+    connect, grpc_user_connect_services,
+    models::User,
+    proto::{
+        CreateUserRequest, GetUserResponse, ImportStatus, ImportUserResult, ImportUsersResponse,
+    },
+    repositories::{RepositoryError, Transactional, UnitOfWork, UserRepository},
+    services::{import_batches, ImportOutcome, UserService, IMPORT_BATCH, IMPORT_BATCH_WAIT},
+    user_router, BearerTokens, EventBus, InMemory,
+};
+use futures_util::{stream, StreamExt};
+use http_body_util::BodyExt;
+use prost::Message;
+use serde_json::{json, Value};
+use tokio::sync::oneshot;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+fn record(username: &str, email: &str) -> CreateUserRequest {
+    CreateUserRequest {
+        username: username.into(),
+        email: email.into(),
+    }
+}
+
+/// Imports `records` over Connect, returning the status and the payloads
+/// of the response envelopes.
+async fn import(
+    app: &Router,
+    procedure: &str,
+    records: &[CreateUserRequest],
+) -> (StatusCode, Vec<Bytes>) {
+    let mut body = Vec::new();
+    for record in records {
+        let message = record.encode_to_vec();
+        body.push(0);
+        body.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        body.extend_from_slice(&message);
+    }
+    let request = Request::post(format!("/users.UserService/{procedure}"))
+        .header(CONTENT_TYPE, "application/connect+proto")
+        .header(connect::PROTOCOL_VERSION, "1")
+        .body(Body::from(body))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let mut bytes = response.into_body().collect().await.unwrap().to_bytes();
+
+    let mut payloads = Vec::new();
+    while !bytes.is_empty() {
+        let length = u32::from_be_bytes(bytes[1..5].try_into().unwrap()) as usize;
+        payloads.push(bytes.slice(5..5 + length));
+        bytes = bytes.slice(5 + length..);
+    }
+    (status, payloads)
+}
+
+fn app() -> (Router, InMemory) {
+    let repo = InMemory::new();
+    let (bus, _receiver) = command_bus(&CommandBusConfig::default());
+    let app = grpc_user_connect_services(
+        repo.clone(),
+        bus,
+        EventBus::default(),
+        BearerTokens::default(),
+    );
+    (app, repo)
+}
+
+#[tokio::test]
+async fn import_users_summarizes_the_records() {
+    let (app, repo) = app();
+    saved_user(&repo, "alice").await;
+
+    let records = [
+        record("bob", " Bob@Example.com "),
+        record("alice", "alice@elsewhere.com"),
+        record("bobby", "bob@example.com"),
+        record("", "nobody@example.com"),
+        record("carol", "carol"),
+        record("carol", "carol@example.com"),
+    ];
+    let (status, payloads) = import(&app, "ImportUsers", &records).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let summary = ImportUsersResponse::decode(payloads[0].clone()).unwrap();
+    assert_eq!(
+        summary,
+        ImportUsersResponse {
+            created: 2,
+            duplicate: 2,
+            invalid: 2,
+        }
+    );
+    let users = repo.list_users(None, 10).await.unwrap();
+    let emails: Vec<_> = users.iter().map(|user| user.email.as_str()).collect();
+    assert_eq!(
+        emails,
+        ["alice@example.com", "bob@example.com", "carol@example.com"]
+    );
+}
+
+#[tokio::test]
+async fn import_users_stream_answers_every_record() {
+    let (app, repo) = app();
+    saved_user(&repo, "alice").await;
+
+    let records = [
+        record("bob", "bob@example.com"),
+        record("alice", "alice@elsewhere.com"),
+        record("carol", "not an address"),
+    ];
+    let (status, payloads) = import(&app, "ImportUsersStream", &records).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // One result per record, then the end of the stream.
+    assert_eq!(payloads.len(), 4);
+    let results: Vec<_> = payloads[..3]
+        .iter()
+        .map(|payload| ImportUserResult::decode(payload.clone()).unwrap())
+        .collect();
+    let statuses: Vec<_> = results.iter().map(|result| result.status()).collect();
+    assert_eq!(
+        statuses,
+        [
+            ImportStatus::Created,
+            ImportStatus::Duplicate,
+            ImportStatus::Invalid
+        ]
+    );
+    assert_eq!(results[2].index, 2);
+    assert_eq!(results[2].error, "email is not an address");
+    let bob = Uuid::parse_str(&results[0].id).unwrap();
+    assert_eq!(
+        repo.find_user_by_id(bob).await.unwrap().unwrap().username,
+        "bob"
+    );
+}
+
+#[tokio::test]
+async fn imports_end_at_the_first_error_keeping_earlier_batches() {
+    let repo = InMemory::new();
+    let (bus, _receiver) = command_bus(&CommandBusConfig::default());
+    let service = UserService::new(repo.clone(), bus);
+
+    let count = IMPORT_BATCH + 10;
+    let records = stream::iter((0..count).map(|i| {
+        Ok(Ok(CreateUser {
+            username: format!("user-{i}"),
+            email: format!("user-{i}@example.com"),
+        }))
+    }))
+    .chain(stream::iter([
+        Err(RepositoryError::NotFound),
+        Ok(Ok(CreateUser {
+            username: "late".into(),
+            email: "late@example.com".into(),
+        })),
+    ]));
+    let outcomes: Vec<_> = service.import_users(records, false).collect().await;
+
+    assert_eq!(outcomes.len(), count + 1);
+    assert!(outcomes[..count]
+        .iter()
+        .all(|outcome| matches!(outcome, Ok(ImportOutcome::Created(_)))));
+    assert!(matches!(outcomes[count], Err(RepositoryError::NotFound)));
+    let first = repo.list_users(None, IMPORT_BATCH as i64).await.unwrap();
+    let rest = repo
+        .list_users(Some(first[first.len() - 1].id), 100)
+        .await
+        .unwrap();
+    assert_eq!(first.len() + rest.len(), count);
+}
+
+#[tokio::test]
+async fn trickling_records_fill_a_batch_until_the_wait_ends() {
+    // Every record arrives after the stream was polled once without one.
+    let trickling = stream::iter(0..5).then(|i| async move {
+        tokio::task::yield_now().await;
+        i
+    });
+    let batches: Vec<_> = import_batches(trickling).collect().await;
+    assert_eq!(batches, [vec![0, 1, 2, 3, 4]]);
+
+    let stalling = stream::iter(0..3).then(|i| async move {
+        if i == 2 {
+            tokio::time::sleep(IMPORT_BATCH_WAIT * 2).await;
+        }
+        i
+    });
+    let batches: Vec<_> = import_batches(stalling).collect().await;
+    assert_eq!(batches, [vec![0, 1], vec![2]]);
+
+    let batches: Vec<_> = import_batches(stream::iter(0..IMPORT_BATCH + 1))
+        .map(|batch| batch.len())
+        .collect()
+        .await;
+    assert_eq!(batches, [IMPORT_BATCH, 1]);
+}
+
+#[tokio::test]
+async fn dry_runs_roll_back_each_batch_and_remember_what_it_took() {
+    let repo = InMemory::new();
+    let (bus, _receiver) = command_bus(&CommandBusConfig::default());
+    let service = UserService::new(repo.clone(), bus);
+
+    let record = |i: usize| {
+        Ok(Ok(CreateUser {
+            username: format!("user-{i}"),
+            email: format!("user-{i}@example.com"),
+        }))
+    };
+    let (release, released) = oneshot::channel::<()>();
+    let records = stream::iter((0..IMPORT_BATCH).map(record)).chain(stream::once(async move {
+        released.await.unwrap();
+        // Taken by the first batch only, which was rolled back.
+        Ok::<_, RepositoryError>(Ok(CreateUser {
+            username: "user-0".into(),
+            email: "other@example.com".into(),
+        }))
+    }));
+    let mut outcomes = Box::pin(service.import_users(records, true));
+
+    for _ in 0..IMPORT_BATCH {
+        let outcome = outcomes.next().await.unwrap();
+        assert!(matches!(outcome, Ok(ImportOutcome::Created(_))));
+    }
+    // The upload goes on, but no unit of work is held open for it.
+    let uow = tokio::time::timeout(Duration::from_secs(1), repo.begin())
+        .await
+        .expect("the dry run kept its unit of work")
+        .unwrap();
+    uow.rollback().await.unwrap();
+
+    release.send(()).unwrap();
+    assert!(matches!(
+        outcomes.next().await,
+        Some(Ok(ImportOutcome::Duplicate))
+    ));
+    assert!(outcomes.next().await.is_none());
+    assert!(repo.list_users(None, 10).await.unwrap().is_empty());
+}
+
+fn rest_app() -> (Router, InMemory) {
+    let repo = InMemory::new();
+    let (bus, _receiver) = command_bus(&CommandBusConfig::default());
+    let app = user_router(
+        repo.clone(),
+        bus,
+        EventBus::default(),
+        BearerTokens::default(),
+    );
+    (app, repo)
+}
+
+async fn post_import(
+    app: &Router,
+    query: &str,
+    content_type: &str,
+    body: &str,
+) -> (StatusCode, Value) {
+    let request = Request::post(format!("/users/import{query}"))
+        .header(CONTENT_TYPE, content_type)
+        .body(Body::from(body.to_owned()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+#[tokio::test]
+async fn csv_imports_report_every_record_and_dry_runs_leave_no_trace() {
+    let (app, repo) = rest_app();
+    saved_user(&repo, "alice").await;
+    let csv = "email,username,team\n\
+               bob@example.com,bob,\"ops, night\"\n\
+               alice@example.com,alicia,ops\n\
+               carol,carol,ops\n\
+               dave@example.com\n";
+
+    let (status, report) = post_import(&app, "?dry_run=true", "text/csv", csv).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["created"], 1);
+    assert_eq!(report["duplicate"], 1);
+    assert_eq!(report["invalid"], 2);
+    assert_eq!(report["dryRun"], true);
+    assert_eq!(
+        report["errors"][0],
+        json!({ "record": 3, "error": "email is not an address" })
+    );
+    assert_eq!(report["errors"][1]["record"], 4);
+    assert_eq!(repo.list_users(None, 10).await.unwrap().len(), 1);
+
+    let (status, report) = post_import(&app, "", "text/csv; charset=utf-8", csv).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["created"], 1);
+    assert_eq!(report["dryRun"], false);
+    let users = repo.list_users(None, 10).await.unwrap();
+    assert_eq!(users[1].username, "bob");
+
+    let (status, report) = post_import(&app, "", "text/csv", "name\nbob\n").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(report["created"], 0);
+    assert!(report["error"]
+        .as_str()
+        .unwrap()
+        .contains("username and email"));
+}
+
+#[tokio::test]
+async fn ndjson_imports_count_unreadable_lines_as_invalid() {
+    let (app, repo) = rest_app();
+    let ndjson = "{\"username\": \"bob\", \"email\": \"bob@example.com\"}\n\
+                  \n\
+                  {\"username\": \"carol\"\n\
+                  {\"username\": \"bobby\", \"email\": \"BOB@example.com\"}\n";
+
+    let (status, report) = post_import(&app, "", "application/x-ndjson", ndjson).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["created"], 1);
+    assert_eq!(report["duplicate"], 1);
+    assert_eq!(report["invalid"], 1);
+    assert_eq!(report["errors"][0]["record"], 2);
+    assert_eq!(repo.list_users(None, 10).await.unwrap().len(), 1);
+
+    let (status, _) = post_import(&app, "", "application/json", "{}").await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[tokio::test]
+async fn exports_stream_every_page_of_users() {
+    let (app, repo) = rest_app();
+    let count = 1234;
+    let users: Vec<_> = (0..count)
+        .map(|i| User {
+            id: Uuid::now_v7(),
+            username: format!("user-{i}"),
+            email: format!("user-{i}@example.com"),
+            version: 1,
+        })
+        .collect();
+    repo.save_users(users.clone()).await.unwrap();
+    repo.delete_user(users[1].id, None).await.unwrap();
+
+    let export = |format: &str| {
+        let request = Request::get(format!("/users/export{format}"))
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(request)
+    };
+
+    let response = export("").await.unwrap();
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/csv");
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let csv = String::from_utf8(bytes.to_vec()).unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines.len(), count);
+    assert_eq!(lines[0], "id,username,email,version");
+    assert_eq!(
+        lines[1],
+        format!("{},user-0,user-0@example.com,1", users[0].id)
+    );
+    assert!(lines[2].contains("user-2"));
+
+    let response = export("?format=ndjson").await.unwrap();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let last: Value =
+        serde_json::from_slice(bytes.split(|&b| b == b'\n').nth(count - 2).unwrap()).unwrap();
+    assert_eq!(last["username"], format!("user-{}", count - 1));
+
+    let response = export("?format=protobuf").await.unwrap();
+    let mut bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let mut exported = 0;
+    while !bytes.is_empty() {
+        GetUserResponse::decode_length_delimited(&mut bytes).unwrap();
+        exported += 1;
+    }
+    assert_eq!(exported, count - 1);
+
+    let response = export("?format=xlsx").await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,121 @@
+//! `serde` on the generated protobuf types follows the proto3 JSON mapping,
+//! the one the REST and Connect routes transcode with.
+
+use coqrs::proto::{
+    google::api::{http_rule::Pattern, HttpRule},
+    well_known::WellKnown,
+    GetUserResponse, ImportStatus, ImportUserResult, ListUsersResponse, UpdateUserRequest,
+};
+use prost_types::{Duration, FieldMask, Timestamp};
+use serde_json::json;
+
+#[test]
+fn messages_use_camel_case_names() {
+    let response = ListUsersResponse {
+        users: vec![GetUserResponse {
+            id: "01917e1c-0000-7000-8000-000000000000".into(),
+            username: "alice".into(),
+            email: "alice@example.com".into(),
+            version: 1,
+        }],
+        next_after: Some("01917e1c-0000-7000-8000-000000000000".into()),
+    };
+
+    let json = serde_json::to_value(&response).unwrap();
+    assert_eq!(json["nextAfter"], "01917e1c-0000-7000-8000-000000000000");
+    assert_eq!(json["users"][0]["username"], "alice");
+    assert_eq!(
+        serde_json::from_value::<ListUsersResponse>(json).unwrap(),
+        response
+    );
+}
+
+#[test]
+fn missing_fields_take_their_defaults() {
+    let request: UpdateUserRequest =
+        serde_json::from_value(json!({ "id": "42", "expectedVersion": 3 })).unwrap();
+    assert_eq!(request.id, "42");
+    assert_eq!(request.username, "");
+    assert_eq!(request.expected_version, Some(3));
+}
+
+#[test]
+fn proto_field_names_are_read_too() {
+    let request: UpdateUserRequest =
+        serde_json::from_value(json!({ "id": "42", "expected_version": 3 })).unwrap();
+    assert_eq!(request.expected_version, Some(3));
+    let response: ListUsersResponse =
+        serde_json::from_value(json!({ "next_after": "42" })).unwrap();
+    assert_eq!(response.next_after.as_deref(), Some("42"));
+    assert!(serde_json::from_value::<UpdateUserRequest>(json!({ "nickname": "al" })).is_err());
+}
+
+#[test]
+fn int64_fields_are_strings_and_enums_are_names() {
+    let result = ImportUserResult {
+        index: 7,
+        status: ImportStatus::Duplicate.into(),
+        ..Default::default()
+    };
+    let json = serde_json::to_value(&result).unwrap();
+    assert_eq!(json["index"], "7");
+    assert_eq!(json["status"], "IMPORT_STATUS_DUPLICATE");
+    assert_eq!(
+        serde_json::from_value::<ImportUserResult>(json).unwrap(),
+        result
+    );
+
+    // Numbers are read as well, and unknown enum numbers kept.
+    let result: ImportUserResult =
+        serde_json::from_value(json!({ "index": 7, "status": 2 })).unwrap();
+    assert_eq!(result.status(), ImportStatus::Duplicate);
+    let json = serde_json::to_value(ImportUserResult {
+        status: 42,
+        ..result
+    })
+    .unwrap();
+    assert_eq!(json["status"], 42);
+    assert!(serde_json::from_value::<ImportUserResult>(json!({ "index": "seven" })).is_err());
+
+    let request: UpdateUserRequest =
+        serde_json::from_value(json!({ "expectedVersion": "3" })).unwrap();
+    assert_eq!(request.expected_version, Some(3));
+    let json = serde_json::to_value(&request).unwrap();
+    assert_eq!(json["expectedVersion"], "3");
+}
+
+#[test]
+fn oneof_fields_sit_beside_the_others() {
+    let rule: HttpRule =
+        serde_json::from_value(json!({ "get": "/v1/users/{id}", "responseBody": "user" })).unwrap();
+    assert_eq!(rule.pattern, Some(Pattern::Get("/v1/users/{id}".into())));
+    assert_eq!(rule.response_body, "user");
+
+    let json = serde_json::to_value(&rule).unwrap();
+    assert_eq!(json["get"], "/v1/users/{id}");
+}
+
+#[test]
+fn well_known_types_use_their_json_form() {
+    let timestamp = Timestamp {
+        seconds: 1_724_662_800,
+        nanos: 500_000_000,
+    };
+    assert_eq!(timestamp.to_json(), json!("2024-08-26T09:00:00.500Z"));
+    assert_eq!(
+        Timestamp::from_json(timestamp.to_json()).unwrap(),
+        timestamp
+    );
+
+    let duration = Duration {
+        seconds: -1,
+        nanos: -500_000_000,
+    };
+    assert_eq!(duration.to_json(), json!("-1.5s"));
+    assert_eq!(Duration::from_json(json!("-1.5s")).unwrap(), duration);
+    assert!(Duration::from_json(json!("1.5")).is_err());
+
+    let mask = FieldMask::from_json(json!("username,expectedVersion")).unwrap();
+    assert_eq!(mask.paths, ["username", "expected_version"]);
+    assert_eq!(mask.to_json(), json!("username,expectedVersion"));
+}
@@ -0,0 +1,227 @@
+//! Predicates of the protocol router, and REST, gRPC-Web and Connect
+//! composed over the in-memory backend the way the binary composes them.
+
+mod common;
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{
+        header::{CONTENT_TYPE, HOST},
+        HeaderName, StatusCode, Version,
+    },
+    Router,
+};
+use common::saved_user;
+use coqrs::{
+    commands::{command_bus, CommandBusConfig},
+    connect::is_connect,
+    grpc_user_connect_services, grpc_user_services,
+    proto::GetUserRequest,
+    user_router, Alpn, BearerTokens, EventBus, InMemory, Predicate, ProtocolRouter,
+};
+use http_body_util::BodyExt;
+use prost::Message;
+use tower::ServiceExt;
+
+/// A backend answering with its name.
+fn backend(name: &'static str) -> Router {
+    Router::new().fallback(move || async move { name })
+}
+
+async fn call(router: &ProtocolRouter, request: Request) -> (StatusCode, String) {
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (status, String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn post(path: &str, content_type: &str) -> Request {
+    Request::post(path)
+        .header(CONTENT_TYPE, content_type)
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn first_matching_backend_serves_the_request() {
+    let router = ProtocolRouter::new()
+        .route(
+            Predicate::content_type("application/grpc-web"),
+            backend("grpc-web"),
+        )
+        .route(Predicate::content_type("application/grpc"), backend("grpc"))
+        .route(Predicate::path_prefix("/admin"), backend("admin"))
+        .fallback(backend("rest"));
+
+    assert_eq!(
+        call(&router, post("/", "application/grpc-web+proto"))
+            .await
+            .1,
+        "grpc-web"
+    );
+    assert_eq!(call(&router, post("/", "Application/GRPC")).await.1, "grpc");
+    assert_eq!(
+        call(&router, post("/admin/x", "application/json")).await.1,
+        "admin"
+    );
+    assert_eq!(
+        call(&router, post("/admin", "application/json")).await.1,
+        "admin"
+    );
+    assert_eq!(
+        call(&router, post("/administrators", "application/json"))
+            .await
+            .1,
+        "rest"
+    );
+    assert_eq!(call(&router, post("/users", "text/plain")).await.1, "rest");
+}
+
+#[tokio::test]
+async fn unmatched_requests_get_415_without_a_fallback() {
+    let router =
+        ProtocolRouter::new().route(Predicate::content_type("application/json"), backend("rest"));
+
+    let (status, _) = call(&router, post("/users", "application/xml")).await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let (status, _) = call(&router, Request::get("/users").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[test]
+fn headers_match_by_presence_or_value() {
+    let name = HeaderName::from_static("connect-protocol-version");
+    let request = Request::post("/")
+        .header(&name, "1")
+        .body(Body::empty())
+        .unwrap();
+
+    assert!(Predicate::header(name.clone()).matches(&request));
+    assert!(Predicate::header_value(name.clone(), "1").matches(&request));
+    assert!(!Predicate::header_value(name, "2").matches(&request));
+    assert!(!Predicate::header(HOST).matches(&request));
+}
+
+#[test]
+fn versions_and_alpn_match_the_connection() {
+    let mut request = Request::post("/")
+        .version(Version::HTTP_2)
+        .body(Body::empty())
+        .unwrap();
+
+    assert!(Predicate::version(Version::HTTP_2).matches(&request));
+    assert!(!Predicate::version(Version::HTTP_11).matches(&request));
+    assert!(!Predicate::alpn(b"h2").matches(&request));
+
+    request.extensions_mut().insert(Alpn(b"h2".to_vec()));
+    assert!(Predicate::alpn(b"h2").matches(&request));
+    assert!(!Predicate::alpn(b"http/1.1").matches(&request));
+}
+
+#[test]
+fn predicates_combine() {
+    let grpc = Predicate::content_type("application/grpc").and(Predicate::version(Version::HTTP_2));
+    let http1 = post("/", "application/grpc");
+    let mut http2 = post("/", "application/grpc");
+    *http2.version_mut() = Version::HTTP_2;
+
+    assert!(!grpc.matches(&http1));
+    assert!(grpc.matches(&http2));
+    assert!((!grpc.clone()).matches(&http1));
+
+    let either = Predicate::path_prefix("/a")
+        .or(Predicate::path_prefix("/b"))
+        .or(Predicate::custom(|request| request.uri().path() == "/c"));
+    for path in ["/a", "/b/1", "/c"] {
+        assert!(either.matches(&post(path, "")), "{path}");
+    }
+    assert!(!either.matches(&post("/d", "")));
+}
+
+/// REST, gRPC-Web and Connect over the in-memory user services.
+fn composed() -> (ProtocolRouter, InMemory) {
+    let repo = InMemory::new();
+    let (bus, _receiver) = command_bus(&CommandBusConfig::default());
+    let events = EventBus::default();
+    let grpc: Router = grpc_user_services(
+        repo.clone(),
+        bus.clone(),
+        events.clone(),
+        BearerTokens::default(),
+    );
+    let router = ProtocolRouter::new()
+        .route(
+            Predicate::content_type("application/grpc-web"),
+            grpc.clone(),
+        )
+        .route(
+            Predicate::content_type("application/grpc").and(Predicate::version(Version::HTTP_2)),
+            grpc,
+        )
+        .route(
+            Predicate::custom(is_connect),
+            grpc_user_connect_services(
+                repo.clone(),
+                bus.clone(),
+                events.clone(),
+                BearerTokens::default(),
+            ),
+        )
+        .route(
+            (!Predicate::header(CONTENT_TYPE)).or(Predicate::content_type("application/json")),
+            user_router(repo.clone(), bus, events, BearerTokens::default()),
+        );
+    (router, repo)
+}
+
+#[tokio::test]
+async fn composed_protocols_reach_the_same_users() {
+    let (router, repo) = composed();
+    let alice = saved_user(&repo, "alice").await;
+
+    let rest = Request::get(format!("/users/{}", alice.id))
+        .body(Body::empty())
+        .unwrap();
+    let (status, body) = call(&router, rest).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("alice"), "{body}");
+
+    let connect = Request::post("/users.UserService/GetUser")
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(format!("{{\"id\": \"{}\"}}", alice.id)))
+        .unwrap();
+    let (status, body) = call(&router, connect).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("alice"), "{body}");
+
+    let message = GetUserRequest {
+        id: alice.id.to_string(),
+    }
+    .encode_to_vec();
+    let mut frame = vec![0];
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&message);
+    let grpc_web = Request::post("/users.UserService/GetUser")
+        .header(CONTENT_TYPE, "application/grpc-web+proto")
+        .body(Body::from(frame))
+        .unwrap();
+    let response = router.clone().oneshot(grpc_web).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[CONTENT_TYPE],
+        "application/grpc-web+proto"
+    );
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    assert!(String::from_utf8_lossy(&bytes).contains("alice"));
+
+    // gRPC needs HTTP/2, and no backend takes XML.
+    let (status, _) = call(
+        &router,
+        post("/users.UserService/GetUser", "application/grpc"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let (status, _) = call(&router, post("/users", "application/xml")).await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
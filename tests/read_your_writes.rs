@@ -0,0 +1,153 @@
+//! Pinning reads to the primary after a write. The replica test needs
+//! `DATABASE_URL` and is skipped otherwise.
+
+mod common;
+
+use std::{convert::Infallible, time::Duration};
+
+use axum::{
+    body::Body,
+    http::{header::CONTENT_TYPE, Request, Response, StatusCode},
+};
+use common::{call as send, postgres, running_bus, user};
+use coqrs::{
+    consistency, db::PgPools, repositories::UserRepository, router, BearerTokens, EventBus,
+    PostgreSQL, ReadYourWritesLayer, READ_PRIMARY_UNTIL,
+};
+use serde_json::json;
+use sqlx::{postgres::PgPoolOptions, Executor, PgPool};
+use tower::{service_fn, Layer, ServiceExt};
+use uuid::Uuid;
+
+/// Echoes whether the request read from the primary, and records a write
+/// for POST requests like the command bus does.
+async fn respond(request: Request<()>) -> Result<Response<bool>, Infallible> {
+    if request.method() == "POST" {
+        consistency::record_write();
+    }
+    Ok(Response::new(consistency::reads_primary()))
+}
+
+async fn call(request: Request<()>) -> Response<bool> {
+    ReadYourWritesLayer::new(Duration::from_secs(2))
+        .layer(service_fn(respond))
+        .oneshot(request)
+        .await
+        .unwrap()
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[tokio::test]
+async fn queries_use_the_replica_by_default() {
+    let response = call(Request::get("/users/1").body(()).unwrap()).await;
+
+    assert!(!response.body());
+    assert!(response.headers().get(READ_PRIMARY_UNTIL).is_none());
+}
+
+#[tokio::test]
+async fn writes_pin_following_reads_to_the_primary() {
+    let response = call(Request::post("/users").body(()).unwrap()).await;
+    let until = response.headers().get(READ_PRIMARY_UNTIL).unwrap().clone();
+
+    let response = call(
+        Request::get("/users/1")
+            .header(READ_PRIMARY_UNTIL, until)
+            .body(())
+            .unwrap(),
+    )
+    .await;
+    assert!(response.body());
+}
+
+#[tokio::test]
+async fn expired_or_forged_deadlines_are_ignored() {
+    for until in [now_ms() - 1, now_ms() + 60_000] {
+        let response = call(
+            Request::get("/users/1")
+                .header(READ_PRIMARY_UNTIL, until)
+                .body(())
+                .unwrap(),
+        )
+        .await;
+        assert!(!response.body());
+    }
+}
+
+/// A replica that has not caught up with anything: its connections see
+/// empty copies of the tables in their own schema.
+async fn lagging_replica(url: &str) -> PgPool {
+    let pool = PgPool::connect(url).await.unwrap();
+    pool.execute(
+        "CREATE SCHEMA IF NOT EXISTS lagging_replica;
+         CREATE TABLE IF NOT EXISTS lagging_replica.users (LIKE public.users INCLUDING ALL);",
+    )
+    .await
+    .unwrap();
+    PgPoolOptions::new()
+        .after_connect(|conn, _| {
+            Box::pin(async move {
+                conn.execute("SET search_path TO lagging_replica").await?;
+                Ok(())
+            })
+        })
+        .connect(url)
+        .await
+        .unwrap()
+}
+
+fn get_user(id: &str, until: Option<&str>) -> Request<Body> {
+    let mut request = Request::get(format!("/users/{id}"));
+    if let Some(until) = until {
+        request = request.header(READ_PRIMARY_UNTIL, until);
+    }
+    request.body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn writers_read_the_primary_inside_their_window() {
+    let Some(primary) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let url = std::env::var("DATABASE_URL").unwrap();
+    let (bus, decoders) = running_bus(&primary);
+    let db = PostgreSQL::from_pools(PgPools {
+        writer: PgPool::connect(&url).await.unwrap(),
+        reader: Some(lagging_replica(&url).await),
+    });
+    let app = ReadYourWritesLayer::new(Duration::from_secs(2)).layer(router(
+        db,
+        bus,
+        decoders,
+        EventBus::default(),
+        BearerTokens::default(),
+        BearerTokens::default(),
+    ));
+    let app = axum::Router::new().fallback_service(app);
+
+    let name = format!("ryw-{}", Uuid::now_v7().simple());
+    let alice = user(&name);
+    primary.save_user(alice.clone()).await.unwrap();
+    let id = &alice.id.to_string();
+    let update = Request::put(format!("/users/{id}"))
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            json!({ "username": format!("{name}-2"), "email": alice.email }).to_string(),
+        ))
+        .unwrap();
+    let (status, headers, _) = send(&app, update).await;
+    assert_eq!(status, StatusCode::OK);
+    let until = headers[READ_PRIMARY_UNTIL].to_str().unwrap();
+
+    let (status, _, _) = send(&app, get_user(id, Some(until))).await;
+    assert_eq!(status, StatusCode::OK);
+    // Without the header the read goes to the replica, still behind.
+    let (status, _, _) = send(&app, get_user(id, None)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
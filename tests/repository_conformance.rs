@@ -0,0 +1,392 @@
+//! One suite run against every `UserRepository` backend. The Postgres run
+//! needs `DATABASE_URL` pointing at a migrated database, is skipped without
+//! it and fails when it cannot be reached. The SQLite run needs the
+//! `sqlite` feature.
+
+mod common;
+
+use coqrs::{
+    models::User,
+    repositories::{RepositoryError, UnitOfWork, UserRepository, UserStore, MAX_PAGE_SIZE},
+};
+use uuid::Uuid;
+
+fn user(name: &str) -> User {
+    // Postgres keeps rows between runs, so names must not repeat.
+    let suffix = Uuid::now_v7().simple().to_string();
+    User {
+        id: Uuid::now_v7(),
+        username: format!("{name}-{suffix}"),
+        email: format!("{name}-{suffix}@example.com"),
+        version: 1,
+    }
+}
+
+fn renamed(user: &User, name: &str) -> User {
+    User {
+        username: format!("{name}-{}", user.id.simple()),
+        ..user.clone()
+    }
+}
+
+async fn saves_and_finds_users<R: UserStore>(repo: R) {
+    let alice = user("alice");
+    repo.save_user(alice.clone()).await.unwrap();
+
+    let found = repo.find_user_by_id(alice.id).await.unwrap().unwrap();
+    assert_eq!(found.username, alice.username);
+    assert_eq!(found.email, alice.email);
+    assert_eq!(found.version, 1);
+}
+
+async fn missing_user_is_none<R: UserStore>(repo: R) {
+    assert!(repo
+        .find_user_by_id(Uuid::now_v7())
+        .await
+        .unwrap()
+        .is_none());
+}
+
+async fn updating_missing_user_is_not_found<R: UserStore>(repo: R) {
+    let result = repo.update_user(user("ghost"), None).await;
+    assert!(matches!(result, Err(RepositoryError::NotFound)));
+}
+
+async fn update_bumps_version<R: UserStore>(repo: R) {
+    let alice = user("alice");
+    repo.save_user(alice.clone()).await.unwrap();
+
+    let updated = repo
+        .update_user(renamed(&alice, "alicia"), Some(1))
+        .await
+        .unwrap();
+    assert_eq!(updated.version, 2);
+    assert!(updated.username.starts_with("alicia"));
+}
+
+async fn stale_version_conflicts<R: UserStore>(repo: R) {
+    let alice = user("alice");
+    repo.save_user(alice.clone()).await.unwrap();
+    repo.update_user(renamed(&alice, "alicia"), Some(1))
+        .await
+        .unwrap();
+
+    let result = repo.update_user(renamed(&alice, "ali"), Some(1)).await;
+    assert!(matches!(
+        result,
+        Err(RepositoryError::Conflict {
+            expected: 1,
+            actual: 2
+        })
+    ));
+}
+
+async fn concurrent_updates_conflict<R: UserStore + Send + Sync>(repo: R) {
+    let alice = user("alice");
+    repo.save_user(alice.clone()).await.unwrap();
+
+    let updates = ["alicia", "ali"].map(|name| {
+        let (repo, user) = (repo.clone(), renamed(&alice, name));
+        tokio::spawn(async move { repo.update_user(user, Some(1)).await })
+    });
+    let mut results = Vec::new();
+    for update in updates {
+        results.push(update.await.unwrap());
+    }
+
+    assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1);
+    assert!(results.iter().any(|r| matches!(
+        r,
+        Err(RepositoryError::Conflict {
+            expected: 1,
+            actual: 2
+        })
+    )));
+    let found = repo.find_user_by_id(alice.id).await.unwrap().unwrap();
+    assert_eq!(found.version, 2);
+}
+
+async fn duplicate_username_is_rejected<R: UserStore>(repo: R) {
+    let alice = user("alice");
+    repo.save_user(alice.clone()).await.unwrap();
+
+    let result = repo
+        .save_user(User {
+            id: Uuid::now_v7(),
+            email: format!("other-{}", alice.email),
+            ..alice.clone()
+        })
+        .await;
+    assert!(matches!(result, Err(RepositoryError::Duplicate(field)) if field == "username"));
+}
+
+async fn duplicate_email_is_rejected<R: UserStore>(repo: R) {
+    let alice = user("alice");
+    repo.save_user(alice.clone()).await.unwrap();
+
+    let result = repo
+        .save_user(User {
+            id: Uuid::now_v7(),
+            username: format!("other-{}", alice.username),
+            ..alice.clone()
+        })
+        .await;
+    assert!(matches!(result, Err(RepositoryError::Duplicate(field)) if field == "email"));
+}
+
+async fn updating_to_taken_username_is_rejected<R: UserStore>(repo: R) {
+    let (alice, bob) = (user("alice"), user("bob"));
+    repo.save_user(alice.clone()).await.unwrap();
+    repo.save_user(bob.clone()).await.unwrap();
+
+    let result = repo
+        .update_user(
+            User {
+                username: alice.username.clone(),
+                ..bob.clone()
+            },
+            None,
+        )
+        .await;
+    assert!(matches!(result, Err(RepositoryError::Duplicate(field)) if field == "username"));
+    let found = repo.find_user_by_id(bob.id).await.unwrap().unwrap();
+    assert_eq!(found.username, bob.username);
+}
+
+async fn lists_pages_in_creation_order<R: UserStore>(repo: R) {
+    // Other cases may insert concurrently, so only the relative order of
+    // these users and the order of every page are checked.
+    let start = Uuid::now_v7();
+    let created = [user("a"), user("b"), user("c")];
+    for user in &created {
+        repo.save_user(user.clone()).await.unwrap();
+    }
+
+    let mut listed = Vec::new();
+    let mut after = Some(start);
+    loop {
+        let page = repo.list_users(after, 2).await.unwrap();
+        assert!(page.len() <= 2);
+        listed.extend(page.iter().map(|user| user.id));
+        match page.last() {
+            Some(last) if page.len() == 2 => after = Some(last.id),
+            _ => break,
+        }
+    }
+
+    assert!(listed.windows(2).all(|ids| ids[0] < ids[1]));
+    let ours: Vec<_> = listed
+        .into_iter()
+        .filter(|id| created.iter().any(|user| user.id == *id))
+        .collect();
+    assert_eq!(ours, created.map(|user| user.id));
+}
+
+async fn out_of_range_limits_are_clamped<R: UserStore>(repo: R) {
+    let start = Some(Uuid::now_v7());
+    for name in ["a", "b"] {
+        repo.save_user(user(name)).await.unwrap();
+    }
+
+    for limit in [0, -1, i64::MIN] {
+        let page = repo.list_users(start, limit).await.unwrap();
+        assert_eq!(page.len(), 1, "{limit}");
+    }
+    let page = repo.list_users(None, i64::MAX).await.unwrap();
+    assert!(!page.is_empty() && page.len() as i64 <= MAX_PAGE_SIZE);
+}
+
+async fn deleted_user_is_hidden<R: UserStore>(repo: R) {
+    let alice = user("alice");
+    repo.save_user(alice.clone()).await.unwrap();
+    repo.delete_user(alice.id, Some(1)).await.unwrap();
+
+    assert!(repo.find_user_by_id(alice.id).await.unwrap().is_none());
+    let listed = repo
+        .list_users(Some(Uuid::from_u128(alice.id.as_u128() - 1)), 1)
+        .await
+        .unwrap();
+    assert!(listed.iter().all(|user| user.id != alice.id));
+    assert!(matches!(
+        repo.update_user(renamed(&alice, "alicia"), None).await,
+        Err(RepositoryError::NotFound)
+    ));
+    assert!(matches!(
+        repo.delete_user(alice.id, None).await,
+        Err(RepositoryError::NotFound)
+    ));
+
+    let events = repo.find_events_by_aggregate_id(alice.id).await.unwrap();
+    let types: Vec<_> = events.iter().map(|e| e.event_type.as_str()).collect();
+    assert_eq!(types, ["UserCreated", "UserDeleted"]);
+}
+
+async fn deleted_username_stays_taken<R: UserStore>(repo: R) {
+    let alice = user("alice");
+    repo.save_user(alice.clone()).await.unwrap();
+    repo.delete_user(alice.id, None).await.unwrap();
+
+    let result = repo
+        .save_user(User {
+            id: Uuid::now_v7(),
+            ..alice.clone()
+        })
+        .await;
+    assert!(matches!(result, Err(RepositoryError::Duplicate(_))));
+}
+
+async fn deleting_missing_user_is_not_found<R: UserStore>(repo: R) {
+    let result = repo.delete_user(Uuid::now_v7(), None).await;
+    assert!(matches!(result, Err(RepositoryError::NotFound)));
+}
+
+async fn stale_delete_conflicts<R: UserStore>(repo: R) {
+    let alice = user("alice");
+    repo.save_user(alice.clone()).await.unwrap();
+    repo.update_user(renamed(&alice, "alicia"), None)
+        .await
+        .unwrap();
+
+    let result = repo.delete_user(alice.id, Some(1)).await;
+    assert!(matches!(
+        result,
+        Err(RepositoryError::Conflict {
+            expected: 1,
+            actual: 2
+        })
+    ));
+    assert!(repo.find_user_by_id(alice.id).await.unwrap().is_some());
+}
+
+async fn writes_record_events_in_order<R: UserStore>(repo: R) {
+    let alice = user("alice");
+    repo.save_user(alice.clone()).await.unwrap();
+    repo.update_user(renamed(&alice, "alicia"), None)
+        .await
+        .unwrap();
+
+    let events = repo.find_events_by_aggregate_id(alice.id).await.unwrap();
+    let types: Vec<_> = events.iter().map(|e| e.event_type.as_str()).collect();
+    assert_eq!(types, ["UserCreated", "UserUpdated"]);
+}
+
+async fn events_resume_after_an_event_in_commit_order<R: UserStore>(repo: R) {
+    let alice = user("alice");
+    repo.save_user(alice.clone()).await.unwrap();
+    repo.update_user(renamed(&alice, "alicia"), None)
+        .await
+        .unwrap();
+    let created = &repo.find_events_by_aggregate_id(alice.id).await.unwrap()[0];
+
+    let events = repo.find_events_after(created.id, 1000).await.unwrap();
+    assert!(events
+        .iter()
+        .all(|event| event.position() > created.position()));
+    assert!(events
+        .windows(2)
+        .all(|pair| pair[0].position() < pair[1].position()));
+    let types: Vec<_> = events
+        .iter()
+        .filter(|e| e.aggregate_id == alice.id)
+        .map(|e| e.event_type.as_str())
+        .collect();
+    assert_eq!(types, ["UserUpdated"]);
+    assert_eq!(
+        repo.find_events_after(Uuid::nil(), 1).await.unwrap().len(),
+        1
+    );
+}
+
+async fn batches_skip_taken_users<R: UserStore>(repo: R) {
+    let alice = user("alice");
+    repo.save_user(alice.clone()).await.unwrap();
+    let bob = user("bob");
+    let carol = user("carol");
+    let batch = vec![
+        bob.clone(),
+        User {
+            email: alice.email.clone(),
+            ..user("dave")
+        },
+        User {
+            id: Uuid::now_v7(),
+            ..bob.clone()
+        },
+        carol.clone(),
+    ];
+
+    let created = repo.save_users(batch).await.unwrap();
+    assert_eq!(created, [bob.id, carol.id]);
+    assert!(repo.find_user_by_id(carol.id).await.unwrap().is_some());
+    let events = repo.find_events_by_aggregate_id(bob.id).await.unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].event_type, "UserCreated");
+    assert!(repo.save_users(Vec::new()).await.unwrap().is_empty());
+}
+
+async fn rolled_back_unit_of_work_leaves_no_trace<R: UserStore>(repo: R) {
+    let alice = user("alice");
+    let uow = repo.begin().await.unwrap();
+    uow.save_user(alice.clone()).await.unwrap();
+    uow.rollback().await.unwrap();
+
+    assert!(repo.find_user_by_id(alice.id).await.unwrap().is_none());
+    assert!(repo
+        .find_events_by_aggregate_id(alice.id)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+/// Expands to one test per case, `$repo` yields `Option<impl UserStore>`
+/// and `None` skips the backend.
+macro_rules! conformance {
+    ($backend:ident, $repo:expr) => {
+        mod $backend {
+            conformance!(@cases $repo;
+                saves_and_finds_users,
+                missing_user_is_none,
+                updating_missing_user_is_not_found,
+                update_bumps_version,
+                stale_version_conflicts,
+                concurrent_updates_conflict,
+                duplicate_username_is_rejected,
+                duplicate_email_is_rejected,
+                updating_to_taken_username_is_rejected,
+                lists_pages_in_creation_order,
+                out_of_range_limits_are_clamped,
+                deleted_user_is_hidden,
+                deleted_username_stays_taken,
+                deleting_missing_user_is_not_found,
+                stale_delete_conflicts,
+                writes_record_events_in_order,
+                events_resume_after_an_event_in_commit_order,
+                batches_skip_taken_users,
+                rolled_back_unit_of_work_leaves_no_trace,
+            );
+        }
+    };
+    (@cases $repo:expr; $($case:ident),* $(,)?) => {
+        $(
+            #[tokio::test]
+            async fn $case() {
+                match $repo {
+                    Some(repo) => super::$case(repo).await,
+                    None => eprintln!("skipping {}", stringify!($case)),
+                }
+            }
+        )*
+    };
+}
+
+conformance!(in_memory, Some(coqrs::InMemory::new()));
+
+conformance!(postgres, crate::common::postgres().await);
+
+#[cfg(feature = "sqlite")]
+conformance!(
+    sqlite,
+    Some(coqrs::SQLite::new(
+        coqrs::db::sqlite_connections("sqlite::memory:").await
+    ))
+);
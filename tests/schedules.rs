@@ -0,0 +1,476 @@
+//! Scheduled commands: leasing due occurrences, moving recurring ones on,
+//! cancelling, and the scheduler running them. These need `DATABASE_URL`
+//! and are skipped otherwise.
+
+mod common;
+
+use std::time::Duration;
+
+use axum::{
+    async_trait,
+    body::Body,
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        Request, StatusCode,
+    },
+};
+use chrono::Utc;
+use common::{call, postgres, running_bus};
+use coqrs::{
+    commands::{
+        command_bus, CommandBus, CommandBusConfig, CommandDecoders, CommandError, CommandHandler,
+        CommandHandlers, CommandScheduler, CommandWorker, OverflowPolicy, SchedulerConfig,
+    },
+    grpc_connect_services, grpc_services,
+    models::ScheduledCommand,
+    proto::{
+        schedule_service_client::ScheduleServiceClient, CreateScheduleRequest, ListSchedulesRequest,
+    },
+    repositories::{DeadLetterRepository, RepositoryError, ScheduleClaim, ScheduleRepository},
+    router, BearerTokens, Command, EventBus, PostgreSQL,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, Mutex, MutexGuard},
+};
+use tonic::Code;
+use uuid::Uuid;
+
+/// Claims take any due schedule, so the tests claiming them take turns.
+static SCHEDULES: Mutex<()> = Mutex::const_new(());
+
+async fn schedules() -> Option<(PostgreSQL, MutexGuard<'static, ()>)> {
+    let db = postgres().await?;
+    Some((db, SCHEDULES.lock().await))
+}
+
+/// A `command_type` command due a second ago.
+fn due(command_type: &str, payload: Value, cron: Option<&str>) -> ScheduledCommand {
+    let now = Utc::now();
+    ScheduledCommand {
+        id: Uuid::now_v7(),
+        command_type: command_type.into(),
+        payload,
+        aggregate_id: None,
+        run_at: now - chrono::Duration::seconds(1),
+        cron: cron.map(Into::into),
+        last_run_at: None,
+        created_at: now,
+        leased_until: None,
+    }
+}
+
+/// The ids of the schedules a claim leased.
+async fn claim(db: &PostgreSQL, lease: Duration) -> Vec<Uuid> {
+    let claim = db.claim_due_schedules(100, lease).await.unwrap();
+    claim
+        .schedules()
+        .iter()
+        .map(|schedule| schedule.id)
+        .collect()
+}
+
+/// The tokens the schedule routes are guarded with.
+fn admin() -> BearerTokens {
+    BearerTokens::new(["secret"])
+}
+
+/// A bodiless request carrying the `admin()` token.
+fn authorized(request: axum::http::request::Builder) -> Request<Body> {
+    request
+        .header(AUTHORIZATION, "Bearer secret")
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// A client of the schedule service of `grpc_services`, served on a free
+/// port.
+async fn schedule_client(
+    db: PostgreSQL,
+    bus: CommandBus,
+    decoders: CommandDecoders,
+) -> ScheduleServiceClient<tonic::transport::Channel> {
+    let app = grpc_services(
+        db,
+        bus,
+        decoders,
+        EventBus::default(),
+        BearerTokens::default(),
+        admin(),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    ScheduleServiceClient::connect(format!("http://{address}"))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn claimed_schedules_stay_hidden_until_their_lease_ends() {
+    let Some((db, _turn)) = schedules().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let schedule = due("Noop", json!({}), None);
+    db.save_schedule(schedule.clone()).await.unwrap();
+
+    let leased = db
+        .claim_due_schedules(100, Duration::from_secs(30))
+        .await
+        .unwrap();
+    let lease = leased
+        .schedules()
+        .iter()
+        .find(|s| s.id == schedule.id)
+        .unwrap();
+    assert!(lease.leased_until.unwrap() > Utc::now());
+    // Reads still show the occurrence it is due at.
+    let stored = db.find_schedule(schedule.id).await.unwrap().unwrap();
+    assert_eq!(stored.run_at, lease.run_at);
+    assert!(stored.run_at < Utc::now());
+    assert_eq!(stored.leased_until, lease.leased_until);
+    assert!(!claim(&db, Duration::from_secs(30))
+        .await
+        .contains(&schedule.id));
+
+    let expiring = due("Noop", json!({}), None);
+    db.save_schedule(expiring.clone()).await.unwrap();
+    assert!(claim(&db, Duration::ZERO).await.contains(&expiring.id));
+    // Not completed before its lease ended.
+    assert!(claim(&db, Duration::ZERO).await.contains(&expiring.id));
+
+    for schedule in [schedule, expiring] {
+        db.delete_schedule(schedule.id).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn claimed_schedules_can_be_cancelled_while_they_run() {
+    let Some((db, _turn)) = schedules().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let schedule = due("Noop", json!({}), None);
+    db.save_schedule(schedule.clone()).await.unwrap();
+    let running = db
+        .claim_due_schedules(100, Duration::from_secs(30))
+        .await
+        .unwrap();
+    assert!(running.schedules().iter().any(|s| s.id == schedule.id));
+
+    // The claim committed its lease and holds no row locks.
+    tokio::time::timeout(Duration::from_secs(5), db.delete_schedule(schedule.id))
+        .await
+        .expect("the claim kept its schedules locked")
+        .unwrap();
+    running.complete(&schedule, Utc::now()).await.unwrap();
+    assert!(db.find_schedule(schedule.id).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn expired_claims_leave_schedules_taken_since_alone() {
+    let Some((db, _turn)) = schedules().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let schedule = due("Noop", json!({}), None);
+    db.save_schedule(schedule.clone()).await.unwrap();
+
+    let expired = db.claim_due_schedules(100, Duration::ZERO).await.unwrap();
+    assert!(expired.schedules().iter().any(|s| s.id == schedule.id));
+    let current = db
+        .claim_due_schedules(100, Duration::from_secs(30))
+        .await
+        .unwrap();
+    assert!(current.schedules().iter().any(|s| s.id == schedule.id));
+
+    // The first claim ran it too late, the second one still owns it.
+    expired.complete(&schedule, Utc::now()).await.unwrap();
+    assert!(db.find_schedule(schedule.id).await.unwrap().is_some());
+    current.complete(&schedule, Utc::now()).await.unwrap();
+    assert!(db.find_schedule(schedule.id).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn completed_recurring_schedules_move_to_their_next_occurrence() {
+    let Some((db, _turn)) = schedules().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let schedule = due("Noop", json!({}), Some("0 0 * * * *"));
+    db.save_schedule(schedule.clone()).await.unwrap();
+    let leased = db
+        .claim_due_schedules(100, Duration::from_secs(30))
+        .await
+        .unwrap();
+    assert!(leased.schedules().iter().any(|s| s.id == schedule.id));
+
+    let fired_at = Utc::now();
+    leased.complete(&schedule, fired_at).await.unwrap();
+    let moved = db.find_schedule(schedule.id).await.unwrap().unwrap();
+    assert_eq!(moved.run_at, schedule.next_run_after(fired_at).unwrap());
+    assert!(moved.run_at > fired_at);
+    assert!(moved.last_run_at.is_some());
+    assert!(!claim(&db, Duration::ZERO).await.contains(&schedule.id));
+
+    db.delete_schedule(schedule.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn cancelled_schedules_are_gone() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let (bus, decoders) = running_bus(&db);
+    let app = router(
+        db,
+        bus,
+        decoders,
+        EventBus::default(),
+        BearerTokens::default(),
+        admin(),
+    );
+    let body = json!({
+        "command_type": "DeleteUser",
+        "payload": { "id": Uuid::now_v7() },
+        "delay_secs": 3600,
+    });
+    let create = Request::post("/schedules")
+        .header(CONTENT_TYPE, "application/json")
+        .header(AUTHORIZATION, "Bearer secret")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let (status, _, body) = call(&app, create).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let schedule: ScheduledCommand = serde_json::from_slice(&body).unwrap();
+    let uri = format!("/schedules/{}", schedule.id);
+
+    let (status, _, _) = call(&app, authorized(Request::get(&uri))).await;
+    assert_eq!(status, StatusCode::OK);
+    for expected in [StatusCode::NO_CONTENT, StatusCode::NOT_FOUND] {
+        let cancel = authorized(Request::delete(&uri));
+        let (status, _, _) = call(&app, cancel).await;
+        assert_eq!(status, expected);
+    }
+    let (status, _, _) = call(&app, authorized(Request::get(&uri))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn out_of_range_delays_are_invalid_arguments() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let (bus, decoders) = running_bus(&db);
+    let payload = json!({ "id": Uuid::now_v7() });
+
+    let app = router(
+        db.clone(),
+        bus.clone(),
+        decoders.clone(),
+        EventBus::default(),
+        BearerTokens::default(),
+        admin(),
+    );
+    for delay in [i64::MAX, i64::MIN] {
+        let body = json!({ "command_type": "DeleteUser", "payload": payload, "delay_secs": delay });
+        let create = Request::post("/schedules")
+            .header(CONTENT_TYPE, "application/json")
+            .header(AUTHORIZATION, "Bearer secret")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let (status, _, _) = call(&app, create).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{delay}");
+    }
+
+    let mut client = schedule_client(db, bus, decoders).await;
+    let mut request = tonic::Request::new(CreateScheduleRequest {
+        command_type: "DeleteUser".into(),
+        payload: payload.to_string(),
+        delay_secs: Some(i64::MAX),
+        ..Default::default()
+    });
+    let token = "Bearer secret".parse().unwrap();
+    request.metadata_mut().insert("authorization", token);
+    let status = client.create_schedule(request).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn schedules_need_an_admin_token() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let (bus, decoders) = running_bus(&db);
+    let events = EventBus::default();
+
+    let app = router(
+        db.clone(),
+        bus.clone(),
+        decoders.clone(),
+        events.clone(),
+        BearerTokens::default(),
+        admin(),
+    );
+    for token in [None, Some("Bearer wrong")] {
+        let mut list = Request::get("/schedules");
+        if let Some(token) = token {
+            list = list.header(AUTHORIZATION, token);
+        }
+        let (status, _, _) = call(&app, list.body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{token:?}");
+    }
+    let (status, _, _) = call(&app, authorized(Request::get("/schedules"))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let connect = grpc_connect_services(
+        db.clone(),
+        bus.clone(),
+        decoders.clone(),
+        events,
+        BearerTokens::default(),
+        admin(),
+    );
+    let list = Request::post("/schedules.ScheduleService/ListSchedules")
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from("{}"))
+        .unwrap();
+    let (status, _, _) = call(&connect, list).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let mut client = schedule_client(db, bus, decoders).await;
+    let status = client
+        .list_schedules(ListSchedulesRequest::default())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Ping {
+    id: Uuid,
+    fail: bool,
+}
+
+impl Command for Ping {
+    const NAME: &'static str = "Ping";
+    type Output = ();
+}
+
+struct Pinged(mpsc::UnboundedSender<Uuid>);
+
+#[async_trait]
+impl CommandHandler<Ping> for Pinged {
+    async fn handle(&self, ping: Ping) -> Result<(), CommandError> {
+        if ping.fail {
+            return Err(RepositoryError::NotFound.into());
+        }
+        let _ = self.0.send(ping.id);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn the_scheduler_runs_due_commands_once_handled() {
+    let Some((db, _turn)) = schedules().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let (pinged, mut pings) = mpsc::unbounded_channel();
+    let (bus, receiver) = command_bus(&CommandBusConfig::default());
+    let pipeline = CommandHandlers::new().register(Pinged(pinged)).build();
+    let decoders = pipeline.decoders.clone();
+    let worker = tokio::spawn(CommandWorker::new(receiver, pipeline).run());
+    let config = SchedulerConfig {
+        poll_interval: Duration::from_millis(10),
+        ..Default::default()
+    };
+    let scheduler = tokio::spawn(CommandScheduler::new(db.clone(), bus, decoders, config).run());
+
+    let id = Uuid::now_v7();
+    let ping = due("Ping", json!({ "id": id, "fail": false }), None);
+    db.save_schedule(ping.clone()).await.unwrap();
+    let failing = due("Ping", json!({ "id": Uuid::now_v7(), "fail": true }), None);
+    db.save_schedule(failing.clone()).await.unwrap();
+
+    let ran = tokio::time::timeout(Duration::from_secs(5), pings.recv()).await;
+    assert_eq!(ran.unwrap(), Some(id));
+    // Completed once the dead letter is saved.
+    let dead_letter = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let dead_letters = db.list_dead_letters(500, 0).await.unwrap();
+            let completed = db.find_schedule(failing.id).await.unwrap().is_none();
+            if let Some(dead_letter) = dead_letters
+                .into_iter()
+                .find(|dead_letter| dead_letter.payload == failing.payload)
+                .filter(|_| completed)
+            {
+                return dead_letter;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("the failing command was not dead-lettered");
+    scheduler.abort();
+    worker.abort();
+
+    assert_eq!(dead_letter.attempts, 1);
+    assert_eq!(dead_letter.last_error, "record not found");
+    assert!(db.find_schedule(ping.id).await.unwrap().is_none());
+    assert!(db.find_schedule(failing.id).await.unwrap().is_none());
+    db.delete_dead_letter(dead_letter.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn overloaded_schedules_wait_for_their_lease() {
+    let Some((db, _turn)) = schedules().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let (pinged, _pings) = mpsc::unbounded_channel();
+    // One full shard, with no worker taking from it.
+    let config = CommandBusConfig {
+        workers: 1,
+        capacity: 1,
+        overflow: OverflowPolicy::Reject,
+        ..Default::default()
+    };
+    let (bus, _receiver) = command_bus(&config);
+    let pipeline = CommandHandlers::new().register(Pinged(pinged)).build();
+    bus.send(Ping {
+        id: Uuid::now_v7(),
+        fail: false,
+    })
+    .await
+    .unwrap();
+    let config = SchedulerConfig {
+        poll_interval: Duration::from_millis(10),
+        lease: Duration::from_secs(30),
+        ..Default::default()
+    };
+    let scheduler = CommandScheduler::new(db.clone(), bus, pipeline.decoders, config);
+    let scheduler = tokio::spawn(scheduler.run());
+
+    let ping = due("Ping", json!({ "id": Uuid::now_v7(), "fail": false }), None);
+    db.save_schedule(ping.clone()).await.unwrap();
+    let leased = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let schedule = db.find_schedule(ping.id).await.unwrap().unwrap();
+            if schedule
+                .leased_until
+                .is_some_and(|until| until > Utc::now())
+            {
+                return schedule;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("the overloaded schedule was not leased");
+    scheduler.abort();
+
+    assert!(leased.last_run_at.is_none());
+    assert!(leased.run_at < Utc::now());
+    assert!(!claim(&db, Duration::ZERO).await.contains(&ping.id));
+    let dead_letters = db.list_dead_letters(500, 0).await.unwrap();
+    assert!(!dead_letters.iter().any(|d| d.payload == ping.payload));
+    db.delete_schedule(ping.id).await.unwrap();
+}
@@ -0,0 +1,210 @@
+//! REST endpoints generated by `build.rs` from `google.api.http`
+//! annotations, served by the gRPC user service over the in-memory backend.
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use common::{running_bus, saved_user};
+use coqrs::{
+    commands::{DeleteUser, UpdateUser},
+    grpc_user_rest_services,
+    proto::{DeleteUserRequest, UpdateUserRequest},
+    repositories::UserRepository,
+    InMemory,
+};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+fn app() -> (Router, InMemory) {
+    let repo = InMemory::new();
+    let (bus, _) = running_bus(&repo);
+    (grpc_user_rest_services(repo.clone(), bus), repo)
+}
+
+async fn call(app: &Router, method: &str, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .body(body)
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[tokio::test]
+async fn path_variables_bind_request_fields() {
+    let (app, repo) = app();
+    let alice = saved_user(&repo, "alice").await;
+
+    let (status, body) = call(&app, "GET", &format!("/users/{}", alice.id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        json!({
+            "id": alice.id.to_string(),
+            "username": "alice",
+            "email": "alice@example.com",
+            "version": "1",
+        })
+    );
+}
+
+#[tokio::test]
+async fn body_and_path_build_the_request() {
+    let (app, repo) = app();
+    let alice = saved_user(&repo, "alice").await;
+
+    let (status, body) = call(
+        &app,
+        "PUT",
+        &format!("/users/{}", alice.id),
+        Some(json!({ "username": "alicia", "email": alice.email, "expectedVersion": 1 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["username"], "alicia");
+    assert_eq!(body["version"], "2");
+}
+
+#[tokio::test]
+async fn query_parameters_bind_request_fields() {
+    let (app, repo) = app();
+    let alice = saved_user(&repo, "alice").await;
+    let bob = saved_user(&repo, "bob").await;
+
+    let (status, body) = call(&app, "GET", "/users?limit=1", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["users"][0]["username"], "alice");
+    assert_eq!(body["nextAfter"], alice.id.to_string());
+
+    let uri = format!("/users?limit=1&after={}", alice.id);
+    let (_, body) = call(&app, "GET", &uri, None).await;
+    assert_eq!(body["users"][0]["id"], bob.id.to_string());
+}
+
+#[tokio::test]
+async fn commands_run_through_the_grpc_service() {
+    let (app, repo) = app();
+    let alice = saved_user(&repo, "alice").await;
+
+    let uri = format!("/users/{}", alice.id);
+    let (status, body) = call(&app, "DELETE", &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({}));
+    assert!(repo.find_user_by_id(alice.id).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn grpc_errors_map_to_http_statuses() {
+    let (app, repo) = app();
+    let alice = saved_user(&repo, "alice").await;
+
+    let (status, body) = call(&app, "GET", &format!("/users/{}", Uuid::now_v7()), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], 5);
+
+    let (status, _) = call(&app, "GET", "/users/not-a-uuid", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, body) = call(
+        &app,
+        "PUT",
+        &format!("/users/{}", alice.id),
+        Some(json!({ "username": "alicia", "email": alice.email, "expectedVersion": "7" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], 10);
+}
+
+#[tokio::test]
+async fn usernames_and_emails_are_validated_like_imports() {
+    let (app, repo) = app();
+    let alice = saved_user(&repo, "alice").await;
+
+    let (status, body) = call(
+        &app,
+        "POST",
+        "/users",
+        Some(json!({ "username": "bob", "email": "bob" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "email is not an address");
+
+    let uri = format!("/users/{}", alice.id);
+    let (status, body) = call(
+        &app,
+        "PUT",
+        &uri,
+        Some(json!({ "username": "  ", "email": alice.email })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "username is empty");
+
+    let (status, body) = call(
+        &app,
+        "PUT",
+        &uri,
+        Some(json!({ "username": " alicia ", "email": "Alicia@Example.COM" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["username"], "alicia");
+    assert_eq!(body["email"], "alicia@example.com");
+}
+
+#[tokio::test]
+async fn unknown_fields_are_rejected() {
+    let (app, _) = app();
+
+    let (status, body) = call(&app, "POST", "/users", Some(json!({ "nickname": "al" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["message"], "unknown field nickname");
+}
+
+#[tokio::test]
+async fn oversized_bodies_are_refused() {
+    let (app, _) = app();
+    let username = "a".repeat(3 * 1024 * 1024);
+    let request = Request::post("/users")
+        .body(Body::from(json!({ "username": username }).to_string()))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[test]
+fn request_messages_convert_to_commands() {
+    let id = Uuid::now_v7();
+    let command = UpdateUser::try_from(UpdateUserRequest {
+        id: id.to_string(),
+        username: "alicia".into(),
+        email: "alicia@example.com".into(),
+        expected_version: Some(3),
+    })
+    .unwrap();
+    assert_eq!(command.id, id);
+    assert_eq!(command.username, "alicia");
+    assert_eq!(command.expected_version, Some(3));
+
+    let command = DeleteUser::try_from(DeleteUserRequest {
+        id: id.to_string(),
+        expected_version: None,
+    })
+    .unwrap();
+    assert_eq!(command.expected_version, None);
+
+    assert!(DeleteUser::try_from(DeleteUserRequest::default()).is_err());
+}
@@ -0,0 +1,236 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::async_trait;
+use coqrs::{
+    commands::{
+        command_bus, CommandBusConfig, CommandError, CommandHandler, CommandHandlers,
+        CommandWorker, RetryLayer,
+    },
+    models::User,
+    repositories::{RepositoryError, Transactional, UnitOfWork, UserRepository},
+    Command, EventBus, InMemory,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+mod common;
+
+use common::{postgres, renamed, user};
+
+#[tokio::test]
+async fn commit_makes_writes_visible() {
+    let repo = InMemory::new();
+    let alice = user("alice");
+
+    let uow = repo.begin().await.unwrap();
+    uow.save_user(alice.clone()).await.unwrap();
+    assert!(uow.find_user_by_id(alice.id).await.unwrap().is_some());
+    uow.commit().await.unwrap();
+
+    assert!(repo.find_user_by_id(alice.id).await.unwrap().is_some());
+    assert_eq!(
+        repo.find_events_by_aggregate_id(alice.id)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn rollback_discards_every_write() {
+    let repo = InMemory::new();
+    let alice = user("alice");
+    repo.save_user(alice.clone()).await.unwrap();
+    let bob = user("bob");
+
+    let uow = repo.begin().await.unwrap();
+    uow.save_user(bob.clone()).await.unwrap();
+    uow.update_user(renamed(&alice, "alicia"), Some(1))
+        .await
+        .unwrap();
+    uow.rollback().await.unwrap();
+
+    assert!(repo.find_user_by_id(bob.id).await.unwrap().is_none());
+    let alice = repo.find_user_by_id(alice.id).await.unwrap().unwrap();
+    assert_eq!(alice.username, "alice");
+    assert_eq!(alice.version, 1);
+}
+
+#[tokio::test]
+async fn dropping_without_commit_rolls_back() {
+    let repo = InMemory::new();
+    let alice = user("alice");
+
+    {
+        let uow = repo.begin().await.unwrap();
+        uow.save_user(alice.clone()).await.unwrap();
+    }
+
+    assert!(repo.find_user_by_id(alice.id).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn failed_step_leaves_earlier_steps_uncommitted() {
+    let repo = InMemory::new();
+    let alice = user("alice");
+    repo.save_user(alice.clone()).await.unwrap();
+    let bob = user("bob");
+
+    let uow = repo.begin().await.unwrap();
+    uow.save_user(bob.clone()).await.unwrap();
+    let conflict = uow.update_user(renamed(&alice, "alicia"), Some(7)).await;
+    assert!(matches!(
+        conflict,
+        Err(RepositoryError::Conflict {
+            expected: 7,
+            actual: 1
+        })
+    ));
+    drop(uow);
+
+    assert!(repo.find_user_by_id(bob.id).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn events_are_published_after_commit() {
+    let events = EventBus::default();
+    let mut subscriber = events.subscribe();
+    let repo = InMemory::new().with_event_bus(events);
+    let alice = user("alice");
+
+    let uow = repo.begin().await.unwrap();
+    uow.save_user(alice.clone()).await.unwrap();
+    assert!(subscriber.try_recv().is_err());
+    uow.commit().await.unwrap();
+
+    let event = subscriber.try_recv().unwrap();
+    assert_eq!(event.event_type, "UserCreated");
+    assert_eq!(event.aggregate_id, alice.id);
+}
+
+#[tokio::test]
+async fn plain_calls_do_not_wait_for_an_open_unit_of_work() {
+    let repo = InMemory::new();
+    let alice = user("alice");
+    let bob = user("bob");
+
+    let uow = repo.begin().await.unwrap();
+    uow.save_user(alice.clone()).await.unwrap();
+    let plain = tokio::time::timeout(Duration::from_secs(1), async {
+        assert!(repo.find_user_by_id(alice.id).await.unwrap().is_none());
+        repo.save_user(bob.clone()).await.unwrap();
+    });
+    plain
+        .await
+        .expect("a plain call waited for the unit of work");
+
+    // Committing would lose bob.
+    let error = uow.commit().await.unwrap_err();
+    assert!(matches!(error, RepositoryError::Serialization));
+    assert!(error.is_transient());
+    assert!(repo.find_user_by_id(alice.id).await.unwrap().is_none());
+    assert!(repo.find_user_by_id(bob.id).await.unwrap().is_some());
+
+    let uow = repo.begin().await.unwrap();
+    uow.save_user(alice.clone()).await.unwrap();
+    uow.commit().await.unwrap();
+    assert!(repo.find_user_by_id(alice.id).await.unwrap().is_some());
+}
+
+/// Saves `user` in a unit of work.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Register {
+    user: User,
+}
+
+impl Command for Register {
+    const NAME: &'static str = "Register";
+    type Output = ();
+}
+
+/// Registers users, while a plain write lands during its first attempt.
+struct Racing {
+    repo: InMemory,
+    attempts: AtomicUsize,
+}
+
+#[async_trait]
+impl CommandHandler<Register> for Arc<Racing> {
+    async fn handle(&self, command: Register) -> Result<(), CommandError> {
+        let uow = self.repo.begin().await?;
+        uow.save_user(command.user).await?;
+        if self.attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+            self.repo.save_user(user("bob")).await?;
+        }
+        uow.commit().await?;
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn units_of_work_overtaken_by_a_write_are_retried() {
+    let repo = InMemory::new();
+    let racing = Arc::new(Racing {
+        repo: repo.clone(),
+        attempts: AtomicUsize::new(0),
+    });
+    let pipeline = CommandHandlers::new()
+        .layer(RetryLayer::new(1, Duration::from_millis(1)))
+        .register::<Register, _>(racing.clone())
+        .build();
+    let (bus, receiver) = command_bus(&CommandBusConfig::default());
+    tokio::spawn(CommandWorker::new(receiver, pipeline).run());
+    let alice = user("alice");
+
+    bus.dispatch(Register {
+        user: alice.clone(),
+    })
+    .await
+    .unwrap();
+    assert_eq!(racing.attempts.load(Ordering::SeqCst), 2);
+    assert!(repo.find_user_by_id(alice.id).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn postgres_units_of_work_commit_or_roll_back_together() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let named = |name: &str| user(&format!("{name}-{}", Uuid::now_v7().simple()));
+    let (committed, rolled_back, dropped) = (named("uow"), named("uow"), named("uow"));
+
+    let uow = db.begin().await.unwrap();
+    uow.save_user(committed.clone()).await.unwrap();
+    assert!(db.find_user_by_id(committed.id).await.unwrap().is_none());
+    uow.commit().await.unwrap();
+    assert!(db.find_user_by_id(committed.id).await.unwrap().is_some());
+    let events = db.find_events_by_aggregate_id(committed.id).await.unwrap();
+    assert_eq!(events.len(), 1);
+
+    let uow = db.begin().await.unwrap();
+    uow.save_user(rolled_back.clone()).await.unwrap();
+    uow.update_user(renamed(&committed, &named("renamed").username), Some(1))
+        .await
+        .unwrap();
+    uow.rollback().await.unwrap();
+    assert!(db.find_user_by_id(rolled_back.id).await.unwrap().is_none());
+    let stored = db.find_user_by_id(committed.id).await.unwrap().unwrap();
+    assert_eq!(stored.version, 1);
+
+    {
+        let uow = db.begin().await.unwrap();
+        uow.save_user(dropped.clone()).await.unwrap();
+    }
+    assert!(db.find_user_by_id(dropped.id).await.unwrap().is_none());
+    assert!(db
+        .find_events_by_aggregate_id(dropped.id)
+        .await
+        .unwrap()
+        .is_empty());
+}
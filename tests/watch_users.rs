@@ -0,0 +1,287 @@
+//! The user event feed: replaying stored events, following live ones, and
+//! serving them over `WatchUsers` and `GET /users/events`.
+
+mod common;
+
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header::CONTENT_TYPE, StatusCode},
+    Router,
+};
+use common::{postgres, renamed, user};
+use coqrs::{
+    commands::{command_bus, CommandBusConfig},
+    connect, event_feed,
+    events::StoredEvent,
+    grpc_user_connect_services,
+    repositories::{Transactional, UnitOfWork, UserRepository},
+    user_router, BearerTokens, EventBus, EventFilter, FeedError, InMemory, PgEventListener,
+    PostgreSQL,
+};
+use futures_util::StreamExt;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+/// Reads the streamed body until `done` holds for what came so far.
+async fn read_until(body: Body, done: impl Fn(&[u8]) -> bool) -> Vec<u8> {
+    let mut body = body.into_data_stream();
+    let mut bytes = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !done(&bytes) {
+            bytes.extend_from_slice(&body.next().await.unwrap().unwrap());
+        }
+    })
+    .await
+    .expect("the feed stalled");
+    bytes
+}
+
+#[tokio::test]
+async fn feed_replays_stored_events_then_follows_live_ones() {
+    let events = EventBus::default();
+    let repo = InMemory::new().with_event_bus(events.clone());
+    let alice = user("alice");
+    let bob = user("bob");
+    repo.save_user(alice.clone()).await.unwrap();
+    repo.save_user(bob.clone()).await.unwrap();
+    repo.update_user(renamed(&alice, "alicia"), None)
+        .await
+        .unwrap();
+    let created = repo.find_events_by_aggregate_id(alice.id).await.unwrap()[0].id;
+
+    let filter = EventFilter {
+        aggregate_ids: vec![alice.id],
+        ..Default::default()
+    };
+    let mut feed = Box::pin(event_feed(repo.clone(), &events, filter, Some(created)));
+    repo.delete_user(bob.id, None).await.unwrap();
+    repo.delete_user(alice.id, None).await.unwrap();
+
+    let mut types = Vec::new();
+    for _ in 0..2 {
+        types.push(feed.next().await.unwrap().unwrap().event_type);
+    }
+    assert_eq!(types, ["UserUpdated", "UserDeleted"]);
+    // The deletion was published while replaying, and is not sent twice.
+    let again = tokio::time::timeout(Duration::from_millis(50), feed.next()).await;
+    assert!(again.is_err());
+}
+
+#[tokio::test]
+async fn slow_subscribers_are_told_to_resume() {
+    let events = EventBus::new(1);
+    let repo = InMemory::new().with_event_bus(events.clone());
+    let mut feed = Box::pin(event_feed(
+        repo.clone(),
+        &events,
+        EventFilter::default(),
+        None,
+    ));
+    for name in ["alice", "bob", "carol"] {
+        repo.save_user(user(name)).await.unwrap();
+    }
+
+    assert!(matches!(feed.next().await, Some(Err(FeedError::Lagged(2)))));
+    assert!(feed.next().await.is_none());
+}
+
+#[tokio::test]
+async fn watch_users_streams_events_and_heartbeats_over_connect() {
+    let events = EventBus::default();
+    let repo = InMemory::new().with_event_bus(events.clone());
+    let (bus, _receiver) = command_bus(&CommandBusConfig::default());
+    let app: Router =
+        grpc_user_connect_services(repo.clone(), bus, events, BearerTokens::default());
+
+    let message = json!({ "eventTypes": ["UserCreated"], "heartbeatSeconds": 1 }).to_string();
+    let mut body = vec![0];
+    body.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    body.extend_from_slice(message.as_bytes());
+    let request = Request::post("/users.UserService/WatchUsers")
+        .header(CONTENT_TYPE, "application/connect+json")
+        .header(connect::PROTOCOL_VERSION, "1")
+        .body(Body::from(body))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let alice = user("alice");
+    repo.save_user(alice.clone()).await.unwrap();
+    repo.update_user(renamed(&alice, "alicia"), None)
+        .await
+        .unwrap();
+
+    // The created event, then a heartbeat as the update is filtered out.
+    let bytes = read_until(response.into_body(), |bytes| {
+        String::from_utf8_lossy(bytes).contains("\"heartbeat\":true")
+    })
+    .await;
+    let length = u32::from_be_bytes(bytes[1..5].try_into().unwrap()) as usize;
+    let created: Value = serde_json::from_slice(&bytes[5..5 + length]).unwrap();
+    assert_eq!(created["eventType"], "UserCreated");
+    assert_eq!(created["userId"], alice.id.to_string());
+    assert_eq!(created["username"], "alice");
+    let heartbeat: Value = serde_json::from_slice(&bytes[10 + length..]).unwrap();
+    assert_eq!(heartbeat["heartbeat"], true);
+    assert_eq!(heartbeat["eventType"], "");
+}
+
+#[tokio::test]
+async fn server_sent_events_resume_from_the_last_event_id() {
+    let events = EventBus::default();
+    let repo = InMemory::new().with_event_bus(events.clone());
+    let (bus, _receiver) = command_bus(&CommandBusConfig::default());
+    let app = user_router(repo.clone(), bus, events, BearerTokens::default());
+    let alice = user("alice");
+    repo.save_user(alice.clone()).await.unwrap();
+    let created = repo.find_events_by_aggregate_id(alice.id).await.unwrap()[0].id;
+    repo.update_user(renamed(&alice, "alicia"), None)
+        .await
+        .unwrap();
+
+    let request = Request::get(format!("/users/events?ids={}", alice.id))
+        .header("last-event-id", created.to_string())
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/event-stream");
+    repo.delete_user(alice.id, None).await.unwrap();
+
+    let bytes = read_until(response.into_body(), |bytes| {
+        String::from_utf8_lossy(bytes).contains("event: UserDeleted")
+    })
+    .await;
+    let text = String::from_utf8(bytes).unwrap();
+    assert!(!text.contains("UserCreated"), "{text}");
+    let updated = text.find("event: UserUpdated").unwrap();
+    assert!(updated < text.find("event: UserDeleted").unwrap());
+    assert!(text.contains("\"username\":\"alicia\""), "{text}");
+
+    let request = Request::get("/users/events")
+        .header("last-event-id", "nope")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn events_of_other_types_are_skipped() {
+    let events = EventBus::default();
+    let repo = InMemory::new().with_event_bus(events.clone());
+    let (bus, _receiver) = command_bus(&CommandBusConfig::default());
+    let app = user_router(repo.clone(), bus, events.clone(), BearerTokens::default());
+    let alice = user("alice");
+    repo.save_user(alice.clone()).await.unwrap();
+
+    let request = Request::get("/users/events").body(Body::empty()).unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    events.publish(StoredEvent {
+        id: Uuid::now_v7(),
+        aggregate_id: Uuid::now_v7(),
+        event_type: "OrderPlaced".into(),
+        version: 1,
+        payload: json!({}),
+        sequence: 1,
+        transaction: 0,
+    });
+    repo.delete_user(alice.id, None).await.unwrap();
+
+    let bytes = read_until(response.into_body(), |bytes| {
+        String::from_utf8_lossy(bytes).contains("event: UserDeleted")
+    })
+    .await;
+    let text = String::from_utf8(bytes).unwrap();
+    assert!(!text.contains("OrderPlaced"), "{text}");
+    assert!(!text.contains("event: error"), "{text}");
+}
+
+#[tokio::test]
+async fn committed_events_reach_every_listening_process() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let events = EventBus::default();
+    tokio::spawn(PgEventListener::new(db.clone(), events.clone()).run());
+    let mut received = events.subscribe();
+
+    // Saved without an event bus, as by another process. The listener
+    // publishes what commits once it is listening.
+    let alice = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let alice = user(&format!("listened-{}", Uuid::now_v7().simple()));
+            db.save_user(alice.clone()).await.unwrap();
+            let published = tokio::time::timeout(Duration::from_millis(200), async {
+                loop {
+                    let event = received.recv().await.unwrap();
+                    if event.aggregate_id == alice.id {
+                        return event;
+                    }
+                }
+            });
+            if let Ok(created) = published.await {
+                assert_eq!(created.event_type, "UserCreated");
+                assert!(created.sequence > 0);
+                return (alice, created);
+            }
+        }
+    })
+    .await
+    .expect("the listener published nothing");
+    let (alice, created) = alice;
+
+    let filter = EventFilter {
+        aggregate_ids: vec![alice.id],
+        ..Default::default()
+    };
+    let mut feed = Box::pin(event_feed(db.clone(), &events, filter, Some(created.id)));
+    let name = format!("renamed-{}", Uuid::now_v7().simple());
+    db.update_user(renamed(&alice, &name), None).await.unwrap();
+    let updated = tokio::time::timeout(Duration::from_secs(5), feed.next())
+        .await
+        .expect("the feed stalled")
+        .unwrap()
+        .unwrap();
+    assert_eq!(updated.event_type, "UserUpdated");
+    assert!(updated.position() > created.position());
+}
+
+#[tokio::test]
+async fn concurrent_writers_do_not_wait_for_each_other() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let named = |prefix| user(&format!("{prefix}-{}", Uuid::now_v7().simple()));
+    let start = named("start");
+    db.save_user(start.clone()).await.unwrap();
+    let start = db.find_events_by_aggregate_id(start.id).await.unwrap()[0].id;
+    let (alice, bob) = (named("alice"), named("bob"));
+    let ids = [alice.id, bob.id];
+    // The users of this test whose events can be read, in the feed order.
+    let saved = |db: PostgreSQL| async move {
+        let events = db.find_events_after(start, 1000).await.unwrap();
+        events
+            .into_iter()
+            .map(|event| event.aggregate_id)
+            .filter(|id| ids.contains(id))
+            .collect::<Vec<_>>()
+    };
+
+    let first = db.begin().await.unwrap();
+    first.save_user(alice.clone()).await.unwrap();
+    tokio::time::timeout(Duration::from_secs(5), db.save_user(bob.clone()))
+        .await
+        .expect("the second writer waited for the first")
+        .unwrap();
+    // Bob's event is held back until the older transaction ends, so it
+    // cannot be read before Alice's.
+    assert!(saved(db.clone()).await.is_empty());
+
+    first.commit().await.unwrap();
+    assert_eq!(saved(db).await, [alice.id, bob.id]);
+}
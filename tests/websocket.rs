@@ -0,0 +1,376 @@
+//! The `/ws` envelope protocol over a real connection: tokens shared with
+//! the REST routes and user RPCs, user commands with their results, and
+//! event subscriptions.
+
+mod common;
+
+use std::time::Duration;
+
+use std::net::SocketAddr;
+
+use axum::{
+    body::Body,
+    http::{header::CONTENT_TYPE, Request},
+    Router,
+};
+use common::{call, running_bus, saved_user};
+use coqrs::{
+    commands::{command_bus, CommandBusConfig, CommandHandlers, CommandWorker},
+    grpc_user_connect_services, grpc_user_services,
+    proto::{user_service_client::UserServiceClient, ListUsersRequest},
+    services::{LogMailer, MailService, UserService},
+    user_router, websocket_router, BearerTokens, EventBus, InMemory,
+};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, http::StatusCode, Error, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+use tonic::Code;
+use uuid::Uuid;
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// The user routes and `/ws` for `tokens`, as the binary merges them, over
+/// the in-memory backend and a bus that also runs `SendWelcomeEmail`.
+fn app(tokens: BearerTokens) -> (Router, InMemory) {
+    let events = EventBus::default();
+    let repo = InMemory::new().with_event_bus(events.clone());
+    let (bus, receiver) = command_bus(&CommandBusConfig::default());
+    let handlers = UserService::new(repo.clone(), bus.clone()).register(CommandHandlers::new());
+    let pipeline = MailService::new(LogMailer).register(handlers).build();
+    let decoders = pipeline.decoders.clone();
+    tokio::spawn(CommandWorker::new(receiver, pipeline).run());
+    let app = user_router(repo.clone(), bus.clone(), events.clone(), tokens.clone())
+        .merge(websocket_router(bus, decoders, events, tokens));
+    (app, repo)
+}
+
+/// Serves [`app`] on a free port for `tokens`.
+async fn serve_with(tokens: BearerTokens) -> (SocketAddr, InMemory) {
+    let (app, repo) = app(tokens);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (address, repo)
+}
+
+/// Serves [`app`] for the token `secret`.
+async fn serve() -> (SocketAddr, InMemory) {
+    serve_with(BearerTokens::new(["secret"])).await
+}
+
+async fn connect() -> (Client, InMemory) {
+    let (address, repo) = serve().await;
+    let url = format!("ws://{address}/ws?access_token=secret");
+    let (client, _) = connect_async(url).await.unwrap();
+    (client, repo)
+}
+
+async fn send(client: &mut Client, message: Value) {
+    client
+        .send(Message::Text(message.to_string()))
+        .await
+        .unwrap();
+}
+
+async fn receive(client: &mut Client) -> Value {
+    let message = tokio::time::timeout(Duration::from_secs(5), client.next())
+        .await
+        .expect("no reply")
+        .unwrap()
+        .unwrap();
+    serde_json::from_str(message.to_text().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn connections_need_a_token() {
+    let (address, _) = serve().await;
+
+    for query in ["", "?access_token=wrong", "?access_token="] {
+        let refused = connect_async(format!("ws://{address}/ws{query}")).await;
+        let Err(Error::Http(response)) = refused else {
+            panic!("{query} was accepted");
+        };
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let mut request = format!("ws://{address}/ws").into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert("authorization", "Bearer secret".parse().unwrap());
+    connect_async(request).await.unwrap();
+}
+
+#[tokio::test]
+async fn rest_and_websockets_share_their_authentication() {
+    let (guarded, _) = app(BearerTokens::new(["secret"]));
+    let users = |authorization: Option<&str>| {
+        let request = Request::get("/users");
+        match authorization {
+            Some(authorization) => request.header("authorization", authorization),
+            None => request,
+        }
+        .body(Body::empty())
+        .unwrap()
+    };
+    for authorization in [None, Some("Bearer wrong")] {
+        let (status, _, _) = call(&guarded, users(authorization)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{authorization:?}");
+    }
+    let (status, _, _) = call(&guarded, users(Some("Bearer secret"))).await;
+    assert_eq!(status, StatusCode::OK);
+    // Only `/ws` takes the token from the query.
+    let query = Request::get("/users?access_token=secret")
+        .body(Body::empty())
+        .unwrap();
+    let (status, _, _) = call(&guarded, query).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let ready = Request::get("/health/ready").body(Body::empty()).unwrap();
+    let (status, _, _) = call(&guarded, ready).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Without tokens both are open.
+    let (open, _) = app(BearerTokens::default());
+    let (status, _, _) = call(&open, users(None)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (address, _) = serve_with(BearerTokens::default()).await;
+    connect_async(format!("ws://{address}/ws")).await.unwrap();
+}
+
+#[tokio::test]
+async fn user_rpcs_need_a_token_over_grpc() {
+    let repo = InMemory::new();
+    let (bus, _) = running_bus(&repo);
+    let app = grpc_user_services(
+        repo,
+        bus,
+        EventBus::default(),
+        BearerTokens::new(["secret"]),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let mut client = UserServiceClient::connect(format!("http://{address}"))
+        .await
+        .unwrap();
+    let list = |authorization: Option<&str>| {
+        let mut request = tonic::Request::new(ListUsersRequest::default());
+        if let Some(authorization) = authorization {
+            let value = authorization.parse().unwrap();
+            request.metadata_mut().insert("authorization", value);
+        }
+        request
+    };
+
+    for authorization in [None, Some("Bearer wrong")] {
+        let status = client.list_users(list(authorization)).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated, "{authorization:?}");
+    }
+    client
+        .list_users(list(Some("Bearer secret")))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn user_rpcs_need_a_token_over_connect() {
+    let repo = InMemory::new();
+    let (bus, _) = running_bus(&repo);
+    let app = grpc_user_connect_services(
+        repo,
+        bus,
+        EventBus::default(),
+        BearerTokens::new(["secret"]),
+    );
+    let list = |authorization: Option<&str>| {
+        let request =
+            Request::post("/users.UserService/ListUsers").header(CONTENT_TYPE, "application/json");
+        match authorization {
+            Some(authorization) => request.header("authorization", authorization),
+            None => request,
+        }
+        .body(Body::from("{}"))
+        .unwrap()
+    };
+
+    for authorization in [None, Some("Bearer wrong")] {
+        let (status, _, _) = call(&app, list(authorization)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{authorization:?}");
+    }
+    let (status, _, _) = call(&app, list(Some("Bearer secret"))).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn only_user_commands_are_dispatched() {
+    let (mut client, _) = connect().await;
+
+    send(
+        &mut client,
+        json!({
+            "type": "command",
+            "id": "1",
+            "command": "SendWelcomeEmail",
+            "payload": { "user_id": Uuid::now_v7(), "username": "alice", "email": "alice@example.com" },
+        }),
+    )
+    .await;
+    assert_eq!(
+        receive(&mut client).await,
+        json!({ "type": "error", "id": "1", "message": "unknown command SendWelcomeEmail" })
+    );
+}
+
+#[tokio::test]
+async fn commands_reply_with_their_output() {
+    let (mut client, repo) = connect().await;
+    let alice = saved_user(&repo, "alice").await;
+
+    send(
+        &mut client,
+        json!({
+            "type": "command",
+            "id": "1",
+            "command": "UpdateUser",
+            "payload": { "id": alice.id, "username": "alicia", "email": alice.email },
+        }),
+    )
+    .await;
+    let reply = receive(&mut client).await;
+    assert_eq!(reply["type"], "result");
+    assert_eq!(reply["id"], "1");
+    assert_eq!(reply["output"]["username"], "alicia");
+    assert_eq!(reply["output"]["version"], 2);
+
+    send(
+        &mut client,
+        json!({
+            "type": "command",
+            "id": "2",
+            "command": "DeleteUser",
+            "payload": { "id": Uuid::now_v7() },
+        }),
+    )
+    .await;
+    assert_eq!(
+        receive(&mut client).await,
+        json!({ "type": "error", "id": "2", "message": "record not found" })
+    );
+
+    send(
+        &mut client,
+        json!({ "type": "command", "id": "3", "command": "Nope" }),
+    )
+    .await;
+    assert_eq!(
+        receive(&mut client).await,
+        json!({ "type": "error", "id": "3", "message": "unknown command Nope" })
+    );
+}
+
+#[tokio::test]
+async fn subscriptions_push_matching_events() {
+    let (mut client, repo) = connect().await;
+    let alice = saved_user(&repo, "alice").await;
+
+    let topic = format!("users/{}", alice.id);
+    send(
+        &mut client,
+        json!({ "type": "subscribe", "id": "1", "topic": topic }),
+    )
+    .await;
+    assert_eq!(
+        receive(&mut client).await,
+        json!({ "type": "subscribed", "id": "1", "topic": topic })
+    );
+
+    send(
+        &mut client,
+        json!({
+            "type": "command",
+            "id": "2",
+            "command": "DeleteUser",
+            "payload": { "id": alice.id },
+        }),
+    )
+    .await;
+    let mut replies = [receive(&mut client).await, receive(&mut client).await];
+    replies.sort_by_key(|reply| reply["type"].as_str().unwrap().to_owned());
+    assert_eq!(replies[0]["type"], "event");
+    assert_eq!(replies[0]["topic"], topic);
+    assert_eq!(replies[0]["event"]["eventType"], "UserDeleted");
+    assert_eq!(replies[0]["event"]["userId"], alice.id.to_string());
+    assert_eq!(
+        replies[1],
+        json!({ "type": "result", "id": "2", "output": null })
+    );
+
+    for (id, kind) in [("3", "subscribe"), ("4", "unsubscribe")] {
+        send(
+            &mut client,
+            json!({ "type": kind, "id": id, "topic": "UserCreated" }),
+        )
+        .await;
+        assert_eq!(receive(&mut client).await["type"], format!("{kind}d"));
+    }
+    // Nothing is pushed for the topic unsubscribed from.
+    saved_user(&repo, "bob").await;
+    send(
+        &mut client,
+        json!({ "type": "subscribe", "id": "5", "topic": "Nope" }),
+    )
+    .await;
+    assert_eq!(
+        receive(&mut client).await,
+        json!({ "type": "error", "id": "5", "message": "unknown topic Nope" })
+    );
+}
+
+#[tokio::test]
+async fn commands_beyond_the_pending_limit_are_refused() {
+    // Nothing runs the commands, so every dispatched one stays pending.
+    let config = CommandBusConfig {
+        capacity: 128,
+        ..Default::default()
+    };
+    let (bus, _receiver) = command_bus(&config);
+    let decoders = UserService::new(InMemory::new(), bus.clone())
+        .register(CommandHandlers::new())
+        .build()
+        .decoders;
+    let app = websocket_router(bus, decoders, EventBus::default(), BearerTokens::default());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let (mut client, _) = connect_async(format!("ws://{address}/ws")).await.unwrap();
+
+    for id in 1..=65 {
+        let delete = json!({
+            "type": "command",
+            "id": id.to_string(),
+            "command": "DeleteUser",
+            "payload": { "id": Uuid::now_v7() },
+        });
+        send(&mut client, delete).await;
+    }
+    let reply = receive(&mut client).await;
+    assert_eq!(reply["type"], "error");
+    assert_eq!(reply["id"], "65");
+}
+
+#[tokio::test]
+async fn unreadable_messages_are_answered_with_an_error() {
+    let (mut client, _) = connect().await;
+
+    client
+        .send(Message::Text("{\"type\": \"dance\"}".into()))
+        .await
+        .unwrap();
+    let reply = receive(&mut client).await;
+    assert_eq!(reply["type"], "error");
+    assert!(reply.get("id").is_none());
+}
@@ -0,0 +1,462 @@
+//! Process managers: running workflow steps, compensating them, resuming
+//! unfinished workflows and user provisioning. These need `DATABASE_URL`
+//! and are skipped otherwise.
+
+mod common;
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::async_trait;
+use chrono::Utc;
+use common::{postgres, user};
+use coqrs::{
+    commands::{
+        command_bus, CommandBusConfig, CommandError, CommandHandler, CommandHandlers, CommandWorker,
+    },
+    events::{StoredEvent, UserCreated},
+    models::{User, Workflow, WorkflowStatus},
+    repositories::{RepositoryError, UserRepository, WorkflowRepository},
+    services::{MailService, Mailer},
+    workflows::{ProcessManager, Step, UserProvisioning, WorkflowConfig, WorkflowRunner},
+    Command, EventBus, PostgreSQL,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// Logs `label`, or fails when `fail` is set.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Record {
+    label: String,
+    fail: bool,
+}
+
+impl Command for Record {
+    const NAME: &'static str = "Record";
+    type Output = ();
+}
+
+#[derive(Clone, Default)]
+struct Log(Arc<Mutex<Vec<String>>>);
+
+impl Log {
+    fn labels(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl CommandHandler<Record> for Log {
+    async fn handle(&self, record: Record) -> Result<(), CommandError> {
+        if record.fail {
+            return Err(RepositoryError::NotFound.into());
+        }
+        self.0.lock().unwrap().push(record.label);
+        Ok(())
+    }
+}
+
+fn record(label: &'static str) -> impl Fn(&Value) -> Result<Record, CommandError> {
+    move |_| {
+        Ok(Record {
+            label: label.into(),
+            fail: false,
+        })
+    }
+}
+
+/// Three steps started by a `Ping` event, the third failing when the
+/// event's payload says so.
+struct Pinged(&'static str);
+
+impl ProcessManager for Pinged {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+
+    fn start(&self, event: &StoredEvent) -> Option<Value> {
+        (event.event_type == "Ping").then(|| event.payload.clone())
+    }
+
+    fn steps(&self) -> Vec<Step> {
+        vec![
+            Step::new("one", record("one")).compensate_with(record("undo one")),
+            Step::new("two", record("two")).compensate_with(record("undo two")),
+            Step::new("three", |context: &Value| {
+                Ok::<_, CommandError>(Record {
+                    label: "three".into(),
+                    fail: context["fail"] == true,
+                })
+            }),
+        ]
+    }
+}
+
+/// `prefix` with a suffix of its own, so workflows left by earlier runs of
+/// a test are not picked up by the next one.
+fn unique(prefix: &str) -> &'static str {
+    Box::leak(format!("{prefix}_{}", Uuid::now_v7().simple()).into_boxed_str())
+}
+
+/// Runs `manager` with a bus handling `Record` into the returned log.
+fn running(db: &PostgreSQL, manager: impl ProcessManager) -> (EventBus, Log) {
+    let log = Log::default();
+    let (bus, receiver) = command_bus(&CommandBusConfig::default());
+    let pipeline = CommandHandlers::new().register(log.clone()).build();
+    tokio::spawn(CommandWorker::new(receiver, pipeline).run());
+    let events = EventBus::default();
+    let config = WorkflowConfig {
+        poll_interval: Duration::from_millis(10),
+        ..Default::default()
+    };
+    let runner = WorkflowRunner::new(db.clone(), bus, events.clone())
+        .register(manager)
+        .with_config(config);
+    tokio::spawn(runner.run());
+    (events, log)
+}
+
+fn ping(correlation_id: Uuid, fail: bool) -> StoredEvent {
+    StoredEvent {
+        id: Uuid::now_v7(),
+        aggregate_id: correlation_id,
+        event_type: "Ping".into(),
+        version: 1,
+        payload: json!({ "fail": fail }),
+        sequence: 0,
+        transaction: 0,
+    }
+}
+
+/// A workflow of `name` at `status` and `step`, as a previous run left it.
+fn unfinished(name: &str, status: WorkflowStatus, step: i32) -> Workflow {
+    let now = Utc::now();
+    Workflow {
+        id: Uuid::now_v7(),
+        name: name.into(),
+        correlation_id: Uuid::now_v7(),
+        status,
+        step,
+        context: json!({ "fail": false }),
+        history: json!([]),
+        error: None,
+        created_at: now,
+        updated_at: now,
+    }
+}
+
+/// Waits for the workflow of `name` correlated to `correlation_id` to stop
+/// running.
+async fn finished(db: &PostgreSQL, name: &str, correlation_id: Uuid) -> Workflow {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let workflows = db.list_workflows(500, 0).await.unwrap();
+            let workflow = workflows
+                .into_iter()
+                .find(|w| w.name == name && w.correlation_id == correlation_id);
+            if let Some(workflow) = workflow {
+                if !matches!(
+                    workflow.status,
+                    WorkflowStatus::Running | WorkflowStatus::Compensating
+                ) {
+                    return workflow;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("the workflow did not finish")
+}
+
+#[tokio::test]
+async fn workflows_run_every_step() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let name = unique("test_steps");
+    let (events, log) = running(&db, Pinged(name));
+    let correlation_id = Uuid::now_v7();
+    events.publish(ping(correlation_id, false));
+
+    let workflow = finished(&db, name, correlation_id).await;
+    assert_eq!(workflow.status, WorkflowStatus::Completed);
+    assert_eq!(workflow.step, 3);
+    assert_eq!(workflow.history.as_array().unwrap().len(), 3);
+    assert_eq!(log.labels(), ["one", "two", "three"]);
+}
+
+#[tokio::test]
+async fn failed_steps_compensate_the_finished_ones() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let name = unique("test_compensation");
+    let (events, log) = running(&db, Pinged(name));
+    let correlation_id = Uuid::now_v7();
+    events.publish(ping(correlation_id, true));
+
+    let workflow = finished(&db, name, correlation_id).await;
+    assert_eq!(workflow.status, WorkflowStatus::Compensated);
+    assert_eq!(workflow.error.as_deref(), Some("record not found"));
+    assert_eq!(log.labels(), ["one", "two", "undo two", "undo one"]);
+}
+
+#[tokio::test]
+async fn unfinished_workflows_resume_at_their_saved_step() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let name = unique("test_resume");
+    let running_workflow = unfinished(name, WorkflowStatus::Running, 2);
+    let compensating = unfinished(name, WorkflowStatus::Compensating, 1);
+    for workflow in [&running_workflow, &compensating] {
+        assert!(db
+            .start_workflow(workflow.clone(), Duration::ZERO)
+            .await
+            .unwrap());
+    }
+
+    let (_events, log) = running(&db, Pinged(name));
+    let resumed = finished(&db, name, running_workflow.correlation_id).await;
+    assert_eq!(resumed.status, WorkflowStatus::Completed);
+    let compensated = finished(&db, name, compensating.correlation_id).await;
+    assert_eq!(compensated.status, WorkflowStatus::Compensated);
+
+    let mut labels = log.labels();
+    labels.sort();
+    assert_eq!(labels, ["three", "undo one"]);
+}
+
+#[tokio::test]
+async fn workflows_only_advance_from_their_saved_state() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let name = unique("test_advance");
+    let workflow = unfinished(name, WorkflowStatus::Running, 0);
+    db.start_workflow(workflow.clone(), Duration::ZERO)
+        .await
+        .unwrap();
+    let advance = |from| {
+        db.advance_workflow(
+            workflow.id,
+            from,
+            WorkflowStatus::Running,
+            1,
+            json!({}),
+            None,
+        )
+    };
+
+    let stale = advance((WorkflowStatus::Running, 1)).await;
+    assert!(matches!(stale, Err(RepositoryError::NotFound)));
+    advance((WorkflowStatus::Running, 0)).await.unwrap();
+    // A second runner saving the same step finds it moved on.
+    let again = advance((WorkflowStatus::Running, 0)).await;
+    assert!(matches!(again, Err(RepositoryError::NotFound)));
+
+    let saved = db.find_workflow(workflow.id).await.unwrap().unwrap();
+    assert_eq!(saved.step, 1);
+    assert_eq!(saved.history.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn leased_workflows_are_claimed_by_one_runner_at_a_time() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let name = unique("test_claim");
+    let left = unfinished(name, WorkflowStatus::Running, 0);
+    let held = unfinished(name, WorkflowStatus::Running, 0);
+    db.start_workflow(left.clone(), Duration::ZERO)
+        .await
+        .unwrap();
+    db.start_workflow(held.clone(), Duration::from_secs(30))
+        .await
+        .unwrap();
+    let names = [name];
+    let claim = || db.claim_unfinished_workflows(&names, 100, Duration::from_secs(30));
+
+    let claimed: Vec<Uuid> = claim().await.unwrap().iter().map(|w| w.id).collect();
+    assert_eq!(claimed, [left.id]);
+    assert!(claim().await.unwrap().is_empty());
+
+    let renew = |at| db.renew_workflow_lease(left.id, at, Duration::ZERO);
+    let stale = renew((WorkflowStatus::Running, 1)).await;
+    assert!(matches!(stale, Err(RepositoryError::NotFound)));
+    renew((WorkflowStatus::Running, 0)).await.unwrap();
+    let claimed: Vec<Uuid> = claim().await.unwrap().iter().map(|w| w.id).collect();
+    assert_eq!(claimed, [left.id]);
+}
+
+#[tokio::test]
+async fn workflows_a_runner_stopped_driving_resume_when_their_lease_ends() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let name = unique("test_lease");
+    let (_events, log) = running(&db, Pinged(name));
+    // As left by a runner whose save failed after the first step, while it
+    // still held the workflow.
+    let stopped = unfinished(name, WorkflowStatus::Running, 1);
+    let started = std::time::Instant::now();
+    db.start_workflow(stopped.clone(), Duration::from_millis(300))
+        .await
+        .unwrap();
+
+    let resumed = finished(&db, name, stopped.correlation_id).await;
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    assert_eq!(resumed.status, WorkflowStatus::Completed);
+    assert_eq!(log.labels(), ["two", "three"]);
+}
+
+/// One step, started by the creation of any of its users.
+struct Created(&'static str, Vec<Uuid>);
+
+impl ProcessManager for Created {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+
+    fn start(&self, event: &StoredEvent) -> Option<Value> {
+        let created = event.event_type == "UserCreated" && self.1.contains(&event.aggregate_id);
+        created.then(|| json!({}))
+    }
+
+    fn steps(&self) -> Vec<Step> {
+        vec![Step::new("one", record("one"))]
+    }
+}
+
+/// Runs a [`Created`] process manager of `name` for `users` on `events`,
+/// once its checkpoint is saved.
+async fn created_runner(
+    db: &PostgreSQL,
+    name: &'static str,
+    users: &[User],
+    events: &EventBus,
+) -> tokio::task::JoinHandle<()> {
+    let (bus, receiver) = command_bus(&CommandBusConfig::default());
+    let pipeline = CommandHandlers::new().register(Log::default()).build();
+    tokio::spawn(CommandWorker::new(receiver, pipeline).run());
+    let manager = Created(name, users.iter().map(|user| user.id).collect());
+    let config = WorkflowConfig {
+        poll_interval: Duration::from_millis(10),
+        ..Default::default()
+    };
+    let runner = WorkflowRunner::new(db.clone(), bus, events.clone())
+        .register(manager)
+        .with_config(config);
+    let runner = tokio::spawn(runner.run());
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while db.workflow_checkpoint(name).await.unwrap().is_none() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("the runner saved no checkpoint");
+    runner
+}
+
+#[tokio::test]
+async fn events_missed_while_lagging_are_read_from_the_store() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let name = unique("test_lag");
+    let users: Vec<_> = (0..3).map(|_| user(unique("lagged"))).collect();
+    let events = EventBus::new(1);
+    let _runner = created_runner(&db, name, &users, &events).await;
+
+    // Stored without being published, then more events than the bus
+    // holds, so the runner only learns it lagged.
+    for user in &users {
+        db.save_user(user.clone()).await.unwrap();
+    }
+    for _ in 0..3 {
+        events.publish(ping(Uuid::now_v7(), false));
+    }
+
+    for user in &users {
+        let workflow = finished(&db, name, user.id).await;
+        assert_eq!(workflow.status, WorkflowStatus::Completed);
+    }
+}
+
+#[tokio::test]
+async fn events_stored_while_no_runner_ran_start_workflows_on_startup() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let name = unique("test_startup");
+    let early = user(unique("early"));
+    let users = [early.clone(), user(unique("down"))];
+    db.save_user(early.clone()).await.unwrap();
+    // A new process manager starts from the events to come.
+    let stopped = created_runner(&db, name, &users, &EventBus::default()).await;
+    stopped.abort();
+    db.save_user(users[1].clone()).await.unwrap();
+
+    let _runner = created_runner(&db, name, &users, &EventBus::default()).await;
+    let workflow = finished(&db, name, users[1].id).await;
+    assert_eq!(workflow.status, WorkflowStatus::Completed);
+    let workflows = db.list_workflows(500, 0).await.unwrap();
+    assert!(!workflows.iter().any(|w| w.correlation_id == early.id));
+}
+
+/// Keeps every email it is asked to send.
+#[derive(Clone, Default)]
+struct Outbox(Arc<Mutex<Vec<(String, String)>>>);
+
+#[async_trait]
+impl Mailer for Outbox {
+    async fn send(&self, to: &str, subject: &str, _body: &str) -> Result<(), CommandError> {
+        self.0.lock().unwrap().push((to.into(), subject.into()));
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn created_users_are_welcomed() {
+    let Some(db) = postgres().await else {
+        return eprintln!("skipping, DATABASE_URL is not set");
+    };
+    let outbox = Outbox::default();
+    let (bus, receiver) = command_bus(&CommandBusConfig::default());
+    let pipeline = MailService::new(outbox.clone())
+        .register(CommandHandlers::new())
+        .build();
+    tokio::spawn(CommandWorker::new(receiver, pipeline).run());
+    let events = EventBus::default();
+    let runner = WorkflowRunner::new(db.clone(), bus, events.clone()).register(UserProvisioning);
+    tokio::spawn(runner.run());
+
+    let id = Uuid::now_v7();
+    let username = unique("alice");
+    let email = format!("{username}@example.com");
+    let created = UserCreated {
+        id,
+        username: username.into(),
+        email: email.clone(),
+    };
+    assert!(UserProvisioning.start(&ping(id, false)).is_none());
+    events.publish(StoredEvent::new(id, &created));
+
+    let workflow = finished(&db, "user_provisioning", id).await;
+    assert_eq!(workflow.status, WorkflowStatus::Completed);
+    assert_eq!(workflow.context["username"], username);
+    // Users other tests created since the last run are welcomed too.
+    let sent: Vec<_> = outbox
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(to, _)| *to == email)
+        .cloned()
+        .collect();
+    assert_eq!(sent, [(email, "Welcome".to_string())]);
+}